@@ -8,10 +8,40 @@
 //! find the required static or shared libraries.
 //!
 //! * `LLVM_CONFIG_PATH` - provides a path to an `llvm-config` executable
+//! * `LLVM_CONFIG_OUTPUT` - provides a path to a file containing recorded
+//!   `llvm-config` output to use instead of executing `llvm-config`
 //! * `LIBCLANG_PATH` - provides a path to a directory containing a `libclang`
 //!   shared library or a path to a specific `libclang` shared library
+//! * `LIBCLANG_PATH_DEBUG` and `LIBCLANG_PATH_RELEASE` - provide paths like
+//!   `LIBCLANG_PATH` which are used instead in debug and release builds
+//! * `LIBCLANG_SELECTION_POLICY` - controls which `libclang` shared library is
+//!   used when several are found (`newest`, `env-path`, or `first-match`)
 //! * `LIBCLANG_STATIC_PATH` - provides a path to a directory containing LLVM
 //!   and Clang static libraries
+//! * `LIBCLANG_STATIC_PATTERN` - provides the pattern matched by the file names
+//!   of the Clang static libraries (e.g., `libclang*_static.a`)
+//! * `LIBCLANG_STATIC_VERIFY` - if set, a program which calls `clang_createIndex`
+//!   is linked to the Clang static libraries to verify that they can be linked
+//! * `LIBCLANG_STUB_SONAME` - provides the name of the `libclang` shared
+//!   library loaded at runtime by binaries linked to a stub shared library
+//! * `PKG_CONFIG` - provides a path to a `pkg-config` executable
+//! * `LIBCLANG_GLIBC_BASELINE` - provides the version of `glibc` available
+//!   where the resulting executable will be run
+//! * `LIBCLANG_GLIBCXX_BASELINE` - provides the `GLIBCXX` symbol version of
+//!   `libstdc++` available where the resulting executable will be run
+//! * `LIBCLANG_PROBE` - if set, the `libclang` shared libraries found are loaded
+//!   in a child process and those which can't be loaded are skipped
+//! * `CLANG_SYS_SELECTION` - provides a path to a file containing values for
+//!   `LIBCLANG_PATH`, `LIBCLANG_STATIC_PATH`, `LLVM_CONFIG_PATH`,
+//!   `LLVM_CONFIG_OUTPUT`, and `CLANG_SYS_NO_XCODE` (used when these
+//!   environment variables are not set)
+//! * `CLANG_SYS_WARNINGS` - controls which warnings are printed (`quiet`,
+//!   `normal`, or `verbose`), all warnings are written to `warnings.json` in
+//!   the build output directory regardless
+//! * `CLANG_SYS_RECORD` - provides a path to a file to which everything
+//!   consulted while searching for a `libclang` shared library is recorded
+//! * `CLANG_SYS_NO_XCODE` - if set, `xcode-select` is never executed to find
+//!   the Xcode toolchain on macOS
 
 #![allow(unused_attributes)]
 
@@ -21,60 +51,260 @@ use std::path::Path;
 #[path = "build/macros.rs"]
 pub mod macros;
 
+#[path = "build/audit.rs"]
+pub mod audit;
 #[path = "build/common.rs"]
 pub mod common;
 #[path = "build/dynamic.rs"]
 pub mod dynamic;
 #[path = "build/static.rs"]
 pub mod r#static;
+#[path = "src/version.rs"]
+pub mod version;
 
-/// Copies a file.
-#[cfg(feature = "runtime")]
-fn copy(source: &str, destination: &Path) {
-    use std::fs::File;
-    use std::io::{Read, Write};
-
-    let mut string = String::new();
-    File::open(source)
-        .unwrap()
-        .read_to_string(&mut string)
-        .unwrap();
-    File::create(destination)
-        .unwrap()
-        .write_all(string.as_bytes())
-        .unwrap();
-}
+/// Whether the modules above are compiled into the build script (rather than
+/// into this crate to find `libclang` at runtime).
+pub const BUILD_SCRIPT: bool = true;
 
-/// Copies the code used to find and link to `libclang` shared libraries into
-/// the build output directory so that it may be used when linking at runtime.
+/// Checks the target and the enabled Cargo features when linking at runtime.
+///
+/// The code used to find `libclang` shared libraries at runtime is compiled
+/// into this crate from the same sources as this build script (see
+/// `src/runtime/discovery.rs`).
 #[cfg(feature = "runtime")]
 fn main() {
     use std::env;
 
-    if cfg!(feature = "static") {
-        println!(
-            "cargo:warning=Both `runtime` and `static` features are enabled; \
-             `runtime` takes priority (`static` is ignored)"
+    // The build script is executed with these arguments to probe whether a
+    // `libclang` shared library can be loaded (see `common::run_probe`), also
+    // at runtime if requested (see `probe` in `src/link.rs`).
+    let arguments = env::args().collect::<Vec<_>>();
+    if let [_, argument, path] = &arguments[..]
+        && argument == common::PROBE_ARGUMENT
+    {
+        return probe(path);
+    }
+
+    if let Some(target) = common::wasm_target() {
+        panic!(
+            "the `runtime` Cargo feature is not supported for WebAssembly targets ({}) which \
+             can't load shared libraries, disable it and enable the `static` Cargo feature \
+             to link to `libclang` static libraries compiled for WebAssembly instead \
+             (experimental)",
+            target,
         );
     }
 
     let out = env::var("OUT_DIR").unwrap();
-    copy("build/macros.rs", &Path::new(&out).join("macros.rs"));
-    copy("build/common.rs", &Path::new(&out).join("common.rs"));
-    copy("build/dynamic.rs", &Path::new(&out).join("dynamic.rs"));
+    let _report = common::WarningReport::new(Path::new(&out).join("warnings.json"));
+
+    if cfg!(feature = "audit") {
+        audit::write(Path::new(&out));
+    }
+
+    // This build script can't be executed at runtime when cross-compiling.
+    if !common::is_cross_compiling()
+        && let Ok(executable) = env::current_exe()
+    {
+        println!(
+            "cargo:rustc-env=CLANG_SYS_PROBE_HELPER={}",
+            executable.display()
+        );
+    }
+
+    if cfg!(feature = "static") && !cfg!(feature = "hybrid") {
+        common::warn(
+            common::Severity::Warning,
+            "Both `runtime` and `static` features are enabled; \
+             `runtime` takes priority (`static` is ignored)",
+        );
+    }
+
+    // With the `hybrid` Cargo feature, `libclang` is also linked to so that
+    // its functions can be called when no shared library is loaded at runtime.
+    #[cfg(feature = "hybrid")]
+    {
+        let linked = if cfg!(feature = "static") {
+            r#static::link()
+        } else {
+            dynamic::link()
+        };
+
+        let includedir = common::find_include_directory();
+        build_info(Path::new(&out), &linked, includedir.as_deref());
+    }
+}
+
+/// Attempts to load a `libclang` shared library and determine its version and
+/// prints `ok` if it could be loaded or the reason it could not be loaded (see
+/// `common::run_probe`).
+///
+/// A corrupted or mismatched shared library can crash or hang this process
+/// instead (which is why this is done in a child process).
+fn probe(path: &str) {
+    use std::ffi::{c_uint, c_void};
+
+    /// The `CXString` returned by `clang_getClangVersion`.
+    #[repr(C)]
+    struct CXString {
+        data: *const c_void,
+        private_flags: c_uint,
+    }
+
+    // Determines the version of a loaded shared library with the supplied
+    // symbol lookup (unless it is a stub which would abort the process).
+    let version = |symbol: &dyn Fn(&str) -> *mut c_void| {
+        let get = symbol("clang_getClangVersion");
+        let dispose = symbol("clang_disposeString");
+        if symbol(dynamic::STUB_MARKER).is_null() && !get.is_null() && !dispose.is_null() {
+            unsafe {
+                let get: unsafe extern "C" fn() -> CXString = std::mem::transmute(get);
+                let dispose: unsafe extern "C" fn(CXString) = std::mem::transmute(dispose);
+                dispose(get());
+            }
+        }
+    };
+
+    #[cfg(unix)]
+    {
+        use std::ffi::{CStr, CString, c_char, c_int};
+
+        unsafe extern "C" {
+            fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+            fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+            fn dlerror() -> *const c_char;
+        }
+
+        // Resolve all symbols immediately to catch missing dependencies.
+        const RTLD_NOW: c_int = 2;
+
+        let path = CString::new(path).unwrap();
+        let handle = unsafe { dlopen(path.as_ptr(), RTLD_NOW) };
+        if !handle.is_null() {
+            version(&|name| {
+                let name = CString::new(name).unwrap();
+                unsafe { dlsym(handle, name.as_ptr()) }
+            });
+            println!("ok");
+        } else {
+            let error = unsafe { dlerror() };
+            if error.is_null() {
+                println!("unknown error");
+            } else {
+                println!("{}", unsafe { CStr::from_ptr(error) }.to_string_lossy());
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use std::ffi::{CString, OsStr};
+        use std::io;
+        use std::os::windows::ffi::OsStrExt;
+
+        #[link(name = "kernel32")]
+        unsafe extern "system" {
+            fn LoadLibraryW(filename: *const u16) -> *mut c_void;
+            fn GetProcAddress(module: *mut c_void, name: *const u8) -> *mut c_void;
+        }
+
+        let path = OsStr::new(path)
+            .encode_wide()
+            .chain(Some(0))
+            .collect::<Vec<_>>();
+        let module = unsafe { LoadLibraryW(path.as_ptr()) };
+        if !module.is_null() {
+            version(&|name| {
+                let name = CString::new(name).unwrap();
+                unsafe { GetProcAddress(module, name.as_ptr().cast()) }
+            });
+            println!("ok");
+        } else {
+            println!("{}", io::Error::last_os_error());
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (path, version);
+        println!("ok");
+    }
 }
 
 /// Finds and links to the required libraries dynamically or statically.
 #[cfg(not(feature = "runtime"))]
 fn main() {
-    if cfg!(feature = "static") {
-        r#static::link();
-    } else {
-        dynamic::link();
+    // The build script is executed with these arguments to probe whether a
+    // `libclang` shared library can be loaded (see `common::run_probe`).
+    let arguments = std::env::args().collect::<Vec<_>>();
+    if let [_, argument, path] = &arguments[..]
+        && argument == common::PROBE_ARGUMENT
+    {
+        return probe(path);
+    }
+
+    let out = std::env::var("OUT_DIR").unwrap();
+    let _report = common::WarningReport::new(Path::new(&out).join("warnings.json"));
+
+    if cfg!(feature = "audit") {
+        audit::write(Path::new(&out));
     }
 
-    if let Some(output) = common::run_llvm_config(&["--includedir"]) {
-        let directory = Path::new(output.trim_end());
-        println!("cargo:include={}", directory.display());
+    for problem in common::check_selection() {
+        common::warn(common::Severity::Warning, problem);
     }
+
+    let linked = if cfg!(feature = "static") {
+        r#static::link()
+    } else {
+        dynamic::link()
+    };
+
+    let includedir = common::find_include_directory();
+    build_info(Path::new(&out), &linked, includedir.as_deref());
+}
+
+/// Makes the `libclang` library linked to (its directory, path, and version)
+/// and the directory containing the Clang headers available to this crate (see
+/// `clang_sys::build_info`), to the build scripts of packages which depend on
+/// this crate (as the `DEP_CLANG_*` environment variables), and to other tools
+/// (as an environment file).
+#[cfg(any(not(feature = "runtime"), feature = "hybrid"))]
+fn build_info(out: &Path, linked: &common::Linked, includedir: Option<&Path>) {
+    let path = out.join("build_info.env");
+    let mut env = String::new();
+    let mut emit = |variable: &str, keys: &[&str], value: &str| {
+        env.push_str(&format!("{}={}\n", variable, value));
+        println!("cargo:rustc-env={}={}", variable, value);
+        for key in keys {
+            println!("cargo:{}={}", key, value);
+        }
+    };
+
+    emit(
+        "CLANG_SYS_LIBDIR",
+        &["libdir"],
+        &linked.libdir.display().to_string(),
+    );
+    if let Some(library) = &linked.library {
+        emit(
+            "CLANG_SYS_LIBRARY",
+            &["libclang_path"],
+            &library.display().to_string(),
+        );
+    }
+    if let Some(version) = &linked.version {
+        emit("CLANG_SYS_VERSION", &["version"], version);
+    }
+    if let Some(includedir) = includedir {
+        emit(
+            "CLANG_SYS_INCLUDEDIR",
+            &["include", "include_dir"],
+            &includedir.display().to_string(),
+        );
+    }
+
+    std::fs::write(&path, env).unwrap();
+    println!("cargo:rustc-env=CLANG_SYS_BUILD_INFO={}", path.display());
+    println!("cargo:build_info={}", path.display());
 }
@@ -0,0 +1,271 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Generates the table of constants used to audit the constants bound by this
+//! crate against the `clang-c` headers (see `clang_sys::audit`) and the
+//! compile-time checks of these constants against the `clang-c` headers
+//! available locally.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::common;
+
+/// Returns the names of the constants defined in the supplied Rust source
+/// (e.g., `src/lib.rs`) along with the `cfg` attributes which apply to them
+/// (see `common::parse_bindings`).
+pub fn parse_constants(source: &str) -> Vec<(String, Vec<String>)> {
+    common::parse_bindings(source)
+        .into_iter()
+        .filter(|b| b.kind == common::BindingKind::Constant)
+        .map(|b| (b.name, b.cfgs))
+        .collect()
+}
+
+/// Generates an expression which evaluates to the names and values of the
+/// supplied constants (where enabled by their `cfg` attributes).
+pub fn generate(constants: &[(String, Vec<String>)]) -> String {
+    let mut code = String::from("{\n    let mut constants = vec![];\n");
+    for (name, cfgs) in constants {
+        for cfg in cfgs {
+            code.push_str(&format!("    {}\n", cfg));
+        }
+        code.push_str(&format!(
+            "    constants.push((\"{0}\", crate::{0} as i64));\n",
+            name,
+        ));
+    }
+    code.push_str("    constants\n}\n");
+    code
+}
+
+/// Evaluates the value of an enum constant in a C header (e.g., `1 << 3` or
+/// `CXCursor_UnexposedDecl`) or returns `None` if it can't be evaluated.
+///
+/// Integer literals, the supplied constants, parentheses, unary `-` and `~`,
+/// and binary `+`, `-`, `<<`, and `|` are supported.
+fn evaluate(expression: &str, constants: &HashMap<String, i64>) -> Option<i64> {
+    /// A parser for expressions with the precedence of C operators.
+    struct Parser<'e> {
+        tokens: Vec<&'e str>,
+        index: usize,
+        constants: &'e HashMap<String, i64>,
+    }
+
+    impl<'e> Parser<'e> {
+        fn peek(&self) -> Option<&'e str> {
+            self.tokens.get(self.index).copied()
+        }
+
+        fn next(&mut self) -> Option<&'e str> {
+            self.index += 1;
+            self.tokens.get(self.index - 1).copied()
+        }
+
+        fn or(&mut self) -> Option<i64> {
+            let mut value = self.shift()?;
+            while self.peek() == Some("|") {
+                self.next();
+                value |= self.shift()?;
+            }
+            Some(value)
+        }
+
+        fn shift(&mut self) -> Option<i64> {
+            let mut value = self.additive()?;
+            while self.peek() == Some("<<") {
+                self.next();
+                value = value.checked_shl(u32::try_from(self.additive()?).ok()?)?;
+            }
+            Some(value)
+        }
+
+        fn additive(&mut self) -> Option<i64> {
+            let mut value = self.unary()?;
+            while let Some(operator @ ("+" | "-")) = self.peek() {
+                let negative = operator == "-";
+                self.next();
+                let operand = self.unary()?;
+                value = if negative {
+                    value.checked_sub(operand)?
+                } else {
+                    value.checked_add(operand)?
+                };
+            }
+            Some(value)
+        }
+
+        fn unary(&mut self) -> Option<i64> {
+            match self.next()? {
+                "-" => self.unary()?.checked_neg(),
+                "~" => Some(!self.unary()?),
+                "(" => {
+                    let value = self.or()?;
+                    (self.next()? == ")").then_some(value)
+                }
+                token => {
+                    let digits = token.trim_end_matches(['u', 'U', 'l', 'L']);
+                    if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+                        i64::from_str_radix(hex, 16).ok()
+                    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+                        digits.parse().ok()
+                    } else {
+                        self.constants.get(token).copied()
+                    }
+                }
+            }
+        }
+    }
+
+    let mut tokens = vec![];
+    let mut rest = expression.trim();
+    while !rest.is_empty() {
+        let length = if rest.starts_with("<<") {
+            2
+        } else if rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+            rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len())
+        } else {
+            rest.chars().next().map_or(1, char::len_utf8)
+        };
+        tokens.push(&rest[..length]);
+        rest = rest[length..].trim_start();
+    }
+
+    let mut parser = Parser {
+        tokens,
+        index: 0,
+        constants,
+    };
+    let value = parser.or()?;
+    (parser.index == parser.tokens.len()).then_some(value)
+}
+
+/// Parses the enum constants defined in the supplied C header (e.g.,
+/// `clang-c/Index.h`) and adds their names and values to the supplied map.
+///
+/// Constants whose values can't be evaluated (see `evaluate`) are skipped
+/// along with the constants following them in the same enum which don't have
+/// values of their own.
+pub fn parse_header_constants(header: &str, constants: &mut HashMap<String, i64>) {
+    // Remove comments and preprocessor directives.
+    let mut text = String::with_capacity(header.len());
+    let mut rest = header;
+    while let Some(start) = rest.find("/*") {
+        text.push_str(&rest[..start]);
+        rest = rest[start + 2..].split_once("*/").map_or("", |(_, r)| r);
+    }
+    text.push_str(rest);
+    let text = text
+        .lines()
+        .map(|l| l.split("//").next().unwrap_or_default())
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find("enum") {
+        let after = &rest[start + 4..];
+        rest = after;
+
+        // Skip identifiers which merely contain `enum` and uses of enum types.
+        let word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let before = text[..text.len() - after.len() - 4].chars().next_back();
+        if before.is_some_and(word) || after.starts_with(word) {
+            continue;
+        }
+
+        let Some((head, body)) = after.split_once('{') else {
+            break;
+        };
+        if head.contains([';', '(', ')', '}']) {
+            continue;
+        }
+
+        let Some((body, after)) = body.split_once('}') else {
+            break;
+        };
+        rest = after;
+
+        let mut next = Some(0);
+        for entry in body.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, value) = match entry.split_once('=') {
+                Some((name, value)) => (name.trim(), evaluate(value, constants)),
+                None => (entry, next),
+            };
+
+            if !name.chars().all(word) {
+                next = None;
+                continue;
+            }
+
+            if let Some(value) = value {
+                constants.insert(name.into(), value);
+            }
+            next = value.and_then(|v| v.checked_add(1));
+        }
+    }
+}
+
+/// Generates compile-time checks that the supplied constants (where enabled
+/// by their `cfg` attributes) have the values of the enum constants of the
+/// same names in the supplied `clang-c` headers.
+///
+/// Constants which aren't in the headers (e.g., constants added in a later
+/// release of `libclang`) aren't checked.
+pub fn generate_checks(
+    constants: &[(String, Vec<String>)],
+    headers: &HashMap<String, i64>,
+) -> String {
+    let mut code = String::new();
+    for (name, cfgs) in constants {
+        let Some(value) = headers.get(name) else {
+            continue;
+        };
+
+        for cfg in cfgs {
+            code.push_str(&format!("{}\n", cfg));
+        }
+        code.push_str(&format!(
+            "const _: () = assert!(\n    crate::{0} as i64 == {1},\n    \"`{0}` differs from the \
+             `clang-c` headers ({1}), enable the `clang_*` Cargo feature for the version of \
+             these headers\",\n);\n",
+            name, value,
+        ));
+    }
+    code
+}
+
+/// Generates the table of constants for the constants bound in `src/lib.rs`
+/// and writes it to `audit.rs` in the supplied build output directory.
+///
+/// The compile-time checks of these constants against the `clang-c` headers
+/// in the directory provided by `common::find_include_directory` (if any) are
+/// written to `audit_checks.rs` in the same directory.
+pub fn write(out: &Path) {
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    let source = fs::read_to_string("src/lib.rs").unwrap();
+    let constants = parse_constants(&source);
+    fs::write(out.join("audit.rs"), generate(&constants)).unwrap();
+
+    let mut headers = HashMap::new();
+    let directory = common::find_include_directory().map(|d| d.join("clang-c"));
+    if let Some(directory) = directory.filter(|d| d.join("Index.h").is_file()) {
+        println!("cargo:rerun-if-changed={}", directory.display());
+        let mut paths = fs::read_dir(&directory)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "h"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            if let Ok(header) = fs::read_to_string(&path) {
+                parse_header_constants(&header, &mut headers);
+            }
+        }
+    }
+
+    let checks = generate_checks(&constants, &headers);
+    fs::write(out.join("audit_checks.rs"), checks).unwrap();
+}
@@ -1,13 +1,344 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::cell::RefCell;
-use std::collections::HashMap;
-use std::env;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, PoisonError};
 
 use glob::{MatchOptions, Pattern};
 
+use super::version;
+
+//================================================
+// Environment
+//================================================
+
+thread_local! {
+    /// The values of the environment variables which are used instead of the
+    /// values in the environment of the process on this thread (see
+    /// `with_env_overrides`).
+    static ENV_OVERRIDES: RefCell<Vec<(String, String)>> = RefCell::default();
+}
+
+/// Calls the supplied function with the supplied values of environment
+/// variables (e.g., `LIBCLANG_PATH`) used instead of the values in the
+/// environment of the process while searching on this thread (see `env`).
+///
+/// This allows a search to be configured without modifying the environment of
+/// the process, which isn't thread-safe.
+pub fn with_env_overrides<T>(overrides: &[(String, String)], f: impl FnOnce() -> T) -> T {
+    struct Restore(Vec<(String, String)>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = std::mem::take(&mut self.0);
+            ENV_OVERRIDES.with(|o| *o.borrow_mut() = previous);
+        }
+    }
+
+    let previous = ENV_OVERRIDES.with(|o| o.replace(overrides.to_vec()));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// The functions of `std::env` used while searching, except that the values
+/// of environment variables are read from the overrides on this thread first
+/// (see `with_env_overrides`).
+pub mod env {
+    use std::ffi::{OsStr, OsString};
+
+    pub use std::env::*;
+
+    /// Returns the value of an environment variable like `std::env::var_os`.
+    pub fn var_os<K: AsRef<OsStr>>(key: K) -> Option<OsString> {
+        let key = key.as_ref();
+        let value = super::ENV_OVERRIDES.with(|o| {
+            let overrides = o.borrow();
+            let value = overrides.iter().rev().find(|(k, _)| OsStr::new(k) == key);
+            value.map(|(_, v)| OsString::from(v))
+        });
+        value.or_else(|| std::env::var_os(key))
+    }
+
+    /// Returns the value of an environment variable like `std::env::var`.
+    pub fn var<K: AsRef<OsStr>>(key: K) -> Result<String, VarError> {
+        match var_os(key) {
+            Some(value) => value.into_string().map_err(VarError::NotUnicode),
+            None => Err(VarError::NotPresent),
+        }
+    }
+}
+
+//================================================
+// Warnings
+//================================================
+
+/// The severity of a warning emitted by the build script.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Details which are only useful when debugging the build script.
+    Debug,
+    /// Routine information (e.g., which `llvm-config` executable was used).
+    Info,
+    /// A problem which may cause the build or the resulting binary to fail.
+    Warning,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Debug => "debug",
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+thread_local! {
+    /// The warnings emitted by the build script.
+    static WARNINGS: RefCell<Vec<(Severity, String)>> = RefCell::default();
+}
+
+/// Returns whether warnings of the supplied severity are printed as configured
+/// by the `CLANG_SYS_WARNINGS` environment variable (`quiet` only prints
+/// problems, `normal` also prints routine information, and `verbose` prints
+/// everything).
+pub fn is_printed(severity: Severity) -> bool {
+    let minimum = match env::var("CLANG_SYS_WARNINGS").as_deref() {
+        Ok("quiet") => Severity::Warning,
+        Ok("verbose") => Severity::Debug,
+        _ => Severity::Info,
+    };
+
+    severity >= minimum
+}
+
+/// Emits a warning which is printed (if its severity is high enough) and added
+/// to the warnings report (regardless of its severity).
+pub fn warn(severity: Severity, message: impl Into<String>) {
+    let message = message.into();
+    if is_printed(severity) {
+        println!("cargo:warning={}", message);
+    }
+
+    WARNINGS.with(|w| w.borrow_mut().push((severity, message)));
+}
+
+/// Returns the warnings emitted by the build script.
+pub fn warnings() -> Vec<(Severity, String)> {
+    WARNINGS.with(|w| w.borrow().clone())
+}
+
+/// Returns the warnings emitted by the build script formatted as JSON.
+pub fn warnings_json() -> String {
+    let escape = |string: &str| {
+        let mut escaped = String::with_capacity(string.len());
+        for c in string.chars() {
+            match c {
+                '"' => escaped.push_str("\\\""),
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                '\t' => escaped.push_str("\\t"),
+                c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    };
+
+    let warnings = warnings()
+        .iter()
+        .map(|(severity, message)| {
+            format!(
+                "  {{\"severity\": \"{}\", \"message\": \"{}\"}}",
+                severity.name(),
+                escape(message),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if warnings.is_empty() {
+        "[]\n".into()
+    } else {
+        format!("[\n{}\n]\n", warnings.join(",\n"))
+    }
+}
+
+/// A struct that writes the warnings emitted by the build script to a JSON
+/// file when dropped.
+///
+/// This ensures the report is written even if the build script panics because
+/// it could not find an instance of `libclang`.
+pub struct WarningReport {
+    path: PathBuf,
+}
+
+impl WarningReport {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Drop for WarningReport {
+    fn drop(&mut self) {
+        let _ = std::fs::write(&self.path, warnings_json());
+    }
+}
+
+//================================================
+// Selection
+//================================================
+
+/// The environment variables which may be provided by the selection file in
+/// the `CLANG_SYS_SELECTION` environment variable.
+pub const SELECTION_VARIABLES: &[&str] = &[
+    "LIBCLANG_PATH",
+    "LIBCLANG_STATIC_PATH",
+    "LLVM_CONFIG_PATH",
+    "LLVM_CONFIG_OUTPUT",
+    "CLANG_SYS_NO_XCODE",
+    "LIBCLANG_SELECTION_POLICY",
+];
+
+/// Reads the selection file provided by the `CLANG_SYS_SELECTION` environment
+/// variable, if any.
+///
+/// A selection file allows a tool (e.g., a toolchain manager or the build
+/// script of another package in the same workspace) to tell this build script
+/// which instance of `libclang` to use without requiring users to set global
+/// environment variables. Each non-empty line in the file which does not start
+/// with `#` is either one of the environment variables in
+/// `SELECTION_VARIABLES` followed by `=` and then its value (relative paths are
+/// relative to the directory containing the file) or `version=` followed by
+/// the version of the selected instance of `libclang` (which is checked against
+/// the enabled Cargo features).
+///
+/// Returns the path to the file and the lines which could be parsed.
+fn read_selection() -> Option<(PathBuf, Vec<(String, String)>)> {
+    let path = PathBuf::from(env::var_os("CLANG_SYS_SELECTION")?);
+    let contents = std::fs::read_to_string(&path).ok()?;
+    let directory = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+    let lines = contents
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| {
+            let (k, v) = (k.trim(), v.trim());
+            // Executables without a directory component are searched for in
+            // `PATH` so they aren't relative to the file (and flags aren't paths).
+            let executable = k == "LLVM_CONFIG_PATH" && Path::new(v).components().count() <= 1;
+            let path = !matches!(k, "CLANG_SYS_NO_XCODE" | "LIBCLANG_SELECTION_POLICY");
+            if SELECTION_VARIABLES.contains(&k) && path && !executable {
+                (k.into(), directory.join(v).to_string_lossy().into_owned())
+            } else {
+                (k.into(), v.into())
+            }
+        })
+        .collect();
+
+    Some((path, lines))
+}
+
+/// Returns the value of one of the environment variables used by this build
+/// script to find `libclang`.
+///
+/// If the environment variable is not set, the value provided for it by the
+/// selection file in the `CLANG_SYS_SELECTION` environment variable is returned
+/// instead, if any (see `read_selection`).
+pub fn var(name: &str) -> Option<String> {
+    if let Ok(value) = env::var(name) {
+        return Some(value);
+    }
+
+    let (_, lines) = read_selection()?;
+    lines.into_iter().find(|(k, _)| k == name).map(|(_, v)| v)
+}
+
+/// Returns the value of the variant of the supplied environment variable for
+/// the Cargo profile being built (e.g., `LIBCLANG_PATH_DEBUG` instead of
+/// `LIBCLANG_PATH` for the `dev` profile), if any.
+///
+/// Only `LIBCLANG_PATH` has these variants (`LIBCLANG_PATH_DEBUG` and
+/// `LIBCLANG_PATH_RELEASE`). Cargo only provides the profile (`debug` or
+/// `release`) to build scripts so these variants are never used at runtime
+/// (even if `PROFILE` happens to be set).
+pub fn profile_var(name: &str) -> Option<String> {
+    if !super::BUILD_SCRIPT || name != "LIBCLANG_PATH" {
+        return None;
+    }
+
+    let profile = env::var("PROFILE").ok()?;
+    env::var(format!("{}_{}", name, profile.to_uppercase())).ok()
+}
+
+/// Checks the selection file in the `CLANG_SYS_SELECTION` environment
+/// variable, if any, and tells Cargo to rerun this build script when it
+/// changes.
+///
+/// Returns the problems found with the selection file (e.g., an unrecognized
+/// key or a version older than the one required by the enabled Cargo features).
+pub fn check_selection() -> Vec<String> {
+    let Some(path) = env::var_os("CLANG_SYS_SELECTION").map(PathBuf::from) else {
+        return vec![];
+    };
+
+    if !test!() {
+        println!("cargo:rerun-if-env-changed=CLANG_SYS_SELECTION");
+        println!("cargo:rerun-if-changed={}", path.display());
+        println!("cargo:rerun-if-changed=build.rs");
+        println!("cargo:rerun-if-changed=build");
+    }
+
+    let Some((path, lines)) = read_selection() else {
+        return vec![format!(
+            "couldn't read CLANG_SYS_SELECTION file ({})",
+            path.display()
+        )];
+    };
+
+    let mut problems = vec![];
+    for (key, value) in lines {
+        if key == "version" {
+            let major = version::parse_components(&value).first().copied();
+            match (major, get_target_clang_version()) {
+                (Some(major), Some(target)) if major < target => problems.push(format!(
+                    "the `libclang` version selected by the CLANG_SYS_SELECTION file ({}) \
+                     is older than the version required by the enabled Cargo features ({})",
+                    value, target,
+                )),
+                (None, _) => problems.push(format!(
+                    "invalid version in CLANG_SYS_SELECTION file ({}): {}",
+                    path.display(),
+                    value,
+                )),
+                _ => {}
+            }
+        } else if !SELECTION_VARIABLES.contains(&key.as_str()) {
+            problems.push(format!(
+                "unrecognized key in CLANG_SYS_SELECTION file ({}): {}",
+                path.display(),
+                key,
+            ));
+        } else if env::var_os(&key).is_some() {
+            problems.push(format!(
+                "{} is set so the value provided for it by the CLANG_SYS_SELECTION \
+                 file ({}) is ignored",
+                key,
+                path.display(),
+            ));
+        }
+    }
+
+    problems
+}
+
 //================================================
 // Commands
 //================================================
@@ -17,6 +348,17 @@ thread_local! {
     static COMMAND_ERRORS: RefCell<HashMap<String, Vec<String>>> = RefCell::default();
 }
 
+/// Returns the target (e.g., `wasm32-wasip1`) if it is a WebAssembly target.
+///
+/// WebAssembly targets don't support shared libraries, so `libclang` can only
+/// be linked to statically (and only with static libraries compiled for the
+/// target) when building for them.
+pub fn wasm_target() -> Option<String> {
+    env::var("TARGET")
+        .ok()
+        .filter(|t| t.starts_with("wasm32-") || t.starts_with("wasm64-"))
+}
+
 /// Adds an error encountered by the build script while executing a command.
 fn add_command_error(name: &str, path: &str, arguments: &[&str], message: String) {
     COMMAND_ERRORS.with(|e| {
@@ -57,30 +399,36 @@ impl Drop for CommandErrorPrinter {
         let errors = COMMAND_ERRORS.with(|e| e.borrow().clone());
 
         if let Some(errors) = errors.get("llvm-config") {
-            println!(
-                "cargo:warning=could not execute `llvm-config` one or more \
-                times, if the LLVM_CONFIG_PATH environment variable is set to \
-                a full path to valid `llvm-config` executable it will be used \
-                to try to find an instance of `libclang` on your system: {}",
-                errors
-                    .iter()
-                    .map(|e| format!("\"{}\"", e))
-                    .collect::<Vec<_>>()
-                    .join("\n  "),
+            warn(
+                Severity::Warning,
+                format!(
+                    "could not execute `llvm-config` one or more \
+                    times, if the LLVM_CONFIG_PATH environment variable is set to \
+                    a full path to valid `llvm-config` executable it will be used \
+                    to try to find an instance of `libclang` on your system: {}",
+                    errors
+                        .iter()
+                        .map(|e| format!("\"{}\"", e))
+                        .collect::<Vec<_>>()
+                        .join("\n  "),
+                ),
             )
         }
 
         if let Some(errors) = errors.get("xcode-select") {
-            println!(
-                "cargo:warning=could not execute `xcode-select` one or more \
-                times, if a valid instance of this executable is on your PATH \
-                it will be used to try to find an instance of `libclang` on \
-                your system: {}",
-                errors
-                    .iter()
-                    .map(|e| format!("\"{}\"", e))
-                    .collect::<Vec<_>>()
-                    .join("\n  "),
+            warn(
+                Severity::Warning,
+                format!(
+                    "could not execute `xcode-select` one or more \
+                    times, if a valid instance of this executable is on your PATH \
+                    it will be used to try to find an instance of `libclang` on \
+                    your system: {}",
+                    errors
+                        .iter()
+                        .map(|e| format!("\"{}\"", e))
+                        .collect::<Vec<_>>()
+                        .join("\n  "),
+                ),
             )
         }
     }
@@ -98,12 +446,65 @@ lazy_static::lazy_static! {
 /// Executes a command and returns the `stdout` output if the command was
 /// successfully executed (errors are added to `COMMAND_ERRORS`).
 fn run_command(name: &str, path: &str, arguments: &[&str]) -> Option<String> {
+    run_command_with_env(name, path, arguments, &[])
+}
+
+/// Executes a command with additional environment variables and returns the
+/// `stdout` output if the command was successfully executed (errors are added
+/// to `COMMAND_ERRORS`).
+fn run_command_with_env(
+    name: &str,
+    path: &str,
+    arguments: &[&str],
+    variables: &[(&str, String)],
+) -> Option<String> {
+    if let Some(replay) = replaying() {
+        return replay_command(&replay, name, arguments);
+    }
+
+    let output = execute_command(name, path, arguments, variables);
+    record_command(name, arguments, &output);
+    output
+}
+
+/// Returns the recorded output of a command.
+fn replay_command(replay: &Recording, name: &str, arguments: &[&str]) -> Option<String> {
+    replay
+        .commands
+        .iter()
+        .find(|(n, a, _)| n == name && a == arguments)
+        .and_then(|(_, _, output)| output.clone())
+}
+
+/// Records the output of a command.
+fn record_command(name: &str, arguments: &[&str], output: &Option<String>) {
+    let arguments = arguments.iter().map(|a| a.to_string()).collect();
+    record(|r| r.commands.push((name.into(), arguments, output.clone())));
+}
+
+/// Executes a command and returns the `stdout` output if the command was
+/// successfully executed (errors are added to `COMMAND_ERRORS`).
+fn execute_command(
+    name: &str,
+    path: &str,
+    arguments: &[&str],
+    variables: &[(&str, String)],
+) -> Option<String> {
     #[cfg(test)]
     if let Some(command) = &*RUN_COMMAND_MOCK.lock().unwrap() {
         return command(name, path, arguments);
     }
 
-    let output = match Command::new(path).args(arguments).output() {
+    let program = if cfg!(windows) {
+        normalize_windows_path(path)
+    } else {
+        path.into()
+    };
+
+    let mut command = Command::new(program);
+    command.args(arguments);
+    command.envs(variables.iter().map(|(k, v)| (k, v)));
+    let output = match command.output() {
         Ok(output) => output,
         Err(error) => {
             let message = format!("error: {}", error);
@@ -128,7 +529,7 @@ fn run_command(name: &str, path: &str, arguments: &[&str]) -> Option<String> {
 /// 2. Auto-detection in well-known platform-specific directories (cached)
 /// 3. Falls back to `"llvm-config"` (relying on PATH lookup)
 fn resolve_llvm_config_path() -> String {
-    if let Ok(path) = env::var("LLVM_CONFIG_PATH") {
+    if let Some(path) = var("LLVM_CONFIG_PATH") {
         return path;
     }
 
@@ -205,12 +606,80 @@ fn get_target_clang_version() -> Option<u32> {
     }
 }
 
+/// How the version of an auto-detected `llvm-config` executable has to relate
+/// to the version targeted by the `clang_X_0` Cargo features.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum VersionPolicy {
+    /// Requires the major version to match the target version.
+    #[default]
+    Exact,
+    /// Accepts a newer major version if the target version isn't installed,
+    /// preferring the oldest version which isn't older than the target version.
+    AtLeast,
+    /// Prefers the newest version which isn't older than the target version.
+    Newest,
+}
+
+impl VersionPolicy {
+    /// Returns the version policy provided by the `CLANG_SYS_VERSION_POLICY`
+    /// environment variable (`exact`, `at-least`, or `newest`), or the default
+    /// version policy if it is not set.
+    pub fn from_env() -> Result<Self, String> {
+        match var("CLANG_SYS_VERSION_POLICY").as_deref() {
+            None | Some("exact") => Ok(VersionPolicy::Exact),
+            Some("at-least") => Ok(VersionPolicy::AtLeast),
+            Some("newest") => Ok(VersionPolicy::Newest),
+            Some(value) => Err(format!(
+                "invalid `CLANG_SYS_VERSION_POLICY` ({}), expected `exact`, `at-least`, or \
+                 `newest`",
+                value,
+            )),
+        }
+    }
+
+    /// Returns the name of this version policy as used in the
+    /// `CLANG_SYS_VERSION_POLICY` environment variable.
+    pub fn name(self) -> &'static str {
+        match self {
+            VersionPolicy::Exact => "exact",
+            VersionPolicy::AtLeast => "at-least",
+            VersionPolicy::Newest => "newest",
+        }
+    }
+
+    /// Returns the index of the `llvm-config` executable to use according to
+    /// this version policy for the supplied target major version, if any.
+    ///
+    /// Executables with the same version are preferred in the order supplied.
+    pub fn select(self, candidates: &[(PathBuf, Vec<u32>)], target: u32) -> Option<usize> {
+        let mut indices = (0..candidates.len()).collect::<Vec<_>>();
+        match self {
+            VersionPolicy::Exact => {}
+            VersionPolicy::AtLeast => {
+                indices.sort_by(|&a, &b| candidates[a].1.cmp(&candidates[b].1))
+            }
+            VersionPolicy::Newest => {
+                indices.sort_by(|&a, &b| candidates[b].1.cmp(&candidates[a].1))
+            }
+        }
+
+        indices.into_iter().find(|&i| {
+            let major = candidates[i].1.first().copied();
+            match self {
+                VersionPolicy::Exact => major == Some(target),
+                _ => major.is_some_and(|m| m >= target),
+            }
+        })
+    }
+}
+
 /// Searches well-known platform-specific directories for an `llvm-config`
 /// executable. Results are cached across calls.
 ///
 /// Prefers the installation matching the target Clang version (derived from
-/// the highest enabled `clang_X_0` feature flag). Falls back to the highest
-/// available version if no exact match is found.
+/// the highest enabled `clang_X_0` feature flag). Whether a newer installation
+/// is used if no exact match is found is determined by the version policy (see
+/// `VersionPolicy::from_env`) and the decision is recorded as a warning.
 fn find_llvm_config() -> Option<String> {
     LLVM_CONFIG_PATH_CACHE.with(|cache| {
         let mut cache = cache.borrow_mut();
@@ -232,6 +701,12 @@ fn find_llvm_config_uncached() -> Option<String> {
     }
 
     let target_version = get_target_clang_version();
+    let policy = VersionPolicy::from_env().unwrap_or_else(|e| {
+        warn(Severity::Warning, format!("clang-sys: {}", e));
+        VersionPolicy::default()
+    });
+
+    let mut candidates: Vec<(PathBuf, Vec<u32>)> = Vec::new();
 
     // If llvm-config is already findable on PATH, check if its version
     // matches our target before accepting it.
@@ -239,20 +714,18 @@ fn find_llvm_config_uncached() -> Option<String> {
         && output.status.success()
     {
         let version_str = String::from_utf8_lossy(&output.stdout);
-        let path_major = version_str
-            .trim()
-            .split('.')
-            .next()
-            .and_then(|s| s.parse::<u32>().ok());
+        let path_major =
+            version::parse_llvm_config_version(&version_str).and_then(|v| v.first().copied());
 
         match (target_version, path_major) {
             // No feature flag set, or version matches -> use PATH.
             (None, _) | (_, None) => return Some("llvm-config".into()),
-            (Some(target), Some(found)) if target == found => {
+            (Some(target), Some(found)) if target == found && policy != VersionPolicy::Newest => {
                 return Some("llvm-config".into());
             }
-            // Version mismatch -> fall through to search for the right one.
-            _ => {}
+            // Version mismatch (or a newer version may be installed elsewhere)
+            // -> consider it along with the other installations found.
+            (Some(_), Some(found)) => candidates.push(("llvm-config".into(), vec![found])),
         }
     }
 
@@ -287,9 +760,14 @@ fn find_llvm_config_uncached() -> Option<String> {
         vec![]
     };
 
-    let mut candidates: Vec<(PathBuf, Vec<u32>)> = Vec::new();
+    // LLVM installed with vcpkg (see `find_vcpkg_directories`).
+    let mut patterns = patterns.into_iter().map(String::from).collect::<Vec<_>>();
+    patterns.extend(find_vcpkg_directories().into_iter().map(|d| {
+        let directory = Pattern::escape(&d.to_string_lossy());
+        format!("{}\\tools\\llvm\\llvm-config.exe", directory)
+    }));
 
-    for pattern in patterns {
+    for pattern in &patterns {
         if let Ok(paths) = glob::glob(pattern) {
             for path in paths.filter_map(Result::ok) {
                 if path.exists() {
@@ -314,36 +792,52 @@ fn find_llvm_config_uncached() -> Option<String> {
     }
 
     // If a target version is specified via feature flags, require a
-    // candidate whose major version matches. Hard-fails if the
-    // requested version is not installed.
+    // candidate whose major version satisfies the version policy. Hard-fails
+    // if no installed version does.
     if let Some(target) = target_version {
-        if let Some((path, _)) = candidates
-            .iter()
-            .find(|(_, v)| v.first().copied() == Some(target))
-        {
+        if let Some(index) = policy.select(&candidates, target) {
+            let (path, version) = &candidates[index];
+            let found = version.first().copied().unwrap_or(target);
             let path_str = path.to_string_lossy().into_owned();
-            println!(
-                "cargo:warning=clang-sys: auto-detected llvm-config (v{}) at: {}",
-                target, path_str
-            );
+            let message = if found == target {
+                format!(
+                    "clang-sys: auto-detected llvm-config (v{}) at: {}",
+                    found, path_str
+                )
+            } else {
+                format!(
+                    "clang-sys: auto-detected llvm-config (v{}) at: {} \
+                     (v{} requested, accepted by the `{}` version policy)",
+                    found,
+                    path_str,
+                    target,
+                    policy.name(),
+                )
+            };
+            warn(Severity::Info, message);
             return Some(path_str);
         }
 
-        // No matching version found. Don't fall back to a different version.
+        // No acceptable version found. Don't fall back to a different version.
         let available: Vec<String> = candidates
             .iter()
             .filter_map(|(_, v)| v.first().map(|n| n.to_string()))
             .collect();
-        println!(
-            "cargo:warning=clang-sys: could not find llvm-config for v{} \
-             (available: {}). Install LLVM {} or set LLVM_CONFIG_PATH.",
-            target,
-            if available.is_empty() {
-                "none".into()
-            } else {
-                available.join(", ")
-            },
-            target,
+        warn(
+            Severity::Warning,
+            format!(
+                "clang-sys: could not find llvm-config for v{} with the `{}` version policy \
+                 (available: {}). Install LLVM {}, set LLVM_CONFIG_PATH, or set \
+                 CLANG_SYS_VERSION_POLICY to `at-least` to accept a newer version.",
+                target,
+                policy.name(),
+                if available.is_empty() {
+                    "none".into()
+                } else {
+                    available.join(", ")
+                },
+                target,
+            ),
         );
         None
     } else {
@@ -351,9 +845,9 @@ fn find_llvm_config_uncached() -> Option<String> {
         candidates.sort_by(|a, b| b.1.cmp(&a.1));
         let (path, _) = &candidates[0];
         let path_str = path.to_string_lossy().into_owned();
-        println!(
-            "cargo:warning=clang-sys: auto-detected llvm-config at: {}",
-            path_str
+        warn(
+            Severity::Info,
+            format!("clang-sys: auto-detected llvm-config at: {}", path_str),
         );
         Some(path_str)
     }
@@ -367,11 +861,10 @@ fn query_llvm_config_version(path: &Path) -> Option<u32> {
         .ok()
         .filter(|o| o.status.success())
         .and_then(|o| {
-            String::from_utf8_lossy(&o.stdout)
-                .trim()
-                .split('.')
-                .next()
-                .and_then(|s| s.parse().ok())
+            let output = String::from_utf8_lossy(&o.stdout);
+            version::parse_llvm_config_version(&output)?
+                .first()
+                .copied()
         })
 }
 
@@ -382,58 +875,261 @@ fn query_llvm_config_version(path: &Path) -> Option<u32> {
 /// - `llvm-17` → `[17]`
 /// - `llvm-config-17` → `[17]`
 /// - Unversioned `llvm` → `[999]` (highest priority, typically the latest)
-fn extract_version_from_llvm_path(path: &Path) -> Vec<u32> {
-    // Check the filename for versioned llvm-config (e.g., llvm-config-17).
+///
+/// Directory names which merely start with `llvm-` or `llvm@` but are not
+/// followed by a version (e.g., `llvm-project-build`) are ignored.
+pub fn extract_version_from_llvm_path(path: &Path) -> Vec<u32> {
+    if let Some(version) = version::parse_llvm_config_path(path) {
+        return version;
+    }
+
+    // A versioned llvm-config with a suffix that isn't a version number
+    // (e.g., llvm-config-garbage); deprioritize.
     if let Some(name) = path.file_name().and_then(|n| n.to_str())
-        && let Some(rest) = name.strip_prefix("llvm-config-")
+        && name.starts_with("llvm-config-")
     {
-        let version: Vec<u32> = rest.split('.').filter_map(|p| p.parse().ok()).collect();
-        if !version.is_empty() {
-            return version;
-        }
-        // Has a suffix but it's not a version number; deprioritize.
         return vec![0];
     }
 
-    // Check path components for versioned directory names.
-    for component in path.components() {
-        let s = component.as_os_str().to_string_lossy();
+    // Unversioned "llvm" directory (e.g., Homebrew's latest formula) gets
+    // highest priority since it typically represents the most recent version.
+    vec![999]
+}
 
-        // Homebrew-style: llvm@17
-        if let Some(rest) = s.strip_prefix("llvm@") {
-            let version: Vec<u32> = rest.split('.').filter_map(|p| p.parse().ok()).collect();
-            if !version.is_empty() {
-                return version;
-            }
+/// Looks up the recorded output for an `llvm-config` command in a file provided
+/// by the `LLVM_CONFIG_OUTPUT` environment variable.
+///
+/// Each non-empty line in the file which does not start with `#` records the
+/// output for a command as the arguments (separated by spaces) followed by `=`
+/// and then the output (e.g., `--libs --link-static=-lLLVMCore -lLLVMSupport`).
+/// If there is no line for the exact arguments, the line for the first argument
+/// alone is used if there is one (e.g., `--libs=...` for `--libs --link-static`).
+fn read_llvm_config_output(path: &str, arguments: &[&str]) -> Option<String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) => {
+            let message = format!("couldn't read LLVM_CONFIG_OUTPUT file: {}", error);
+            add_command_error("llvm-config", path, arguments, message);
+            return None;
         }
+    };
 
-        // Package/directory-style: llvm-17
-        if let Some(rest) = s.strip_prefix("llvm-") {
-            let version: Vec<u32> = rest.split('.').filter_map(|p| p.parse().ok()).collect();
-            if !version.is_empty() {
-                return version;
-            }
-        }
-    }
+    let outputs = contents
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.split_whitespace().collect::<Vec<_>>(), v.trim()))
+        .collect::<Vec<_>>();
+
+    let output = outputs.iter().find(|(k, _)| k == arguments).or_else(|| {
+        let first = arguments.first()?;
+        outputs.iter().find(|(k, _)| k[..] == [*first])
+    });
 
-    // Unversioned "llvm" directory (e.g., Homebrew's latest formula) gets
-    // highest priority since it typically represents the most recent version.
-    vec![999]
+    if let Some((_, output)) = output {
+        Some(format!("{}\n", output))
+    } else {
+        let message = "not recorded in LLVM_CONFIG_OUTPUT file".into();
+        add_command_error("llvm-config", path, arguments, message);
+        None
+    }
 }
 
 /// Executes the `llvm-config` command and returns the `stdout` output if the
 /// command was successfully executed (errors are added to `COMMAND_ERRORS`).
+///
+/// If the `LLVM_CONFIG_OUTPUT` environment variable is set, `llvm-config` is
+/// not executed and the output recorded in the file provided by that
+/// environment variable is returned instead.
 pub fn run_llvm_config(arguments: &[&str]) -> Option<String> {
+    if let Some(replay) = replaying() {
+        return replay_command(&replay, "llvm-config", arguments);
+    }
+
+    if let Some(path) = var("LLVM_CONFIG_OUTPUT") {
+        let output = read_llvm_config_output(&path, arguments);
+        record_command("llvm-config", arguments, &output);
+        return output;
+    }
+
     let path = resolve_llvm_config_path();
     run_command("llvm-config", &path, arguments)
 }
 
 /// Executes the `xcode-select` command and returns the `stdout` output if the
 /// command was successfully executed (errors are added to `COMMAND_ERRORS`).
+///
+/// The command is never executed if the `CLANG_SYS_NO_XCODE` environment
+/// variable is set since, on macOS systems without the Command Line Tools
+/// installed, it may prompt to install them or take several seconds.
 pub fn run_xcode_select(arguments: &[&str]) -> Option<String> {
+    if var("CLANG_SYS_NO_XCODE").is_some() {
+        return None;
+    }
+
     run_command("xcode-select", "xcode-select", arguments)
 }
 
+/// The argument which makes the build script attempt to load a `libclang`
+/// shared library instead of linking to `libclang` (see `run_probe`).
+pub const PROBE_ARGUMENT: &str = "--clang-sys-probe";
+
+/// Executes the build script in a child process to attempt to load the
+/// supplied `libclang` shared library and returns the `stdout` output if the
+/// command was successfully executed (errors are added to `COMMAND_ERRORS`).
+///
+/// The output is `ok` if the shared library could be loaded and a description
+/// of the problem otherwise (e.g., a missing dependency of the shared library).
+pub fn run_probe(path: &str) -> Option<String> {
+    let executable = env::current_exe().ok()?;
+    run_command(
+        "libclang-probe",
+        executable.to_str()?,
+        &[PROBE_ARGUMENT, path],
+    )
+}
+
+/// The environment variables which configure `pkg-config` and are passed to it
+/// with their values for the target (see `pkg_config_var`).
+const PKG_CONFIG_VARIABLES: &[&str] = &[
+    "PKG_CONFIG_PATH",
+    "PKG_CONFIG_LIBDIR",
+    "PKG_CONFIG_SYSROOT_DIR",
+];
+
+/// Returns the value of a `pkg-config` environment variable for the target
+/// following the conventions of the `pkg-config` crate.
+///
+/// The first of these environment variables which is set is used (e.g., for
+/// `PKG_CONFIG_PATH` when building for `aarch64-unknown-linux-gnu`):
+///
+/// 1. `PKG_CONFIG_PATH_aarch64-unknown-linux-gnu`
+/// 2. `PKG_CONFIG_PATH_aarch64_unknown_linux_gnu`
+/// 3. `TARGET_PKG_CONFIG_PATH` when cross-compiling (`HOST_PKG_CONFIG_PATH`
+///    otherwise)
+/// 4. `PKG_CONFIG_PATH`
+fn pkg_config_var(name: &str) -> Option<String> {
+    let target = env::var("TARGET").ok();
+    let kind = if is_cross_compiling() {
+        "TARGET"
+    } else {
+        "HOST"
+    };
+
+    let mut names = vec![];
+    if let Some(target) = &target {
+        names.push(format!("{}_{}", name, target));
+        names.push(format!("{}_{}", name, target.replace('-', "_")));
+    }
+    names.push(format!("{}_{}", kind, name));
+    names.push(name.into());
+    names.iter().find_map(|n| env::var(n).ok())
+}
+
+/// Returns whether the build script is cross-compiling (i.e., whether the
+/// `TARGET` and `HOST` environment variables provided by Cargo differ).
+pub fn is_cross_compiling() -> bool {
+    matches!((env::var("TARGET"), env::var("HOST")), (Ok(t), Ok(h)) if t != h)
+}
+
+/// Executes the `pkg-config` command and returns the `stdout` output if the
+/// command was successfully executed (errors are added to `COMMAND_ERRORS`).
+///
+/// If the `PKG_CONFIG` environment variable is set, it is used as the path to
+/// the `pkg-config` executable. The `PKG_CONFIG_PATH`, `PKG_CONFIG_LIBDIR`, and
+/// `PKG_CONFIG_SYSROOT_DIR` environment variables for the target are passed to
+/// it (see `pkg_config_var`).
+///
+/// When cross-compiling, the packages found by `pkg-config` are usually built
+/// for the host, so it is only executed if `PKG_CONFIG_ALLOW_CROSS` is set to
+/// `1` or if `PKG_CONFIG_SYSROOT_DIR` is set (and `PKG_CONFIG_ALLOW_CROSS` isn't
+/// set to `0`).
+pub fn run_pkg_config(arguments: &[&str]) -> Option<String> {
+    if is_cross_compiling() {
+        let allowed = match pkg_config_var("PKG_CONFIG_ALLOW_CROSS").as_deref() {
+            Some("0") => false,
+            Some("1") => true,
+            _ => pkg_config_var("PKG_CONFIG_SYSROOT_DIR").is_some(),
+        };
+
+        if !allowed {
+            warn(
+                Severity::Debug,
+                "not executing `pkg-config` since cross-compiling (set \
+                PKG_CONFIG_ALLOW_CROSS=1 or PKG_CONFIG_SYSROOT_DIR to use it)",
+            );
+            return None;
+        }
+    }
+
+    let path = pkg_config_var("PKG_CONFIG").unwrap_or_else(|| "pkg-config".into());
+    let variables = PKG_CONFIG_VARIABLES
+        .iter()
+        .filter_map(|n| Some((*n, pkg_config_var(n)?)))
+        .collect::<Vec<_>>();
+    run_command_with_env("pkg-config", &path, arguments, &variables)
+}
+
+/// The `pkg-config` packages which may provide `libclang` (e.g., `libclang.pc`
+/// or `clang.pc`) in the order they are queried.
+const PKG_CONFIG_PACKAGES: &[&str] = &["libclang", "clang"];
+
+/// Returns the value of a path variable (e.g., `libdir` or `includedir`) of the
+/// first `pkg-config` package which provides `libclang`, if any.
+pub fn find_pkg_config_directory(variable: &str) -> Option<PathBuf> {
+    let argument = format!("--variable={}", variable);
+    PKG_CONFIG_PACKAGES.iter().find_map(|p| {
+        let output = run_pkg_config(&[&argument, p])?;
+        let directory = output.lines().next()?.trim();
+        (!directory.is_empty()).then(|| directory.into())
+    })
+}
+
+/// Returns the directory containing the Clang headers (e.g., `clang-c`)
+/// provided by `llvm-config --includedir` or, if `llvm-config` isn't available,
+/// by the `pkg-config` package which provides `libclang`.
+pub fn find_include_directory() -> Option<PathBuf> {
+    run_llvm_config(&["--includedir"])
+        .and_then(|o| o.lines().next().map(|l| PathBuf::from(l.trim_end())))
+        .or_else(|| find_pkg_config_directory("includedir"))
+}
+
+/// The `libclang` library linked to by the build script.
+#[derive(Clone, Debug)]
+pub struct Linked {
+    /// The directory containing the `libclang` shared library (or the Clang
+    /// static libraries).
+    pub libdir: PathBuf,
+    /// The path to the `libclang` shared library or monolithic static library
+    /// (if any).
+    pub library: Option<PathBuf>,
+    /// The version of `libclang` (e.g., `18.1.8`) if it could be determined.
+    pub version: Option<String>,
+}
+
+impl Linked {
+    /// Constructs a new `Linked` with the supplied version components or, if
+    /// they are empty, the version provided by `llvm-config --version`.
+    pub fn new(libdir: PathBuf, library: Option<PathBuf>, version: &[u32]) -> Self {
+        let version = if version.is_empty() {
+            run_llvm_config(&["--version"])
+                .and_then(|o| version::parse_llvm_config_version(&o))
+                .unwrap_or_default()
+        } else {
+            version.to_vec()
+        };
+
+        let version = (!version.is_empty()).then(|| version::format(&version));
+
+        Self {
+            libdir,
+            library,
+            version,
+        }
+    }
+}
+
 //================================================
 // Search Directories
 //================================================
@@ -464,6 +1160,8 @@ const DIRECTORIES_LINUX: &[&str] = &[
     "/usr/lib*/*/*",
     "/usr/lib*/*",
     "/usr/lib*",
+    // GitHub Actions tool cache (`<tool>/<version>/<architecture>`)
+    "/opt/hostedtoolcache/LLVM/*/*/lib",
 ];
 
 /// `libclang` directory patterns for macOS.
@@ -479,6 +1177,8 @@ const DIRECTORIES_MACOS: &[&str] = &[
     "/Applications/Xcode.app/Contents/Developer/Toolchains/XcodeDefault.xctoolchain/usr/lib",
     // MacPorts
     "/opt/local/libexec/llvm-*/lib",
+    // GitHub Actions tool cache (`<tool>/<version>/<architecture>`)
+    "/Users/runner/hostedtoolcache/LLVM/*/*/lib",
 ];
 
 /// `libclang` directory patterns for Windows.
@@ -500,167 +1200,1576 @@ const DIRECTORIES_WINDOWS: &[(&str, bool)] = &[
         "C:\\Program Files*\\Microsoft Visual Studio\\*\\VC\\Tools\\Llvm\\**\\lib",
         true,
     ),
+    // GitHub Actions tool cache (`<tool>/<version>/<architecture>`)
+    ("C:\\hostedtoolcache\\windows\\LLVM\\*\\*\\lib", true),
 ];
 
 /// `libclang` directory patterns for illumos
 const DIRECTORIES_ILLUMOS: &[&str] = &["/opt/ooce/llvm-*/lib", "/opt/ooce/clang-*/lib"];
 
-//================================================
-// Searching
-//================================================
-
-/// Finds the files in a directory that match one or more filename glob patterns
-/// and returns the paths to and filenames of those files.
-fn search_directory(directory: &Path, filenames: &[String]) -> Vec<(PathBuf, String)> {
-    // Escape the specified directory in case it contains characters that have
-    // special meaning in glob patterns (e.g., `[` or `]`).
-    let directory = Pattern::escape(directory.to_str().unwrap());
-    let directory = Path::new(&directory);
-
-    // Join the escaped directory to the filename glob patterns to obtain
-    // complete glob patterns for the files being searched for.
-    let paths = filenames
-        .iter()
-        .map(|f| directory.join(f).to_str().unwrap().to_owned());
-
-    // Prevent wildcards from matching path separators to ensure that the search
-    // is limited to the specified directory.
-    let mut options = MatchOptions::new();
-    options.require_literal_separator = true;
+/// Returns the `libclang` directory patterns for installations of LLVM + Clang
+/// for the current user on Linux, FreeBSD, and macOS (i.e., installations which
+/// don't require root such as those managed by toolchain managers).
+fn find_user_directory_patterns() -> Vec<String> {
+    if !(target_os!("linux") || target_os!("freebsd") || target_os!("macos")) {
+        return vec![];
+    }
 
-    paths
-        .map(|p| glob::glob_with(&p, options))
-        .filter_map(Result::ok)
-        .flatten()
-        .filter_map(|p| {
-            let path = p.ok()?;
-            let filename = path.file_name()?.to_str().unwrap();
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let root = |variable: &str, default: &str| {
+        env::var_os(variable)
+            .map(PathBuf::from)
+            .or_else(|| Some(home.as_ref()?.join(default)))
+    };
 
-            // The `libclang_shared` library has been renamed to `libclang-cpp`
-            // in Clang 10. This can cause instances of this library (e.g.,
-            // `libclang-cpp.so.10`) to be matched by patterns looking for
-            // instances of `libclang`.
-            if filename.contains("-cpp.") {
-                return None;
-            }
+    let data = root("XDG_DATA_HOME", ".local/share");
+    let directories = [
+        // Installations with a user prefix (e.g., `--prefix=~/.local`).
+        (home.clone(), ".local/lib"),
+        (data.clone(), "llvm*/lib"),
+        // asdf (https://asdf-vm.com)
+        (root("ASDF_DATA_DIR", ".asdf"), "installs/llvm/*/lib"),
+        // mise (https://mise.jdx.dev)
+        (
+            env::var_os("MISE_DATA_DIR")
+                .map(PathBuf::from)
+                .or_else(|| Some(data.as_ref()?.join("mise"))),
+            "installs/llvm/*/lib",
+        ),
+        // proto (https://moonrepo.dev/proto)
+        (root("PROTO_HOME", ".proto"), "tools/llvm/*/lib"),
+    ];
+
+    directories
+        .into_iter()
+        .filter_map(|(root, pattern)| {
+            let root = Pattern::escape(root?.to_str()?);
+            Some(Path::new(&root).join(pattern).to_str()?.to_owned())
+        })
+        .collect()
+}
 
-            Some((path.parent().unwrap().to_owned(), filename.into()))
+/// Returns the `libclang` directory patterns for installations of LLVM + Clang
+/// by GitHub Actions workflows (i.e., in the tool cache of a self-hosted runner
+/// or by actions which provide the path to the installation).
+fn find_github_actions_directory_patterns() -> Vec<String> {
+    let directories = [
+        // The tool cache of the runner (the default locations of the tool
+        // cache on hosted runners are also included in the directory patterns
+        // for the target platform).
+        (env::var_os("RUNNER_TOOL_CACHE"), "LLVM/*/*/lib"),
+        // install-llvm-action (https://github.com/KyleMayes/install-llvm-action)
+        (env::var_os("LLVM_PATH"), "lib"),
+    ];
+
+    directories
+        .into_iter()
+        .filter_map(|(root, pattern)| {
+            let root = Pattern::escape(root?.to_str()?);
+            Some(Path::new(&root).join(pattern).to_str()?.to_owned())
         })
-        .collect::<Vec<_>>()
+        .collect()
 }
 
-/// Finds the files in a directory (and any relevant sibling directories) that
-/// match one or more filename glob patterns and returns the paths to and
-/// filenames of those files.
-fn search_directories(directory: &Path, filenames: &[String]) -> Vec<(PathBuf, String)> {
-    let mut results = search_directory(directory, filenames);
+//================================================
+// vcpkg
+//================================================
 
-    // On Windows, `libclang.dll` is usually found in the LLVM `bin` directory
-    // while `libclang.lib` is usually found in the LLVM `lib` directory. To
-    // keep things consistent with other platforms, only LLVM `lib` directories
-    // are included in the backup search directory globs so we need to search
-    // the LLVM `bin` directory here.
-    if target_os!("windows") && directory.ends_with("lib") {
-        let sibling = directory.parent().unwrap().join("bin");
-        results.extend(search_directory(&sibling, filenames));
+/// Returns the vcpkg triplets which may contain LLVM for the target in order of
+/// preference (e.g., `x64-windows`, `x64-windows-static-md`, and
+/// `x64-windows-static`) or the triplet provided by the `VCPKGRS_TRIPLET`
+/// environment variable (used by the `vcpkg` crate).
+fn find_vcpkg_triplets() -> Vec<String> {
+    if let Ok(triplet) = env::var("VCPKGRS_TRIPLET") {
+        return vec![triplet];
     }
 
-    results
+    let arch = if target_arch!("x86_64") {
+        "x64"
+    } else if target_arch!("x86") {
+        "x86"
+    } else if target_arch!("aarch64") {
+        "arm64"
+    } else {
+        return vec![];
+    };
+
+    ["windows", "windows-static-md", "windows-static"]
+        .iter()
+        .map(|s| format!("{}-{}", arch, s))
+        .collect()
 }
 
-/// Finds the `libclang` static or dynamic libraries matching one or more
-/// filename glob patterns and returns the paths to and filenames of those files.
-pub fn search_libclang_directories(filenames: &[String], variable: &str) -> Vec<(PathBuf, String)> {
-    // Search only the path indicated by the relevant environment variable
-    // (e.g., `LIBCLANG_PATH`) if it is set.
-    if let Ok(path) = env::var(variable).map(|d| Path::new(&d).to_path_buf()) {
-        // Check if the path is a matching file.
-        if let Some(parent) = path.parent() {
-            let filename = path.file_name().unwrap().to_str().unwrap();
-            let libraries = search_directories(parent, filenames);
-            if libraries.iter().any(|(_, f)| f == filename) {
-                return vec![(parent.into(), filename.into())];
+/// Returns the directories of the vcpkg triplets which may contain LLVM for
+/// the target (see `find_vcpkg_triplets`) on Windows.
+///
+/// The triplets are found in the directory provided by the
+/// `VCPKG_INSTALLED_DIR` environment variable (for manifest mode) or in the
+/// `installed` directory in the vcpkg installation provided by the `VCPKG_ROOT`
+/// environment variable (or by the `VCPKG_INSTALLATION_ROOT` environment
+/// variable, which is set on GitHub Actions runners).
+fn find_vcpkg_directories() -> Vec<PathBuf> {
+    if !target_os!("windows") {
+        return vec![];
+    }
+
+    let installed = if let Some(directory) = env::var_os("VCPKG_INSTALLED_DIR") {
+        PathBuf::from(directory)
+    } else if let Some(root) =
+        env::var_os("VCPKG_ROOT").or_else(|| env::var_os("VCPKG_INSTALLATION_ROOT"))
+    {
+        Path::new(&root).join("installed")
+    } else {
+        return vec![];
+    };
+
+    find_vcpkg_triplets()
+        .iter()
+        .map(|t| installed.join(t))
+        .collect()
+}
+
+//================================================
+// Android
+//================================================
+
+/// Environment variables which provide a path to an Android NDK.
+const ANDROID_NDK_VARIABLES: &[&str] = &["ANDROID_NDK_HOME", "ANDROID_NDK_ROOT", "ANDROID_NDK"];
+
+/// Environment variables which provide a path to an Android SDK.
+const ANDROID_SDK_VARIABLES: &[&str] = &["ANDROID_HOME", "ANDROID_SDK_ROOT"];
+
+/// Parses the `Pkg.Revision` property in the `source.properties` file in an
+/// Android NDK (e.g., `Pkg.Revision = 26.1.10909125`).
+fn parse_android_ndk_revision(ndk: &Path) -> Vec<u32> {
+    let properties = std::fs::read_to_string(ndk.join("source.properties")).unwrap_or_default();
+    properties
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .find(|(k, _)| k.trim() == "Pkg.Revision")
+        .map(|(_, v)| v.trim().split('.').filter_map(|p| p.parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Finds the Android NDKs on this system and returns the paths to them.
+///
+/// NDKs provided directly by an environment variable (e.g., `ANDROID_NDK_HOME`)
+/// are listed first. These are followed by the NDKs installed in Android SDKs
+/// provided by an environment variable (e.g., `ANDROID_HOME`) or installed in
+/// the default Android SDK location used by Android Studio, listed in
+/// descending order of NDK revision.
+fn find_android_ndks() -> Vec<PathBuf> {
+    let mut ndks = ANDROID_NDK_VARIABLES
+        .iter()
+        .filter_map(env::var_os)
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+
+    let mut sdks = ANDROID_SDK_VARIABLES
+        .iter()
+        .filter_map(env::var_os)
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+
+    // We use temporary directories when testing the build script so we'll
+    // ignore the default Android SDK locations.
+    if !test!() {
+        if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+            sdks.push(home.join("Android/Sdk"));
+            sdks.push(home.join("Library/Android/sdk"));
+        }
+
+        if let Some(local) = env::var_os("LOCALAPPDATA").map(PathBuf::from) {
+            sdks.push(local.join("Android").join("Sdk"));
+        }
+    }
+
+    let mut installed = vec![];
+    for sdk in sdks.iter().filter(|s| s.is_dir()) {
+        installed.push(sdk.join("ndk-bundle"));
+        if let Some(sdk) = sdk.to_str() {
+            let pattern = Path::new(&Pattern::escape(sdk)).join("ndk").join("*");
+            if let Ok(paths) = glob::glob(&pattern.to_string_lossy()) {
+                installed.extend(paths.filter_map(Result::ok));
             }
         }
+    }
 
-        // Check if the path is directory containing a matching file.
-        return search_directories(&path, filenames);
+    installed.retain(|n| n.is_dir());
+    installed.sort_by_cached_key(|n| std::cmp::Reverse(parse_android_ndk_revision(n)));
+
+    for ndk in installed {
+        if !ndks.contains(&ndk) {
+            ndks.push(ndk);
+        }
     }
 
-    let mut found = vec![];
+    ndks
+}
 
-    // Search the `bin` and `lib` directories in the directory returned by
-    // `llvm-config --prefix`.
-    if let Some(output) = run_llvm_config(&["--prefix"]) {
-        let directory = Path::new(output.lines().next().unwrap()).to_path_buf();
-        found.extend(search_directories(&directory.join("bin"), filenames));
-        found.extend(search_directories(&directory.join("lib"), filenames));
-        found.extend(search_directories(&directory.join("lib64"), filenames));
+/// Finds the directories in the Android NDKs on this system which may contain
+/// `libclang` and returns the paths to those directories.
+fn find_android_ndk_directories() -> Vec<PathBuf> {
+    let mut directories = vec![];
+    for ndk in find_android_ndks() {
+        let Some(ndk) = ndk.to_str() else { continue };
+        let prebuilt = Path::new(&Pattern::escape(ndk)).join("toolchains/llvm/prebuilt/*");
+        for lib in ["lib", "lib64"] {
+            let pattern = prebuilt.join(lib);
+            if let Ok(paths) = glob::glob(&pattern.to_string_lossy()) {
+                directories.extend(paths.filter_map(Result::ok).filter(|p| p.is_dir()));
+            }
+        }
     }
+    directories
+}
 
-    // Search the toolchain directory in the directory returned by
-    // `xcode-select --print-path`.
-    if target_os!("macos")
-        && let Some(output) = run_xcode_select(&["--print-path"])
-    {
-        let directory = Path::new(output.lines().next().unwrap()).to_path_buf();
+/// Returns the version of the Clang toolchain in an Android NDK if the supplied
+/// directory is a directory in such a toolchain (e.g.,
+/// `toolchains/llvm/prebuilt/linux-x86_64/lib`).
+///
+/// The version is read from the `AndroidVersion.txt` file included in the NDK
+/// toolchain since the `libclang` shared libraries in these toolchains are not
+/// reliably versioned (e.g., `libclang.so.17git`).
+pub fn android_ndk_clang_version(directory: &Path) -> Option<Vec<u32>> {
+    let toolchain = directory.parent()?;
+    let contents = std::fs::read_to_string(toolchain.join("AndroidVersion.txt")).ok()?;
+    let version = contents
+        .lines()
+        .next()?
+        .trim()
+        .split('.')
+        .map_while(|p| p.parse().ok())
+        .collect::<Vec<_>>();
+    (!version.is_empty()).then_some(version)
+}
+
+//================================================
+// Paths
+//================================================
+
+/// The maximum length (in UTF-16 code units and including the null terminator)
+/// of a path that can be passed to most Windows APIs without an extended-length
+/// prefix.
+const MAX_PATH: usize = 260;
+
+/// Returns whether the supplied Windows path is an absolute path that starts
+/// with a drive letter (e.g., `C:\LLVM`).
+fn is_drive_absolute(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'\\' || bytes[2] == b'/')
+}
+
+/// Removes the extended-length prefix (i.e., `\\?\` or `\\?\UNC\`) from the
+/// supplied Windows path if it can be represented without one.
+///
+/// Glob patterns and some programs don't support these prefixes which are
+/// added by `std::fs::canonicalize` and are common on CI agents.
+pub fn strip_extended_length_prefix(path: &str) -> String {
+    if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        return format!(r"\\{}", unc);
+    }
+
+    match path.strip_prefix(r"\\?\") {
+        Some(stripped) if is_drive_absolute(stripped) => stripped.into(),
+        _ => path.into(),
+    }
+}
+
+/// Adds an extended-length prefix (i.e., `\\?\` or `\\?\UNC\`) to the supplied
+/// absolute Windows path if it is too long to be used without one.
+///
+/// Windows APIs like `LoadLibraryExW` and `CreateProcessW` don't support paths
+/// longer than `MAX_PATH` unless they have an extended-length prefix (or long
+/// paths are enabled for the process).
+pub fn add_extended_length_prefix(path: &str) -> String {
+    if path.starts_with(r"\\?\") || path.encode_utf16().count() < MAX_PATH {
+        return path.into();
+    }
+
+    // Extended-length paths are not normalized so they must only contain
+    // backslashes as path separators.
+    let path = path.replace('/', r"\");
+    if let Some(unc) = path.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{}", unc)
+    } else if is_drive_absolute(&path) {
+        format!(r"\\?\{}", path)
+    } else {
+        path
+    }
+}
+
+/// Normalizes the supplied Windows path so that it has an extended-length
+/// prefix if and only if it is too long to be used without one.
+pub fn normalize_windows_path(path: &str) -> String {
+    add_extended_length_prefix(&strip_extended_length_prefix(path))
+}
+
+//================================================
+// Recording
+//================================================
+
+/// The environment variables recorded by `CLANG_SYS_RECORD` (see `Recording`),
+/// which are all of the environment variables consulted when searching for a
+/// `libclang` shared library.
+pub const RECORDED_VARIABLES: &[&str] = &[
+    "ANDROID_HOME",
+    "ANDROID_NDK",
+    "ANDROID_NDK_HOME",
+    "ANDROID_NDK_ROOT",
+    "ANDROID_SDK_ROOT",
+    "ASDF_DATA_DIR",
+    "CLANG_SYS_LIBCLANG_PATH",
+    "CLANG_SYS_NO_XCODE",
+    "CLANG_SYS_SELECTION",
+    "HOME",
+    "LD_LIBRARY_PATH",
+    "LIBCLANG_GLIBC_BASELINE",
+    "LIBCLANG_GLIBCXX_BASELINE",
+    "LIBCLANG_PATH",
+    "LIBCLANG_PATH_DEBUG",
+    "LIBCLANG_PATH_RELEASE",
+    "LIBCLANG_PROBE",
+    "LIBCLANG_SELECTION_POLICY",
+    "LLVM_CONFIG_OUTPUT",
+    "LLVM_CONFIG_PATH",
+    "LLVM_PATH",
+    "MISE_DATA_DIR",
+    "PATH",
+    "PKG_CONFIG",
+    "PKG_CONFIG_ALLOW_CROSS",
+    "PKG_CONFIG_LIBDIR",
+    "PKG_CONFIG_PATH",
+    "PKG_CONFIG_SYSROOT_DIR",
+    "PROFILE",
+    "PROTO_HOME",
+    "RUNNER_TOOL_CACHE",
+    "VCPKGRS_TRIPLET",
+    "VCPKG_INSTALLATION_ROOT",
+    "VCPKG_INSTALLED_DIR",
+    "VCPKG_ROOT",
+    "XDG_DATA_HOME",
+];
+
+/// The properties of a library found by a search which were determined by
+/// reading the library.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LibraryProperties {
+    /// Whether the library is valid for the target (or why it isn't).
+    pub valid: Result<(), String>,
+    /// The version of the library (empty if unknown or the library is invalid).
+    pub version: Vec<u32>,
+    /// Whether the library can be loaded on this system (or why it can't).
+    pub compatible: Result<(), String>,
+}
+
+/// A recording of everything consulted while finding `libclang` which can be
+/// replayed to find `libclang` again in exactly the same way (e.g., to
+/// reproduce the selection of a `libclang` shared library on another system).
+///
+/// A recording is made by the search for a `libclang` shared library when the
+/// `CLANG_SYS_RECORD` environment variable is set to the path the recording
+/// should be written to. Recordings are written as text with one record per
+/// line and fields separated by tabs (see `Recording::serialize`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    /// The target operating system, architecture, pointer width, and
+    /// environment.
+    pub target: Vec<String>,
+    /// Whether `libclang` was searched for to be linked to at runtime.
+    pub runtime: bool,
+    /// The environment variables which were set.
+    pub variables: Vec<(String, String)>,
+    /// Whether each discovery strategy was exclusive, the kind of locations it
+    /// provided, and the locations it provided (see `DiscoveryStrategy`).
+    pub strategies: Vec<(bool, DiscoverySource, Vec<Vec<PathBuf>>)>,
+    /// The names, arguments, and outputs of the commands executed.
+    pub commands: Vec<(String, Vec<String>, Option<String>)>,
+    /// The entries in the directories read (`None` for directories which
+    /// couldn't be read).
+    pub directories: Vec<(PathBuf, Option<Entries>)>,
+    /// The properties of the libraries found.
+    pub libraries: Vec<(PathBuf, LibraryProperties)>,
+    /// The path to the library selected or the error message.
+    pub result: Option<Result<PathBuf, String>>,
+}
+
+impl Recording {
+    /// Returns this recording as text.
+    ///
+    /// Each line is one of the following records (where `+` and `-` indicate
+    /// success and failure respectively):
+    ///
+    /// * `target <os> <arch> <pointer width> <env>`
+    /// * `runtime <true|false>`
+    /// * `variable <name> <value>`
+    /// * `strategy <+ (exclusive)|-> <source>` followed by `group <path>...`
+    ///   records
+    /// * `command <name> <+output|-> <argument>...`
+    /// * `directory <path> <+|-> <entry>...` (directories end with `/`)
+    /// * `library <path> <+|-message> <version> <+|-message>` (whether the
+    ///   library is valid, its version, and whether it is compatible)
+    /// * `result <+path|-message>`
+    pub fn serialize(&self) -> String {
+        fn status(result: &Result<(), String>) -> String {
+            match result {
+                Ok(()) => "+".into(),
+                Err(message) => format!("-{}", message),
+            }
+        }
+
+        let mut target = vec!["target".into()];
+        target.extend(self.target.iter().cloned());
+        let mut lines = vec![target, vec!["runtime".into(), self.runtime.to_string()]];
+
+        for (name, value) in &self.variables {
+            lines.push(vec!["variable".into(), name.clone(), value.clone()]);
+        }
+
+        for (exclusive, source, groups) in &self.strategies {
+            let exclusive = if *exclusive { "+" } else { "-" };
+            lines.push(vec![
+                "strategy".into(),
+                exclusive.into(),
+                source.name().into(),
+            ]);
+            for group in groups {
+                let mut line = vec!["group".into()];
+                line.extend(group.iter().map(|p| p.to_string_lossy().into_owned()));
+                lines.push(line);
+            }
+        }
+
+        for (name, arguments, output) in &self.commands {
+            let output = match output {
+                Some(output) => format!("+{}", output),
+                None => "-".into(),
+            };
+            let mut line = vec!["command".into(), name.clone(), output];
+            line.extend(arguments.iter().cloned());
+            lines.push(line);
+        }
+
+        for (directory, entries) in &self.directories {
+            let mut line = vec!["directory".into(), directory.to_string_lossy().into_owned()];
+            if let Some(entries) = entries {
+                line.push("+".into());
+                line.extend(entries.iter().map(|(name, directory)| {
+                    if *directory {
+                        format!("{}/", name)
+                    } else {
+                        name.clone()
+                    }
+                }));
+            } else {
+                line.push("-".into());
+            }
+            lines.push(line);
+        }
+
+        for (path, properties) in &self.libraries {
+            let version = properties.version.iter().map(|v| v.to_string());
+            lines.push(vec![
+                "library".into(),
+                path.to_string_lossy().into_owned(),
+                status(&properties.valid),
+                version.collect::<Vec<_>>().join("."),
+                status(&properties.compatible),
+            ]);
+        }
+
+        match &self.result {
+            Some(Ok(path)) => lines.push(vec![
+                "result".into(),
+                format!("+{}", path.to_string_lossy()),
+            ]),
+            Some(Err(message)) => lines.push(vec!["result".into(), format!("-{}", message)]),
+            None => {}
+        }
+
+        let escape = |field: &String| {
+            field
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+        };
+
+        let mut text =
+            "# Recorded by clang-sys while finding `libclang` (CLANG_SYS_RECORD).\n".to_string();
+        for line in lines {
+            text.push_str(&line.iter().map(escape).collect::<Vec<_>>().join("\t"));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Parses a recording returned by `Recording::serialize`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        fn unescape(field: &str) -> String {
+            let mut unescaped = String::with_capacity(field.len());
+            let mut chars = field.chars();
+            while let Some(c) = chars.next() {
+                if c != '\\' {
+                    unescaped.push(c);
+                    continue;
+                }
+
+                match chars.next() {
+                    Some('t') => unescaped.push('\t'),
+                    Some('n') => unescaped.push('\n'),
+                    Some('r') => unescaped.push('\r'),
+                    Some(c) => unescaped.push(c),
+                    None => unescaped.push('\\'),
+                }
+            }
+            unescaped
+        }
+
+        fn status(field: &str) -> Result<(), String> {
+            match field.strip_prefix('-') {
+                Some(message) => Err(message.into()),
+                None => Ok(()),
+            }
+        }
+
+        let mut recording = Recording::default();
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.split('\t').map(unescape).collect::<Vec<_>>();
+            let invalid = || format!("invalid record on line {}: {}", index + 1, line);
+            let field = |index: usize| fields.get(index).cloned().ok_or_else(invalid);
+            match fields[0].as_str() {
+                "target" => recording.target = fields[1..].to_vec(),
+                "runtime" => recording.runtime = field(1)? == "true",
+                "variable" => recording.variables.push((field(1)?, field(2)?)),
+                "strategy" => {
+                    // Recordings made before the sources of locations were
+                    // recorded treat every location as custom.
+                    let source = fields.get(2).map_or(Some(DiscoverySource::Custom), |s| {
+                        DiscoverySource::from_name(s)
+                    });
+                    let source = source.ok_or_else(invalid)?;
+                    recording
+                        .strategies
+                        .push((field(1)? == "+", source, vec![]));
+                }
+                "group" => {
+                    let (_, _, groups) = recording.strategies.last_mut().ok_or_else(invalid)?;
+                    groups.push(fields[1..].iter().map(PathBuf::from).collect());
+                }
+                "command" => {
+                    let output = field(2)?;
+                    let output = output.strip_prefix('+').map(|o| o.to_string());
+                    let arguments = fields.get(3..).unwrap_or_default().to_vec();
+                    recording.commands.push((field(1)?, arguments, output));
+                }
+                "directory" => {
+                    let entries = (field(2)? == "+").then(|| {
+                        let entries = fields[3..].iter().map(|e| match e.strip_suffix('/') {
+                            Some(name) => (name.to_string(), true),
+                            None => (e.clone(), false),
+                        });
+                        Rc::new(entries.collect())
+                    });
+                    recording.directories.push((field(1)?.into(), entries));
+                }
+                "library" => {
+                    let properties = LibraryProperties {
+                        valid: status(&field(2)?),
+                        version: version::parse_components(&field(3)?),
+                        compatible: status(&field(4)?),
+                    };
+                    recording.libraries.push((field(1)?.into(), properties));
+                }
+                "result" => {
+                    let result = field(1)?;
+                    recording.result = Some(match result.strip_prefix('-') {
+                        Some(message) => Err(message.into()),
+                        None => Ok(result.trim_start_matches('+').into()),
+                    });
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(recording)
+    }
+}
+
+thread_local! {
+    /// The recording being made, if any (see `start_recording`).
+    static RECORDING: RefCell<Option<Recording>> = RefCell::default();
+    /// The recording being replayed, if any (see `set_replay`).
+    static REPLAY: RefCell<Option<Rc<Recording>>> = RefCell::default();
+}
+
+/// Returns the target operating system, architecture, pointer width, and
+/// environment.
+///
+/// The build script is compiled for the host, so the target is provided by the
+/// `CARGO_CFG_TARGET_*` environment variables Cargo sets for build scripts
+/// (the target this code was compiled for is used if they aren't set, e.g., at
+/// runtime).
+fn recorded_target() -> Vec<String> {
+    if test!() {
+        return [
+            "_CLANG_SYS_TEST_OS",
+            "_CLANG_SYS_TEST_ARCH",
+            "_CLANG_SYS_TEST_POINTER_WIDTH",
+            "_CLANG_SYS_TEST_ENV",
+        ]
+        .iter()
+        .map(|v| env::var(v).unwrap_or_default())
+        .collect();
+    }
+
+    let env = if cfg!(target_env = "msvc") {
+        "msvc"
+    } else if cfg!(target_env = "gnu") {
+        "gnu"
+    } else if cfg!(target_env = "musl") {
+        "musl"
+    } else {
+        ""
+    };
+
+    let cfg = |name: &str, default: String| {
+        env::var(format!("CARGO_CFG_TARGET_{}", name)).unwrap_or(default)
+    };
+
+    vec![
+        cfg("OS", env::consts::OS.into()),
+        cfg("ARCH", env::consts::ARCH.into()),
+        cfg("POINTER_WIDTH", usize::BITS.to_string()),
+        cfg("ENV", env.into()),
+    ]
+}
+
+/// Starts recording everything consulted while finding `libclang` (unless a
+/// recording is being replayed).
+pub fn start_recording(runtime: bool) {
+    if is_replaying() {
+        return;
+    }
+
+    let variables = RECORDED_VARIABLES
+        .iter()
+        .filter_map(|v| Some((v.to_string(), env::var(v).ok()?)))
+        .collect();
+
+    let recording = Recording {
+        target: recorded_target(),
+        runtime,
+        variables,
+        ..Default::default()
+    };
+
+    RECORDING.with(|r| *r.borrow_mut() = Some(recording));
+}
+
+/// Stops recording and writes the recording (with the supplied result) to the
+/// supplied path.
+pub fn finish_recording(path: &Path, result: &Result<PathBuf, String>) {
+    let Some(mut recording) = RECORDING.with(|r| r.borrow_mut().take()) else {
+        return;
+    };
+
+    recording.result = Some(result.clone());
+    if let Err(error) = fs::write(path, recording.serialize()) {
+        let hint = if error.kind() == io::ErrorKind::ReadOnlyFilesystem {
+            ", set CLANG_SYS_RECORD to a path on a writable file system"
+        } else {
+            ""
+        };
+        warn(
+            Severity::Warning,
+            format!(
+                "couldn't write CLANG_SYS_RECORD file ({}): {}{}",
+                path.display(),
+                error,
+                hint,
+            ),
+        );
+    }
+}
+
+/// Calls the supplied function with the recording being made, if any.
+fn record(f: impl FnOnce(&mut Recording)) {
+    RECORDING.with(|r| {
+        if let Some(recording) = r.borrow_mut().as_mut() {
+            f(recording);
+        }
+    });
+}
+
+/// Records the properties of a library found by a search.
+pub fn record_library(path: &Path, properties: &LibraryProperties) {
+    record(|r| r.libraries.push((path.into(), properties.clone())));
+}
+
+/// Sets the recording to replay instead of consulting the environment, the
+/// file system, and commands when finding `libclang` (or stops replaying).
+pub fn set_replay(recording: Option<Recording>) {
+    REPLAY.with(|r| *r.borrow_mut() = recording.map(Rc::new));
+}
+
+/// Returns whether a recording is being replayed.
+pub fn is_replaying() -> bool {
+    REPLAY.with(|r| r.borrow().is_some())
+}
+
+/// Returns the recording being replayed, if any.
+fn replaying() -> Option<Rc<Recording>> {
+    REPLAY.with(|r| r.borrow().clone())
+}
+
+/// Returns the recorded properties of a library found by a search if a
+/// recording is being replayed.
+pub fn replay_library(path: &Path) -> Option<LibraryProperties> {
+    let replay = replaying()?;
+    let properties = replay.libraries.iter().find(|(p, _)| p == path);
+    Some(match properties {
+        Some((_, properties)) => properties.clone(),
+        None => LibraryProperties {
+            valid: Err("not recorded".into()),
+            version: vec![],
+            compatible: Ok(()),
+        },
+    })
+}
+
+//================================================
+// Discovery
+//================================================
+
+/// The kind of location a `libclang` library was found in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DiscoverySource {
+    /// A path provided by an environment variable (e.g., `LIBCLANG_PATH` or
+    /// `LD_LIBRARY_PATH`).
+    Environment,
+    /// A directory in the installation returned by `llvm-config --prefix`.
+    LlvmConfig,
+    /// The library directory of a `pkg-config` package which provides
+    /// `libclang` (e.g., `libclang.pc`).
+    PkgConfig,
+    /// A directory in a vcpkg installation (e.g., `installed/x64-windows/bin`).
+    Vcpkg,
+    /// A known installation directory for the target platform (e.g.,
+    /// `/usr/lib/llvm-*/lib`, the Xcode toolchain, or an Android NDK).
+    KnownDirectory,
+    /// A location provided by a custom strategy (see `set_discovery_strategies`)
+    /// or supplied to the search.
+    Custom,
+}
+
+impl DiscoverySource {
+    fn name(self) -> &'static str {
+        match self {
+            DiscoverySource::Environment => "env",
+            DiscoverySource::LlvmConfig => "llvm-config",
+            DiscoverySource::PkgConfig => "pkg-config",
+            DiscoverySource::Vcpkg => "vcpkg",
+            DiscoverySource::KnownDirectory => "known-dir",
+            DiscoverySource::Custom => "custom",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [
+            DiscoverySource::Environment,
+            DiscoverySource::LlvmConfig,
+            DiscoverySource::PkgConfig,
+            DiscoverySource::Vcpkg,
+            DiscoverySource::KnownDirectory,
+            DiscoverySource::Custom,
+        ]
+        .into_iter()
+        .find(|s| s.name() == name)
+    }
+}
+
+/// A strategy for finding the locations which may contain `libclang` libraries
+/// (e.g., the directories provided by an environment variable).
+///
+/// The locations returned by the strategies used by a search are searched in
+/// the order the strategies are used in. Custom strategies (e.g., for locations
+/// specific to an environment) can be added with `set_discovery_strategies`.
+pub trait DiscoveryStrategy: Send + Sync {
+    /// Returns the files or directories which may contain `libclang` libraries
+    /// grouped by search priority (the locations in the same group share the
+    /// same search priority and earlier groups have lower search priorities).
+    fn locations(&self) -> Vec<Vec<PathBuf>>;
+
+    /// Returns whether the locations returned by this strategy (if any) are the
+    /// only ones searched.
+    fn is_exclusive(&self) -> bool {
+        false
+    }
+
+    /// Returns the kind of locations returned by this strategy.
+    fn source(&self) -> DiscoverySource {
+        DiscoverySource::Custom
+    }
+}
+
+/// The custom strategies used before the built-in strategies (other than the
+/// environment variable which provides the path to `libclang`).
+static DISCOVERY_STRATEGIES: Mutex<Vec<Arc<dyn DiscoveryStrategy>>> = Mutex::new(Vec::new());
+
+/// Sets the custom strategies used to find `libclang` libraries and returns the
+/// previous custom strategies.
+///
+/// Custom strategies are used in the order they are provided after the
+/// environment variable which provides the path to `libclang` (e.g.,
+/// `LIBCLANG_PATH`) and before the built-in strategies.
+pub fn set_discovery_strategies(
+    strategies: Vec<Arc<dyn DiscoveryStrategy>>,
+) -> Vec<Arc<dyn DiscoveryStrategy>> {
+    let mut previous = DISCOVERY_STRATEGIES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    std::mem::replace(&mut *previous, strategies)
+}
+
+/// Finds the path provided by an environment variable (e.g., `LIBCLANG_PATH`).
+struct EnvironmentVariable(String);
+
+impl DiscoveryStrategy for EnvironmentVariable {
+    fn locations(&self) -> Vec<Vec<PathBuf>> {
+        let Some(path) = profile_var(&self.0).or_else(|| var(&self.0)) else {
+            return vec![];
+        };
+
+        // Linkers and other tools don't reliably support extended-length
+        // prefixes so they are removed from the paths this search returns.
+        let path = if cfg!(windows) {
+            PathBuf::from(strip_extended_length_prefix(&path))
+        } else {
+            PathBuf::from(path)
+        };
+
+        vec![vec![path]]
+    }
+
+    fn is_exclusive(&self) -> bool {
+        true
+    }
+
+    fn source(&self) -> DiscoverySource {
+        DiscoverySource::Environment
+    }
+}
+
+/// Finds the `bin` and `lib` directories in the directory returned by
+/// `llvm-config --prefix`.
+struct LlvmConfig;
+
+impl DiscoveryStrategy for LlvmConfig {
+    fn locations(&self) -> Vec<Vec<PathBuf>> {
+        let Some(output) = run_llvm_config(&["--prefix"]) else {
+            return vec![];
+        };
+
+        let directory = Path::new(output.lines().next().unwrap());
+        ["bin", "lib", "lib64"]
+            .iter()
+            .map(|d| vec![directory.join(d)])
+            .collect()
+    }
+
+    fn source(&self) -> DiscoverySource {
+        DiscoverySource::LlvmConfig
+    }
+}
+
+/// Finds the library directory of the `pkg-config` package which provides
+/// `libclang` (see `PKG_CONFIG_PACKAGES`).
+struct PkgConfig;
+
+impl DiscoveryStrategy for PkgConfig {
+    fn locations(&self) -> Vec<Vec<PathBuf>> {
+        find_pkg_config_directory("libdir")
+            .map(|d| vec![vec![d]])
+            .unwrap_or_default()
+    }
+
+    fn source(&self) -> DiscoverySource {
+        DiscoverySource::PkgConfig
+    }
+}
+
+/// Finds the `bin` and `lib` directories of the vcpkg triplets which may
+/// contain LLVM on Windows (see `find_vcpkg_directories`).
+struct Vcpkg;
+
+impl DiscoveryStrategy for Vcpkg {
+    fn locations(&self) -> Vec<Vec<PathBuf>> {
+        find_vcpkg_directories()
+            .into_iter()
+            .flat_map(|d| ["bin", "lib"].map(|s| vec![d.join(s)]))
+            .collect()
+    }
+
+    fn source(&self) -> DiscoverySource {
+        DiscoverySource::Vcpkg
+    }
+}
+
+/// Finds the toolchain directory in the directory returned by
+/// `xcode-select --print-path` on macOS.
+struct XcodeSelect;
+
+impl DiscoveryStrategy for XcodeSelect {
+    fn locations(&self) -> Vec<Vec<PathBuf>> {
+        if !target_os!("macos") {
+            return vec![];
+        }
+
+        let Some(output) = run_xcode_select(&["--print-path"]) else {
+            return vec![];
+        };
+
+        let directory = Path::new(output.lines().next().unwrap());
         let directory = directory.join("Toolchains/XcodeDefault.xctoolchain/usr/lib");
-        found.extend(search_directories(&directory, filenames));
+        vec![vec![directory]]
+    }
+
+    fn source(&self) -> DiscoverySource {
+        DiscoverySource::KnownDirectory
     }
+}
 
-    // Search the directories in the `LD_LIBRARY_PATH` environment variable.
-    if let Ok(path) = env::var("LD_LIBRARY_PATH") {
-        for directory in env::split_paths(&path) {
-            found.extend(search_directories(&directory, filenames));
+/// Finds the directories in the `LD_LIBRARY_PATH` environment variable.
+struct LdLibraryPath;
+
+impl DiscoveryStrategy for LdLibraryPath {
+    fn locations(&self) -> Vec<Vec<PathBuf>> {
+        let Ok(path) = env::var("LD_LIBRARY_PATH") else {
+            return vec![];
+        };
+
+        env::split_paths(&path).map(|d| vec![d]).collect()
+    }
+
+    fn source(&self) -> DiscoverySource {
+        DiscoverySource::Environment
+    }
+}
+
+/// Finds the directories matching the `libclang` directory patterns for the
+/// target platform (followed by the patterns for installations for the current
+/// user and by GitHub Actions workflows).
+struct DirectoryPatterns;
+
+impl DiscoveryStrategy for DirectoryPatterns {
+    fn locations(&self) -> Vec<Vec<PathBuf>> {
+        let directories: Vec<&str> = if target_os!("haiku") {
+            DIRECTORIES_HAIKU.into()
+        } else if target_os!("linux") || target_os!("freebsd") {
+            DIRECTORIES_LINUX.into()
+        } else if target_os!("macos") {
+            DIRECTORIES_MACOS.into()
+        } else if target_os!("windows") {
+            let msvc = target_env!("msvc");
+            DIRECTORIES_WINDOWS
+                .iter()
+                .filter(|d| d.1 || !msvc)
+                .map(|d| d.0)
+                .collect()
+        } else if target_os!("illumos") {
+            DIRECTORIES_ILLUMOS.into()
+        } else {
+            vec![]
+        };
+
+        // We use temporary directories when testing the build script so we'll
+        // remove the prefixes that make the directories absolute.
+        let directories = if test!() {
+            directories
+                .iter()
+                .map(|d| {
+                    d.strip_prefix('/')
+                        .or_else(|| d.strip_prefix("C:\\"))
+                        .unwrap_or(d)
+                })
+                .collect::<Vec<_>>()
+        } else {
+            directories
+        };
+
+        // Patterns which are repeated (e.g., the tool cache of a hosted runner)
+        // are only searched once.
+        let mut patterns: Vec<String> = vec![];
+        for pattern in directories
+            .iter()
+            .map(|d| d.to_string())
+            .chain(find_user_directory_patterns())
+            .chain(find_github_actions_directory_patterns())
+        {
+            if !patterns.contains(&pattern) {
+                patterns.push(pattern);
+            }
         }
+
+        let mut options = MatchOptions::new();
+        options.case_sensitive = false;
+        find_directories(&patterns, options)
     }
 
-    // Determine the `libclang` directory patterns.
-    let directories: Vec<&str> = if target_os!("haiku") {
-        DIRECTORIES_HAIKU.into()
-    } else if target_os!("linux") || target_os!("freebsd") {
-        DIRECTORIES_LINUX.into()
-    } else if target_os!("macos") {
-        DIRECTORIES_MACOS.into()
-    } else if target_os!("windows") {
-        let msvc = target_env!("msvc");
-        DIRECTORIES_WINDOWS
+    fn source(&self) -> DiscoverySource {
+        DiscoverySource::KnownDirectory
+    }
+}
+
+/// Finds the Clang toolchains in any Android NDKs.
+struct AndroidNdk;
+
+impl DiscoveryStrategy for AndroidNdk {
+    fn locations(&self) -> Vec<Vec<PathBuf>> {
+        find_android_ndk_directories()
+            .into_iter()
+            .map(|d| vec![d])
+            .collect()
+    }
+
+    fn source(&self) -> DiscoverySource {
+        DiscoverySource::KnownDirectory
+    }
+}
+
+/// Returns the strategies used to find `libclang` libraries in the order they
+/// are used in where `variable` is the environment variable which provides the
+/// path to `libclang` (e.g., `LIBCLANG_PATH`).
+fn discovery_strategies(variable: &str) -> Vec<Arc<dyn DiscoveryStrategy>> {
+    let custom = DISCOVERY_STRATEGIES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+
+    let mut strategies: Vec<Arc<dyn DiscoveryStrategy>> =
+        vec![Arc::new(EnvironmentVariable(variable.into()))];
+    strategies.extend(custom);
+    strategies.push(Arc::new(LlvmConfig));
+    strategies.push(Arc::new(PkgConfig));
+    strategies.push(Arc::new(Vcpkg));
+    strategies.push(Arc::new(XcodeSelect));
+    strategies.push(Arc::new(LdLibraryPath));
+    strategies.push(Arc::new(DirectoryPatterns));
+    strategies.push(Arc::new(AndroidNdk));
+    strategies
+}
+
+//================================================
+// Searching
+//================================================
+
+thread_local! {
+    /// The directories searched by the build script for files (in the order
+    /// they were searched).
+    static SEARCHED_DIRECTORIES: RefCell<Vec<PathBuf>> = RefCell::default();
+}
+
+/// Returns the directories searched by the build script for files (in the
+/// order they were searched) by the last search for `libclang` libraries.
+pub fn searched_directories() -> Vec<PathBuf> {
+    SEARCHED_DIRECTORIES.with(|d| d.borrow().clone())
+}
+
+thread_local! {
+    /// The errors encountered by the build script while reading directories
+    /// (e.g., because of insufficient permissions) when searching for files.
+    static SEARCH_ERRORS: RefCell<Vec<(PathBuf, String)>> = RefCell::default();
+}
+
+/// Adds an error encountered by the build script while reading a directory
+/// (unless an error has already been added for that directory).
+fn add_search_error(directory: &Path, error: &io::Error) {
+    let added = SEARCH_ERRORS.with(|e| {
+        let mut errors = e.borrow_mut();
+        if errors.iter().any(|(d, _)| d == directory) {
+            return false;
+        }
+
+        errors.push((directory.into(), error.to_string()));
+        true
+    });
+
+    if added {
+        warn(
+            Severity::Debug,
+            format!(
+                "couldn't read directory {} while searching ({})",
+                directory.display(),
+                error,
+            ),
+        );
+    }
+}
+
+/// Returns the errors encountered by the build script while reading
+/// directories when searching for files (e.g., `(/usr/lib/llvm: permission
+/// denied)`).
+pub fn search_errors() -> Vec<String> {
+    SEARCH_ERRORS.with(|e| {
+        e.borrow()
             .iter()
-            .filter(|d| d.1 || !msvc)
-            .map(|d| d.0)
+            .map(|(d, e)| format!("({}: {})", d.display(), e))
             .collect()
-    } else if target_os!("illumos") {
-        DIRECTORIES_ILLUMOS.into()
+    })
+}
+
+/// The entries in a directory (their names and whether they are directories)
+/// sorted by name.
+type Entries = Rc<Vec<(String, bool)>>;
+
+thread_local! {
+    /// The entries in the directories read while searching (`None` for the
+    /// directories which couldn't be read) so that each directory is only read
+    /// once per search.
+    static ENTRIES: RefCell<HashMap<PathBuf, Option<Entries>>> = RefCell::default();
+}
+
+/// Returns the entries in a directory or `None` if the directory couldn't be
+/// read (errors other than the directory not existing are added to
+/// `SEARCH_ERRORS`).
+fn read_directory(directory: &Path) -> Option<Entries> {
+    if let Some(entries) = ENTRIES.with(|e| e.borrow().get(directory).cloned()) {
+        return entries;
+    }
+
+    if let Some(replay) = replaying() {
+        let entries = replay.directories.iter().find(|(d, _)| d == directory);
+        let entries = entries.and_then(|(_, e)| e.clone());
+        ENTRIES.with(|e| e.borrow_mut().insert(directory.into(), entries.clone()));
+        return entries;
+    }
+
+    let path = if directory.as_os_str().is_empty() {
+        Path::new(".")
     } else {
-        vec![]
+        directory
     };
 
-    // We use temporary directories when testing the build script so we'll
-    // remove the prefixes that make the directories absolute.
-    let directories = if test!() {
-        directories
-            .iter()
-            .map(|d| {
-                d.strip_prefix('/')
-                    .or_else(|| d.strip_prefix("C:\\"))
-                    .unwrap_or(d)
-            })
-            .collect::<Vec<_>>()
-    } else {
-        directories
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => {
+            let mut entries = entries
+                .filter_map(Result::ok)
+                .filter_map(|e| {
+                    let name = e.file_name().into_string().ok()?;
+                    // Only symlinks require an additional call to determine
+                    // whether they point to directories.
+                    let directory = match e.file_type() {
+                        Ok(t) if t.is_symlink() => e.path().is_dir(),
+                        Ok(t) => t.is_dir(),
+                        Err(_) => false,
+                    };
+                    Some((name, directory))
+                })
+                .collect::<Vec<_>>();
+            entries.sort();
+            Some(Rc::new(entries))
+        }
+        Err(error) => {
+            if !matches!(
+                error.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::NotADirectory,
+            ) {
+                add_search_error(directory, &error);
+            }
+
+            None
+        }
     };
 
-    // Search the directories provided by the `libclang` directory patterns.
-    let mut options = MatchOptions::new();
-    options.case_sensitive = false;
-    options.require_literal_separator = true;
-    for directory in directories.iter() {
-        if let Ok(directories) = glob::glob_with(directory, options) {
-            for directory in directories.filter_map(Result::ok).filter(|p| p.is_dir()) {
-                found.extend(search_directories(&directory, filenames));
+    record(|r| {
+        if !r.directories.iter().any(|(d, _)| d == directory) {
+            r.directories.push((directory.into(), entries.clone()));
+        }
+    });
+
+    ENTRIES.with(|e| e.borrow_mut().insert(directory.into(), entries.clone()));
+    entries
+}
+
+/// A component of a directory glob pattern.
+enum Component {
+    /// A component without any wildcards (e.g., `lib`).
+    Literal(String),
+    /// A component with wildcards (e.g., `llvm*`).
+    Pattern(Pattern),
+    /// A component which matches any number of directories (i.e., `**`).
+    Recursive,
+}
+
+/// Splits a directory glob pattern into the directory its matches are in (the
+/// leading components without wildcards) and the remaining components.
+fn parse_directory_pattern(pattern: &str) -> Option<(PathBuf, Vec<Component>)> {
+    let mut root = PathBuf::new();
+    let mut components = vec![];
+    for component in Path::new(pattern).components() {
+        let string = component.as_os_str().to_str()?;
+        if string == "**" {
+            components.push(Component::Recursive);
+        } else if string.contains(['*', '?', '[']) {
+            components.push(Component::Pattern(Pattern::new(string).ok()?));
+        } else if components.is_empty() {
+            root.push(component);
+        } else {
+            components.push(Component::Literal(string.into()));
+        }
+    }
+
+    Some((root, components))
+}
+
+/// Finds the directories matching one or more directory glob patterns and
+/// returns the directories matching each pattern sorted by path.
+///
+/// Instead of expanding each pattern separately (which would read directories
+/// shared by multiple patterns such as `/usr/lib` once for each pattern), the
+/// patterns are expanded together by a single walk of the file system which
+/// reads each directory at most once and only descends into the directories
+/// which could match at least one pattern.
+pub fn find_directories(patterns: &[String], options: MatchOptions) -> Vec<Vec<PathBuf>> {
+    // Directories read before this walk may have changed since.
+    ENTRIES.with(|e| e.borrow_mut().clear());
+
+    let patterns = patterns
+        .iter()
+        .map(|p| parse_directory_pattern(p))
+        .collect::<Vec<_>>();
+
+    // The directories being walked and, for each directory, the patterns being
+    // matched and the index of the next component of each pattern to match.
+    let mut roots = BTreeMap::<PathBuf, Vec<(usize, usize)>>::new();
+    for (index, pattern) in patterns.iter().enumerate() {
+        if let Some((root, _)) = pattern {
+            roots.entry(root.clone()).or_default().push((index, 0));
+        }
+    }
+
+    let mut matches = vec![vec![]; patterns.len()];
+    let mut stack = roots.into_iter().rev().collect::<Vec<_>>();
+    while let Some((directory, mut states)) = stack.pop() {
+        // A recursive component may match no directories at all.
+        let mut index = 0;
+        while index < states.len() {
+            let (pattern, component) = states[index];
+            let components = &patterns[pattern].as_ref().unwrap().1;
+            if let Some(Component::Recursive) = components.get(component) {
+                states.push((pattern, component + 1));
             }
+            index += 1;
+        }
+
+        let mut next = BTreeMap::<PathBuf, Vec<(usize, usize)>>::new();
+        for (pattern, component) in states {
+            let components = &patterns[pattern].as_ref().unwrap().1;
+            let entries = || read_directory(&directory).unwrap_or_default();
+            match components.get(component) {
+                None => {
+                    if directory.is_dir() {
+                        matches[pattern].push(directory.clone());
+                    }
+                }
+                Some(Component::Literal(name)) => {
+                    let path = directory.join(name);
+                    next.entry(path).or_default().push((pattern, component + 1));
+                }
+                Some(Component::Pattern(glob)) => {
+                    for (name, _) in entries()
+                        .iter()
+                        .filter(|(n, d)| *d && glob.matches_with(n, options))
+                    {
+                        let path = directory.join(name);
+                        next.entry(path).or_default().push((pattern, component + 1));
+                    }
+                }
+                Some(Component::Recursive) => {
+                    for (name, _) in entries().iter().filter(|(_, d)| *d) {
+                        let path = directory.join(name);
+                        next.entry(path).or_default().push((pattern, component));
+                    }
+                }
+            }
+        }
+
+        stack.extend(next.into_iter().rev());
+    }
+
+    for matches in &mut matches {
+        matches.sort();
+        matches.dedup();
+    }
+
+    matches
+}
+
+/// Finds the files in a directory that match one or more filename glob patterns
+/// and returns the paths to and filenames of those files.
+fn search_directory(directory: &Path, filenames: &[String]) -> Vec<(PathBuf, String)> {
+    SEARCHED_DIRECTORIES.with(|d| {
+        let mut directories = d.borrow_mut();
+        if !directories.iter().any(|d| d == directory) {
+            log!(Debug, "searching directory {}", directory.display());
+            directories.push(directory.into());
+        }
+    });
+
+    // Skip directories which can't be read (e.g., because of insufficient
+    // permissions) instead of trying (and failing) to read them again.
+    let Some(entries) = read_directory(directory) else {
+        return vec![];
+    };
+
+    // Remove any extended-length prefix from the specified directory since
+    // linkers and other tools don't reliably support these prefixes.
+    let directory = match directory.to_str() {
+        Some(directory) if cfg!(windows) => PathBuf::from(strip_extended_length_prefix(directory)),
+        _ => directory.to_path_buf(),
+    };
+
+    filenames
+        .iter()
+        .filter_map(|f| Pattern::new(f).ok())
+        .flat_map(|pattern| {
+            entries
+                .iter()
+                .filter(move |(name, _)| pattern.matches(name))
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        })
+        .filter(|filename| {
+            // The `libclang_shared` library has been renamed to `libclang-cpp`
+            // in Clang 10. This can cause instances of this library (e.g.,
+            // `libclang-cpp.so.10`) to be matched by patterns looking for
+            // instances of `libclang`.
+            !filename.contains("-cpp.")
+        })
+        .map(|filename| {
+            log!(Debug, "found {}", directory.join(filename).display());
+            (directory.clone(), filename.clone())
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Finds the files in a directory (and any relevant sibling directories) that
+/// match one or more filename glob patterns and returns the paths to and
+/// filenames of those files.
+fn search_directories(directory: &Path, filenames: &[String]) -> Vec<(PathBuf, String)> {
+    let mut results = search_directory(directory, filenames);
+
+    // On Windows, `libclang.dll` is usually found in the LLVM `bin` directory
+    // while `libclang.lib` is usually found in the LLVM `lib` directory. To
+    // keep things consistent with other platforms, only LLVM `lib` directories
+    // are included in the backup search directory globs so we need to search
+    // the LLVM `bin` directory here.
+    if target_os!("windows") && directory.ends_with("lib") {
+        let sibling = directory.parent().unwrap().join("bin");
+        results.extend(search_directory(&sibling, filenames));
+    }
+
+    results
+}
+
+/// Searches a file or directory for `libclang` libraries matching one or more
+/// filename glob patterns and returns the paths to and filenames of those
+/// files.
+fn search_location(path: &Path, filenames: &[String]) -> Vec<(PathBuf, String)> {
+    // Check if the path is a matching file.
+    let is_file = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(filename)) if is_replaying() => read_directory(parent)
+            .is_some_and(|e| e.iter().any(|(n, d)| n.as_str() == filename && !d)),
+        _ => path.is_file(),
+    };
+
+    if is_file && let (Some(parent), Some(filename)) = (path.parent(), path.file_name()) {
+        let libraries = search_directories(parent, filenames);
+        if libraries.iter().any(|(_, f)| f.as_str() == filename) {
+            return vec![(parent.into(), filename.to_str().unwrap().into())];
+        }
+    }
+
+    // Check if the path is directory containing a matching file.
+    search_directories(path, filenames)
+}
+
+/// Finds the `libclang` static or dynamic libraries matching one or more
+/// filename glob patterns and returns the paths to, filenames of, search
+/// priorities of, and kinds of locations of those files.
+///
+/// The locations provided by the discovery strategies are searched in the
+/// following order (see `DiscoveryStrategy`):
+///
+/// 1. the path provided by the relevant environment variable (e.g.,
+///    `LIBCLANG_PATH`), which is the only path searched if it is set
+/// 2. the locations provided by the custom strategies (if any)
+/// 3. the `bin` and `lib` directories in the directory returned by
+///    `llvm-config --prefix`
+/// 4. the toolchain directory in the directory returned by
+///    `xcode-select --print-path` (macOS only)
+/// 5. the directories in the `LD_LIBRARY_PATH` environment variable
+/// 6. the directories matching the `libclang` directory patterns
+/// 7. the Clang toolchains in any Android NDKs
+///
+/// The files are returned in the order they were found. The search priority of
+/// a file is the index of the search location it was found in (e.g., the
+/// `LD_LIBRARY_PATH` environment variable or one of the `libclang` directory
+/// patterns for the target platform) where lower values are preferred. Files
+/// found in directories matching the same directory pattern share the same
+/// search priority.
+pub fn search_libclang_directories(
+    filenames: &[String],
+    variable: &str,
+) -> Vec<(PathBuf, String, usize, DiscoverySource)> {
+    SEARCHED_DIRECTORIES.with(|d| d.borrow_mut().clear());
+    SEARCH_ERRORS.with(|e| e.borrow_mut().clear());
+    ENTRIES.with(|e| e.borrow_mut().clear());
+
+    let mut found = vec![];
+    let mut priority = 0;
+    // The locations provided by the discovery strategies are recorded since
+    // the strategies consult more than directory listings and commands (e.g.,
+    // the `LIBCLANG_PATH` environment variable or the Android NDKs installed).
+    let strategies: Box<dyn Iterator<Item = (bool, DiscoverySource, Vec<Vec<PathBuf>>)>> =
+        match replaying() {
+            Some(replay) => Box::new(replay.strategies.clone().into_iter()),
+            None => Box::new(
+                discovery_strategies(variable)
+                    .into_iter()
+                    .map(|s| (s.is_exclusive(), s.source(), s.locations())),
+            ),
+        };
+
+    for (exclusive, source, locations) in strategies {
+        record(|r| r.strategies.push((exclusive, source, locations.clone())));
+        if locations.is_empty() {
+            continue;
+        }
+
+        for group in &locations {
+            for location in group {
+                let libraries = search_location(location, filenames);
+                found.extend(libraries.into_iter().map(|(d, f)| (d, f, priority, source)));
+            }
+
+            priority += 1;
+        }
+
+        if exclusive {
+            break;
         }
     }
 
     found
 }
+
+/// Finds the `libclang` libraries matching one or more filename glob patterns
+/// in the supplied files or directories (instead of the usual locations) and
+/// returns the paths to, filenames of, search priorities of, and kinds of
+/// locations (always `DiscoverySource::Custom`) of those files.
+///
+/// The search priority of a file is the index of the file or directory it was
+/// found in.
+pub fn search_libclang_locations(
+    locations: &[PathBuf],
+    filenames: &[String],
+) -> Vec<(PathBuf, String, usize, DiscoverySource)> {
+    SEARCHED_DIRECTORIES.with(|d| d.borrow_mut().clear());
+    SEARCH_ERRORS.with(|e| e.borrow_mut().clear());
+    ENTRIES.with(|e| e.borrow_mut().clear());
+
+    let mut found = vec![];
+    for (priority, location) in locations.iter().enumerate() {
+        let libraries = search_location(location, filenames);
+        let source = DiscoverySource::Custom;
+        found.extend(libraries.into_iter().map(|(d, f)| (d, f, priority, source)));
+    }
+
+    found
+}
+
+//================================================
+// Bindings
+//================================================
+
+/// The kind of an item bound in `src/lib.rs`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BindingKind {
+    /// A function (e.g., `clang_createIndex`).
+    Function,
+    /// A constant (e.g., `CXCursor_StructDecl`).
+    Constant,
+}
+
+/// An item bound in `src/lib.rs`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Binding {
+    /// The kind of this item.
+    pub kind: BindingKind,
+    /// The name of this item.
+    pub name: String,
+    /// The `cfg` attributes which apply to this item (e.g.,
+    /// `#[cfg(feature = "clang_16_0")]`).
+    pub cfgs: Vec<String>,
+}
+
+/// Parses the items bound in the supplied Rust source (e.g., `src/lib.rs`)
+/// along with the `cfg` attributes which apply to them.
+///
+/// This recognizes the functions declared with `pub fn clang_*` (e.g., in the
+/// `link!` macro), the constants defined with the `cenum!` macro (including
+/// the `cfg` attributes of the enum), and the constants defined with
+/// `pub const`.
+pub fn parse_bindings(source: &str) -> Vec<Binding> {
+    let mut bindings = vec![];
+    let mut push = |kind, name: &str, cfgs| {
+        let name = name.trim().into();
+        bindings.push(Binding { kind, name, cfgs });
+    };
+
+    // The depth of the braces in the `cenum!` invocation being parsed (if any).
+    let mut depth = 0;
+    // The `cfg` attributes of the enum being defined with `cenum!`.
+    let mut enum_cfgs = vec![];
+    // The `cfg` attributes preceding the next item.
+    let mut cfgs = vec![];
+
+    for line in source.lines().map(str::trim) {
+        if line.starts_with("#[cfg(") {
+            cfgs.push(line.to_string());
+        } else if line.starts_with("cenum!") {
+            cfgs.clear();
+        } else if depth == 1 && line.starts_with("enum ") {
+            enum_cfgs = std::mem::take(&mut cfgs);
+        } else if let Some(rest) = line.strip_prefix("const ").filter(|_| depth == 2) {
+            let name = rest.split(|c: char| c.is_whitespace() || c == '=').next();
+            let mut all = enum_cfgs.clone();
+            all.append(&mut cfgs);
+            push(BindingKind::Constant, name.unwrap_or_default(), all);
+        } else if let Some(rest) = line.strip_prefix("pub const ").filter(|_| depth == 0) {
+            let name = rest.split(':').next().unwrap_or_default();
+            push(BindingKind::Constant, name, std::mem::take(&mut cfgs));
+        } else if let Some(rest) = line.strip_prefix("pub fn clang_") {
+            let name = format!("clang_{}", rest.split('(').next().unwrap_or_default());
+            push(BindingKind::Function, &name, std::mem::take(&mut cfgs));
+        } else if !line.starts_with("#[") && !line.starts_with("///") {
+            cfgs.clear();
+        }
+
+        if depth > 0 || line.starts_with("cenum!") {
+            depth += line.matches('{').count();
+            depth -= line.matches('}').count();
+        }
+    }
+
+    bindings
+}
@@ -1,11 +1,12 @@
 // SPDX-License-Identifier: Apache-2.0
 
-use std::env;
+use std::cmp::Ordering;
 use std::fs::File;
 use std::io::{self, Error, ErrorKind, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 
-use super::common;
+use super::common::{self, env};
+use super::version;
 
 //================================================
 // Validation
@@ -56,6 +57,668 @@ fn parse_pe_header(path: &Path) -> io::Result<(u16, u16)> {
     Ok((magic_number, machine_type))
 }
 
+/// The load commands of interest in a Mach-O shared library.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MachODylibs {
+    /// The install name of the shared library (`LC_ID_DYLIB`).
+    pub id: Option<String>,
+    /// The install names of the shared libraries the shared library depends on
+    /// (`LC_LOAD_DYLIB`, `LC_LOAD_WEAK_DYLIB`, and `LC_REEXPORT_DYLIB`).
+    pub dependencies: Vec<String>,
+}
+
+/// Reads a `u32` from a Mach-O file at the supplied offset.
+fn read_u32(file: &mut File, offset: u64, big_endian: bool) -> io::Result<u32> {
+    let mut buffer = [0; 4];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+    if big_endian {
+        Ok(u32::from_be_bytes(buffer))
+    } else {
+        Ok(u32::from_le_bytes(buffer))
+    }
+}
+
+/// Returns the Mach-O CPU type for the target architecture.
+fn mach_o_cpu_type() -> Option<u32> {
+    if target_arch!("x86_64") {
+        Some(0x01000007)
+    } else if target_arch!("aarch64") {
+        Some(0x0100000C)
+    } else if target_arch!("x86") {
+        Some(0x00000007)
+    } else {
+        None
+    }
+}
+
+/// Returns the CPU types of and offsets to the Mach-O headers in a Mach-O file
+/// (one for each slice of universal binaries).
+fn mach_o_slices(file: &mut File) -> io::Result<Vec<(u32, u64)>> {
+    let magic = read_u32(file, 0, true)?;
+    if !matches!(magic, 0xCAFEBABE | 0xCAFEBABF) {
+        return match read_u32(file, 0, false)? {
+            0xFEEDFACE | 0xFEEDFACF => Ok(vec![(read_u32(file, 4, false)?, 0)]),
+            _ => Err(Error::new(ErrorKind::InvalidData, "invalid Mach-O header")),
+        };
+    }
+
+    let count = read_u32(file, 4, true)?;
+    let (size, field) = if magic == 0xCAFEBABE {
+        (20, 4)
+    } else {
+        (32, 8)
+    };
+
+    let mut slices = vec![];
+    for index in 0..u64::from(count.min(64)) {
+        let start = 8 + index * size;
+        let cpu_type = read_u32(file, start, true)?;
+        let offset = if field == 4 {
+            u64::from(read_u32(file, start + 8, true)?)
+        } else {
+            let high = u64::from(read_u32(file, start + 8, true)?);
+            let low = u64::from(read_u32(file, start + 12, true)?);
+            (high << 32) | low
+        };
+        slices.push((cpu_type, offset));
+    }
+
+    Ok(slices)
+}
+
+/// Returns the offset to the Mach-O header for the target architecture in a
+/// Mach-O file (selecting the appropriate slice of universal binaries).
+fn mach_o_offset(file: &mut File) -> io::Result<u64> {
+    let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_string());
+
+    let slices = mach_o_slices(file)?;
+    let cpu_type = mach_o_cpu_type();
+    match slices[..] {
+        // Thin binaries start with the Mach-O header.
+        [(thin, 0)] if cpu_type.is_none_or(|t| t == thin) => Ok(0),
+        [(_, 0)] => Err(invalid("invalid Mach-O CPU type")),
+        _ => slices
+            .iter()
+            .find(|(t, _)| Some(*t) == cpu_type)
+            .map(|(_, o)| *o)
+            .ok_or_else(|| invalid("universal binary without target architecture")),
+    }
+}
+
+/// Parses the load commands following the Mach-O header at the supplied offset
+/// and returns the install names and the minimum operating system version
+/// (`LC_BUILD_VERSION` or `LC_VERSION_MIN_MACOSX`, if any).
+fn parse_mach_o_commands(
+    file: &mut File,
+    offset: u64,
+) -> io::Result<(MachODylibs, Option<Vec<u32>>)> {
+    let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_string());
+
+    let header_size = match read_u32(file, offset, false)? {
+        0xFEEDFACE => 28,
+        0xFEEDFACF => 32,
+        _ => return Err(invalid("invalid Mach-O header")),
+    };
+
+    // The sizes of load commands are checked against the length of the file so
+    // that a corrupt file can't cause large buffers to be allocated.
+    let length = file.metadata()?.len();
+
+    let mut dylibs = MachODylibs::default();
+    let mut minimum = None;
+    let count = read_u32(file, offset + 16, false)?;
+    let mut command = offset + header_size;
+    for _ in 0..count {
+        let kind = read_u32(file, command, false)?;
+        let size = read_u32(file, command + 4, false)?;
+        if size < 8 || command + u64::from(size) > length {
+            return Err(invalid("invalid Mach-O load command"));
+        }
+
+        if matches!(kind, 0xD | 0xC | 0x80000018 | 0x8000001F) {
+            let name = read_u32(file, command + 8, false)?;
+            if name >= size {
+                return Err(invalid("invalid Mach-O load command"));
+            }
+
+            let mut buffer = vec![0; (size - name) as usize];
+            file.seek(SeekFrom::Start(command + u64::from(name)))?;
+            file.read_exact(&mut buffer)?;
+            let end = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+            let name = String::from_utf8_lossy(&buffer[..end]).into_owned();
+            if kind == 0xD {
+                dylibs.id = Some(name);
+            } else {
+                dylibs.dependencies.push(name);
+            }
+        } else if matches!(kind, 0x32 | 0x24) && minimum.is_none() {
+            // The version is encoded as `xxxx.yy.zz` in nibbles.
+            let field = if kind == 0x32 { 12 } else { 8 };
+            let version = read_u32(file, command + field, false)?;
+            minimum = Some(vec![version >> 16, (version >> 8) & 0xFF, version & 0xFF]);
+        }
+
+        command += u64::from(size);
+    }
+
+    Ok((dylibs, minimum))
+}
+
+/// Extracts the install names in a Mach-O shared library for the target
+/// architecture (selecting the appropriate slice of universal binaries).
+pub fn parse_mach_o_dylibs(path: &Path) -> io::Result<MachODylibs> {
+    let mut file = File::open(path)?;
+    let offset = mach_o_offset(&mut file)?;
+    let (dylibs, _) = parse_mach_o_commands(&mut file, offset)?;
+    Ok(dylibs)
+}
+
+/// Reads an unsigned integer of the supplied size (in bytes) from a file at the
+/// supplied offset.
+fn read_integer(file: &mut File, offset: u64, size: usize, big_endian: bool) -> io::Result<u64> {
+    let mut buffer = [0; 8];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer[..size])?;
+    let bytes = &buffer[..size];
+    Ok(if big_endian {
+        bytes.iter().fold(0, |n, b| (n << 8) | u64::from(*b))
+    } else {
+        bytes.iter().rev().fold(0, |n, b| (n << 8) | u64::from(*b))
+    })
+}
+
+/// Reads a NUL-terminated string from a file at the supplied offset.
+fn read_string(file: &mut File, offset: u64) -> io::Result<String> {
+    let mut buffer = [0; 256];
+    file.seek(SeekFrom::Start(offset))?;
+    let size = file.read(&mut buffer)?;
+    let end = buffer[..size].iter().position(|b| *b == 0).unwrap_or(size);
+    Ok(String::from_utf8_lossy(&buffer[..end]).into_owned())
+}
+
+/// A section header in an ELF file.
+#[derive(Copy, Clone, Debug)]
+struct ElfSection {
+    kind: u64,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u64,
+    info: u64,
+}
+
+/// An ELF file and the information in its header needed to read it.
+struct ElfFile {
+    file: File,
+    wide: bool,
+    big_endian: bool,
+    sections: Vec<ElfSection>,
+}
+
+impl ElfFile {
+    /// Opens an ELF file and reads its section headers.
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+
+        let mut ident = [0; 6];
+        file.read_exact(&mut ident)?;
+        if ident[..4] != [127, 69, 76, 70] {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid ELF header"));
+        }
+
+        let wide = ident[4] == 2;
+        let big_endian = ident[5] == 2;
+        let read = |file: &mut File, offset, size| read_integer(file, offset, size, big_endian);
+
+        // Find the section headers.
+        let word = if wide { 8 } else { 4 };
+        let (shoff, shentsize, shnum) = if wide {
+            (
+                read(&mut file, 0x28, 8)?,
+                read(&mut file, 0x3A, 2)?,
+                read(&mut file, 0x3C, 2)?,
+            )
+        } else {
+            (
+                read(&mut file, 0x20, 4)?,
+                read(&mut file, 0x2E, 2)?,
+                read(&mut file, 0x30, 2)?,
+            )
+        };
+
+        // Read the type, flags, offset, size, link, and info fields of the
+        // section headers.
+        let mut sections = vec![];
+        for index in 0..shnum {
+            let start = shoff + index * shentsize;
+            let offset = start + 8 + 2 * word;
+            sections.push(ElfSection {
+                kind: read(&mut file, start + 4, 4)?,
+                flags: read(&mut file, start + 8, word as usize)?,
+                offset: read(&mut file, offset, word as usize)?,
+                size: read(&mut file, offset + word, word as usize)?,
+                link: read(&mut file, offset + 2 * word, 4)?,
+                info: read(&mut file, offset + 2 * word + 4, 4)?,
+            });
+        }
+
+        Ok(Self {
+            file,
+            wide,
+            big_endian,
+            sections,
+        })
+    }
+
+    /// Reads an unsigned integer of the supplied size (in bytes) at the supplied
+    /// offset.
+    fn read(&mut self, offset: u64, size: usize) -> io::Result<u64> {
+        read_integer(&mut self.file, offset, size, self.big_endian)
+    }
+
+    /// Returns the first section of the supplied type, if any.
+    fn find_section(&self, kind: u64) -> Option<ElfSection> {
+        self.sections.iter().find(|s| s.kind == kind).copied()
+    }
+
+    /// Returns the offset of the string table linked to the supplied section.
+    fn strings(&self, section: ElfSection) -> io::Result<u64> {
+        match self.sections.get(section.link as usize) {
+            Some(strings) => Ok(strings.offset),
+            None => Err(Error::new(
+                ErrorKind::InvalidData,
+                "invalid ELF section link",
+            )),
+        }
+    }
+}
+
+/// Extracts the symbol versions required by an ELF shared library (e.g.,
+/// `GLIBC_2.34` or `GLIBCXX_3.4.29`) from its `.gnu.version_r` section.
+pub fn parse_elf_version_needs(path: &Path) -> io::Result<Vec<String>> {
+    let mut elf = ElfFile::open(path)?;
+
+    // Find the `.gnu.version_r` section (`SHT_GNU_verneed`).
+    let Some(verneed) = elf.find_section(0x6FFFFFFE) else {
+        return Ok(vec![]);
+    };
+
+    let strings = elf.strings(verneed)?;
+
+    // Iterate over the required shared libraries and their required versions.
+    let mut needs = vec![];
+    let mut need = verneed.offset;
+    for _ in 0..verneed.info.min(1024) {
+        let auxiliaries = elf.read(need + 2, 2)?;
+        let mut auxiliary = need + elf.read(need + 8, 4)?;
+        for _ in 0..auxiliaries {
+            let name = elf.read(auxiliary + 8, 4)?;
+            needs.push(read_string(&mut elf.file, strings + name)?);
+            auxiliary += elf.read(auxiliary + 12, 4)?;
+        }
+
+        match elf.read(need + 12, 4)? {
+            0 => break,
+            next => need += next,
+        }
+    }
+
+    Ok(needs)
+}
+
+/// Extracts the strings of the entries with the supplied tag (e.g., `DT_NEEDED`)
+/// in the `.dynamic` section of an ELF shared library.
+fn read_elf_dynamic_strings(elf: &mut ElfFile, tag: u64) -> io::Result<Vec<String>> {
+    // Find the `.dynamic` section (`SHT_DYNAMIC`).
+    let Some(dynamic) = elf.find_section(6) else {
+        return Ok(vec![]);
+    };
+
+    let strings = elf.strings(dynamic)?;
+
+    // Iterate over the entries until `DT_NULL` is found.
+    let mut values = vec![];
+    let word = if elf.wide { 8 } else { 4 };
+    for index in 0..(dynamic.size / (2 * word)).min(4096) {
+        let entry = dynamic.offset + index * 2 * word;
+        match elf.read(entry, word as usize)? {
+            0 => break,
+            kind if kind == tag => {
+                let name = elf.read(entry + word, word as usize)?;
+                values.push(read_string(&mut elf.file, strings + name)?);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(values)
+}
+
+/// Extracts the name of an ELF shared library (e.g., `libclang.so.18.1`) from
+/// the `DT_SONAME` entry in its `.dynamic` section.
+pub fn parse_elf_soname(path: &Path) -> io::Result<Option<String>> {
+    let mut elf = ElfFile::open(path)?;
+    Ok(read_elf_dynamic_strings(&mut elf, 14)?.into_iter().next())
+}
+
+/// A PE file and the offsets of the structures in its headers needed to read
+/// it.
+struct PeFile {
+    file: File,
+    header: u64,
+    optional: u64,
+    directories: u64,
+    table: u64,
+    count: u64,
+}
+
+impl PeFile {
+    /// Opens a PE file and finds the structures in its headers.
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let read = |file: &mut File, offset, size| read_integer(file, offset, size, false);
+
+        let header = read(&mut file, 0x3C, 4)?;
+        if read(&mut file, header, 4)? != 0x4550 {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid PE header"));
+        }
+
+        let count = read(&mut file, header + 6, 2)?;
+        let optional = header + 24;
+        let table = optional + read(&mut file, header + 20, 2)?;
+        let directories = match read(&mut file, optional, 2)? {
+            267 => optional + 96,
+            523 => optional + 112,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "invalid PE magic number",
+                ));
+            }
+        };
+
+        Ok(Self {
+            file,
+            header,
+            optional,
+            directories,
+            table,
+            count,
+        })
+    }
+
+    /// Reads an unsigned integer of the supplied size (in bytes) at the supplied
+    /// offset.
+    fn read(&mut self, offset: u64, size: usize) -> io::Result<u64> {
+        read_integer(&mut self.file, offset, size, false)
+    }
+
+    /// Returns the address and size of the data directory with the supplied
+    /// index (e.g., `2` for the resource table), if present.
+    fn directory(&mut self, index: u64) -> io::Result<Option<(u64, u64)>> {
+        let directory = self.directories + index * 8;
+        if directory + 8 > self.table {
+            return Ok(None);
+        }
+
+        let address = self.read(directory, 4)?;
+        let size = self.read(directory + 4, 4)?;
+        Ok((address != 0).then_some((address, size)))
+    }
+
+    /// Returns the offset in the file of the supplied address, if it is in a
+    /// section.
+    fn offset(&mut self, address: u64) -> io::Result<Option<u64>> {
+        for index in 0..self.count {
+            let section = self.table + index * 40;
+            let start = self.read(section + 12, 4)?;
+            let raw = self.read(section + 16, 4)?;
+            if (start..start + raw).contains(&address) {
+                return Ok(Some(self.read(section + 20, 4)? + address - start));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Extracts the file version (e.g., `[18, 1, 8]`) from the `VS_FIXEDFILEINFO`
+/// structure in the version resource of a PE shared library.
+pub fn parse_pe_file_version(path: &Path) -> io::Result<Option<Vec<u32>>> {
+    let mut pe = PeFile::open(path)?;
+
+    // Find the offset of the resource table (the third data directory).
+    let Some((address, size)) = pe.directory(2)? else {
+        return Ok(None);
+    };
+
+    let Some(offset) = pe.offset(address)? else {
+        return Ok(None);
+    };
+
+    // Find the `VS_FIXEDFILEINFO` structure by its signature.
+    let mut resources = vec![];
+    pe.file.seek(SeekFrom::Start(offset))?;
+    pe.file
+        .by_ref()
+        .take(size.min(1 << 24))
+        .read_to_end(&mut resources)?;
+    let signature = 0xFEEF04BDu32.to_le_bytes();
+    let Some(start) = resources.windows(4).position(|w| w == signature) else {
+        return Ok(None);
+    };
+
+    let Some(info) = resources.get(start..start + 16) else {
+        return Ok(None);
+    };
+
+    let ms = u32::from_le_bytes(info[8..12].try_into().unwrap());
+    let ls = u32::from_le_bytes(info[12..16].try_into().unwrap());
+    Ok(Some(vec![ms >> 16, ms & 0xFFFF, ls >> 16]))
+}
+
+/// The maximum number of bytes scanned for an embedded `clang` version string
+/// in shared libraries whose string sections can't be found.
+const SCAN_WINDOW: u64 = 1 << 26;
+
+/// Returns the offsets to and sizes of the `__TEXT,__cstring` sections
+/// following the Mach-O header at the supplied offset.
+fn mach_o_cstring_sections(file: &mut File, offset: u64) -> io::Result<Vec<(u64, u64)>> {
+    let wide = match read_u32(file, offset, false)? {
+        0xFEEDFACE => false,
+        0xFEEDFACF => true,
+        _ => return Err(Error::new(ErrorKind::InvalidData, "invalid Mach-O header")),
+    };
+
+    // The sizes of the header, segment commands, and section headers.
+    let (header, segment, section) = if wide { (32, 72, 80) } else { (28, 56, 68) };
+
+    let mut sections = vec![];
+    let count = read_u32(file, offset + 16, false)?;
+    let mut command = offset + header;
+    for _ in 0..count {
+        let kind = read_u32(file, command, false)?;
+        let size = read_u32(file, command + 4, false)?;
+        if size < 8 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "invalid Mach-O load command",
+            ));
+        }
+
+        if kind == if wide { 0x19 } else { 0x1 } {
+            let count = read_u32(file, command + segment - 8, false)?;
+            for index in 0..u64::from(count).min(u64::from(size) / section) {
+                let start = command + segment + index * section;
+                let section_name = read_string(file, start)?;
+                let segment_name = read_string(file, start + 16)?;
+                if section_name.starts_with("__cstring") && segment_name.starts_with("__TEXT") {
+                    let (size, field) = if wide {
+                        (read_integer(file, start + 40, 8, false)?, start + 48)
+                    } else {
+                        (u64::from(read_u32(file, start + 36, false)?), start + 40)
+                    };
+                    let field = u64::from(read_u32(file, field, false)?);
+                    sections.push((offset + field, size));
+                }
+            }
+        }
+
+        command += u64::from(size);
+    }
+
+    Ok(sections)
+}
+
+/// Scans a shared library for an embedded `clang` version string (e.g.,
+/// `clang version 18.1.8`) and returns the version components.
+///
+/// Only the read-only data sections (e.g., `.rodata`) are scanned in ELF
+/// shared libraries since the `.comment` section contains the version of the
+/// compiler used to build the shared library (which may also be `clang`) and
+/// only the `__TEXT,__cstring` sections are scanned in Mach-O shared libraries.
+/// Only the first `SCAN_WINDOW` bytes of other shared libraries are scanned.
+pub fn scan_clang_version(path: &Path) -> io::Result<Option<Vec<u32>>> {
+    const NEEDLE: &[u8] = b"clang version ";
+    // The maximum length of the version following the needle.
+    const TAIL: usize = 64;
+
+    let mach_o = || -> io::Result<Vec<(u64, u64)>> {
+        let mut file = File::open(path)?;
+        let offset = mach_o_offset(&mut file)?;
+        mach_o_cstring_sections(&mut file, offset)
+    };
+
+    // Read-only data sections are allocated (`SHF_ALLOC`) but neither writable
+    // (`SHF_WRITE`) nor executable (`SHF_EXECINSTR`).
+    let ranges = match ElfFile::open(path) {
+        Ok(elf) => elf
+            .sections
+            .iter()
+            .filter(|s| s.kind == 1 && s.flags & 7 == 2)
+            .map(|s| (s.offset, s.size))
+            .collect(),
+        Err(_) => mach_o().unwrap_or_else(|_| vec![(0, SCAN_WINDOW)]),
+    };
+
+    let mut file = File::open(path)?;
+    let mut chunk = vec![0; 1 << 16];
+    for (offset, size) in ranges {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader = (&mut file).take(size);
+        let mut buffer = vec![];
+        loop {
+            let read = reader.read(&mut chunk)?;
+            buffer.extend_from_slice(&chunk[..read]);
+
+            // Only search the part of the buffer which is followed by enough
+            // bytes for a version (unless the end has been reached).
+            let searchable = if read == 0 {
+                buffer.len()
+            } else {
+                buffer.len().saturating_sub(NEEDLE.len() + TAIL)
+            };
+
+            let version = (0..searchable)
+                .filter(|i| buffer[*i..].starts_with(NEEDLE))
+                .find_map(|i| {
+                    let string = &buffer[i..buffer.len().min(i + NEEDLE.len() + TAIL)];
+                    let end = string.iter().position(|b| *b == 0).unwrap_or(string.len());
+                    version::parse_clang_version(&String::from_utf8_lossy(&string[..end]))
+                });
+
+            if version.is_some() {
+                return Ok(version);
+            } else if read == 0 {
+                break;
+            }
+
+            buffer.drain(..searchable);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the highest of the supplied symbol versions with the supplied
+/// prefix (e.g., `[2, 34]` for `GLIBC_2.34` with the `GLIBC_` prefix).
+pub fn max_symbol_version(needs: &[String], prefix: &str) -> Option<Vec<u32>> {
+    needs
+        .iter()
+        .filter_map(|n| n.strip_prefix(prefix))
+        .map(version::parse_components)
+        .filter(|v| !v.is_empty())
+        .max()
+}
+
+/// Returns the version of the C standard library in use by this process.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn host_glibc_version() -> Option<Vec<u32>> {
+    use std::ffi::{CStr, c_char};
+
+    unsafe extern "C" {
+        fn gnu_get_libc_version() -> *const c_char;
+    }
+
+    let version = unsafe { CStr::from_ptr(gnu_get_libc_version()) };
+    Some(version::parse_components(version.to_str().ok()?))
+}
+
+/// Returns the version of the C standard library in use by this process.
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+fn host_glibc_version() -> Option<Vec<u32>> {
+    None
+}
+
+/// Checks that the `glibc` and `libstdc++` symbol versions required by a
+/// `libclang` shared library are available.
+///
+/// The available versions are provided by the `LIBCLANG_GLIBC_BASELINE` and
+/// `LIBCLANG_GLIBCXX_BASELINE` environment variables (e.g., `2.17` and
+/// `3.4.19`). If the former is not set, the version of `glibc` in use by this
+/// process is used instead. If the latter is not set, the `libstdc++` symbol
+/// versions are not checked.
+fn check_runtime_requirements(path: &Path) -> Result<(), String> {
+    let Ok(needs) = parse_elf_version_needs(path) else {
+        return Ok(());
+    };
+
+    let baseline = |variable: &str| {
+        let version = env::var(variable).ok()?;
+        Some(version::parse_components(&version)).filter(|v| !v.is_empty())
+    };
+
+    let glibc = baseline("LIBCLANG_GLIBC_BASELINE").or_else(|| {
+        // We use fake shared libraries when testing the build script so we'll
+        // skip the `glibc` in use by the tests.
+        if test!() { None } else { host_glibc_version() }
+    });
+
+    let glibcxx = baseline("LIBCLANG_GLIBCXX_BASELINE");
+
+    let format = |v: &[u32]| {
+        v.iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(".")
+    };
+
+    for (prefix, available) in [("GLIBC_", glibc), ("GLIBCXX_", glibcxx)] {
+        if let (Some(required), Some(available)) = (max_symbol_version(&needs, prefix), available)
+            && required > available
+        {
+            return Err(format!(
+                "requires {prefix}{} but only {prefix}{} is available",
+                format(&required),
+                format(&available),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 /// Checks that a `libclang` shared library matches the target platform.
 fn validate_library(path: &Path) -> Result<(), String> {
     if target_os!("linux") || target_os!("freebsd") {
@@ -93,78 +756,671 @@ fn validate_library(path: &Path) -> Result<(), String> {
         } else {
             Ok(())
         }
+    } else if target_os!("macos") {
+        let dylibs = parse_mach_o_dylibs(path).map_err(|e| e.to_string())?;
+
+        // Shared libraries found through symlinks (e.g., into an app bundle)
+        // should still be `libclang` shared libraries.
+        let id = dylibs.id.as_deref().unwrap_or_default();
+        let name = id.rsplit('/').next().unwrap_or_default();
+        if !id.is_empty() && !name.contains("clang") {
+            return Err(format!("invalid install name ({id})"));
+        }
+
+        Ok(())
     } else {
         Ok(())
     }
 }
 
+/// The cause of a failure to open a shared library.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OpenFailure {
+    /// A dependency of the shared library (e.g., `libtinfo.so.5`) couldn't be
+    /// found.
+    MissingDependency(String),
+    /// A symbol version required by the shared library (e.g., `GLIBC_2.34`)
+    /// isn't provided by the C or C++ standard library.
+    RuntimeTooOld(String),
+    /// The shared library is for another architecture (or ELF class).
+    WrongArchitecture,
+    /// A symbol required by the shared library isn't defined by it or its
+    /// dependencies.
+    UndefinedSymbol(String),
+}
+
+/// Determines the cause of a failure to open the shared library with the
+/// supplied filename from the error message of the dynamic loader (e.g., the
+/// message returned by `dlerror`), if it is a common cause.
+pub fn parse_open_error(filename: &str, message: &str) -> Option<OpenFailure> {
+    // Returns the text in the message following the supplied prefix up to the
+    // first of the supplied terminators.
+    let following = |prefix: &str, terminators: &[char]| {
+        let start = message.find(prefix)? + prefix.len();
+        let text = message[start..].split(terminators).next()?;
+        (!text.is_empty()).then(|| text.to_string())
+    };
+
+    // glibc and musl report the name of the shared library which couldn't be
+    // found, which is the shared library itself if it doesn't exist.
+    if let Some(index) = message.find(": cannot open shared object file") {
+        let name = message[..index].rsplit(": ").next().unwrap_or_default();
+        let base = |n: &str| Path::new(n).file_name().map(|n| n.to_os_string());
+        if !name.is_empty() && base(name) != base(filename) {
+            return Some(OpenFailure::MissingDependency(name.into()));
+        }
+    }
+
+    if let Some(name) = following("Library not loaded: ", &['\n', ' ']) {
+        return Some(OpenFailure::MissingDependency(name));
+    }
+
+    if message.contains("' not found")
+        && let Some(requirement) = following("version `", &['\''])
+    {
+        return Some(OpenFailure::RuntimeTooOld(requirement));
+    }
+
+    let architecture = [
+        "wrong ELF class",
+        "incompatible architecture",
+        "not a valid Win32 application",
+        "(os error 193)",
+    ];
+    if architecture.iter().any(|a| message.contains(a)) {
+        return Some(OpenFailure::WrongArchitecture);
+    }
+
+    following("undefined symbol: ", &['\n', ' ', ','])
+        .or_else(|| following("Symbol not found: ", &['\n', ' ']))
+        .map(OpenFailure::UndefinedSymbol)
+}
+
+/// The properties of a shared library which determine whether it can be
+/// loaded into a process.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BinaryInfo {
+    /// The architectures the shared library contains code for, named like the
+    /// `target_arch` values (e.g., `x86_64`). Universal binaries contain code
+    /// for several architectures.
+    pub architectures: Vec<String>,
+    /// The minimum operating system version the shared library supports, if
+    /// specified (`LC_BUILD_VERSION` or `LC_VERSION_MIN_MACOSX` for Mach-O
+    /// shared libraries and the subsystem version for PE shared libraries).
+    pub min_os_version: Option<Vec<u32>>,
+    /// The names of the shared libraries the shared library depends on.
+    pub dependencies: Vec<String>,
+}
+
+/// Returns the architecture (named like `target_arch`) for a machine type in
+/// an ELF header.
+fn elf_architecture(machine: u64, wide: bool) -> Option<&'static str> {
+    match (machine, wide) {
+        (3, _) => Some("x86"),
+        (8, false) => Some("mips"),
+        (8, true) => Some("mips64"),
+        (20, _) => Some("powerpc"),
+        (21, _) => Some("powerpc64"),
+        (22, _) => Some("s390x"),
+        (40, _) => Some("arm"),
+        (43, _) => Some("sparc64"),
+        (62, _) => Some("x86_64"),
+        (183, _) => Some("aarch64"),
+        (243, false) => Some("riscv32"),
+        (243, true) => Some("riscv64"),
+        (258, _) => Some("loongarch64"),
+        _ => None,
+    }
+}
+
+/// Returns the architecture (named like `target_arch`) for a machine type in a
+/// PE header.
+fn pe_architecture(machine: u64) -> Option<&'static str> {
+    match machine {
+        0x014C => Some("x86"),
+        0x01C4 => Some("arm"),
+        0x8664 => Some("x86_64"),
+        0xA641 => Some("arm64ec"),
+        0xAA64 => Some("aarch64"),
+        _ => None,
+    }
+}
+
+/// Returns the architecture (named like `target_arch`) for a CPU type in a
+/// Mach-O header.
+fn mach_o_architecture(cpu_type: u32) -> Option<&'static str> {
+    match cpu_type {
+        0x00000007 => Some("x86"),
+        0x0000000C => Some("arm"),
+        0x00000012 => Some("powerpc"),
+        0x01000007 => Some("x86_64"),
+        0x0100000C => Some("aarch64"),
+        0x01000012 => Some("powerpc64"),
+        _ => None,
+    }
+}
+
+/// Extracts the properties of an ELF, PE, or Mach-O shared library which
+/// determine whether it can be loaded into a process (regardless of the target
+/// platform).
+///
+/// The minimum operating system version and the dependencies of universal
+/// binaries are extracted from the slice for the target architecture (or the
+/// first slice if there is no such slice).
+pub fn parse_binary_info(path: &Path) -> io::Result<BinaryInfo> {
+    let mut file = File::open(path)?;
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+
+    let mut info = BinaryInfo::default();
+    if magic == [127, 69, 76, 70] {
+        let mut elf = ElfFile::open(path)?;
+        let machine = elf.read(18, 2)?;
+        info.architectures
+            .extend(elf_architecture(machine, elf.wide).map(String::from));
+        info.dependencies = read_elf_dynamic_strings(&mut elf, 1)?;
+    } else if magic[..2] == *b"MZ" {
+        let mut pe = PeFile::open(path)?;
+        let machine = pe.read(pe.header + 4, 2)?;
+        info.architectures
+            .extend(pe_architecture(machine).map(String::from));
+        let major = pe.read(pe.optional + 48, 2)?;
+        let minor = pe.read(pe.optional + 50, 2)?;
+        info.min_os_version = Some(vec![major as u32, minor as u32]);
+
+        // Iterate over the import directory entries (the second data
+        // directory) until an empty entry is found.
+        if let Some((address, _)) = pe.directory(1)?
+            && let Some(offset) = pe.offset(address)?
+        {
+            for index in 0..1024 {
+                let name = pe.read(offset + index * 20 + 12, 4)?;
+                if name == 0 {
+                    break;
+                }
+
+                if let Some(name) = pe.offset(name)? {
+                    info.dependencies.push(read_string(&mut pe.file, name)?);
+                }
+            }
+        }
+    } else {
+        let slices = mach_o_slices(&mut file)?;
+        let cpu_type = mach_o_cpu_type();
+        for (cpu_type, _) in &slices {
+            info.architectures
+                .extend(mach_o_architecture(*cpu_type).map(String::from));
+        }
+
+        let slice = slices
+            .iter()
+            .find(|(t, _)| Some(*t) == cpu_type)
+            .or(slices.first());
+        if let Some((_, offset)) = slice {
+            let (dylibs, minimum) = parse_mach_o_commands(&mut file, *offset)?;
+            info.min_os_version = minimum;
+            info.dependencies = dylibs.dependencies;
+        }
+    }
+
+    Ok(info)
+}
+
+/// Returns the paths to the LLVM shared libraries (e.g., `libLLVM-18.so.1`) an
+/// ELF `libclang` shared library depends on which can be found in the directory
+/// containing it (or the file it is a symlink to) or, failing that, in the
+/// directory provided by the supplied function (e.g., `llvm-config --libdir`).
+///
+/// Dependencies which can't be found in these directories are omitted.
+pub fn find_llvm_dependencies(
+    path: &Path,
+    libdir: impl FnOnce() -> Option<PathBuf>,
+) -> Vec<PathBuf> {
+    let Ok(info) = parse_binary_info(path) else {
+        return vec![];
+    };
+
+    let names = info
+        .dependencies
+        .iter()
+        .filter(|d| d.starts_with("libLLVM"));
+    let names = names.collect::<Vec<_>>();
+    if names.is_empty() {
+        return vec![];
+    }
+
+    let mut directories = vec![];
+    directories.extend(path.parent().map(Path::to_path_buf));
+    directories.extend(
+        path.canonicalize()
+            .ok()
+            .and_then(|p| p.parent().map(Path::to_path_buf)),
+    );
+
+    let mut libdir = Some(libdir);
+    let mut dependencies = vec![];
+    for name in names {
+        let found = directories
+            .iter()
+            .map(|d| d.join(name))
+            .find(|p| p.is_file());
+        let found = found.or_else(|| {
+            // The directory is only determined if it is needed (and only once).
+            if let Some(libdir) = libdir.take() {
+                directories.extend(libdir());
+            }
+
+            directories
+                .iter()
+                .map(|d| d.join(name))
+                .find(|p| p.is_file())
+        });
+        dependencies.extend(found);
+    }
+
+    dependencies
+}
+
 //================================================
 // Searching
 //================================================
 
+/// A naming scheme for `libclang` shared library filenames.
+pub struct FilenameScheme {
+    /// The glob pattern which matches the filenames (e.g., `libclang-*.so`).
+    ///
+    /// The version in a filename (if any) is matched by the first `*`, any
+    /// other `*` matches an ABI version (e.g., the `1` in `libclang-15.so.1`).
+    pub pattern: &'static str,
+    /// Whether the first `*` in the pattern matches the version (otherwise it
+    /// matches an ABI version, e.g., the `1` in `libclang.1.dylib`).
+    pub versioned: bool,
+    /// Whether shared libraries with these filenames can be linked to with `ld`
+    /// (otherwise they are only searched for at runtime).
+    pub linkable: bool,
+    /// Returns whether this naming scheme is used on the target platform.
+    pub target: fn() -> bool,
+}
+
+impl FilenameScheme {
+    /// Extracts the version in a filename which uses this naming scheme, or
+    /// returns `None` if the filename doesn't use this naming scheme or isn't
+    /// versioned.
+    pub fn version<'f>(&self, filename: &'f str) -> Option<&'f str> {
+        if !self.versioned {
+            return None;
+        }
+
+        let (prefix, suffix) = self.pattern.split_once('*')?;
+        let version = filename.strip_prefix(prefix)?;
+        let version = match suffix.split_once('*') {
+            Some((separator, _)) => version.split_once(separator)?.0,
+            None => version.strip_suffix(suffix)?,
+        };
+
+        let valid = version
+            .split('.')
+            .all(|c| !c.is_empty() && c.bytes().all(|b| b.is_ascii_digit()));
+        valid.then_some(version)
+    }
+}
+
+/// Returns whether the target platform is a BSD (or Haiku) system.
+fn target_bsd() -> bool {
+    target_os!("freebsd") || target_os!("haiku") || target_os!("netbsd") || target_os!("openbsd")
+}
+
+/// The naming schemes for `libclang` shared library filenames, in the order
+/// the filename patterns are searched for.
+pub const FILENAME_SCHEMES: &[FilenameScheme] = &[
+    // `libclang.so` (or `libclang.dylib` and `clang.dll`).
+    FilenameScheme {
+        pattern: "libclang.so",
+        versioned: false,
+        linkable: true,
+        target: || !target_os!("macos") && !target_os!("windows"),
+    },
+    FilenameScheme {
+        pattern: "libclang.dylib",
+        versioned: false,
+        linkable: true,
+        target: || target_os!("macos"),
+    },
+    FilenameScheme {
+        pattern: "clang.dll",
+        versioned: false,
+        linkable: true,
+        target: || target_os!("windows"),
+    },
+    // Some Linux distributions don't create a `libclang.so` symlink, so we
+    // need to look for versioned files (e.g., `libclang-3.9.so`).
+    FilenameScheme {
+        pattern: "libclang-*.so",
+        versioned: true,
+        linkable: true,
+        target: || target_os!("linux"),
+    },
+    // Some Linux distributions don't create a `libclang.so` symlink and don't
+    // have versioned files as described above, so we need to look for suffix
+    // versioned files (e.g., `libclang.so.1` or `libclang.so.15.0.7`).
+    // However, `ld` cannot link to these files, so they are only searched for
+    // at runtime.
+    FilenameScheme {
+        pattern: "libclang.so.*",
+        versioned: true,
+        linkable: false,
+        target: || target_os!("linux"),
+    },
+    // Some Linux distributions version the name of the shared library and
+    // suffix it with an ABI version (e.g., `libclang-15.so.1`).
+    FilenameScheme {
+        pattern: "libclang-*.so.*",
+        versioned: true,
+        linkable: false,
+        target: || target_os!("linux"),
+    },
+    // Some BSD distributions don't create a `libclang.so` symlink either, but
+    // use a different naming scheme for versioned files (e.g.,
+    // `libclang.so.7.0` or `libclang.so.13`).
+    FilenameScheme {
+        pattern: "libclang.so.*",
+        versioned: true,
+        linkable: true,
+        target: target_bsd,
+    },
+    // Some macOS distributions don't create a `libclang.dylib` symlink, so we
+    // need to look for files with an ABI version (e.g., `libclang.1.dylib`,
+    // which doesn't contain the version of `libclang`).
+    FilenameScheme {
+        pattern: "libclang.*.dylib",
+        versioned: false,
+        linkable: true,
+        target: || target_os!("macos"),
+    },
+    // The official LLVM build uses `libclang.dll` on Windows instead of
+    // `clang.dll`. However, unofficial builds such as MinGW use `clang.dll`.
+    FilenameScheme {
+        pattern: "libclang.dll",
+        versioned: false,
+        linkable: true,
+        target: || target_os!("windows"),
+    },
+];
+
+/// Returns the filename patterns for `libclang` shared libraries on the target
+/// platform (see `FILENAME_SCHEMES`).
+///
+/// If `runtime` is `false`, only the filename patterns for shared libraries
+/// which can be linked to are returned.
+pub fn filename_patterns(runtime: bool) -> Vec<String> {
+    let mut patterns: Vec<String> = vec![];
+    for scheme in FILENAME_SCHEMES {
+        if (scheme.target)()
+            && (runtime || scheme.linkable)
+            && !patterns.iter().any(|p| p == scheme.pattern)
+        {
+            patterns.push(scheme.pattern.into());
+        }
+    }
+    patterns
+}
+
 /// Extracts the version components in a `libclang` shared library filename.
-fn parse_version(filename: &str) -> Vec<u32> {
-    let version = if let Some(version) = filename.strip_prefix("libclang.so.") {
-        version
-    } else if filename.starts_with("libclang-") {
-        &filename[9..filename.len() - 3]
+///
+/// Filenames which use any of the naming schemes in `FILENAME_SCHEMES` are
+/// accepted regardless of the target platform.
+pub fn parse_version(filename: &str) -> Vec<u32> {
+    FILENAME_SCHEMES
+        .iter()
+        .find_map(|s| s.version(filename))
+        .map(|v| v.split('.').map(|s| s.parse().unwrap_or(0)).collect())
+        .unwrap_or_default()
+}
+
+/// Determines the version components of a `libclang` shared library without
+/// loading it (which could run the static initializers in the shared library
+/// and its dependencies).
+///
+/// The version is determined from the first of the following which provides
+/// one (an empty vector is returned if none do):
+///
+/// 1. the `AndroidVersion.txt` file in an Android NDK Clang toolchain
+/// 2. the filename (e.g., `libclang-18.so` or `libclang.so.18.1`)
+/// 3. the `DT_SONAME` of an ELF shared library (e.g., for `libclang.so`) or the
+///    file version in the version resource of a PE shared library
+/// 4. an embedded `clang` version string (e.g., `clang version 18.1.8`)
+fn library_version(directory: &Path, filename: &str) -> Vec<u32> {
+    if let Some(version) = common::android_ndk_clang_version(directory) {
+        return version;
+    }
+
+    let version = parse_version(filename);
+    if !version.is_empty() {
+        return version;
+    }
+
+    let path = directory.join(filename);
+    let version = if target_os!("windows") {
+        parse_pe_file_version(&path).ok().flatten()
     } else {
-        return vec![];
+        // The name of a shared library is either versioned like its filename
+        // (e.g., `libclang-18.so.18`) or a suffix versioned filename (e.g.,
+        // `libclang.so.18.1`).
+        let soname = parse_elf_soname(&path).ok().flatten().unwrap_or_default();
+        Some(parse_version(&soname)).filter(|v| !v.is_empty())
     };
 
-    version.split('.').map(|s| s.parse().unwrap_or(0)).collect()
+    version
+        .or_else(|| scan_clang_version(&path).ok().flatten())
+        .unwrap_or_default()
 }
 
-/// Finds `libclang` shared libraries and returns the paths to, filenames of,
-/// and versions of those shared libraries.
-fn search_libclang_directories(runtime: bool) -> Result<Vec<(PathBuf, String, Vec<u32>)>, String> {
-    let mut files = vec![format!(
-        "{}clang{}",
-        env::consts::DLL_PREFIX,
-        env::consts::DLL_SUFFIX
-    )];
-
-    if target_os!("linux") {
-        // Some Linux distributions don't create a `libclang.so` symlink, so we
-        // need to look for versioned files (e.g., `libclang-3.9.so`).
-        files.push("libclang-*.so".into());
-
-        // Some Linux distributions don't create a `libclang.so` symlink and
-        // don't have versioned files as described above, so we need to look for
-        // suffix versioned files (e.g., `libclang.so.1`). However, `ld` cannot
-        // link to these files, so this will only be included when linking at
-        // runtime.
-        if runtime {
-            files.push("libclang.so.*".into());
-            files.push("libclang-*.so.*".into());
-        }
-    }
-
-    if target_os!("freebsd") || target_os!("haiku") || target_os!("netbsd") || target_os!("openbsd")
-    {
-        // Some BSD distributions don't create a `libclang.so` symlink either,
-        // but use a different naming scheme for versioned files (e.g.,
-        // `libclang.so.7.0`).
-        files.push("libclang.so.*".into());
+/// A `libclang` shared library found while searching.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    /// The directory containing this shared library.
+    pub directory: PathBuf,
+    /// The filename of this shared library.
+    pub filename: String,
+    /// The version components of this shared library (empty if unversioned).
+    pub version: Vec<u32>,
+    /// The search priority of the location this shared library was found in
+    /// (lower values are preferred).
+    pub priority: usize,
+    /// Whether the C and C++ standard library requirements of this shared
+    /// library are known to be satisfied (see `check_runtime_requirements`).
+    pub compatible: bool,
+    /// The kind of location this shared library was found in.
+    pub source: common::DiscoverySource,
+}
+
+impl Candidate {
+    /// Returns the path to this shared library.
+    pub fn path(&self) -> PathBuf {
+        self.directory.join(&self.filename)
     }
+}
 
-    if target_os!("windows") {
-        // The official LLVM build uses `libclang.dll` on Windows instead of
-        // `clang.dll`. However, unofficial builds such as MinGW use `clang.dll`.
-        files.push("libclang.dll".into());
+/// Compares two `libclang` shared libraries by preference, the most preferred
+/// shared library being the lesser of the two.
+///
+/// Shared libraries are ordered by the following criteria, with each criterion
+/// only being used to break ties in the previous ones:
+///
+/// 1. compatibility (compatible shared libraries are preferred)
+/// 2. version (higher versions are preferred)
+/// 3. search priority (lower search priorities are preferred)
+/// 4. path (lexicographically lesser paths are preferred)
+///
+/// Since no two shared libraries found by a search have the same path, this is
+/// a total ordering and the shared library selected for a given set of shared
+/// libraries does not depend on the order in which they were found (e.g., the
+/// directory iteration order of the filesystem).
+pub fn compare_candidates(a: &Candidate, b: &Candidate) -> Ordering {
+    b.compatible
+        .cmp(&a.compatible)
+        .then_with(|| b.version.cmp(&a.version))
+        .then_with(|| a.priority.cmp(&b.priority))
+        .then_with(|| a.path().cmp(&b.path()))
+}
+
+/// How a `libclang` shared library is selected when several are found.
+#[derive(Copy, Clone, Debug, Default)]
+pub enum SelectionPolicy {
+    /// Prefers the shared library with the highest version (see
+    /// `compare_candidates`).
+    #[default]
+    PreferNewest,
+    /// Prefers the shared library found in the location searched earliest
+    /// (e.g., the directories in `LD_LIBRARY_PATH` before the directories
+    /// matching the `libclang` directory patterns), breaking ties like
+    /// `PreferNewest`.
+    PreferEnvPath,
+    /// Selects the first shared library found in the order the locations are
+    /// searched (and then by path) regardless of its version or compatibility.
+    PreferFirstMatch,
+    /// Prefers the lesser of two shared libraries according to the supplied
+    /// comparison function.
+    Custom(fn(&Candidate, &Candidate) -> Ordering),
+}
+
+impl SelectionPolicy {
+    /// Returns the selection policy provided by the `LIBCLANG_SELECTION_POLICY`
+    /// environment variable (`newest`, `env-path`, or `first-match`), or the
+    /// default selection policy if it is not set.
+    pub fn from_env() -> Result<Self, String> {
+        match common::var("LIBCLANG_SELECTION_POLICY").as_deref() {
+            None | Some("newest") => Ok(SelectionPolicy::PreferNewest),
+            Some("env-path") => Ok(SelectionPolicy::PreferEnvPath),
+            Some("first-match") => Ok(SelectionPolicy::PreferFirstMatch),
+            Some(value) => Err(format!(
+                "invalid `LIBCLANG_SELECTION_POLICY` ({}), expected `newest`, `env-path`, \
+                 or `first-match`",
+                value,
+            )),
+        }
+    }
+
+    /// Compares two `libclang` shared libraries by preference according to this
+    /// selection policy, the most preferred shared library being the lesser of
+    /// the two.
+    pub fn compare(&self, a: &Candidate, b: &Candidate) -> Ordering {
+        match self {
+            SelectionPolicy::PreferNewest => compare_candidates(a, b),
+            SelectionPolicy::PreferEnvPath => a
+                .priority
+                .cmp(&b.priority)
+                .then_with(|| compare_candidates(a, b)),
+            SelectionPolicy::PreferFirstMatch => a
+                .priority
+                .cmp(&b.priority)
+                .then_with(|| a.path().cmp(&b.path())),
+            SelectionPolicy::Custom(compare) => compare(a, b),
+        }
+    }
+}
+
+/// Determines the properties of a `libclang` shared library found by a search
+/// by reading it.
+fn library_properties(directory: &Path, filename: &str) -> common::LibraryProperties {
+    let path = directory.join(filename);
+    if let Err(message) = validate_library(&path) {
+        return common::LibraryProperties {
+            valid: Err(message),
+            version: vec![],
+            compatible: Ok(()),
+        };
+    }
+
+    let compatible = if target_os!("linux") {
+        check_runtime_requirements(&path)
+    } else {
+        Ok(())
+    };
+
+    common::LibraryProperties {
+        valid: Ok(()),
+        version: library_version(directory, filename),
+        compatible,
+    }
+}
+
+/// Finds `libclang` shared libraries and returns the paths to, filenames of,
+/// versions of, search priorities of, and kinds of locations of those shared
+/// libraries.
+///
+/// If any locations are supplied, only those locations are searched. If any
+/// filename patterns are supplied, they are used instead of the filename
+/// patterns for the target platform.
+fn search_libclang_directories(
+    runtime: bool,
+    locations: &[PathBuf],
+    patterns: &[String],
+) -> Result<Vec<Candidate>, String> {
+    let mut files = filename_patterns(runtime);
+
+    if !patterns.is_empty() {
+        files = patterns.to_vec();
     }
 
+    // At runtime, `CLANG_SYS_LIBCLANG_PATH` takes precedence over
+    // `LIBCLANG_PATH` so that a binary can be pointed at a specific shared
+    // library without affecting build scripts which use `LIBCLANG_PATH`.
+    let variable = if runtime && env::var_os("CLANG_SYS_LIBCLANG_PATH").is_some() {
+        "CLANG_SYS_LIBCLANG_PATH"
+    } else {
+        "LIBCLANG_PATH"
+    };
+
     // Find and validate `libclang` shared libraries and collect the versions.
     let mut valid = vec![];
     let mut invalid = vec![];
-    for (directory, filename) in common::search_libclang_directories(&files, "LIBCLANG_PATH") {
+    let found = if locations.is_empty() {
+        common::search_libclang_directories(&files, variable)
+    } else {
+        common::search_libclang_locations(locations, &files)
+    };
+    for (directory, filename, priority, source) in found {
         let path = directory.join(&filename);
-        match validate_library(&path) {
+        let properties = common::replay_library(&path)
+            .unwrap_or_else(|| library_properties(&directory, &filename));
+        common::record_library(&path, &properties);
+
+        match properties.valid {
             Ok(()) => {
-                let version = parse_version(&filename);
-                valid.push((directory, filename, version))
+                // Shared libraries which require a newer `glibc` or `libstdc++`
+                // than is available would fail to load, so they're only used
+                // if nothing else is available.
+                let compatible = properties.compatible.is_ok();
+                if let Err(message) = &properties.compatible {
+                    log!(Debug, "deprioritized {} ({})", path.display(), message);
+                }
+                if let Err(message) = properties.compatible
+                    && !runtime
+                {
+                    common::warn(
+                        common::Severity::Warning,
+                        format!(
+                            "deprioritized `libclang` shared library {} ({})",
+                            path.display(),
+                            message,
+                        ),
+                    );
+                }
+
+                valid.push(Candidate {
+                    directory,
+                    filename,
+                    version: properties.version,
+                    priority,
+                    compatible,
+                    source,
+                });
+            }
+            Err(message) => {
+                log!(Debug, "skipped invalid {} ({})", path.display(), message);
+                invalid.push(format!("({}: {})", path.display(), message));
             }
-            Err(message) => invalid.push(format!("({}: {})", path.display(), message)),
         }
     }
 
@@ -172,65 +1428,342 @@ fn search_libclang_directories(runtime: bool) -> Result<Vec<(PathBuf, String, Ve
         return Ok(valid);
     }
 
-    let message = format!(
-        "couldn't find any valid shared libraries matching: [{}], set the \
-         `LIBCLANG_PATH` environment variable to a path where one of these files \
-         can be found (invalid: [{}])",
+    let hint = if locations.is_empty() {
+        format!(
+            "set the `{}` environment variable to a path where one of these \
+             files can be found",
+            variable,
+        )
+    } else {
+        format!(
+            "searched: [{}]",
+            locations
+                .iter()
+                .map(|l| l.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    };
+
+    let mut message = format!(
+        "couldn't find any valid shared libraries matching: [{}], {} (invalid: [{}])",
         files
             .iter()
             .map(|f| format!("'{}'", f))
             .collect::<Vec<_>>()
             .join(", "),
+        hint,
         invalid.join(", "),
     );
 
+    let unreadable = common::search_errors();
+    if !unreadable.is_empty() {
+        message.push_str(&format!(" (unreadable: [{}])", unreadable.join(", ")));
+    }
+
     Err(message)
 }
 
 /// Finds the "best" `libclang` shared library and returns the directory and
 /// filename of that library.
+///
+/// See `compare_candidates` for how the "best" shared library is selected by
+/// default and `SelectionPolicy::from_env` for how this can be changed.
+///
+/// If the `LIBCLANG_PROBE` environment variable is set and not linking at
+/// runtime, the shared libraries are loaded in a child process in order of
+/// preference and the first shared library which can be loaded is selected
+/// (see `probe`). Shared libraries are not probed when cross-compiling since
+/// the shared libraries found for the target can't be loaded on the host.
+///
+/// If the `CLANG_SYS_RECORD` environment variable is set, everything consulted
+/// while finding the shared library is recorded to the file provided by that
+/// environment variable (see `common::Recording`).
 pub fn find(runtime: bool) -> Result<(PathBuf, String), String> {
-    search_libclang_directories(runtime)?
-        .iter()
-        // We want to find the `libclang` shared library with the highest
-        // version number, hence `max_by_key` below.
-        //
-        // However, in the case where there are multiple such `libclang` shared
-        // libraries, we want to use the order in which they appeared in the
-        // list returned by `search_libclang_directories` as a tiebreaker since
-        // that function returns `libclang` shared libraries in descending order
-        // of preference by how they were found.
-        //
-        // `max_by_key`, perhaps surprisingly, returns the *last* item with the
-        // maximum key rather than the first which results in the opposite of
-        // the tiebreaking behavior we want. This is easily fixed by reversing
-        // the list first.
-        .rev()
-        .max_by_key(|f| &f.2)
-        .cloned()
-        .map(|(path, filename, _)| (path, filename))
-        .ok_or_else(|| "unreachable".into())
+    let Some(path) = env::var_os("CLANG_SYS_RECORD").filter(|_| !common::is_replaying()) else {
+        return find_libclang(runtime);
+    };
+
+    common::start_recording(runtime);
+    let result = find_libclang(runtime);
+    let selected = result.as_ref().map(|(d, f)| d.join(f));
+    common::finish_recording(Path::new(&path), &selected.map_err(|e| e.clone()));
+    result
+}
+
+/// Finds the `libclang` shared libraries matching the supplied filename
+/// patterns in the supplied locations (see `search_libclang_directories`) for
+/// linking at runtime and returns them in order of preference according to the
+/// supplied selection policy (or the one provided by the environment, see
+/// `SelectionPolicy::from_env`).
+pub fn find_candidates(
+    locations: &[PathBuf],
+    patterns: &[String],
+    policy: Option<SelectionPolicy>,
+) -> Result<Vec<Candidate>, String> {
+    let policy = policy.map_or_else(SelectionPolicy::from_env, Ok)?;
+    let mut candidates = search_libclang_directories(true, locations, patterns)?;
+    candidates.sort_by(|a, b| policy.compare(a, b));
+    Ok(candidates)
+}
+
+/// Finds the "best" `libclang` shared library and returns the directory and
+/// filename of that library (see `find`).
+fn find_libclang(runtime: bool) -> Result<(PathBuf, String), String> {
+    let policy = SelectionPolicy::from_env()?;
+    let mut candidates = search_libclang_directories(runtime, &[], &[])?;
+    candidates.sort_by(|a, b| policy.compare(a, b));
+
+    log!(Debug, "found {} valid candidates", candidates.len());
+    let probe_requested = !runtime && env::var_os("LIBCLANG_PROBE").is_some();
+    if probe_requested && common::is_cross_compiling() {
+        common::warn(
+            common::Severity::Warning,
+            "ignoring `LIBCLANG_PROBE` because the build script is cross-compiling \
+             (the `libclang` shared libraries found for the target can't be loaded \
+             on the host)",
+        );
+    }
+
+    let candidate = if probe_requested && !common::is_cross_compiling() {
+        probe(candidates)?
+    } else {
+        candidates.into_iter().next().ok_or("unreachable")?
+    };
+
+    log!(
+        Debug,
+        "selected {} (version: {:?}, search priority: {}, compatible: {})",
+        candidate.directory.join(&candidate.filename).display(),
+        candidate.version,
+        candidate.priority,
+        candidate.compatible,
+    );
+    Ok((candidate.directory, candidate.filename))
+}
+
+/// Returns the first of the supplied `libclang` shared libraries which can be
+/// loaded in a child process.
+///
+/// This catches shared libraries which would be linked to successfully but
+/// which could not be loaded when the resulting binary is executed (e.g.,
+/// shared libraries with missing dependencies such as `libtinfo` or which
+/// require a newer C standard library). A warning is printed for each shared
+/// library which is skipped. Shared libraries which could not be probed (e.g.,
+/// because the child process could not be executed) are assumed to be loadable.
+fn probe(candidates: Vec<Candidate>) -> Result<Candidate, String> {
+    let mut skipped = vec![];
+    for candidate in candidates {
+        let path = candidate.directory.join(&candidate.filename);
+        match common::run_probe(&path.to_string_lossy()) {
+            Some(output) if output.trim() != "ok" => {
+                log!(Debug, "skipped {} ({})", path.display(), output.trim());
+                skipped.push(format!("({}: {})", path.display(), output.trim()));
+            }
+            _ => {
+                for skipped in &skipped {
+                    common::warn(
+                        common::Severity::Warning,
+                        format!(
+                            "skipped `libclang` shared library which could not be loaded {}",
+                            skipped,
+                        ),
+                    );
+                }
+
+                return Ok(candidate);
+            }
+        }
+    }
+
+    Err(format!(
+        "couldn't load any of the `libclang` shared libraries found, unset the \
+         `LIBCLANG_PROBE` environment variable to link to one anyway (errors: [{}])",
+        skipped.join(", "),
+    ))
+}
+
+//================================================
+// Stubs
+//================================================
+
+/// Returns the names of the functions bound in the supplied Rust source (e.g.,
+/// `src/lib.rs`, see `common::parse_bindings`).
+pub fn parse_functions(source: &str) -> Vec<String> {
+    common::parse_bindings(source)
+        .into_iter()
+        .filter(|b| b.kind == common::BindingKind::Function)
+        .map(|b| b.name)
+        .collect()
+}
+
+/// The name of the variable exported by stub `libclang` shared libraries so
+/// that they can be recognized without calling any of their functions.
+pub const STUB_MARKER: &str = "clang_sys_stub";
+
+/// Returns the source of a stub `libclang` shared library which exports the
+/// supplied functions (which abort the process if they are ever called).
+pub fn generate_stub(functions: &[String]) -> String {
+    let mut source = String::from("#![allow(non_snake_case)]\n");
+    source.push_str(&format!(
+        "\n#[no_mangle]\npub static {}: u8 = 0;\n",
+        STUB_MARKER
+    ));
+    for function in functions {
+        source.push_str(&format!(
+            "\n#[no_mangle]\npub extern \"C\" fn {}() {{\n    std::process::abort()\n}}\n",
+            function,
+        ));
+    }
+    source
+}
+
+/// Builds a stub `libclang` shared library which exports the supplied
+/// functions in the supplied directory and returns its filename.
+///
+/// The stub shared library is named with the supplied name (the `DT_SONAME`
+/// on ELF platforms and the install name on macOS) so that binaries linked to
+/// it load the real `libclang` shared library with that name at runtime. It
+/// is built with the `rustc`, target, and linker used by Cargo (if available).
+pub fn build_stub(directory: &Path, functions: &[String], name: &str) -> Result<String, String> {
+    use std::fs;
+    use std::process::Command;
+
+    let source = directory.join("clang_sys_stub.rs");
+    fs::write(&source, generate_stub(functions))
+        .map_err(|e| format!("could not write {}: {}", source.display(), e))?;
+
+    let filename = if target_os!("macos") {
+        "libclang.dylib"
+    } else {
+        "libclang.so"
+    };
+    let argument = if target_os!("macos") {
+        format!("-Clink-arg=-Wl,-install_name,{}", name)
+    } else {
+        format!("-Clink-arg=-Wl,-soname,{}", name)
+    };
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let mut command = Command::new(&rustc);
+    command
+        .args(["--edition", "2021", "--crate-type", "cdylib"])
+        .args(["--crate-name", "clang", &argument])
+        .arg("-o")
+        .arg(directory.join(filename))
+        .arg(&source);
+    if let Ok(target) = env::var("TARGET") {
+        command.args(["--target", &target]);
+    }
+    if let Ok(linker) = env::var("RUSTC_LINKER") {
+        command.arg(format!("-Clinker={}", linker));
+    }
+
+    let result = command
+        .output()
+        .map_err(|e| format!("could not execute `{}`: {}", rustc, e))?;
+    if result.status.success() {
+        return Ok(filename.into());
+    }
+
+    Err(format!(
+        "the stub `libclang` shared library could not be built, output of `rustc`:\n{}",
+        String::from_utf8_lossy(&result.stderr),
+    ))
+}
+
+/// Links to a stub `libclang` shared library (with the `stub` Cargo feature)
+/// because a `libclang` shared library couldn't be found (for the supplied
+/// reason) and returns the directory containing it.
+///
+/// On Windows, the functions are imported from `libclang.dll` with `raw-dylib`
+/// instead (see `link!`) so nothing needs to be built.
+#[cfg(any(not(feature = "runtime"), feature = "hybrid"))]
+fn link_stub(reason: &str) -> PathBuf {
+    common::warn(
+        common::Severity::Warning,
+        format!(
+            "linking to a stub `libclang` shared library since one couldn't be \
+             found, a `libclang` shared library must be available at runtime ({})",
+            reason,
+        ),
+    );
+
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap());
+    if target_os!("windows") {
+        return out;
+    }
+
+    let directory = out.join("stub");
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let name = env::var("LIBCLANG_STUB_SONAME").unwrap_or_else(|_| {
+        if target_os!("macos") {
+            "@rpath/libclang.dylib".into()
+        } else {
+            "libclang.so".into()
+        }
+    });
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    let source = std::fs::read_to_string("src/lib.rs").unwrap();
+    let functions = parse_functions(&source);
+    if let Err(error) = build_stub(&directory, &functions, &name) {
+        panic!("{}", error);
+    }
+
+    println!("cargo:rustc-link-search=native={}", directory.display());
+    println!("cargo:rustc-link-lib=dylib=clang");
+    directory
 }
 
 //================================================
 // Linking
 //================================================
 
-/// Finds and links to a `libclang` shared library.
-#[cfg(not(feature = "runtime"))]
-pub fn link() {
+/// Finds and links to a `libclang` shared library and returns it.
+///
+/// If a `libclang` shared library can't be found and the `stub` Cargo feature
+/// is enabled, a stub shared library is linked to instead (see `link_stub`).
+#[cfg(any(not(feature = "runtime"), feature = "hybrid"))]
+pub fn link() -> common::Linked {
+    if let Some(target) = common::wasm_target() {
+        panic!(
+            "`libclang` can't be linked to dynamically for WebAssembly targets ({}) which \
+             don't support shared libraries, enable the `static` Cargo feature and set the \
+             `LIBCLANG_STATIC_PATH` environment variable to a directory containing `libclang` \
+             static libraries compiled for WebAssembly instead (experimental)",
+            target,
+        );
+    }
+
     let cep = common::CommandErrorPrinter::default();
 
     use std::fs;
 
-    let (directory, filename) = find(false).unwrap();
+    let found = find(false);
+    if let Err(reason) = &found
+        && cfg!(feature = "stub")
+    {
+        cep.discard();
+        return common::Linked {
+            libdir: link_stub(reason),
+            library: None,
+            version: None,
+        };
+    }
+
+    let (directory, filename) = found.unwrap();
     println!("cargo:rustc-link-search={}", directory.display());
 
-    if cfg!(all(target_os = "windows", target_env = "msvc")) {
+    if cfg!(feature = "stub") && target_os!("windows") {
+        // The functions are imported from `libclang.dll` with `raw-dylib` so
+        // an import library isn't required (see `link!`).
+    } else if cfg!(all(target_os = "windows", target_env = "msvc")) {
         // Find the `libclang` stub static library required for the MSVC
         // toolchain.
         let lib = if !directory.ends_with("bin") {
-            directory
+            directory.clone()
         } else {
             directory.parent().unwrap().join("lib")
         };
@@ -278,4 +1811,10 @@ pub fn link() {
     }
 
     cep.discard();
+
+    let path = directory.join(&filename);
+    let version = common::replay_library(&path)
+        .unwrap_or_else(|| library_properties(&directory, &filename))
+        .version;
+    common::Linked::new(directory, Some(path), &version)
 }
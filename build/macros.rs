@@ -49,3 +49,15 @@ macro_rules! target_env {
         }
     };
 }
+
+/// Emits a log record (e.g., `log!(Debug, "found {}", path)`) with the `log`
+/// crate if the `log` Cargo feature is enabled.
+macro_rules! log {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        ::log::log!(target: "clang_sys", ::log::Level::$level, $($arg)+);
+        // The arguments are still type checked (but not formatted) otherwise.
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    };
+}
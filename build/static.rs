@@ -1,6 +1,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Error, ErrorKind, Read};
 use std::path::{Path, PathBuf};
+use std::process::Command;
 
 use glob::Pattern;
 
@@ -57,25 +61,328 @@ fn get_llvm_libraries() -> Vec<String> {
         .collect()
 }
 
-/// Gets the Clang static libraries required to link to `libclang`.
-fn get_clang_libraries<P: AsRef<Path>>(directory: P) -> Vec<String> {
+/// System libraries which LLVM optionally depends on (depending on how LLVM was
+/// configured) and the `pkg-config` packages which provide them.
+const OPTIONAL_SYSTEM_LIBRARIES: &[(&str, &str)] = &[("zstd", "libzstd"), ("xml2", "libxml-2.0")];
+
+/// Directories searched for optional system libraries as a last resort.
+const SYSTEM_LIBRARY_DIRECTORIES: &[&str] = &[
+    "/usr/local/lib",
+    "/usr/lib",
+    "/usr/lib64",
+    "/usr/lib/*-linux-gnu*",
+    "/opt/homebrew/lib",
+];
+
+/// Finds the optional system libraries (e.g., `zstd`) required by the LLVM
+/// static libraries and returns the names of those libraries and the
+/// directories containing those libraries (if they could be found).
+///
+/// The optional system libraries required by the LLVM static libraries are
+/// determined with `llvm-config --system-libs`. These libraries are searched
+/// for in the directories listed by `llvm-config`, the LLVM library directory,
+/// the directories provided by `pkg-config`, and some common system library
+/// directories (in that order).
+pub fn find_optional_system_libraries() -> Vec<(String, Option<PathBuf>)> {
+    let Some(output) = common::run_llvm_config(&["--system-libs", "--link-static"]) else {
+        return vec![];
+    };
+
+    // Depending on the version of `llvm-config` in use, listed libraries may be
+    // in one of two forms, a full path to the library or simply prefixed with
+    // `-l`. Directories may also be listed with `-L`.
+    let mut names = vec![];
+    let mut directories = vec![];
+    for argument in output.split_whitespace() {
+        if let Some(name) = argument.strip_prefix("-l") {
+            names.push(name.to_string());
+        } else if let Some(directory) = argument.strip_prefix("-L") {
+            directories.push(PathBuf::from(directory));
+        } else {
+            let path = Path::new(argument);
+            names.extend(get_library_name(path).map(|n| n.split('.').next().unwrap().into()));
+            directories.extend(path.parent().map(Path::to_path_buf));
+        }
+    }
+
+    if let Some(libdir) = common::run_llvm_config(&["--libdir"]) {
+        directories.push(libdir.trim_end().into());
+    }
+
+    OPTIONAL_SYSTEM_LIBRARIES
+        .iter()
+        .filter(|(name, _)| names.iter().any(|n| n == name))
+        .map(|(name, package)| {
+            let mut directories = directories.clone();
+
+            if let Some(output) = common::run_pkg_config(&["--libs-only-L", package]) {
+                let paths = output.split_whitespace();
+                directories.extend(
+                    paths
+                        .filter_map(|p| p.strip_prefix("-L"))
+                        .map(PathBuf::from),
+                );
+            }
+
+            // We use temporary directories when testing the build script so
+            // we'll skip the system directories.
+            if !test!() {
+                for pattern in SYSTEM_LIBRARY_DIRECTORIES {
+                    if let Ok(paths) = glob::glob(pattern) {
+                        directories.extend(paths.filter_map(Result::ok));
+                    }
+                }
+            }
+
+            let filenames = [
+                format!("lib{}.a", name),
+                format!("lib{}.so", name),
+                format!("lib{}.dylib", name),
+                format!("lib{}.dll.a", name),
+                format!("{}.lib", name),
+            ];
+
+            let directory = directories
+                .into_iter()
+                .find(|d| filenames.iter().any(|f| d.join(f).is_file()));
+
+            (name.to_string(), directory)
+        })
+        .collect()
+}
+
+/// The default pattern matched by the file names of the Clang static libraries
+/// (where `*` is the name of a Clang component, e.g., `Basic`).
+const DEFAULT_PATTERN: &str = "libclang*.a";
+
+/// Returns the pattern matched by the file names of the Clang static libraries
+/// (from the `LIBCLANG_STATIC_PATTERN` environment variable, if set).
+///
+/// Some vendor distributions of LLVM rename the Clang static libraries (e.g.,
+/// `libclangBasic_static.a` or `libclangBasic-18.a`), the `*` in the pattern
+/// takes the place of the name of a Clang component (e.g., `Basic`).
+pub fn get_pattern() -> Result<String, String> {
+    let Some(pattern) = env::var_os("LIBCLANG_STATIC_PATTERN") else {
+        return Ok(DEFAULT_PATTERN.into());
+    };
+
+    let pattern = pattern
+        .into_string()
+        .map_err(|_| "`LIBCLANG_STATIC_PATTERN` is not valid Unicode".to_string())?;
+    if pattern.matches('*').count() != 1 || pattern.contains(['/', '\\']) {
+        return Err(format!(
+            "`LIBCLANG_STATIC_PATTERN` ({}) must be a file name containing exactly one `*` \
+            (e.g., `libclang*_static.a`)",
+            pattern,
+        ));
+    }
+
+    Pattern::new(&pattern).map_err(|e| {
+        format!(
+            "`LIBCLANG_STATIC_PATTERN` ({}) is not a valid pattern: {}",
+            pattern, e
+        )
+    })?;
+
+    Ok(pattern)
+}
+
+/// Gets the Clang static libraries matching the supplied pattern (see
+/// `get_pattern`) required to link to `libclang` and returns the names of
+/// those libraries and the directories containing them.
+///
+/// Any of the libraries that are actually linker scripts (e.g., `INPUT(...)`)
+/// or MRI scripts (e.g., `addlib ...`) are replaced by the libraries they
+/// list. Thin archives are rejected since `rustc` can't bundle them.
+pub fn get_clang_libraries<P: AsRef<Path>>(
+    directory: P,
+    pattern: &str,
+) -> Result<Vec<(PathBuf, String)>, String> {
+    let root = directory.as_ref();
+
     // Escape the directory in case it contains characters that have special
     // meaning in glob patterns (e.g., `[` or `]`).
-    let directory = Pattern::escape(directory.as_ref().to_str().unwrap());
+    let directory = Pattern::escape(root.to_str().unwrap());
     let directory = Path::new(&directory);
 
-    let pattern = directory.join("libclang*.a").to_str().unwrap().to_owned();
-    if let Ok(libraries) = glob::glob(&pattern) {
-        libraries
-            .filter_map(|l| l.ok().and_then(|l| get_library_name(&l)))
-            .collect()
-    } else {
-        CLANG_LIBRARIES.iter().map(|l| (*l).to_string()).collect()
+    let pattern = directory.join(pattern).to_str().unwrap().to_owned();
+    let Ok(libraries) = glob::glob(&pattern) else {
+        return Ok(CLANG_LIBRARIES
+            .iter()
+            .map(|l| (root.to_path_buf(), (*l).to_string()))
+            .collect());
+    };
+
+    let mut resolved = vec![];
+    for library in libraries.filter_map(Result::ok) {
+        for path in resolve_archive(&library, 0)? {
+            let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            if let Some(library) = get_library_name(&path).map(|n| (directory, n))
+                && !resolved.contains(&library)
+            {
+                resolved.push(library);
+            }
+        }
     }
+
+    Ok(resolved)
 }
 
-/// Finds a directory containing LLVM and Clang static libraries and returns the
-/// path to that directory.
+/// The maximum depth of nested linker or MRI scripts that will be followed.
+const MAX_SCRIPT_DEPTH: usize = 8;
+
+/// Returns the paths to the regular archives that should be linked to in place
+/// of the supplied file (which should be an archive or a script listing other
+/// archives).
+///
+/// Only the magic number of an archive is read. The relative paths listed in a
+/// script are resolved relative to the directory containing the script.
+fn resolve_archive(path: &Path, depth: usize) -> Result<Vec<PathBuf>, String> {
+    let error = |e: io::Error| format!("could not read {}: {}", path.display(), e);
+
+    let mut magic = [0; 8];
+    let mut file = File::open(path).map_err(error)?;
+    let magic = file.read_exact(&mut magic).ok().map(|_| &magic);
+
+    if magic == Some(b"!<arch>\n") {
+        return Ok(vec![path.into()]);
+    }
+
+    if magic == Some(b"!<thin>\n") {
+        return Err(format!(
+            "{} is a thin archive (which only references its members) and can't be \
+            linked to statically, convert it to a regular archive (e.g., with \
+            `llvm-ar rcs --format=gnu <output> $(llvm-ar t {})`) or use an LLVM \
+            build that doesn't produce thin archives",
+            path.display(),
+            path.display(),
+        ));
+    }
+
+    let contents = fs::read(path).map_err(error)?;
+    let inputs = std::str::from_utf8(&contents)
+        .ok()
+        .and_then(parse_archive_script);
+    let Some(inputs) = inputs else {
+        return Err(format!(
+            "{} is neither an archive nor a linker or MRI script listing archives",
+            path.display(),
+        ));
+    };
+
+    if depth >= MAX_SCRIPT_DEPTH {
+        return Err(format!(
+            "{} is nested too deeply in linker or MRI scripts",
+            path.display(),
+        ));
+    }
+
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut resolved = vec![];
+    for input in inputs {
+        let input = parent.join(input);
+
+        if !input.is_file() {
+            return Err(format!(
+                "{} lists {} which could not be found",
+                path.display(),
+                input.display(),
+            ));
+        }
+
+        resolved.extend(resolve_archive(&input, depth + 1)?);
+    }
+
+    Ok(resolved)
+}
+
+/// Parses a linker script (e.g., `INPUT(libclangBasic.a)` or `GROUP(-lfoo)`)
+/// or an MRI script (e.g., `addlib libclangBasic.a`) and returns the paths to
+/// the archives it lists (or `None` if it is not such a script).
+///
+/// The `-lfoo` inputs of linker scripts are returned as `libfoo.a` and the
+/// module lists of `addlib` commands (e.g., `addlib libfoo.a (foo.o)`) are
+/// ignored.
+fn parse_archive_script(script: &str) -> Option<Vec<String>> {
+    // Remove C-style comments which may appear in linker scripts.
+    let mut text = String::with_capacity(script.len());
+    let mut rest = script;
+    while let Some(start) = rest.find("/*") {
+        text.push_str(&rest[..start]);
+        rest = rest[start + 2..].split_once("*/").map_or("", |(_, r)| r);
+    }
+    text.push_str(rest);
+
+    // Linker scripts.
+    let mut inputs = vec![];
+    let mut found = false;
+    for command in ["INPUT", "GROUP"] {
+        let mut rest = text.as_str();
+        while let Some(start) = rest.find(command) {
+            let after = rest[start + command.len()..].trim_start();
+            let Some(after) = after.strip_prefix('(') else {
+                rest = &rest[start + command.len()..];
+                continue;
+            };
+
+            // Find the matching parenthesis since `AS_NEEDED(...)` may be
+            // nested inside of `INPUT(...)` or `GROUP(...)`.
+            let mut depth = 1;
+            let end = after.find(|c| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })?;
+
+            inputs.extend(
+                after[..end]
+                    .replace("AS_NEEDED", " ")
+                    .split(|c: char| c.is_whitespace() || matches!(c, ',' | '(' | ')'))
+                    .filter(|i| !i.is_empty())
+                    .map(|i| match i.strip_prefix("-l") {
+                        Some(name) => format!("lib{}.a", name),
+                        None => i.into(),
+                    }),
+            );
+            found = true;
+            rest = &after[end + 1..];
+        }
+    }
+
+    if found {
+        return Some(inputs);
+    }
+
+    // MRI scripts.
+    let mut mri = false;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['*', ';']) {
+            continue;
+        }
+
+        let (command, arguments) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match command.to_ascii_lowercase().as_str() {
+            "create" | "createthin" | "open" | "save" | "end" => mri = true,
+            "addlib" => {
+                mri = true;
+                let archive = arguments
+                    .split(|c: char| c.is_whitespace() || c == '(')
+                    .next();
+                inputs.extend(archive.filter(|a| !a.is_empty()).map(String::from));
+            }
+            _ => return None,
+        }
+    }
+
+    if mri { Some(inputs) } else { None }
+}
+
+/// Finds a directory containing LLVM and Clang static libraries with file names
+/// matching the supplied pattern (see `get_pattern`) and returns the path to
+/// that directory.
 ///
 /// This function searches for static libraries using multiple strategies:
 /// 1. Look for `libclang.a` (monolithic static library - older LLVM builds)
@@ -85,52 +392,346 @@ fn get_clang_libraries<P: AsRef<Path>>(directory: P) -> Vec<String> {
 /// Modern LLVM installations (especially from package managers like Homebrew)
 /// split libclang into component libraries rather than providing a monolithic
 /// `libclang.a`. This function handles both styles transparently.
-fn find() -> PathBuf {
+fn find(pattern: &str) -> PathBuf {
     // Try to find either the monolithic library or a component library that
     // always exists in Clang static builds.
-    let candidates = if target_os!("windows") {
-        vec!["libclang.lib", "clangBasic.lib"]
-    } else {
-        vec!["libclang.a", "libclangBasic.a"]
-    };
+    let candidates = get_markers(pattern);
 
     let files = common::search_libclang_directories(
-        &candidates.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+        &candidates
+            .iter()
+            .map(|c| Pattern::escape(c))
+            .collect::<Vec<_>>(),
         "LIBCLANG_STATIC_PATH",
     );
 
-    if let Some((directory, filename)) = files.into_iter().next() {
+    if let Some((directory, filename, ..)) = files.into_iter().next() {
         // Log which marker file we found for debugging
-        println!(
-            "cargo:warning=found Clang static libraries using marker: {}",
-            filename
+        common::warn(
+            common::Severity::Debug,
+            format!("found Clang static libraries using marker: {}", filename),
         );
         directory
     } else {
+        let unreadable = common::search_errors();
+        let unreadable = if unreadable.is_empty() {
+            String::new()
+        } else {
+            format!(" (unreadable: [{}])", unreadable.join(", "))
+        };
+
         panic!(
             "could not find Clang static libraries (searched for {} or component libraries), \
-            set LIBCLANG_STATIC_PATH to the directory containing libclang*.a files, see the \
-            README for more information: \
-            https://github.com/KyleMayes/clang-sys?tab=readme-ov-file#static",
-            candidates.join(" or ")
+            set LIBCLANG_STATIC_PATH to the directory containing {} files, see the README \
+            for more information: \
+            https://github.com/KyleMayes/clang-sys?tab=readme-ov-file#static{}",
+            candidates.join(" or "),
+            pattern,
+            unreadable,
         );
     }
 }
 
+/// Returns the file names which mark a directory as containing Clang static
+/// libraries with file names matching the supplied pattern (see `get_pattern`),
+/// the file name of the monolithic `libclang` static library first.
+fn get_markers(pattern: &str) -> Vec<String> {
+    if pattern != DEFAULT_PATTERN {
+        vec![
+            pattern.replacen('*', "", 1),
+            pattern.replacen('*', "Basic", 1),
+        ]
+    } else if target_os!("windows") {
+        vec!["libclang.lib".into(), "clangBasic.lib".into()]
+    } else {
+        vec!["libclang.a".into(), "libclangBasic.a".into()]
+    }
+}
+
+//================================================
+// Archives
+//================================================
+
+/// Reads an unsigned integer of the supplied size (in bytes) from the start of
+/// the supplied bytes.
+fn read_integer(bytes: &[u8], size: usize, big_endian: bool) -> io::Result<u64> {
+    let bytes = bytes
+        .get(..size)
+        .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "truncated symbol table"))?;
+    let fold = |n: u64, b: &u8| (n << 8) | u64::from(*b);
+    if big_endian {
+        Ok(bytes.iter().fold(0, fold))
+    } else {
+        Ok(bytes.iter().rev().fold(0, fold))
+    }
+}
+
+/// Reads the NUL-terminated strings in the supplied bytes.
+fn read_strings(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .split(|b| *b == 0)
+        .filter(|s| !s.is_empty())
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect()
+}
+
+/// Extracts the names of the symbols defined by the members of an archive
+/// (e.g., `libclang.a`) from the symbol table of the archive.
+///
+/// The symbol tables of GNU (and COFF) archives and of BSD archives are
+/// supported. An empty vector is returned if the archive has no symbol table.
+pub fn parse_archive_symbols(path: &Path) -> io::Result<Vec<String>> {
+    let mut file = File::open(path)?;
+    let mut header = [0; 68];
+    file.read_exact(&mut header)?;
+    if &header[..8] != b"!<arch>\n" {
+        return Err(Error::new(ErrorKind::InvalidData, "invalid archive header"));
+    }
+
+    let name = String::from_utf8_lossy(&header[8..24])
+        .trim_end()
+        .to_string();
+    let size = String::from_utf8_lossy(&header[56..66])
+        .trim()
+        .parse::<u64>();
+    let size = size.map_err(|_| Error::new(ErrorKind::InvalidData, "invalid member size"))?;
+
+    let mut data = vec![];
+    file.take(size).read_to_end(&mut data)?;
+
+    // GNU and COFF archives (big-endian offsets followed by the names).
+    let width = match name.as_str() {
+        "/" => Some(4),
+        "/SYM64/" => Some(8),
+        _ => None,
+    };
+
+    if let Some(width) = width {
+        let count = read_integer(&data, width, true)? as usize;
+        let names = data.get(width * (count + 1)..).unwrap_or_default();
+        return Ok(read_strings(names).into_iter().take(count).collect());
+    }
+
+    // BSD archives (the name of the symbol table follows the header).
+    let Some(length) = name
+        .strip_prefix("#1/")
+        .and_then(|l| l.parse::<usize>().ok())
+    else {
+        return Ok(vec![]);
+    };
+
+    let member = read_strings(data.get(..length).unwrap_or_default());
+    let width = match member.first().map(String::as_str) {
+        Some("__.SYMDEF" | "__.SYMDEF SORTED") => 4,
+        Some("__.SYMDEF_64" | "__.SYMDEF_64 SORTED") => 8,
+        _ => return Ok(vec![]),
+    };
+
+    // The symbol table consists of the size of the entries, the entries (the
+    // offsets of the names and of the members), the size of the names, and the
+    // names.
+    let data = &data[length..];
+    let entries = read_integer(data, width, false)? as usize;
+    let table = data.get(width..).unwrap_or_default();
+    let strings = table.get(entries + width..).unwrap_or_default();
+    let mut names = vec![];
+    for entry in table
+        .get(..entries)
+        .unwrap_or_default()
+        .chunks_exact(width * 2)
+    {
+        let offset = read_integer(entry, width, false)? as usize;
+        if let Some(name) = strings
+            .get(offset..)
+            .and_then(|s| read_strings(s).into_iter().next())
+        {
+            names.push(name);
+        }
+    }
+
+    Ok(names)
+}
+
+/// What a monolithic `libclang` static library (e.g., `libclang.a`) contains.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StaticContents {
+    /// The `libclang` C API, Clang, and LLVM (or the contents are unknown).
+    Complete,
+    /// The `libclang` C API and Clang but not LLVM, which has to be linked to
+    /// dynamically (e.g., `libLLVM-18.so`).
+    WithoutLlvm,
+    /// Only the `libclang` C API, Clang and LLVM have to be linked to
+    /// dynamically (e.g., `libclang-cpp.so` and `libLLVM-18.so`).
+    CApiOnly,
+}
+
+/// Returns whether a symbol is in the supplied C++ namespace (e.g., `llvm`).
+fn is_in_namespace(symbol: &str, namespace: &str) -> bool {
+    // Itanium (e.g., `_ZN4llvm2cl3optE` or `__ZNK5clang4Decl...` on macOS).
+    let itanium = symbol
+        .trim_start_matches('_')
+        .strip_prefix("ZN")
+        .is_some_and(|s| {
+            let s = s.strip_prefix('K').unwrap_or(s);
+            s.strip_prefix(&namespace.len().to_string())
+                .is_some_and(|s| s.starts_with(namespace))
+        });
+
+    // Microsoft (e.g., `?opt@cl@llvm@@...`).
+    itanium || symbol.starts_with('?') && symbol.contains(&format!("@{}@@", namespace))
+}
+
+/// Determines what a monolithic `libclang` static library contains from the
+/// names of the symbols it defines (see `parse_archive_symbols`).
+///
+/// Some vendor SDKs provide a `libclang.a` which only contains the `libclang`
+/// C API (a shim) and depends on shared libraries for Clang and LLVM.
+pub fn get_static_contents(symbols: &[String]) -> StaticContents {
+    let defines = |namespace| symbols.iter().any(|s| is_in_namespace(s, namespace));
+    let api = symbols
+        .iter()
+        .any(|s| s.trim_start_matches('_') == "clang_createIndex");
+    if !api || defines("llvm") {
+        StaticContents::Complete
+    } else if defines("clang") {
+        StaticContents::WithoutLlvm
+    } else {
+        StaticContents::CApiOnly
+    }
+}
+
+/// Returns the names of the LLVM shared libraries (e.g., `LLVM-18`) in the
+/// supplied directory which a `libclang` static library which doesn't contain
+/// LLVM is linked to (see `StaticContents`).
+///
+/// The LLVM shared libraries are determined with `llvm-config --libs
+/// --link-shared` or, if that fails, from the file names of the LLVM shared
+/// libraries in the directory (e.g., `libLLVM-18.so`).
+pub fn get_shared_llvm_libraries(directory: &Path) -> Vec<String> {
+    let output = common::run_llvm_config(&["--libs", "--link-shared"]);
+    let libraries = output
+        .iter()
+        .flat_map(|o| o.split_whitespace())
+        .filter_map(|p| match p.strip_prefix("-l") {
+            Some(name) => Some(name.into()),
+            None => get_library_name(Path::new(p)),
+        })
+        .collect::<Vec<_>>();
+    if !libraries.is_empty() {
+        return libraries;
+    }
+
+    let mut names = fs::read_dir(directory)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok()?.file_name().into_string().ok())
+        .filter_map(|f| {
+            let name = f.strip_prefix("lib")?;
+            let name = name
+                .strip_suffix(".so")
+                .or_else(|| name.strip_suffix(".dylib"))?;
+            (name == "LLVM" || name.starts_with("LLVM-")).then(|| name.to_string())
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    names.truncate(1);
+    if names.is_empty() {
+        names.push("LLVM".into());
+    }
+    names
+}
+
 //================================================
 // Linking
 //================================================
 
-/// Finds and links to `libclang` static libraries.
-pub fn link() {
+/// The link directives printed by the build script, recorded as `rustc`
+/// arguments so that they can be verified (see `verify`).
+#[derive(Default)]
+struct Directives(Vec<String>);
+
+impl Directives {
+    /// Adds a directory to the library search path.
+    fn search(&mut self, directory: &Path) {
+        println!("cargo:rustc-link-search=native={}", directory.display());
+        self.0
+            .extend(["-L".into(), format!("native={}", directory.display())]);
+    }
+
+    /// Links to a library (e.g., `static=clangBasic` or `z`).
+    fn link(&mut self, library: &str) {
+        println!("cargo:rustc-link-lib={}", library);
+        self.0.extend(["-l".into(), library.into()]);
+    }
+
+    /// Adds flags (e.g., `-l z`) to the `rustc` invocation.
+    fn flags(&mut self, flags: &str) {
+        println!("cargo:rustc-flags={}", flags);
+        self.0.extend(flags.split_whitespace().map(String::from));
+    }
+}
+
+/// Finds and links to `libclang` static libraries and returns them.
+pub fn link() -> common::Linked {
+    if let Some(target) = common::wasm_target() {
+        return common::Linked {
+            libdir: link_wasm(&target),
+            library: None,
+            version: None,
+        };
+    }
+
     let cep = common::CommandErrorPrinter::default();
+    let mut directives = Directives::default();
+
+    let pattern = get_pattern().unwrap_or_else(|e| panic!("{}", e));
+    let directory = find(&pattern);
+
+    // A monolithic `libclang` static library may only contain the `libclang` C
+    // API and depend on Clang and LLVM shared libraries.
+    let monolithic = directory.join(&get_markers(&pattern)[0]);
+    let contents = parse_archive_symbols(&monolithic)
+        .map(|s| get_static_contents(&s))
+        .unwrap_or(StaticContents::Complete);
+    if contents == StaticContents::Complete {
+        link_libraries(&mut directives, &directory, &pattern);
+    } else {
+        link_shim(&mut directives, &monolithic, contents);
+    }
+
+    // Verify that the Clang static libraries can actually be linked to now
+    // instead of when linking the crate which depends on this crate.
+    if env::var_os("LIBCLANG_STATIC_VERIFY").is_some() {
+        let out = env::var("OUT_DIR").unwrap();
+        if let Err(error) = verify(Path::new(&out), &directives.0) {
+            panic!("{}", error);
+        }
+    }
+
+    cep.discard();
 
-    let directory = find();
+    let library = monolithic.exists().then_some(monolithic);
+    common::Linked::new(directory, library, &[])
+}
 
+/// Links to the Clang static libraries matching the supplied pattern in the
+/// supplied directory and the LLVM and system libraries they depend on.
+fn link_libraries(directives: &mut Directives, directory: &Path, pattern: &str) {
     // Specify required Clang static libraries.
-    println!("cargo:rustc-link-search=native={}", directory.display());
-    for library in get_clang_libraries(directory) {
-        println!("cargo:rustc-link-lib=static={}", library);
+    let libraries = match get_clang_libraries(directory, pattern) {
+        Ok(libraries) => libraries,
+        Err(error) => panic!("could not link to the Clang static libraries: {}", error),
+    };
+
+    directives.search(directory);
+    let mut directories = vec![directory.to_path_buf()];
+    for (directory, library) in libraries {
+        if !directories.contains(&directory) {
+            directives.search(&directory);
+            directories.push(directory);
+        }
+
+        directives.link(&format!("static={}", library));
     }
 
     // Determine the shared mode used by LLVM.
@@ -142,27 +743,227 @@ pub fn link() {
     };
 
     // Specify required LLVM static libraries.
-    println!(
-        "cargo:rustc-link-search=native={}",
-        common::run_llvm_config(&["--libdir"]).unwrap().trim_end()
-    );
+    let libdir = common::run_llvm_config(&["--libdir"]).unwrap();
+    directives.search(Path::new(libdir.trim_end()));
     for library in get_llvm_libraries() {
-        println!("cargo:rustc-link-lib={}{}", prefix, library);
+        directives.link(&format!("{}{}", prefix, library));
+    }
+
+    // Specify required optional system libraries.
+    for (library, directory) in find_optional_system_libraries() {
+        if let Some(directory) = directory {
+            directives.search(&directory);
+        } else {
+            common::warn(
+                common::Severity::Warning,
+                format!(
+                    "LLVM requires the `{}` system library but it could not be found, \
+                     install it or add the directory containing it to `PKG_CONFIG_PATH`",
+                    library,
+                ),
+            );
+        }
+        directives.link(&library);
     }
 
     // Specify required system libraries.
     // MSVC doesn't need this, as it tracks dependencies inside `.lib` files.
     if cfg!(target_os = "freebsd") {
-        println!("cargo:rustc-flags=-l ffi -l ncursesw -l c++ -l z");
+        directives.flags("-l ffi -l ncursesw -l c++ -l z");
     } else if cfg!(any(target_os = "haiku", target_os = "linux")) {
         if cfg!(feature = "libcpp") {
-            println!("cargo:rustc-flags=-l c++");
+            directives.flags("-l c++");
         } else {
-            println!("cargo:rustc-flags=-l ffi -l ncursesw -l stdc++ -l z");
+            directives.flags("-l ffi -l ncursesw -l stdc++ -l z");
         }
     } else if cfg!(target_os = "macos") {
-        println!("cargo:rustc-flags=-l ffi -l ncurses -l c++ -l z");
+        directives.flags("-l ffi -l ncurses -l c++ -l z");
     }
+}
 
-    cep.discard();
+/// Links to a monolithic `libclang` static library which doesn't contain Clang
+/// or LLVM (see `StaticContents`) and the Clang and LLVM shared libraries it
+/// depends on.
+fn link_shim(directives: &mut Directives, path: &Path, contents: StaticContents) {
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let name = get_library_name(path).unwrap_or_else(|| "clang".into());
+    directives.search(directory);
+    directives.link(&format!("static={}", name));
+
+    let libdir = common::run_llvm_config(&["--libdir"]).map(|d| PathBuf::from(d.trim_end()));
+    let libdir = libdir.unwrap_or_else(|| directory.into());
+    if libdir != directory {
+        directives.search(&libdir);
+    }
+
+    let mut libraries = vec![];
+    if contents == StaticContents::CApiOnly {
+        libraries.push("clang-cpp".to_string());
+    }
+    libraries.extend(get_shared_llvm_libraries(&libdir));
+    for library in &libraries {
+        directives.link(library);
+    }
+
+    let missing = if contents == StaticContents::CApiOnly {
+        "Clang or LLVM"
+    } else {
+        "LLVM"
+    };
+
+    common::warn(
+        common::Severity::Info,
+        format!(
+            "{} doesn't contain {}, also linking to shared libraries: {}",
+            path.display(),
+            missing,
+            libraries.join(", "),
+        ),
+    );
+
+    // Specify the C++ standard library required by the `libclang` C API.
+    if cfg!(any(target_os = "freebsd", target_os = "macos")) {
+        directives.flags("-l c++");
+    } else if cfg!(any(target_os = "haiku", target_os = "linux")) {
+        if cfg!(feature = "libcpp") {
+            directives.flags("-l c++");
+        } else {
+            directives.flags("-l stdc++");
+        }
+    }
+}
+
+/// Gets the Clang static libraries matching the supplied pattern (see
+/// `get_clang_libraries`) and the LLVM static libraries (i.e., `libLLVM*.a`) in
+/// the supplied directory and returns the names of those libraries and the
+/// directories containing them.
+pub fn get_wasm_libraries(
+    directory: &Path,
+    pattern: &str,
+) -> Result<Vec<(PathBuf, String)>, String> {
+    let mut libraries = get_clang_libraries(directory, pattern)?;
+    if libraries.is_empty() {
+        return Err(format!(
+            "could not find any Clang static libraries matching {} in {}",
+            pattern,
+            directory.display(),
+        ));
+    }
+
+    libraries.extend(get_clang_libraries(directory, "libLLVM*.a")?);
+    Ok(libraries)
+}
+
+/// Links to `libclang` static libraries compiled for a WebAssembly target and
+/// returns the directory containing them (experimental).
+///
+/// `llvm-config` and the system libraries describe the host rather than the
+/// WebAssembly target, so only the Clang and LLVM static libraries in the
+/// directory provided by the `LIBCLANG_STATIC_PATH` environment variable are
+/// linked to (see `get_wasm_libraries`).
+fn link_wasm(target: &str) -> PathBuf {
+    let Some(directory) = env::var_os("LIBCLANG_STATIC_PATH") else {
+        panic!(
+            "linking to `libclang` statically for WebAssembly targets ({}) requires the \
+             `LIBCLANG_STATIC_PATH` environment variable to be set to a directory containing \
+             `libclang` static libraries compiled for WebAssembly",
+            target,
+        );
+    };
+
+    let directory = PathBuf::from(directory);
+    let pattern = get_pattern().unwrap_or_else(|e| panic!("{}", e));
+    let libraries = match get_wasm_libraries(&directory, &pattern) {
+        Ok(libraries) => libraries,
+        Err(error) => panic!("could not link to the Clang static libraries: {}", error),
+    };
+
+    common::warn(
+        common::Severity::Warning,
+        format!(
+            "linking to `libclang` statically for WebAssembly ({}) is experimental",
+            target,
+        ),
+    );
+
+    let mut directives = Directives::default();
+    directives.search(&directory);
+    let mut directories = vec![directory.clone()];
+    for (directory, library) in libraries {
+        if !directories.contains(&directory) {
+            directives.search(&directory);
+            directories.push(directory);
+        }
+
+        directives.link(&format!("static={}", library));
+    }
+
+    directory
+}
+
+//================================================
+// Verifying
+//================================================
+
+/// A program which calls `clang_createIndex` (and so requires the Clang static
+/// libraries to link successfully).
+const PROBE: &str = r#"
+extern "C" {
+    fn clang_createIndex(exclude: i32, display: i32) -> *mut u8;
+}
+
+fn main() {
+    let function: unsafe extern "C" fn(i32, i32) -> *mut u8 = clang_createIndex;
+    std::hint::black_box(function);
+}
+"#;
+
+/// Links a program which calls `clang_createIndex` with the supplied `rustc`
+/// arguments (e.g., `-l static=clangBasic`) to verify that the Clang static
+/// libraries (and the libraries they depend on) can be linked to.
+///
+/// The program is built in the supplied directory with the `rustc`, target,
+/// and linker used by Cargo (if available). The output of `rustc` (including
+/// the output of the linker) is returned if the program can't be linked.
+pub fn verify(directory: &Path, arguments: &[String]) -> Result<(), String> {
+    let source = directory.join("clang_sys_probe.rs");
+    let output = directory.join("clang_sys_probe");
+    fs::write(&source, PROBE)
+        .map_err(|e| format!("could not write {}: {}", source.display(), e))?;
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let mut command = Command::new(&rustc);
+    command
+        .args([
+            "--edition",
+            "2021",
+            "--crate-type",
+            "bin",
+            "--crate-name",
+            "clang_sys_probe",
+        ])
+        .arg("-o")
+        .arg(&output)
+        .arg(&source)
+        .args(arguments);
+    if let Ok(target) = env::var("TARGET") {
+        command.args(["--target", &target]);
+    }
+    if let Ok(linker) = env::var("RUSTC_LINKER") {
+        command.arg(format!("-Clinker={}", linker));
+    }
+
+    let result = command
+        .output()
+        .map_err(|e| format!("could not execute `{}`: {}", rustc, e))?;
+    if result.status.success() {
+        return Ok(());
+    }
+
+    Err(format!(
+        "the Clang static libraries could not be linked to (verified because \
+        LIBCLANG_STATIC_VERIFY is set), the system libraries they depend on may be \
+        missing, output of `rustc`:\n{}",
+        String::from_utf8_lossy(&result.stderr),
+    ))
 }
@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Fuzzes the version parsers used to select `llvm-config` executables and to
+//! detect the version of runtime-loaded `libclang` shared libraries.
+//!
+//! Run with `cargo +nightly fuzz run version` from the `fuzz` directory.
+
+#![no_main]
+
+use std::path::Path;
+
+use libfuzzer_sys::fuzz_target;
+
+#[macro_use]
+#[path = "../../build/macros.rs"]
+mod macros;
+
+#[allow(dead_code)]
+#[path = "../../build/common.rs"]
+mod common;
+#[path = "../../src/version.rs"]
+mod version;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(string) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let components = version::parse_components(string);
+    assert!(components.len() <= string.matches('.').count() + 1);
+
+    if let Some(version) = version::parse_clang_version(string) {
+        assert!(!version.is_empty());
+        assert!(string.contains("version"));
+    }
+
+    let version = common::extract_version_from_llvm_path(Path::new(string));
+    assert!(!version.is_empty());
+});
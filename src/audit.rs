@@ -0,0 +1,25 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Provides the values of the constants bound by this crate so they can be
+//! audited against the `clang-c` headers of an instance of `libclang`.
+//!
+//! The values of the constants in the `clang-c` headers (e.g., `CXCursor_*`)
+//! are not checked by the compiler, so a constant which is renumbered between
+//! releases of `libclang` would silently produce incorrect results. The
+//! `tests/audit.rs` test (enabled with the `audit` Cargo feature) compares the
+//! constants returned by [`constants`] with the values of the enum constants
+//! of the same names in the `clang-c` headers available locally.
+//!
+//! The constants are also checked at compile time against the `clang-c`
+//! headers in the directory provided by `llvm-config --includedir` (if any),
+//! so this crate fails to compile with the `audit` Cargo feature if they
+//! differ.
+
+/// Returns the names and values of the constants bound by this crate (except
+/// those which are excluded by the enabled `clang_*` Cargo features).
+#[allow(clippy::vec_init_then_push)]
+pub fn constants() -> Vec<(&'static str, i64)> {
+    include!(concat!(env!("OUT_DIR"), "/audit.rs"))
+}
+
+include!(concat!(env!("OUT_DIR"), "/audit_checks.rs"));
@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Provides information about the instance of `libclang` found by the build
+//! script of this crate.
+//!
+//! This is useful for packages which compile C or C++ code that uses Clang or
+//! LLVM (e.g., with the `cc` crate) and want to use the exact same instance of
+//! LLVM as this crate. The same information is also available to the build
+//! scripts of packages which depend on this crate directly as the
+//! `DEP_CLANG_LIBDIR`, `DEP_CLANG_LIBCLANG_PATH`, `DEP_CLANG_VERSION`,
+//! `DEP_CLANG_INCLUDE_DIR` (or `DEP_CLANG_INCLUDE`), and `DEP_CLANG_BUILD_INFO`
+//! environment variables so they don't need to search for `libclang` again.
+//!
+//! When the `runtime` Cargo feature is enabled, the build script does not look
+//! for `libclang` (unless the `hybrid` Cargo feature is also enabled) so none
+//! of this information is available.
+
+/// The directory containing the `libclang` shared library (or the Clang static
+/// libraries) linked to.
+pub const LIBDIR: Option<&str> = option_env!("CLANG_SYS_LIBDIR");
+
+/// The path to the `libclang` shared library (or the monolithic `libclang`
+/// static library) linked to if it isn't a stub shared library.
+pub const LIBRARY: Option<&str> = option_env!("CLANG_SYS_LIBRARY");
+
+/// The version of the `libclang` library linked to (e.g., `18.1.8`) if it could
+/// be determined.
+pub const VERSION: Option<&str> = option_env!("CLANG_SYS_VERSION");
+
+/// The directory containing the Clang and LLVM headers (as reported by
+/// `llvm-config --includedir`) if it could be determined.
+pub const INCLUDEDIR: Option<&str> = option_env!("CLANG_SYS_INCLUDEDIR");
+
+/// The path to an environment file in the build output directory of this crate
+/// which contains the above information as `CLANG_SYS_LIBDIR=...`,
+/// `CLANG_SYS_LIBRARY=...`, `CLANG_SYS_VERSION=...`, and
+/// `CLANG_SYS_INCLUDEDIR=...` lines.
+pub const ENV_FILE: Option<&str> = option_env!("CLANG_SYS_BUILD_INFO");
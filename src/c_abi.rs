@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! A C ABI for the `libclang` functions called through this crate.
+//!
+//! With the `c-abi` Cargo feature, every `libclang` function provided by this
+//! crate is also exported with the `clang_sys_` prefix (e.g.,
+//! `clang_sys_clang_createIndex`) and the same signature. These functions call
+//! the function of the same name in this crate, so the non-Rust components of
+//! an application which link to this crate (e.g., when built as a `cdylib` or
+//! `staticlib` with `cargo rustc --crate-type cdylib --features c-abi`) use the
+//! same instance of `libclang` as the Rust components (e.g., the `libclang`
+//! shared library loaded at runtime with the `runtime` Cargo feature).
+//!
+//! The exported names only depend on the names of the `libclang` functions
+//! (and not on the version of this crate or the instance of `libclang` used),
+//! so they remain stable as long as `libclang` does.
+//!
+//! A function which isn't available (e.g., because no `libclang` shared library
+//! is loaded or the function is not supported by the loaded `libclang` shared
+//! library) panics, which aborts the process since the exported functions
+//! can't unwind. Use `clang_sys_load` and `clang_sys_is_loaded` first.
+
+use std::ffi::c_int;
+
+/// Loads a `libclang` shared library for use in all threads (see `load_global`)
+/// and returns `0`, or returns `-1` if it couldn't be loaded.
+///
+/// When the `runtime` Cargo feature is not enabled, `libclang` is linked to so
+/// this function does nothing and returns `0`.
+#[unsafe(no_mangle)]
+pub extern "C" fn clang_sys_load() -> c_int {
+    #[cfg(feature = "runtime")]
+    if crate::load_global().is_err() {
+        return -1;
+    }
+
+    0
+}
+
+/// Returns `1` if a `libclang` shared library is loaded on the current thread
+/// or process-wide (see `is_loaded`), `0` otherwise.
+///
+/// When the `runtime` Cargo feature is not enabled (or the `hybrid` Cargo
+/// feature is enabled), `libclang` is linked to so this function always
+/// returns `1`.
+#[unsafe(no_mangle)]
+pub extern "C" fn clang_sys_is_loaded() -> c_int {
+    #[cfg(feature = "runtime")]
+    if !crate::is_loaded() && !cfg!(feature = "hybrid") {
+        return 0;
+    }
+
+    1
+}
@@ -0,0 +1,583 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Provides information about and validation of the environment variables used
+//! by this crate.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+//================================================
+// Enums
+//================================================
+
+/// When an environment variable is used by this crate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// The environment variable is used by the build script.
+    Compiletime,
+    /// The environment variable is used by the functions in this crate.
+    Runtime,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stage::Compiletime => write!(f, "compiletime"),
+            Stage::Runtime => write!(f, "runtime"),
+        }
+    }
+}
+
+/// The kind of value expected in an environment variable.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// A path to a directory.
+    Directory,
+    /// A path to a file.
+    File,
+    /// A path to a directory or a file.
+    DirectoryOrFile,
+    /// A path to a file which is written (in an existing directory).
+    Output,
+    /// A path to an executable or the name of an executable in `PATH`.
+    Executable,
+    /// A path to an executable or the name of an executable in `PATH`
+    /// followed by arguments.
+    Command,
+    /// Any value (only whether the environment variable is set matters).
+    Flag,
+    /// A list of paths to files or names of files separated like the paths in
+    /// `PATH`.
+    Files,
+    /// A version number (e.g., `2.17`).
+    Version,
+    /// Command-line arguments.
+    Arguments,
+    /// A file name pattern containing exactly one `*` (e.g., `libclang*.a`).
+    Pattern,
+    /// The name a shared library is loaded with (e.g., `libclang.so.18`).
+    Name,
+    /// A list of SHA-256 digests (64 hexadecimal digits each) separated by
+    /// commas or whitespace.
+    Digests,
+    /// A number of seconds (e.g., `2.5`).
+    Seconds,
+    /// One of the supplied values.
+    Choice(&'static [&'static str]),
+}
+
+/// A problem with the environment variables used by this crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Issue {
+    /// An environment variable is set which is likely a misspelling of an
+    /// environment variable used by this crate.
+    Typo {
+        /// The name of the environment variable which is set.
+        name: String,
+        /// The name of the environment variable which was likely intended.
+        suggestion: &'static str,
+    },
+    /// An environment variable used by this crate contains a path which does
+    /// not exist or is not of the expected kind.
+    InvalidPath {
+        /// The name of the environment variable.
+        name: &'static str,
+        /// The path contained in the environment variable.
+        path: PathBuf,
+        /// The kind of path expected in the environment variable.
+        kind: Kind,
+    },
+    /// An environment variable used by this crate does not contain valid
+    /// Unicode and will be ignored.
+    NotUnicode {
+        /// The name of the environment variable.
+        name: &'static str,
+    },
+    /// Environment variables used by this crate are set in a way that causes
+    /// some of them to be ignored.
+    Conflict {
+        /// The names of the environment variables involved.
+        names: Vec<&'static str>,
+        /// A description of the conflict.
+        message: &'static str,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::Typo { name, suggestion } => {
+                write!(f, "`{}` is set, did you mean `{}`?", name, suggestion)
+            }
+            Issue::InvalidPath { name, path, kind } => {
+                let expected = match kind {
+                    Kind::Directory => "an existing directory".into(),
+                    Kind::File => "an existing file".into(),
+                    Kind::DirectoryOrFile => "an existing directory or file".into(),
+                    Kind::Output => "a file in an existing directory".into(),
+                    Kind::Executable | Kind::Command => "an existing executable".into(),
+                    Kind::Files => "a list of existing files".into(),
+                    Kind::Flag => "a flag".into(),
+                    Kind::Version => "a version number".into(),
+                    Kind::Arguments => "arguments".into(),
+                    Kind::Pattern => "a file name pattern containing exactly one `*`".into(),
+                    Kind::Name => "a shared library name".into(),
+                    Kind::Digests => "a list of SHA-256 digests".into(),
+                    Kind::Seconds => "a number of seconds".into(),
+                    Kind::Choice(values) => {
+                        let values = values
+                            .iter()
+                            .map(|v| format!("`{}`", v))
+                            .collect::<Vec<_>>();
+                        format!("one of {}", values.join(", "))
+                    }
+                };
+                write!(
+                    f,
+                    "`{}` is set to {} which is not {}",
+                    name,
+                    path.display(),
+                    expected
+                )
+            }
+            Issue::NotUnicode { name } => write!(f, "`{}` is set but is not valid Unicode", name),
+            Issue::Conflict { names, message } => {
+                let names = names.iter().map(|n| format!("`{}`", n)).collect::<Vec<_>>();
+                let verb = if names.len() == 1 { "is" } else { "are" };
+                write!(f, "{} {} set: {}", names.join(" and "), verb, message)
+            }
+        }
+    }
+}
+
+//================================================
+// Structs
+//================================================
+
+/// An environment variable used by this crate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Variable {
+    /// The name of this environment variable.
+    pub name: &'static str,
+    /// When this environment variable is used.
+    pub stage: Stage,
+    /// The kind of value expected in this environment variable.
+    pub kind: Kind,
+    /// A description of this environment variable.
+    pub description: &'static str,
+}
+
+/// The result of validating the environment variables used by this crate.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The environment variables used by this crate which are set and their
+    /// values.
+    pub set: Vec<(&'static str, OsString)>,
+    /// The problems found with the environment variables.
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    /// Returns whether no problems were found with the environment variables.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in &self.set {
+            writeln!(f, "{}={}", name, value.to_string_lossy())?;
+        }
+        for issue in &self.issues {
+            writeln!(f, "warning: {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+//================================================
+// Constants
+//================================================
+
+/// The environment variables used by this crate.
+pub const VARIABLES: &[Variable] = &[
+    Variable {
+        name: "LLVM_CONFIG_PATH",
+        stage: Stage::Compiletime,
+        kind: Kind::Executable,
+        description: "provides a full path to an `llvm-config` executable (including the executable itself [i.e., `/usr/local/bin/llvm-config-8.0`])",
+    },
+    Variable {
+        name: "CLANG_SYS_VERSION_POLICY",
+        stage: Stage::Compiletime,
+        kind: Kind::Choice(&["exact", "at-least", "newest"]),
+        description: "controls whether an auto-detected `llvm-config` executable newer than the version targeted by the `clang_X_0` Cargo features is used (`exact`, `at-least`, or `newest`) (see below)",
+    },
+    Variable {
+        name: "LLVM_CONFIG_OUTPUT",
+        stage: Stage::Compiletime,
+        kind: Kind::File,
+        description: "provides a path to a file containing recorded `llvm-config` output to use instead of executing `llvm-config` (see below)",
+    },
+    Variable {
+        name: "LIBCLANG_PATH",
+        stage: Stage::Compiletime,
+        kind: Kind::DirectoryOrFile,
+        description: "provides a path to a directory containing a `libclang` shared library or a full path to a specific `libclang` shared library",
+    },
+    Variable {
+        name: "LIBCLANG_PATH_DEBUG",
+        stage: Stage::Compiletime,
+        kind: Kind::DirectoryOrFile,
+        description: "provides a path like `LIBCLANG_PATH` which is used instead of `LIBCLANG_PATH` in debug builds (e.g., to link to a `libclang` shared library with assertions enabled) (see below)",
+    },
+    Variable {
+        name: "LIBCLANG_PATH_RELEASE",
+        stage: Stage::Compiletime,
+        kind: Kind::DirectoryOrFile,
+        description: "provides a path like `LIBCLANG_PATH` which is used instead of `LIBCLANG_PATH` in release builds (see below)",
+    },
+    Variable {
+        name: "LIBCLANG_GLIBC_BASELINE",
+        stage: Stage::Compiletime,
+        kind: Kind::Version,
+        description: "provides the version of `glibc` available where the resulting executable will be run (e.g., `2.17`) instead of the version used by the build script (see below)",
+    },
+    Variable {
+        name: "LIBCLANG_GLIBCXX_BASELINE",
+        stage: Stage::Compiletime,
+        kind: Kind::Version,
+        description: "provides the `GLIBCXX` symbol version of `libstdc++` available where the resulting executable will be run (e.g., `3.4.19`) (see below)",
+    },
+    Variable {
+        name: "LIBCLANG_PROBE",
+        stage: Stage::Compiletime,
+        kind: Kind::Flag,
+        description: "if set, the `libclang` shared libraries found are loaded in a child process before linking and those which can't be loaded are skipped (see below)",
+    },
+    Variable {
+        name: "LIBCLANG_SELECTION_POLICY",
+        stage: Stage::Compiletime,
+        kind: Kind::Choice(&["newest", "env-path", "first-match"]),
+        description: "controls which `libclang` shared library is used when several are found (`newest`, `env-path`, or `first-match`) (see below)",
+    },
+    Variable {
+        name: "LIBCLANG_STATIC_PATH",
+        stage: Stage::Compiletime,
+        kind: Kind::Directory,
+        description: "provides a path to a directory containing LLVM and Clang static libraries",
+    },
+    Variable {
+        name: "LIBCLANG_STATIC_PATTERN",
+        stage: Stage::Compiletime,
+        kind: Kind::Pattern,
+        description: "provides the pattern matched by the file names of the Clang static libraries where `*` is the name of a Clang component (e.g., `libclang*_static.a`) instead of `libclang*.a` (see below)",
+    },
+    Variable {
+        name: "LIBCLANG_STATIC_VERIFY",
+        stage: Stage::Compiletime,
+        kind: Kind::Flag,
+        description: "if set, the build script links a program which calls `clang_createIndex` to the Clang static libraries to verify that they can be linked to (see below)",
+    },
+    Variable {
+        name: "LIBCLANG_STUB_SONAME",
+        stage: Stage::Compiletime,
+        kind: Kind::Name,
+        description: "provides the name of the `libclang` shared library loaded at runtime by binaries linked to a stub `libclang` shared library with the `stub` Cargo feature (e.g., `libclang.so.18`) instead of `libclang.so` or `@rpath/libclang.dylib` (see below)",
+    },
+    Variable {
+        name: "PKG_CONFIG",
+        stage: Stage::Compiletime,
+        kind: Kind::Executable,
+        description: "provides a path to a `pkg-config` executable (used to find `libclang` and the system libraries required by the LLVM static libraries, see below)",
+    },
+    Variable {
+        name: "RUNNER_TOOL_CACHE",
+        stage: Stage::Compiletime,
+        kind: Kind::Directory,
+        description: "provides a path to the tool cache of a GitHub Actions runner which is searched for installations of LLVM + Clang (see below)",
+    },
+    Variable {
+        name: "LLVM_PATH",
+        stage: Stage::Compiletime,
+        kind: Kind::Directory,
+        description: "provides a path to an installation of LLVM + Clang which is searched for a `libclang` shared library (set by [install-llvm-action](https://github.com/KyleMayes/install-llvm-action), see below)",
+    },
+    Variable {
+        name: "CLANG_SYS_SELECTION",
+        stage: Stage::Compiletime,
+        kind: Kind::File,
+        description: "provides a path to a file containing values for `LIBCLANG_PATH`, `LIBCLANG_STATIC_PATH`, `LLVM_CONFIG_PATH`, `LLVM_CONFIG_OUTPUT`, `CLANG_SYS_NO_XCODE`, and `LIBCLANG_SELECTION_POLICY` which are used when these environment variables are not set (see below)",
+    },
+    Variable {
+        name: "CLANG_SYS_WARNINGS",
+        stage: Stage::Compiletime,
+        kind: Kind::Choice(&["quiet", "normal", "verbose"]),
+        description: "controls which warnings are printed by the build script (`quiet`, `normal`, or `verbose`) (see below)",
+    },
+    Variable {
+        name: "CLANG_SYS_RECORD",
+        stage: Stage::Compiletime,
+        kind: Kind::Output,
+        description: "provides a path to a file to which everything consulted while searching for a `libclang` shared library is recorded (see below)",
+    },
+    Variable {
+        name: "CLANG_SYS_NO_XCODE",
+        stage: Stage::Compiletime,
+        kind: Kind::Flag,
+        description: "if set, `xcode-select` is never executed to find the Xcode toolchain on macOS (e.g., on CI images without the Command Line Tools where it prompts to install them)",
+    },
+    Variable {
+        name: "CLANG_SYS_LIBCLANG_PATH",
+        stage: Stage::Runtime,
+        kind: Kind::DirectoryOrFile,
+        description: "provides a path to a directory containing a `libclang` shared library or a full path to a specific `libclang` shared library which is loaded at runtime instead of the one provided by `LIBCLANG_PATH`",
+    },
+    Variable {
+        name: "LIBCLANG_PRELOAD",
+        stage: Stage::Runtime,
+        kind: Kind::Files,
+        description: "provides a list of shared libraries (e.g., `libclang-cpp.so.18`) separated like the paths in `PATH` which are loaded before a `libclang` shared library is loaded at runtime (see below)",
+    },
+    Variable {
+        name: "CLANG_SYS_LIBCLANG_SHA256",
+        stage: Stage::Runtime,
+        kind: Kind::Digests,
+        description: "provides a list of SHA-256 digests separated by commas or whitespace, one of which a `libclang` shared library must match to be loaded at runtime (see below)",
+    },
+    Variable {
+        name: "CLANG_SYS_PROBE_TIMEOUT",
+        stage: Stage::Runtime,
+        kind: Kind::Seconds,
+        description: "if set, provides the number of seconds a `libclang` shared library may take to be opened and have its version determined in a child process before it is loaded at runtime (see below)",
+    },
+    Variable {
+        name: "CLANG_SYS_RELAXED_BINDING",
+        stage: Stage::Runtime,
+        kind: Kind::Flag,
+        description: "if set, the shared libraries opened with `RTLD_NOW` at runtime (e.g., those listed in `LIBCLANG_PRELOAD`) are opened again with `RTLD_LAZY` if a symbol isn't defined (see below)",
+    },
+    Variable {
+        name: "CLANG_PATH",
+        stage: Stage::Runtime,
+        kind: Kind::File,
+        description: "provides a path to a `clang` executable",
+    },
+    Variable {
+        name: "CC",
+        stage: Stage::Runtime,
+        kind: Kind::Command,
+        description: "provides a GCC-compatible C compiler used by `support::GccFallback` (only if explicitly requested) or a `clang` executable and arguments in Yocto SDK environments (see below)",
+    },
+    Variable {
+        name: "CLANGCC",
+        stage: Stage::Runtime,
+        kind: Kind::Command,
+        description: "provides a `clang` executable and arguments in Yocto SDK environments (see below)",
+    },
+    Variable {
+        name: "OECORE_TARGET_SYSROOT",
+        stage: Stage::Runtime,
+        kind: Kind::Directory,
+        description: "provides the target sysroot in Yocto SDK environments (see below)",
+    },
+    Variable {
+        name: "BINDGEN_EXTRA_CLANG_ARGS",
+        stage: Stage::Runtime,
+        kind: Kind::Arguments,
+        description: "provides the extra arguments `bindgen` passes to `libclang` (also `BINDGEN_EXTRA_CLANG_ARGS_<target>`), the `--target`, `--sysroot`, and `-isysroot` arguments of which are used by `support::Clang::find_for_bindgen`",
+    },
+];
+
+//================================================
+// Functions
+//================================================
+
+/// Returns the list of environment variables used by this crate formatted as
+/// Markdown (as found in the README).
+pub fn markdown() -> String {
+    let mut markdown = String::new();
+    for variable in VARIABLES {
+        markdown.push_str(&format!(
+            "* `{}` **({})** - {}\n",
+            variable.name, variable.stage, variable.description,
+        ));
+    }
+    markdown
+}
+
+/// Returns the edit distance (allowing transpositions) between two strings.
+fn distance(a: &str, b: &str) -> usize {
+    let a = a.as_bytes();
+    let b = b.as_bytes();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Returns the environment variable used by this crate which the supplied
+/// environment variable name is likely a misspelling of, if any.
+///
+/// Names which only differ in case or in the placement of underscores and
+/// dashes (e.g., `LIB_CLANG_PATH`) are always considered misspellings, other
+/// names are only considered misspellings if they are long enough to make a
+/// single edit unlikely to be a coincidence (e.g., `LIBCLANG_PAHT`).
+fn suggest(name: &str) -> Option<&'static str> {
+    let normalize = |n: &str| {
+        n.chars()
+            .filter(|c| *c != '_' && *c != '-')
+            .collect::<String>()
+            .to_uppercase()
+    };
+
+    if VARIABLES.iter().any(|v| v.name == name) {
+        return None;
+    }
+
+    let normalized = normalize(name);
+    VARIABLES
+        .iter()
+        .find(|v| {
+            normalize(v.name) == normalized || (v.name.len() >= 8 && distance(v.name, name) == 1)
+        })
+        .map(|v| v.name)
+}
+
+/// Returns whether the supplied path is of the supplied kind.
+fn is_kind(path: &Path, kind: Kind) -> bool {
+    match kind {
+        Kind::Directory => path.is_dir(),
+        Kind::File => path.is_file(),
+        Kind::DirectoryOrFile => path.exists(),
+        Kind::Output => {
+            let parent = path.parent().filter(|p| !p.as_os_str().is_empty());
+            !path.is_dir() && parent.is_none_or(Path::is_dir)
+        }
+        // Executables without a directory component are searched for in `PATH`.
+        Kind::Executable => path.components().count() <= 1 || path.is_file(),
+        Kind::Command => {
+            let executable = path.to_str().and_then(|p| p.split_whitespace().next());
+            executable.is_none_or(|e| is_kind(Path::new(e), Kind::Executable))
+        }
+        Kind::Files => std::env::split_paths(path)
+            .all(|p| p.components().count() <= 1 || is_kind(&p, Kind::File)),
+        Kind::Flag | Kind::Version | Kind::Arguments | Kind::Name => true,
+        Kind::Pattern => path
+            .to_str()
+            .is_some_and(|p| p.matches('*').count() == 1 && !p.contains(['/', '\\'])),
+        Kind::Choice(values) => values.iter().any(|v| path.as_os_str() == *v),
+        Kind::Seconds => path
+            .to_str()
+            .and_then(|p| p.trim().parse::<f64>().ok())
+            .is_some_and(|s| s.is_finite() && s >= 0.0),
+        Kind::Digests => path.to_str().is_some_and(|p| {
+            p.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|d| !d.is_empty())
+                .all(|d| d.len() == 64 && d.bytes().all(|b| b.is_ascii_hexdigit()))
+        }),
+    }
+}
+
+/// Validates the environment variables used by this crate.
+///
+/// This checks for:
+///
+///   * environment variables which are likely misspellings of the environment
+///     variables used by this crate (e.g., `LIB_CLANG_PATH`)
+///   * environment variables used by this crate which contain paths that do not
+///     exist
+///   * environment variables used by this crate which are ignored because of
+///     other environment variables or the enabled Cargo features
+///
+/// Note that the environment variables used at compiletime are only meaningful
+/// in the environment of the build script, so this function is most useful
+/// when called from a build script or in the environment of a build.
+pub fn validate() -> Report {
+    let mut report = Report::default();
+
+    for (name, _) in std::env::vars_os() {
+        let name = name.to_string_lossy();
+        if let Some(suggestion) = suggest(&name) {
+            report.issues.push(Issue::Typo {
+                name: name.into(),
+                suggestion,
+            });
+        }
+    }
+
+    for variable in VARIABLES {
+        let Some(value) = std::env::var_os(variable.name) else {
+            continue;
+        };
+
+        report.set.push((variable.name, value.clone()));
+
+        match value.to_str() {
+            Some(path) if !path.is_empty() => {
+                let path = Path::new(path);
+                if !is_kind(path, variable.kind) {
+                    report.issues.push(Issue::InvalidPath {
+                        name: variable.name,
+                        path: path.into(),
+                        kind: variable.kind,
+                    });
+                }
+            }
+            Some(_) => {}
+            None => report.issues.push(Issue::NotUnicode {
+                name: variable.name,
+            }),
+        }
+    }
+
+    let is_set = |name: &str| report.set.iter().any(|(n, _)| *n == name);
+
+    let mut conflicts = vec![];
+
+    if is_set("LLVM_CONFIG_PATH") && is_set("LLVM_CONFIG_OUTPUT") {
+        conflicts.push(Issue::Conflict {
+            names: vec!["LLVM_CONFIG_PATH", "LLVM_CONFIG_OUTPUT"],
+            message: "the build script uses the recorded `llvm-config` output instead of \
+                      executing the `llvm-config` executable",
+        });
+    }
+
+    let linking_statically = cfg!(feature = "static") && !cfg!(feature = "runtime");
+
+    if linking_statically && is_set("LIBCLANG_PATH") && !is_set("LIBCLANG_STATIC_PATH") {
+        conflicts.push(Issue::Conflict {
+            names: vec!["LIBCLANG_PATH"],
+            message: "`LIBCLANG_PATH` is ignored when linking statically \
+                      (`LIBCLANG_STATIC_PATH` is used instead)",
+        });
+    }
+
+    if !linking_statically && is_set("LIBCLANG_STATIC_PATH") {
+        conflicts.push(Issue::Conflict {
+            names: vec!["LIBCLANG_STATIC_PATH"],
+            message: "`LIBCLANG_STATIC_PATH` is ignored unless linking statically \
+                      (`LIBCLANG_PATH` is used instead)",
+        });
+    }
+
+    report.issues.extend(conflicts);
+    report
+}
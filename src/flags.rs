@@ -0,0 +1,430 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Provides typed wrappers for `libclang` option masks.
+//!
+//! This module is only available when the `flags` Cargo feature is enabled.
+
+use std::ops;
+
+use super::*;
+
+//================================================
+// Macros
+//================================================
+
+/// Defines a typed wrapper for a `libclang` option mask.
+macro_rules! flags {
+    (
+        $(#[doc = $doc:expr])*
+        $(#[cfg($cfg:meta)])*
+        pub struct $name:ident($raw:ty) {
+            $($(#[$fmeta:meta])* const $flag:ident = $value:expr;)+
+        }
+    ) => (
+        $(#[doc = $doc])*
+        $(#[cfg($cfg)])*
+        #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+        #[repr(transparent)]
+        pub struct $name($raw);
+
+        $(#[cfg($cfg)])*
+        impl $name {
+            $($(#[$fmeta])* pub const $flag: Self = Self($value);)+
+
+            /// Returns an empty set of options.
+            pub const fn empty() -> Self {
+                Self(0)
+            }
+
+            /// Returns the set of all options known to this type.
+            #[allow(unused_doc_comments)]
+            pub const fn all() -> Self {
+                let mut bits = 0;
+                $($(#[$fmeta])* {
+                    bits |= $value;
+                })+
+                Self(bits)
+            }
+
+            /// Returns a set of options from the supplied raw value or `None`
+            /// if it contains any bits which are not known to this type.
+            pub const fn from_bits(bits: $raw) -> Option<Self> {
+                if bits & !Self::all().0 == 0 {
+                    Some(Self(bits))
+                } else {
+                    None
+                }
+            }
+
+            /// Returns a set of options from the supplied raw value, discarding
+            /// any bits which are not known to this type.
+            pub const fn from_bits_truncate(bits: $raw) -> Self {
+                Self(bits & Self::all().0)
+            }
+
+            /// Returns a set of options from the supplied raw value, retaining
+            /// any bits which are not known to this type.
+            pub const fn from_bits_retain(bits: $raw) -> Self {
+                Self(bits)
+            }
+
+            /// Returns the raw value of this set of options.
+            pub const fn bits(self) -> $raw {
+                self.0
+            }
+
+            /// Returns whether this set of options is empty.
+            pub const fn is_empty(self) -> bool {
+                self.0 == 0
+            }
+
+            /// Returns whether this set of options contains all of the supplied
+            /// options.
+            pub const fn contains(self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Returns whether this set of options contains any of the supplied
+            /// options.
+            pub const fn intersects(self, other: Self) -> bool {
+                self.0 & other.0 != 0
+            }
+
+            /// Adds the supplied options to this set of options.
+            pub fn insert(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+
+            /// Removes the supplied options from this set of options.
+            pub fn remove(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+        }
+
+        $(#[cfg($cfg)])*
+        impl From<$raw> for $name {
+            fn from(bits: $raw) -> Self {
+                Self(bits)
+            }
+        }
+
+        $(#[cfg($cfg)])*
+        impl From<$name> for $raw {
+            fn from(flags: $name) -> Self {
+                flags.0
+            }
+        }
+
+        $(#[cfg($cfg)])*
+        impl ops::BitOr for $name {
+            type Output = Self;
+
+            fn bitor(self, other: Self) -> Self {
+                Self(self.0 | other.0)
+            }
+        }
+
+        $(#[cfg($cfg)])*
+        impl ops::BitOrAssign for $name {
+            fn bitor_assign(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+        }
+
+        $(#[cfg($cfg)])*
+        impl ops::BitAnd for $name {
+            type Output = Self;
+
+            fn bitand(self, other: Self) -> Self {
+                Self(self.0 & other.0)
+            }
+        }
+
+        $(#[cfg($cfg)])*
+        impl ops::BitAndAssign for $name {
+            fn bitand_assign(&mut self, other: Self) {
+                self.0 &= other.0;
+            }
+        }
+
+        $(#[cfg($cfg)])*
+        impl ops::BitXor for $name {
+            type Output = Self;
+
+            fn bitxor(self, other: Self) -> Self {
+                Self(self.0 ^ other.0)
+            }
+        }
+
+        $(#[cfg($cfg)])*
+        impl ops::BitXorAssign for $name {
+            fn bitxor_assign(&mut self, other: Self) {
+                self.0 ^= other.0;
+            }
+        }
+
+        $(#[cfg($cfg)])*
+        impl ops::Sub for $name {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                Self(self.0 & !other.0)
+            }
+        }
+
+        $(#[cfg($cfg)])*
+        impl ops::SubAssign for $name {
+            fn sub_assign(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+        }
+
+        $(#[cfg($cfg)])*
+        impl ops::Not for $name {
+            type Output = Self;
+
+            /// Returns the options known to this type which are not in this set
+            /// of options.
+            fn not(self) -> Self {
+                Self(!self.0 & Self::all().0)
+            }
+        }
+    );
+}
+
+//================================================
+// Structs
+//================================================
+
+flags! {
+    /// Options for parsing a translation unit (`CXTranslationUnit_Flags`).
+    pub struct TranslationUnitFlags(CXTranslationUnit_Flags) {
+        const DETAILED_PREPROCESSING_RECORD = CXTranslationUnit_DetailedPreprocessingRecord;
+        const INCOMPLETE = CXTranslationUnit_Incomplete;
+        const PRECOMPILED_PREAMBLE = CXTranslationUnit_PrecompiledPreamble;
+        const CACHE_COMPLETION_RESULTS = CXTranslationUnit_CacheCompletionResults;
+        const FOR_SERIALIZATION = CXTranslationUnit_ForSerialization;
+        const CXX_CHAINED_PCH = CXTranslationUnit_CXXChainedPCH;
+        const SKIP_FUNCTION_BODIES = CXTranslationUnit_SkipFunctionBodies;
+        const INCLUDE_BRIEF_COMMENTS_IN_CODE_COMPLETION = CXTranslationUnit_IncludeBriefCommentsInCodeCompletion;
+        /// Only available on `libclang` 3.8 and later.
+        #[cfg(feature = "clang_3_8")]
+        const CREATE_PREAMBLE_ON_FIRST_PARSE = CXTranslationUnit_CreatePreambleOnFirstParse;
+        /// Only available on `libclang` 3.9 and later.
+        #[cfg(feature = "clang_3_9")]
+        const KEEP_GOING = CXTranslationUnit_KeepGoing;
+        /// Only available on `libclang` 5.0 and later.
+        #[cfg(feature = "clang_5_0")]
+        const SINGLE_FILE_PARSE = CXTranslationUnit_SingleFileParse;
+        /// Only available on `libclang` 7.0 and later.
+        #[cfg(feature = "clang_7_0")]
+        const LIMIT_SKIP_FUNCTION_BODIES_TO_PREAMBLE = CXTranslationUnit_LimitSkipFunctionBodiesToPreamble;
+        /// Only available on `libclang` 8.0 and later.
+        #[cfg(feature = "clang_8_0")]
+        const INCLUDE_ATTRIBUTED_TYPES = CXTranslationUnit_IncludeAttributedTypes;
+        /// Only available on `libclang` 8.0 and later.
+        #[cfg(feature = "clang_8_0")]
+        const VISIT_IMPLICIT_ATTRIBUTES = CXTranslationUnit_VisitImplicitAttributes;
+        /// Only available on `libclang` 9.0 and later.
+        #[cfg(feature = "clang_9_0")]
+        const IGNORE_NON_ERRORS_FROM_INCLUDED_FILES = CXTranslationUnit_IgnoreNonErrorsFromIncludedFiles;
+        /// Only available on `libclang` 10.0 and later.
+        #[cfg(feature = "clang_10_0")]
+        const RETAIN_EXCLUDED_CONDITIONAL_BLOCKS = CXTranslationUnit_RetainExcludedConditionalBlocks;
+    }
+}
+
+impl TranslationUnitFlags {
+    /// Returns the options recommended by `libclang` for parsing a translation
+    /// unit which will be reparsed frequently (e.g., by an editor).
+    ///
+    /// See `clang_defaultEditingTranslationUnitOptions`.
+    pub fn default_editing() -> Self {
+        unsafe { Self(clang_defaultEditingTranslationUnitOptions()) }
+    }
+}
+
+flags! {
+    /// Options for reparsing a translation unit (`CXReparse_Flags`).
+    ///
+    /// `libclang` does not currently define any reparsing options.
+    pub struct ReparseFlags(CXReparse_Flags) {
+        const NONE = CXReparse_None;
+    }
+}
+
+impl ReparseFlags {
+    /// Returns the options recommended by `libclang` for reparsing the supplied
+    /// translation unit.
+    ///
+    /// See `clang_defaultReparseOptions`.
+    ///
+    /// # Safety
+    ///
+    /// The supplied translation unit must be valid.
+    pub unsafe fn default_for(tu: CXTranslationUnit) -> Self {
+        unsafe { Self(clang_defaultReparseOptions(tu)) }
+    }
+}
+
+flags! {
+    /// Options for saving a translation unit (`CXSaveTranslationUnit_Flags`).
+    ///
+    /// `libclang` does not currently define any saving options.
+    pub struct SaveTranslationUnitFlags(CXSaveTranslationUnit_Flags) {
+        const NONE = CXSaveTranslationUnit_None;
+    }
+}
+
+impl SaveTranslationUnitFlags {
+    /// Returns the options recommended by `libclang` for saving the supplied
+    /// translation unit.
+    ///
+    /// See `clang_defaultSaveOptions`.
+    ///
+    /// # Safety
+    ///
+    /// The supplied translation unit must be valid.
+    pub unsafe fn default_for(tu: CXTranslationUnit) -> Self {
+        unsafe { Self(clang_defaultSaveOptions(tu)) }
+    }
+}
+
+flags! {
+    /// Options for formatting a diagnostic (`CXDiagnosticDisplayOptions`).
+    pub struct DiagnosticDisplayOptions(CXDiagnosticDisplayOptions) {
+        const SOURCE_LOCATION = CXDiagnostic_DisplaySourceLocation;
+        const COLUMN = CXDiagnostic_DisplayColumn;
+        const SOURCE_RANGES = CXDiagnostic_DisplaySourceRanges;
+        const OPTION = CXDiagnostic_DisplayOption;
+        const CATEGORY_ID = CXDiagnostic_DisplayCategoryId;
+        const CATEGORY_NAME = CXDiagnostic_DisplayCategoryName;
+    }
+}
+
+impl DiagnosticDisplayOptions {
+    /// Returns the options recommended by `libclang` for formatting a
+    /// diagnostic (i.e., the options used by `clang` itself).
+    ///
+    /// See `clang_defaultDiagnosticDisplayOptions`.
+    pub fn default_display() -> Self {
+        unsafe { Self(clang_defaultDiagnosticDisplayOptions()) }
+    }
+}
+
+flags! {
+    /// Options for the threads created by an index (`CXGlobalOptFlags`).
+    pub struct GlobalOptFlags(CXGlobalOptFlags) {
+        const NONE = CXGlobalOpt_None;
+        const THREAD_BACKGROUND_PRIORITY_FOR_INDEXING = CXGlobalOpt_ThreadBackgroundPriorityForIndexing;
+        const THREAD_BACKGROUND_PRIORITY_FOR_EDITING = CXGlobalOpt_ThreadBackgroundPriorityForEditing;
+        const THREAD_BACKGROUND_PRIORITY_FOR_ALL = CXGlobalOpt_ThreadBackgroundPriorityForAll;
+    }
+}
+
+impl GlobalOptFlags {
+    /// Returns the options of the supplied index.
+    ///
+    /// See `clang_CXIndex_getGlobalOptions`.
+    ///
+    /// # Safety
+    ///
+    /// The supplied index must be valid.
+    pub unsafe fn for_index(index: CXIndex) -> Self {
+        unsafe { Self(clang_CXIndex_getGlobalOptions(index)) }
+    }
+}
+
+flags! {
+    /// Options for indexing a source file or a translation unit
+    /// (`CXIndexOptFlags`).
+    pub struct IndexOptFlags(CXIndexOptFlags) {
+        const NONE = CXIndexOptNone;
+        const SUPPRESS_REDUNDANT_REFS = CXIndexOptSuppressRedundantRefs;
+        const INDEX_FUNCTION_LOCAL_SYMBOLS = CXIndexOptIndexFunctionLocalSymbols;
+        const INDEX_IMPLICIT_TEMPLATE_INSTANTIATIONS = CXIndexOptIndexImplicitTemplateInstantiations;
+        const SUPPRESS_WARNINGS = CXIndexOptSuppressWarnings;
+        const SKIP_PARSED_BODIES_IN_SESSION = CXIndexOptSkipParsedBodiesInSession;
+    }
+}
+
+flags! {
+    /// Options for creating an index with `clang_createIndexWithOptions`
+    /// (`CXIndexOptions_Flags`).
+    ///
+    /// Only available on `libclang` 17.0 and later.
+    #[cfg(feature = "clang_17_0")]
+    pub struct IndexOptionsFlags(CXIndexOptions_Flags) {
+        const EXCLUDE_DECLARATIONS_FROM_PCH = CXIndexOptions_ExcludeDeclarationsFromPCH;
+        const DISPLAY_DIAGNOSTICS = CXIndexOptions_DisplayDiagnostics;
+        const STORE_PREAMBLES_IN_MEMORY = CXIndexOptions_StorePreamblesInMemory;
+    }
+}
+
+flags! {
+    /// Options for performing code completion (`CXCodeComplete_Flags`).
+    pub struct CodeCompleteFlags(CXCodeComplete_Flags) {
+        const INCLUDE_MACROS = CXCodeComplete_IncludeMacros;
+        const INCLUDE_CODE_PATTERNS = CXCodeComplete_IncludeCodePatterns;
+        const INCLUDE_BRIEF_COMMENTS = CXCodeComplete_IncludeBriefComments;
+        const SKIP_PREAMBLE = CXCodeComplete_SkipPreamble;
+        const INCLUDE_COMPLETIONS_WITH_FIX_ITS = CXCodeComplete_IncludeCompletionsWithFixIts;
+    }
+}
+
+impl CodeCompleteFlags {
+    /// Returns the options recommended by `libclang` for performing code
+    /// completion.
+    ///
+    /// See `clang_defaultCodeCompleteOptions`.
+    pub fn default_code_complete() -> Self {
+        unsafe { Self(clang_defaultCodeCompleteOptions()) }
+    }
+}
+
+flags! {
+    /// Options for the source range returned by
+    /// `clang_getCursorReferenceNameRange` (`CXNameRefFlags`).
+    pub struct NameRefFlags(CXNameRefFlags) {
+        const WANT_QUALIFIER = CXNameRange_WantQualifier;
+        const WANT_TEMPLATE_ARGS = CXNameRange_WantTemplateArgs;
+        const WANT_SINGLE_PIECE = CXNameRange_WantSinglePiece;
+    }
+}
+
+flags! {
+    /// The qualifiers of an Objective-C method or parameter declaration
+    /// (`CXObjCDeclQualifierKind`).
+    pub struct ObjCDeclQualifiers(CXObjCDeclQualifierKind) {
+        const NONE = CXObjCDeclQualifier_None;
+        const IN = CXObjCDeclQualifier_In;
+        const INOUT = CXObjCDeclQualifier_Inout;
+        const OUT = CXObjCDeclQualifier_Out;
+        const BYCOPY = CXObjCDeclQualifier_Bycopy;
+        const BYREF = CXObjCDeclQualifier_Byref;
+        const ONEWAY = CXObjCDeclQualifier_Oneway;
+    }
+}
+
+flags! {
+    /// The attributes of an Objective-C property declaration
+    /// (`CXObjCPropertyAttrKind`).
+    pub struct ObjCPropertyAttributes(CXObjCPropertyAttrKind) {
+        const NOATTR = CXObjCPropertyAttr_noattr;
+        const READONLY = CXObjCPropertyAttr_readonly;
+        const GETTER = CXObjCPropertyAttr_getter;
+        const ASSIGN = CXObjCPropertyAttr_assign;
+        const READWRITE = CXObjCPropertyAttr_readwrite;
+        const RETAIN = CXObjCPropertyAttr_retain;
+        const COPY = CXObjCPropertyAttr_copy;
+        const NONATOMIC = CXObjCPropertyAttr_nonatomic;
+        const SETTER = CXObjCPropertyAttr_setter;
+        const ATOMIC = CXObjCPropertyAttr_atomic;
+        const WEAK = CXObjCPropertyAttr_weak;
+        const STRONG = CXObjCPropertyAttr_strong;
+        const UNSAFE_UNRETAINED = CXObjCPropertyAttr_unsafe_unretained;
+        /// Only available on `libclang` 3.9 and later.
+        #[cfg(feature = "clang_3_9")]
+        const CLASS = CXObjCPropertyAttr_class;
+    }
+}
@@ -22,7 +22,19 @@
 #![allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
 #![allow(clippy::unreadable_literal)]
 
+#[cfg(feature = "audit")]
+pub mod audit;
+pub mod build_info;
+#[cfg(feature = "c-abi")]
+pub mod c_abi;
+pub mod env;
+#[cfg(feature = "flags")]
+pub mod flags;
+#[cfg(feature = "runtime")]
+mod runtime;
 pub mod support;
+pub mod threading;
+pub mod version;
 
 #[macro_use]
 mod link;
@@ -1551,12 +1563,14 @@ opaque!(CXIdxClientFile);
 opaque!(CXIndex);
 opaque!(CXIndexAction);
 opaque!(CXModule);
+opaque!(CXModuleMapDescriptor);
 #[cfg(feature = "clang_7_0")]
 opaque!(CXPrintingPolicy);
 opaque!(CXRemapping);
 #[cfg(feature = "clang_5_0")]
 opaque!(CXTargetInfo);
 opaque!(CXTranslationUnit);
+opaque!(CXVirtualFileOverlay);
 
 // Transparent ___________________________________
 
@@ -1964,9 +1978,11 @@ link! {
     pub fn clang_CXCursorSet_contains(set: CXCursorSet, cursor: CXCursor) -> c_uint;
     pub fn clang_CXCursorSet_insert(set: CXCursorSet, cursor: CXCursor) -> c_uint;
     pub fn clang_CXIndex_getGlobalOptions(index: CXIndex) -> CXGlobalOptFlags;
+    #[cfg_attr(feature = "clang_17_0", deprecated(note = "deprecated in `libclang` 17.0, use `clang_createIndexWithOptions` instead"))]
     pub fn clang_CXIndex_setGlobalOptions(index: CXIndex, flags: CXGlobalOptFlags);
     /// Only available on `libclang` 6.0 and later.
     #[cfg(feature = "clang_6_0")]
+    #[cfg_attr(feature = "clang_17_0", deprecated(note = "deprecated in `libclang` 17.0, use `clang_createIndexWithOptions` instead"))]
     pub fn clang_CXIndex_setInvocationEmissionPathOption(index: CXIndex, path: *const c_char);
     /// Only available on `libclang` 3.9 and later.
     #[cfg(feature = "clang_3_9")]
@@ -2055,6 +2071,9 @@ link! {
     pub fn clang_Cursor_getObjCPropertySetterName(cursor: CXCursor) -> CXString;
     pub fn clang_Cursor_getObjCSelectorIndex(cursor: CXCursor) -> c_int;
     /// Only available on `libclang` 3.7 and later.
+    ///
+    /// Returns the offset of the field in bits or a negative `CXTypeLayoutError`
+    /// value if the offset could not be computed.
     #[cfg(feature = "clang_3_7")]
     pub fn clang_Cursor_getOffsetOfField(cursor: CXCursor) -> c_longlong;
     pub fn clang_Cursor_getRawCommentText(cursor: CXCursor) -> CXString;
@@ -2094,6 +2113,7 @@ link! {
     /// Only available on `libclang` 9.0 and later.
     #[cfg(feature = "clang_9_0")]
     pub fn clang_Cursor_isAnonymousRecordDecl(cursor: CXCursor) -> c_uint;
+    /// Returns non-zero if the cursor is a bit-field declaration.
     pub fn clang_Cursor_isBitField(cursor: CXCursor) -> c_uint;
     pub fn clang_Cursor_isDynamicCall(cursor: CXCursor) -> c_int;
     /// Only available on `libclang` 5.0 and later.
@@ -2184,6 +2204,11 @@ link! {
     pub fn clang_IndexAction_dispose(index: CXIndexAction);
     pub fn clang_Location_isFromMainFile(location: CXSourceLocation) -> c_int;
     pub fn clang_Location_isInSystemHeader(location: CXSourceLocation) -> c_int;
+    pub fn clang_ModuleMapDescriptor_create(options: c_uint) -> CXModuleMapDescriptor;
+    pub fn clang_ModuleMapDescriptor_dispose(descriptor: CXModuleMapDescriptor);
+    pub fn clang_ModuleMapDescriptor_setFrameworkModuleName(descriptor: CXModuleMapDescriptor, name: *const c_char) -> CXErrorCode;
+    pub fn clang_ModuleMapDescriptor_setUmbrellaHeader(descriptor: CXModuleMapDescriptor, name: *const c_char) -> CXErrorCode;
+    pub fn clang_ModuleMapDescriptor_writeToBuffer(descriptor: CXModuleMapDescriptor, options: c_uint, buffer: *mut *mut c_char, size: *mut c_uint) -> CXErrorCode;
     pub fn clang_Module_getASTFile(module: CXModule) -> CXFile;
     pub fn clang_Module_getFullName(module: CXModule) -> CXString;
     pub fn clang_Module_getName(module: CXModule) -> CXString;
@@ -2210,6 +2235,8 @@ link! {
     /// Only available on `libclang` 5.0 and later.
     #[cfg(feature = "clang_5_0")]
     pub fn clang_TargetInfo_getTriple(info: CXTargetInfo) -> CXString;
+    /// Returns the alignment of the type in bytes or a negative
+    /// `CXTypeLayoutError` value if the alignment could not be computed.
     pub fn clang_Type_getAlignOf(type_: CXType) -> c_longlong;
     pub fn clang_Type_getCXXRefQualifier(type_: CXType) -> CXRefQualifierKind;
     pub fn clang_Type_getClassType(type_: CXType) -> CXType;
@@ -2241,7 +2268,11 @@ link! {
     /// Only available on `libclang` 8.0 and later.
     #[cfg(feature = "clang_8_0")]
     pub fn clang_Type_getObjCTypeArg(type_: CXType, index: c_uint) -> CXType;
+    /// Returns the offset of the named field of the record type in bits or a
+    /// negative `CXTypeLayoutError` value if the offset could not be computed.
     pub fn clang_Type_getOffsetOf(type_: CXType, field: *const c_char) -> c_longlong;
+    /// Returns the size of the type in bytes or a negative `CXTypeLayoutError`
+    /// value if the size could not be computed.
     pub fn clang_Type_getSizeOf(type_: CXType) -> c_longlong;
     pub fn clang_Type_getTemplateArgumentAsType(type_: CXType, index: c_uint) -> CXType;
     /// Only available on `libclang` 11.0 and later.
@@ -2253,6 +2284,11 @@ link! {
     /// Only available on `libclang` 3.7 and later.
     #[cfg(feature = "clang_3_7")]
     pub fn clang_Type_visitFields(type_: CXType, visitor: CXFieldVisitor, data: CXClientData) -> CXVisitorResult;
+    pub fn clang_VirtualFileOverlay_addFileMapping(overlay: CXVirtualFileOverlay, virtual_path: *const c_char, real_path: *const c_char) -> CXErrorCode;
+    pub fn clang_VirtualFileOverlay_create(options: c_uint) -> CXVirtualFileOverlay;
+    pub fn clang_VirtualFileOverlay_dispose(overlay: CXVirtualFileOverlay);
+    pub fn clang_VirtualFileOverlay_setCaseSensitivity(overlay: CXVirtualFileOverlay, case_sensitive: c_int) -> CXErrorCode;
+    pub fn clang_VirtualFileOverlay_writeToBuffer(overlay: CXVirtualFileOverlay, options: c_uint, buffer: *mut *mut c_char, size: *mut c_uint) -> CXErrorCode;
     /// Only available on `libclang` 20.0 and later.
     #[cfg(feature = "clang_20_0")]
     pub fn clang_visitCXXBaseClasses(type_: CXType, visitor: CXCursorVisitor, data: CXClientData) -> c_uint;
@@ -2278,8 +2314,11 @@ link! {
     /// Only available on `libclang` 17.0 and later.
     #[cfg(feature = "clang_17_0")]
     pub fn clang_createIndexWithOptions(options: CXIndexOptions) -> CXIndex;
+    /// Use `clang_createTranslationUnit2` instead, which returns an error code on failure.
     pub fn clang_createTranslationUnit(index: CXIndex, file: *const c_char) -> CXTranslationUnit;
     pub fn clang_createTranslationUnit2(index: CXIndex, file: *const c_char, tu: *mut CXTranslationUnit) -> CXErrorCode;
+    /// Use `clang_parseTranslationUnit2` instead, which also supports translation unit flags (e.g.,
+    /// `CXTranslationUnit_PrecompiledPreamble`) and returns an error code on failure.
     pub fn clang_createTranslationUnitFromSourceFile(index: CXIndex, file: *const c_char, n_arguments: c_int, arguments: *const *const c_char, n_unsaved: c_uint, unsaved: *mut CXUnsavedFile) -> CXTranslationUnit;
     pub fn clang_defaultCodeCompleteOptions() -> CXCodeComplete_Flags;
     pub fn clang_defaultDiagnosticDisplayOptions() -> CXDiagnosticDisplayOptions;
@@ -2325,6 +2364,7 @@ link! {
     /// Only available on `libclang` 17.0 and later.
     #[cfg(feature = "clang_17_0")]
     pub fn clang_getBinaryOperatorKindSpelling(kind: CXBinaryOperatorKind) -> CXString;
+    pub fn clang_getBuildSessionTimestamp() -> c_ulonglong;
     pub fn clang_getCString(string: CXString) -> *const c_char;
     pub fn clang_getCXTUResourceUsage(tu: CXTranslationUnit) -> CXTUResourceUsage;
     pub fn clang_getCXXAccessSpecifier(cursor: CXCursor) -> CX_CXXAccessSpecifier;
@@ -2345,6 +2385,7 @@ link! {
     /// Only available on `libclang` 7.0 and later.
     #[cfg(feature = "clang_7_0")]
     pub fn clang_getCompletionNumFixIts(results: *mut CXCodeCompleteResults, completion_index: c_uint) -> c_uint;
+    #[deprecated = "deprecated in `libclang`, the `kind` parameter is always set to `CXCursor_NotImplemented`"]
     pub fn clang_getCompletionParent(string: CXCompletionString, kind: *mut CXCursorKind) -> CXString;
     pub fn clang_getCompletionPriority(string: CXCompletionString) -> c_uint;
     pub fn clang_getCursor(tu: CXTranslationUnit, location: CXSourceLocation) -> CXCursor;
@@ -2392,6 +2433,7 @@ link! {
     pub fn clang_getDefinitionSpellingAndExtent(cursor: CXCursor, start: *mut *const c_char, end: *mut *const c_char, start_line: *mut c_uint, start_column: *mut c_uint, end_line: *mut c_uint, end_column: *mut c_uint);
     pub fn clang_getDiagnostic(tu: CXTranslationUnit, index: c_uint) -> CXDiagnostic;
     pub fn clang_getDiagnosticCategory(diagnostic: CXDiagnostic) -> c_uint;
+    #[deprecated = "use `clang_getDiagnosticCategoryText` instead"]
     pub fn clang_getDiagnosticCategoryName(category: c_uint) -> CXString;
     pub fn clang_getDiagnosticCategoryText(diagnostic: CXDiagnostic) -> CXString;
     pub fn clang_getDiagnosticFixIt(diagnostic: CXDiagnostic, index: c_uint, range: *mut CXSourceRange) -> CXString;
@@ -2412,6 +2454,8 @@ link! {
     #[cfg(feature = "clang_5_0")]
     pub fn clang_getExceptionSpecificationType(type_: CXType) -> CXCursor_ExceptionSpecificationKind;
     pub fn clang_getExpansionLocation(location: CXSourceLocation, file: *mut CXFile, line: *mut c_uint, column: *mut c_uint, offset: *mut c_uint);
+    /// Returns the width of the bit-field declaration in bits or `-1` if the
+    /// cursor is not a bit-field declaration.
     pub fn clang_getFieldDeclBitWidth(cursor: CXCursor) -> c_int;
     pub fn clang_getFile(tu: CXTranslationUnit, file: *const c_char) -> CXFile;
     /// Only available on `libclang` 21.0 and later.
@@ -4,6 +4,32 @@
 // Macros
 //================================================
 
+/// With the `c-abi` Cargo feature, exports the supplied functions with the `clang_sys_` prefix
+/// (see `c_abi`).
+macro_rules! export {
+    (
+        $(
+            $(#[cfg($cfg:meta)])*
+            pub fn $name:ident($($pname:ident: $pty:ty), *) $(-> $ret:ty)*;
+        )+
+    ) => (
+        #[cfg(feature = "c-abi")]
+        #[allow(deprecated)]
+        mod exports {
+            use super::*;
+
+            $(
+                #[cfg_attr(clippy, allow(clippy::too_many_arguments))]
+                $(#[cfg($cfg)])*
+                #[unsafe(export_name = concat!("clang_sys_", stringify!($name)))]
+                pub unsafe extern "C" fn $name($($pname: $pty), *) $(-> $ret)* {
+                    unsafe { super::$name($($pname), *) }
+                }
+            )+
+        }
+    );
+}
+
 #[cfg(feature = "runtime")]
 macro_rules! link {
     (
@@ -15,11 +41,7 @@ macro_rules! link {
         $(#[doc=$doc])*
         #[cfg($cfg)]
         pub fn $name(library: &mut super::SharedLibrary) {
-            let symbol = unsafe { library.library.get(stringify!($name).as_bytes()) }.ok();
-            library.functions.$name = match symbol {
-                Some(s) => *s,
-                None => None,
-            };
+            library.functions.$name = unsafe { library.get_symbol(stringify!($name)) };
         }
 
         #[cfg(not($cfg))]
@@ -33,15 +55,33 @@ macro_rules! link {
         link!(@LOAD: #[cfg(feature = "runtime")] fn $name($($pname: $pty), *) $(-> $ret)*);
     );
 
+    (@CFG: #[cfg($cfg:meta)]) => (Some(stringify!($cfg)));
+    (@CFG:) => (None);
+
+    (@RET: $ret:ty) => ($ret);
+    (@RET:) => (());
+
+    (@USED: $name:ident $(#[cfg($cfg:meta)])*) => (
+        #[cfg(feature = "usage-audit")]
+        {
+            use std::sync::atomic::{AtomicBool, Ordering};
+            static USED: AtomicBool = AtomicBool::new(false);
+            if !USED.swap(true, Ordering::Relaxed) {
+                record_usage(stringify!($name), link!(@CFG: $(#[cfg($cfg)])*));
+            }
+        }
+    );
+
     (
         $(
-            $(#[doc=$doc:expr] #[cfg($cfg:meta)])*
+            $(#[doc=$doc:expr])* $(#[cfg($cfg:meta)])*
+            $(#[cfg_attr($acfg:meta, $attr:meta)])* $(#[deprecated = $deprecated:literal])?
             pub fn $name:ident($($pname:ident: $pty:ty), *) $(-> $ret:ty)*;
         )+
     ) => (
-        use std::cell::{RefCell};
+        use std::cell::{Cell, RefCell};
         use std::fmt;
-        use std::sync::{Arc};
+        use std::sync::{Arc, OnceLock, RwLock};
         use std::path::{Path, PathBuf};
 
         /// The (minimum) version of a `libclang` shared library.
@@ -100,26 +140,230 @@ macro_rules! link {
             }
         }
 
+        impl Version {
+            /// Returns the version of `libclang` which provides the functions gated by the
+            /// supplied `cfg` attribute (e.g., `feature = "clang_16_0"`), or the oldest supported
+            /// version for the functions which aren't gated.
+            const fn from_cfg(cfg: Option<&str>) -> Version {
+                use Version::*;
+
+                let Some(cfg) = cfg else { return V3_5 };
+
+                // Parse the major and minor versions from the name of the Cargo feature.
+                let bytes = cfg.as_bytes();
+                let mut numbers = [0, 0];
+                let mut index = 0;
+                let mut i = 0;
+                while i < bytes.len() {
+                    match bytes[i] {
+                        b @ b'0'..=b'9' => numbers[index] = numbers[index] * 10 + (b - b'0') as u32,
+                        b'_' if numbers[0] != 0 => index = 1,
+                        _ => {}
+                    }
+                    i += 1;
+                }
+
+                match (numbers[0], numbers[1]) {
+                    (3, 5) => V3_5,
+                    (3, 6) => V3_6,
+                    (3, 7) => V3_7,
+                    (3, 8) => V3_8,
+                    (3, 9) => V3_9,
+                    (4, _) => V4_0,
+                    (5, _) => V5_0,
+                    (6, _) => V6_0,
+                    (7, _) => V7_0,
+                    (8, _) => V8_0,
+                    (9..=10, _) => V9_0,
+                    (11, _) => V11_0,
+                    (12..=15, _) => V12_0,
+                    (16, _) => V16_0,
+                    (17, _) => V17_0,
+                    (18, _) => V18_0,
+                    (19, _) => V19_0,
+                    (20, _) => V20_0,
+                    (21, _) => V21_0,
+                    (22, _) => V22_0,
+                    (23, _) => V23_0,
+                    _ => panic!("unrecognized `cfg` attribute for a `libclang` function"),
+                }
+            }
+        }
+
+        /// The names of all the functions in this crate and the minimum version of `libclang`
+        /// which provides each of them (in the order they are declared in this crate).
+        ///
+        /// Unlike `Functions::all`, this includes the functions excluded by the enabled `clang_*`
+        /// Cargo features so it can be used to explain why a function isn't available in a
+        /// `libclang` shared library (e.g., by comparing the version of the shared library with
+        /// the version required by the function).
+        pub static FUNCTION_VERSIONS: &[(&str, Version)] = &[
+            $((stringify!($name), Version::from_cfg(link!(@CFG: $(#[cfg($cfg)])*))),)+
+        ];
+
+        /// A feature of `libclang` which may not be supported by a `libclang` shared library
+        /// (see `SharedLibrary::supports`).
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        #[non_exhaustive]
+        pub enum Capability {
+            /// Saving translation units as precompiled headers (`clang_saveTranslationUnit`),
+            /// loading them (`clang_createTranslationUnit2`), and reparsing translation units with
+            /// precompiled preambles (`CXTranslationUnit_PrecompiledPreamble` and
+            /// `clang_reparseTranslationUnit`).
+            Pch,
+        }
+
+        impl Capability {
+            /// Returns the names of the functions required by this capability.
+            pub fn functions(self) -> &'static [&'static str] {
+                match self {
+                    Capability::Pch => &[
+                        "clang_createTranslationUnit2",
+                        "clang_defaultReparseOptions",
+                        "clang_defaultSaveOptions",
+                        "clang_reparseTranslationUnit",
+                        "clang_saveTranslationUnit",
+                    ],
+                }
+            }
+        }
+
         /// The set of functions loaded dynamically.
-        #[derive(Debug, Default)]
+        ///
+        /// This struct has a C-compatible layout so it can be passed across an FFI boundary (e.g.,
+        /// to a C++ helper library) to share the functions loaded by this crate instead of loading
+        /// them again. Each field is a function pointer which is null if the function could not be
+        /// loaded and the fields are in the same order as the functions are declared in this crate
+        /// (with the functions excluded by the enabled `clang_*` Cargo features omitted).
+        #[derive(Copy, Clone, Debug, Default)]
+        #[repr(C)]
         pub struct Functions {
             $(
-                $(#[doc=$doc] #[cfg($cfg)])*
+                $(#[doc=$doc])* $(#[cfg($cfg)])*
                 pub $name: Option<unsafe extern "C" fn($($pname: $pty), *) $(-> $ret)*>,
             )+
         }
 
+        impl Functions {
+            /// Returns the names of all the functions (in the order they are declared in this
+            /// crate, with the functions excluded by the enabled `clang_*` Cargo features
+            /// omitted).
+            pub fn all() -> &'static [&'static str] {
+                const ALL: &[&str] = &[$($(#[cfg($cfg)])* stringify!($name),)+];
+                ALL
+            }
+
+            /// Returns the names of the functions which have been loaded (in the same order as
+            /// `all`).
+            ///
+            /// The `functions` of a shared library opened lazily are never populated so this is
+            /// always empty for them (see `SharedLibrary::missing_functions` instead).
+            pub fn loaded(&self) -> impl Iterator<Item = &'static str> {
+                let mut loaded = vec![];
+                $(
+                    $(#[cfg($cfg)])*
+                    if self.$name.is_some() {
+                        loaded.push(stringify!($name));
+                    }
+                )+
+                loaded.into_iter()
+            }
+        }
+
+        /// The functions resolved on first use from a `libclang` shared library opened lazily
+        /// (see `SharedLibrary::open_lazy`).
+        #[derive(Debug, Default)]
+        struct LazyFunctions {
+            $(
+                $(#[cfg($cfg)])*
+                $name: OnceLock<Option<unsafe extern "C" fn($($pname: $pty), *) $(-> $ret)*>>,
+            )+
+        }
+
         /// A dynamically loaded instance of the `libclang` library.
         #[derive(Debug)]
         pub struct SharedLibrary {
             pub(crate) library: libloading::Library,
+            /// The platform-specific handle of `library` (see `as_raw`).
+            raw: usize,
             pub(crate) path: PathBuf,
+            /// Whether this shared library was built with LLVM assertions enabled (determined on
+            /// first use, see `has_assertions`).
+            assertions: OnceLock<bool>,
             pub functions: Functions,
+            /// The functions resolved on first use (if this shared library was opened lazily).
+            lazy: Option<Box<LazyFunctions>>,
+            /// The dependencies loaded before this shared library (which must outlive it).
+            pub(crate) dependencies: Vec<libloading::Library>,
         }
 
         impl SharedLibrary {
             fn new(library: libloading::Library, path: PathBuf) -> Self {
-                Self { library, path, functions: Functions::default() }
+                // The handle is extracted when the library is opened since `libloading` only
+                // provides it by consuming the library.
+                #[cfg(unix)]
+                let (library, raw) = {
+                    use libloading::os::unix::Library;
+                    let raw = Library::from(library).into_raw();
+                    (unsafe { Library::from_raw(raw) }.into(), raw as usize)
+                };
+                #[cfg(windows)]
+                let (library, raw) = {
+                    use libloading::os::windows::Library;
+                    let raw = Library::from(library).into_raw();
+                    (unsafe { Library::from_raw(raw) }.into(), raw as usize)
+                };
+
+                Self {
+                    library,
+                    raw,
+                    path,
+                    assertions: OnceLock::new(),
+                    functions: Functions::default(),
+                    lazy: None,
+                    dependencies: vec![],
+                }
+            }
+
+            /// Returns the function or variable with the supplied name from this shared library or,
+            /// if it isn't found, from the dependencies loaded before this shared library.
+            ///
+            /// # Safety
+            ///
+            /// `T` must be the type of the function or variable (e.g., an `Option` of a function
+            /// pointer).
+            unsafe fn get_symbol<T: Copy + Default>(&self, name: &str) -> T {
+                let function = unsafe { self.get_function::<T>(name) };
+                #[cfg(feature = "log")]
+                if function.is_none() {
+                    log::trace!(target: "clang_sys", "couldn't resolve {}", name);
+                }
+                function.unwrap_or_default()
+            }
+
+            /// Returns the function with the supplied name (e.g., a function which isn't bound by
+            /// this crate yet) from this shared library or, if it isn't found, from the
+            /// dependencies loaded before this shared library.
+            ///
+            /// Returns `None` if the function can't be found or if `F` isn't the same size as a
+            /// pointer.
+            ///
+            /// ```no_run
+            /// # use clang_sys::*;
+            /// let library = load_manually().unwrap();
+            /// type F = unsafe extern "C" fn(CXCursor) -> CXString;
+            /// let function = unsafe { library.get_function::<F>("clang_getCursorUSR") };
+            /// ```
+            ///
+            /// # Safety
+            ///
+            /// `F` must be the type of the function (e.g., `unsafe extern "C" fn(CXCursor) ->
+            /// CXString`) and the function must not be called after this shared library is
+            /// dropped.
+            pub unsafe fn get_function<F: Copy>(&self, name: &str) -> Option<F> {
+                std::iter::once(&self.library)
+                    .chain(&self.dependencies)
+                    .find_map(|l| unsafe { l.get::<F>(name.as_bytes()) }.ok().map(|s| *s))
             }
 
             /// Returns the path to this `libclang` shared library.
@@ -127,6 +371,33 @@ macro_rules! link {
                 &self.path
             }
 
+            /// Returns the platform-specific handle of this shared library (the handle returned by
+            /// `dlopen` on Unix or the `HMODULE` returned by `LoadLibraryExW` on Windows).
+            ///
+            /// This handle can be passed to other code (e.g., a C++ helper library) which calls
+            /// `dlsym` or `GetProcAddress` with it instead of loading `libclang` again. The handle
+            /// must not be closed (e.g., with `dlclose` or `FreeLibrary`) and must not be used
+            /// after this shared library is dropped.
+            pub fn as_raw(&self) -> *mut std::ffi::c_void {
+                self.raw as *mut std::ffi::c_void
+            }
+
+            /// Returns the `libloading` library this shared library was opened as.
+            pub fn as_library(&self) -> &libloading::Library {
+                &self.library
+            }
+
+            /// Converts this shared library into the `libloading` library it was opened as (e.g.,
+            /// to take ownership of the platform-specific handle with `into_raw`).
+            ///
+            /// The dependencies loaded before this shared library (e.g., the `@rpath` dependencies
+            /// of a shared library in an app bundle on macOS) are never unloaded since they must
+            /// outlive it.
+            pub fn into_library(self) -> libloading::Library {
+                std::mem::forget(self.dependencies);
+                self.library
+            }
+
             /// Returns the (minimum) version of this `libclang` shared library.
             ///
             /// This method uses a hybrid detection strategy:
@@ -262,19 +533,253 @@ macro_rules! link {
                 None
             }
 
-            /// Parse version from `clang_getClangVersion()` string.
+            /// Returns whether this `libclang` shared library was built with LLVM assertions
+            /// enabled.
             ///
-            /// This method provides accurate version detection for all Clang versions,
-            /// including those that don't introduce unique marker functions in the
-            /// C API (such as v18, v22, and v23).
+            /// Shared libraries built with assertions enabled are much slower and abort the process
+            /// when an internal inconsistency is detected, so host applications may want to warn
+            /// about them. This is detected (the first time this is called) by checking for the
+            /// `llvm::EnableABIBreakingChecks` symbol which is exported by LLVM builds with
+            /// assertions enabled.
             ///
-            /// The version string format is typically: `"clang version MAJOR.MINOR.PATCH"`
-            /// (e.g., `"clang version 23.1.0"`).
+            /// Shared libraries which only export the `libclang` API (and don't depend on an LLVM
+            /// shared library which exports this symbol) are assumed to have been built without
+            /// assertions enabled.
+            pub fn has_assertions(&self) -> bool {
+                *self.assertions.get_or_init(|| {
+                    // SAFETY: Symbol lookup is safe. The symbol is a variable, not a function, so
+                    // it is never dereferenced.
+                    unsafe { self.get_function::<*const u8>("_ZN4llvm23EnableABIBreakingChecksE") }
+                        .is_some()
+                })
+            }
+
+            /// Returns whether the functions in this `libclang` shared library are resolved on first
+            /// use instead of when it was loaded (see `SharedLibrary::open_lazy`).
             ///
-            /// # Returns
+            /// The `functions` of a shared library opened lazily are never populated.
+            pub fn is_lazy(&self) -> bool {
+                self.lazy.is_some()
+            }
+
+            /// Returns the version string of this `libclang` shared library (i.e., the string
+            /// returned by `clang_getClangVersion`) if it could be retrieved.
             ///
-            /// - `Some(Version::VXX_0)` if the version can be successfully parsed
-            /// - `None` if version parsing fails or the version is unsupported
+            /// This is the exact version string including any vendor prefixes and suffixes (e.g.,
+            /// `Ubuntu clang version 14.0.0-1ubuntu1.1`).
+            pub fn clang_version(&self) -> Option<String> {
+                // SAFETY: Opened shared libraries are never stubs.
+                unsafe { self.version_string() }
+            }
+
+            /// Returns the exact version (major, minor, and patch) of this `libclang` shared
+            /// library parsed from its version string (see `clang_version`) if it could be
+            /// parsed.
+            ///
+            /// Unlike `version`, which only identifies the range of versions with the same
+            /// functions, this distinguishes every release (e.g., `(17, 0, 6)` for a shared
+            /// library which includes a fix missing from `(17, 0, 5)`). Missing components are
+            /// zero (e.g., `(18, 0, 0)` for `clang version 18`).
+            pub fn version_full(&self) -> Option<(u32, u32, u32)> {
+                let version = crate::version::parse_clang_version(&self.clang_version()?)?;
+                let component = |i: usize| version.get(i).copied().unwrap_or(0);
+                Some((component(0), component(1), component(2)))
+            }
+
+            /// Returns the vendor of this `libclang` shared library (i.e., the text preceding
+            /// `clang version` in its version string, see `clang_version`) if it has one.
+            ///
+            /// Upstream LLVM releases don't have a vendor while distributed builds usually do
+            /// (e.g., `Apple` for `Apple clang version 15.0.0 (clang-1500.3.9.4)`, `Ubuntu` for
+            /// `Ubuntu clang version 14.0.0-1ubuntu1.1`, or `Android (11349228, +pgo, +bolt,
+            /// +lto, -mlgo, based on r487747e)` for the Android NDK).
+            pub fn vendor(&self) -> Option<String> {
+                let version = self.clang_version()?;
+                let vendor = version[..version.find("clang version")?].trim();
+                (!vendor.is_empty()).then(|| vendor.into())
+            }
+
+            /// Returns the default target triple of this `libclang` shared library (e.g.,
+            /// `x86_64-pc-linux-gnu` or `arm64-apple-macosx14.0.0`) if it could be determined.
+            ///
+            /// The version string doesn't include the default target so this parses an empty
+            /// in-memory translation unit without any arguments and returns the triple of its
+            /// target. This requires `libclang` 5.0 or later (regardless of the enabled `clang_*`
+            /// Cargo features).
+            pub fn default_target(&self) -> Option<String> {
+                use std::ffi::CStr;
+                use std::os::raw::c_void;
+
+                type GetTargetInfo = unsafe extern "C" fn(CXTranslationUnit) -> *mut c_void;
+                type GetTriple = unsafe extern "C" fn(*mut c_void) -> CXString;
+                type DisposeTargetInfo = unsafe extern "C" fn(*mut c_void);
+
+                // SAFETY: The functions were loaded from this library with their `libclang`
+                // signatures and the objects they return are disposed before returning.
+                unsafe {
+                    let get_info = self.get_function::<GetTargetInfo>("clang_getTranslationUnitTargetInfo")?;
+                    let get_triple = self.get_function::<GetTriple>("clang_TargetInfo_getTriple")?;
+                    let dispose_info = self.get_function::<DisposeTargetInfo>("clang_TargetInfo_dispose")?;
+                    let create_index = lazy::clang_createIndex(self)?;
+                    let dispose_index = lazy::clang_disposeIndex(self)?;
+                    let parse = lazy::clang_parseTranslationUnit(self)?;
+                    let dispose_tu = lazy::clang_disposeTranslationUnit(self)?;
+                    let get_cstring = lazy::clang_getCString(self)?;
+                    let dispose_string = lazy::clang_disposeString(self)?;
+
+                    let index = create_index(0, 0);
+                    if index.is_null() {
+                        return None;
+                    }
+
+                    let filename = c"clang-sys-target.c";
+                    let mut unsaved = CXUnsavedFile {
+                        Filename: filename.as_ptr(),
+                        Contents: c"".as_ptr(),
+                        Length: 0,
+                    };
+
+                    let tu = parse(
+                        index,
+                        filename.as_ptr(),
+                        std::ptr::null(),
+                        0,
+                        &mut unsaved,
+                        1,
+                        CXTranslationUnit_None,
+                    );
+
+                    let mut triple = None;
+                    if !tu.is_null() {
+                        let info = get_info(tu);
+                        if !info.is_null() {
+                            let string = get_triple(info);
+                            let pointer = get_cstring(string);
+                            if !pointer.is_null() {
+                                triple = Some(CStr::from_ptr(pointer).to_string_lossy().into_owned());
+                            }
+                            dispose_string(string);
+                            dispose_info(info);
+                        }
+                        dispose_tu(tu);
+                    }
+
+                    dispose_index(index);
+                    triple.filter(|t| !t.is_empty())
+                }
+            }
+
+            /// Verifies that this `libclang` shared library works by calling a few of its
+            /// functions.
+            ///
+            /// A shared library can be named like and export the functions of a `libclang` shared
+            /// library but still not work (e.g., a stub shared library which is only meant to be
+            /// linked to, a thunk for another architecture, or a corrupted file), which would
+            /// otherwise only be discovered when a consumer calls one of its functions. This
+            /// checks that this shared library isn't a stub built by this crate (see the `stub`
+            /// Cargo feature), that `clang_getClangVersion` returns a `clang` version string, and
+            /// that `clang_createIndex` returns an index (which is disposed immediately).
+            ///
+            /// # Failures
+            ///
+            /// * this `libclang` shared library doesn't work (`LoadError::VerificationFailed`)
+            pub fn verify(&self) -> Result<(), LoadError> {
+                let fail = |message: String| LoadError::VerificationFailed {
+                    path: self.path.clone(),
+                    message,
+                };
+
+                if self.is_stub() {
+                    return Err(fail(STUB_MESSAGE.into()));
+                }
+
+                // SAFETY: The library was verified to not be a stub.
+                let version = unsafe { self.version_string() };
+                let version = version.ok_or_else(|| fail("`clang_getClangVersion` failed".into()))?;
+                if crate::version::parse_clang_version(&version).is_none() {
+                    return Err(fail(format!(
+                        "`clang_getClangVersion` returned an unrecognized version string ({:?})",
+                        version,
+                    )));
+                }
+
+                let create = lazy::clang_createIndex(self);
+                let dispose = lazy::clang_disposeIndex(self);
+                let (Some(create), Some(dispose)) = (create, dispose) else {
+                    return Err(fail("`clang_createIndex` or `clang_disposeIndex` is missing".into()));
+                };
+
+                // SAFETY: The functions were loaded from this library.
+                unsafe {
+                    let index = create(0, 0);
+                    if index.is_null() {
+                        return Err(fail("`clang_createIndex` returned a null index".into()));
+                    }
+
+                    dispose(index);
+                }
+
+                Ok(())
+            }
+
+            /// Returns whether this `libclang` shared library is a stub built by this crate (see
+            /// the `stub` Cargo feature) whose functions abort the process if they are called.
+            fn is_stub(&self) -> bool {
+                let marker = discovery::dynamic::STUB_MARKER.as_bytes();
+                // SAFETY: Symbol lookup is safe. The symbol is never dereferenced.
+                unsafe { self.library.get::<*const u8>(marker) }.is_ok()
+            }
+
+            /// Returns whether this `libclang` shared library supports the supplied capability
+            /// (i.e., exports all of the functions it requires).
+            ///
+            /// This doesn't depend on the enabled `clang_*` Cargo features so it can be used to
+            /// decide whether to use a feature of `libclang` (e.g., precompiled preambles in an
+            /// editor integration) before calling any of its functions.
+            pub fn supports(&self, capability: Capability) -> bool {
+                capability.functions().iter().all(|f| {
+                    // SAFETY: The function pointers are never called.
+                    unsafe { self.get_function::<unsafe extern "C" fn()>(f) }.is_some()
+                })
+            }
+
+            /// Returns the names of the functions which could not be loaded from this `libclang`
+            /// shared library (in the order they are declared in this crate).
+            ///
+            /// Calling any of these functions will panic. Functions excluded by the enabled
+            /// `clang_*` Cargo features are never loaded and are not included. If this shared
+            /// library was opened lazily, this resolves every function which hasn't been
+            /// resolved yet.
+            pub fn missing_functions(&self) -> Vec<&'static str> {
+                self.missing_functions_by_cfg().into_iter().flat_map(|(_, f)| f).collect()
+            }
+
+            /// Returns the names of the functions which could not be loaded from this `libclang`
+            /// shared library grouped by the `cfg` attribute which gates them (e.g.,
+            /// `feature = "clang_16_0"`), or `None` for the functions which aren't gated.
+            ///
+            /// Groups are in the order they are first encountered in the declarations of the
+            /// functions in this crate.
+            pub fn missing_functions_by_cfg(&self) -> Vec<(Option<&'static str>, Vec<&'static str>)> {
+                let mut groups: Vec<(Option<&'static str>, Vec<&'static str>)> = vec![];
+                let mut add = |cfg: Option<&'static str>, name: &'static str| {
+                    match groups.iter_mut().find(|(c, _)| *c == cfg) {
+                        Some((_, names)) => names.push(name),
+                        None => groups.push((cfg, vec![name])),
+                    }
+                };
+
+                $(
+                    $(#[cfg($cfg)])*
+                    if lazy::$name(self).is_none() {
+                        add(link!(@CFG: $(#[cfg($cfg)])*), stringify!($name));
+                    }
+                )+
+
+                groups
+            }
+
+            /// Returns the string returned by `clang_getClangVersion()`.
             ///
             /// # Safety
             ///
@@ -285,7 +790,7 @@ macro_rules! link {
             /// - The library exports the required functions: `clang_getClangVersion`,
             ///   `clang_getCString`, and `clang_disposeString`
             /// - The library remains loaded for the duration of this call
-            unsafe fn version_from_string(&self) -> Option<Version> {
+            unsafe fn version_string(&self) -> Option<String> {
                 use std::ffi::CStr;
                 use std::os::raw::c_char;
 
@@ -327,176 +832,2248 @@ macro_rules! link {
 
                     // SAFETY: c_str_ptr is non-null and points to a valid C string
                     // managed by libclang. The string remains valid until we dispose
-                    // the CXString.
-                    let version_str = CStr::from_ptr(c_str_ptr).to_str().ok()?;
-
-                    // Parse "clang version 23.1.0" or similar.
-                    // Expected format: "clang version MAJOR.MINOR.PATCH"
-                    // We extract only the MAJOR version for our coarse-grained detection.
-                    let major = version_str
-                        .split_whitespace()
-                        .nth(2)?  // Extract "23.1.0" from "clang version 23.1.0"
-                        .split('.')
-                        .next()?  // Extract "23" from "23.1.0"
-                        .parse::<u32>()
-                        .ok()?;
+                    // the CXString, so we copy it before doing so.
+                    let version_str = CStr::from_ptr(c_str_ptr).to_string_lossy().into_owned();
 
                     // Dispose the CXString to free libclang-managed memory.
                     // SAFETY: Library is valid. Symbol lookup is safe.
-                    let dispose = self.library
+                    if let Ok(dispose) = self.library
                         .get::<unsafe extern "C" fn(CXString)>(b"clang_disposeString")
-                        .ok()?;
-
-                    // SAFETY: version_cxstring is a valid CXString that hasn't been
-                    // disposed yet. This is the standard cleanup for CXString values.
-                    dispose(version_cxstring);
-
-                    // Map LLVM/Clang major version to our Version enum.
-                    // Versions are grouped to match the granularity of our enum variants.
-                    match major {
-                        23.. => Some(Version::V23_0),      // Clang 23.x and newer
-                        22 => Some(Version::V22_0),         // Clang 22.x
-                        21 => Some(Version::V21_0),         // Clang 21.x
-                        20 => Some(Version::V20_0),         // Clang 20.x
-                        19 => Some(Version::V19_0),         // Clang 19.x
-                        18 => Some(Version::V18_0),         // Clang 18.x
-                        17 => Some(Version::V17_0),         // Clang 17.x
-                        16 => Some(Version::V16_0),         // Clang 16.x
-                        12..=15 => Some(Version::V12_0),    // Clang 12.x - 15.x
-                        11 => Some(Version::V11_0),         // Clang 11.x
-                        9 | 10 => Some(Version::V9_0),      // Clang 9.x - 10.x
-                        8 => Some(Version::V8_0),           // Clang 8.x
-                        7 => Some(Version::V7_0),           // Clang 7.x
-                        6 => Some(Version::V6_0),           // Clang 6.x
-                        5 => Some(Version::V5_0),           // Clang 5.x
-                        4 => Some(Version::V4_0),           // Clang 4.x
-                        _ => None,                          // Unsupported (3.x or unknown)
+                    {
+                        // SAFETY: version_cxstring is a valid CXString that hasn't been
+                        // disposed yet. This is the standard cleanup for CXString values.
+                        dispose(version_cxstring);
                     }
+
+                    Some(version_str)
                 }
             }
-        }
 
-        thread_local!(static LIBRARY: RefCell<Option<Arc<SharedLibrary>>> = RefCell::new(None));
+            /// Parse version from `clang_getClangVersion()` string.
+            ///
+            /// This method provides accurate version detection for all Clang versions,
+            /// including those that don't introduce unique marker functions in the
+            /// C API (such as v18, v22, and v23).
+            ///
+            /// The version string format is typically: `"clang version MAJOR.MINOR.PATCH"`
+            /// (e.g., `"clang version 23.1.0"`).
+            ///
+            /// # Returns
+            ///
+            /// - `Some(Version::VXX_0)` if the version can be successfully parsed
+            /// - `None` if version parsing fails or the version is unsupported
+            ///
+            /// # Safety
+            ///
+            /// See `version_string`.
+            unsafe fn version_from_string(&self) -> Option<Version> {
+                // SAFETY: Upheld by the caller.
+                let version_str = unsafe { self.version_string()? };
 
-        /// Returns whether a `libclang` shared library is loaded on this thread.
-        pub fn is_loaded() -> bool {
-            LIBRARY.with(|l| l.borrow().is_some())
-        }
+                // Parse "clang version 23.1.0" or similar (including vendor
+                // variants such as "Ubuntu clang version 14.0.0-1ubuntu1").
+                // We extract only the MAJOR version for our coarse-grained detection.
+                let major = *crate::version::parse_clang_version(&version_str)?.first()?;
 
-        fn with_library<T, F>(f: F) -> Option<T> where F: FnOnce(&SharedLibrary) -> T {
-            LIBRARY.with(|l| {
-                match l.borrow().as_ref() {
-                    Some(library) => Some(f(&library)),
-                    _ => None,
+                // Map LLVM/Clang major version to our Version enum.
+                // Versions are grouped to match the granularity of our enum variants.
+                match major {
+                    23.. => Some(Version::V23_0),      // Clang 23.x and newer
+                    22 => Some(Version::V22_0),         // Clang 22.x
+                    21 => Some(Version::V21_0),         // Clang 21.x
+                    20 => Some(Version::V20_0),         // Clang 20.x
+                    19 => Some(Version::V19_0),         // Clang 19.x
+                    18 => Some(Version::V18_0),         // Clang 18.x
+                    17 => Some(Version::V17_0),         // Clang 17.x
+                    16 => Some(Version::V16_0),         // Clang 16.x
+                    12..=15 => Some(Version::V12_0),    // Clang 12.x - 15.x
+                    11 => Some(Version::V11_0),         // Clang 11.x
+                    9 | 10 => Some(Version::V9_0),      // Clang 9.x - 10.x
+                    8 => Some(Version::V8_0),           // Clang 8.x
+                    7 => Some(Version::V7_0),           // Clang 7.x
+                    6 => Some(Version::V6_0),           // Clang 6.x
+                    5 => Some(Version::V5_0),           // Clang 5.x
+                    4 => Some(Version::V4_0),           // Clang 4.x
+                    _ => None,                          // Unsupported (3.x or unknown)
                 }
-            })
+            }
         }
 
-        $(
-            #[cfg_attr(clippy, allow(clippy::missing_safety_doc))]
-            #[cfg_attr(clippy, allow(clippy::too_many_arguments))]
-            $(#[doc=$doc] #[cfg($cfg)])*
-            pub unsafe fn $name($($pname: $pty), *) $(-> $ret)* {
-                let f = with_library(|library| {
-                    if let Some(function) = library.functions.$name {
-                        function
-                    } else {
-                        panic!(
-                            r#"
-A `libclang` function was called that is not supported by the loaded `libclang` instance.
-
-    called function = `{0}`
-    loaded `libclang` instance = {1}
+        /// An error encountered while loading a `libclang` shared library.
+        #[derive(Debug)]
+        #[non_exhaustive]
+        pub enum LoadError {
+            /// A `libclang` shared library could not be found.
+            NotFound {
+                /// The directories which were searched for `libclang` shared libraries.
+                searched: Vec<PathBuf>,
+                /// A description of why no `libclang` shared library could be found.
+                message: String,
+            },
+            /// A `libclang` shared library was found but could not be opened.
+            OpenFailed {
+                /// The path to the `libclang` shared library (or to one of the shared libraries listed
+                /// in the `LIBCLANG_PRELOAD` environment variable).
+                path: PathBuf,
+                /// The error encountered while opening the `libclang` shared library.
+                source: libloading::Error,
+            },
+            /// A `libclang` shared library was found but could not be opened because one of its
+            /// dependencies could not be found (e.g., `libtinfo.so.5`).
+            MissingDependency {
+                /// The path to the `libclang` shared library (or to one of the shared libraries
+                /// listed in the `LIBCLANG_PRELOAD` environment variable).
+                path: PathBuf,
+                /// The name of the dependency which could not be found.
+                dependency: String,
+                /// The error encountered while opening the `libclang` shared library.
+                source: libloading::Error,
+            },
+            /// A `libclang` shared library was found but could not be opened because it requires
+            /// a newer version of the C or C++ standard library than is available.
+            RuntimeTooOld {
+                /// The path to the `libclang` shared library (or to one of the shared libraries
+                /// listed in the `LIBCLANG_PRELOAD` environment variable).
+                path: PathBuf,
+                /// The symbol version which is required but not available (e.g., `GLIBC_2.34`).
+                requirement: String,
+                /// The error encountered while opening the `libclang` shared library.
+                source: libloading::Error,
+            },
+            /// A `libclang` shared library was found but could not be opened because it was built
+            /// for another architecture (e.g., a 32-bit shared library in a 64-bit process).
+            WrongArchitecture {
+                /// The path to the `libclang` shared library (or to one of the shared libraries
+                /// listed in the `LIBCLANG_PRELOAD` environment variable).
+                path: PathBuf,
+                /// The error encountered while opening the `libclang` shared library.
+                source: libloading::Error,
+            },
+            /// A `libclang` shared library was found but could not be opened because a symbol it
+            /// requires isn't defined by its dependencies (e.g., because they are from another
+            /// version of LLVM).
+            UndefinedSymbol {
+                /// The path to the `libclang` shared library (or to one of the shared libraries
+                /// listed in the `LIBCLANG_PRELOAD` environment variable).
+                path: PathBuf,
+                /// The name of the symbol which isn't defined.
+                symbol: String,
+                /// The error encountered while opening the `libclang` shared library.
+                source: libloading::Error,
+            },
+            /// A `libclang` shared library was opened but is older than the oldest supported
+            /// version (i.e., `Version::V3_5`) or the minimum version required by the caller.
+            VersionTooOld {
+                /// The path to the `libclang` shared library.
+                path: PathBuf,
+                /// The version of the `libclang` shared library (if it is a supported version).
+                version: Option<Version>,
+                /// The minimum version of `libclang` required.
+                minimum: Version,
+            },
+            /// A `libclang` shared library was opened but doesn't work (see
+            /// `SharedLibrary::verify`).
+            VerificationFailed {
+                /// The path to the `libclang` shared library.
+                path: PathBuf,
+                /// A description of why the `libclang` shared library doesn't work.
+                message: String,
+            },
+            /// None of the `libclang` shared libraries found could be opened (e.g., because the
+            /// most preferred one has a missing dependency and the others are too old).
+            AllFailed {
+                /// The errors encountered while opening each shared library (in the order the
+                /// shared libraries were tried).
+                failures: Vec<LoadError>,
+            },
+            /// The contents of a `libclang` shared library could not be written to a file to open
+            /// (see `SharedLibrary::from_bytes`).
+            #[cfg(feature = "in-memory")]
+            WriteFailed {
+                /// The path to the file which could not be written.
+                path: PathBuf,
+                /// The error encountered while writing the file.
+                source: std::io::Error,
+            },
+            /// The SHA-256 digest of a `libclang` shared library isn't in the allowlist provided
+            /// by the `CLANG_SYS_LIBCLANG_SHA256` environment variable or `LoadOptions::sha256`.
+            IntegrityMismatch {
+                /// The path to the `libclang` shared library.
+                path: PathBuf,
+                /// The SHA-256 digest of the `libclang` shared library (as lowercase hexadecimal
+                /// digits) or `None` if it could not be read.
+                digest: Option<String>,
+            },
+        }
 
-The minimum `libclang` requirement for this particular function can be found here:
-https://docs.rs/clang-sys/latest/clang_sys/{0}/index.html
+        /// The description of why a stub `libclang` shared library doesn't work.
+        const STUB_MESSAGE: &str = "it is a stub shared library which can only be linked to";
 
-Instructions for installing `libclang` can be found here:
-https://rust-lang.github.io/rust-bindgen/requirements.html
-"#,
-                            stringify!($name),
-                            library
-                                .version()
-                                .map(|v| format!("{}", v))
-                                .unwrap_or_else(|| "unsupported version".into()),
-                        );
-                    }
-                }).expect("a `libclang` shared library is not loaded on this thread");
-                unsafe { f($($pname), *) }
+        impl fmt::Display for LoadError {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self {
+                    LoadError::NotFound { message, .. } => write!(f, "{}", message),
+                    LoadError::OpenFailed { path, source } => write!(
+                        f,
+                        "the `libclang` shared library at {} could not be opened: {}",
+                        path.display(),
+                        source,
+                    ),
+                    LoadError::MissingDependency { path, dependency, source } => write!(
+                        f,
+                        "the `libclang` shared library at {} could not be opened because its \
+                         dependency {} could not be found ({}), install the package which \
+                         provides it or add the directory containing it to the directories \
+                         searched for shared libraries (e.g., `LD_LIBRARY_PATH`)",
+                        path.display(),
+                        dependency,
+                        source,
+                    ),
+                    LoadError::RuntimeTooOld { path, requirement, source } => write!(
+                        f,
+                        "the `libclang` shared library at {} could not be opened because it \
+                         requires {} which isn't available ({}), use a `libclang` shared library \
+                         built for this system (e.g., from the packages of this distribution)",
+                        path.display(),
+                        requirement,
+                        source,
+                    ),
+                    LoadError::WrongArchitecture { path, source } => write!(
+                        f,
+                        "the `libclang` shared library at {} could not be opened because it \
+                         wasn't built for this architecture ({}), use a `libclang` shared library \
+                         built for {} ({}-bit)",
+                        path.display(),
+                        source,
+                        std::env::consts::ARCH,
+                        usize::BITS,
+                    ),
+                    LoadError::UndefinedSymbol { path, symbol, source } => write!(
+                        f,
+                        "the `libclang` shared library at {} could not be opened because the \
+                         symbol {} isn't defined ({}), make sure its dependencies (e.g., the LLVM \
+                         shared library) are from the same release of LLVM",
+                        path.display(),
+                        symbol,
+                        source,
+                    ),
+                    LoadError::VersionTooOld { path, version, minimum } => write!(
+                        f,
+                        "the `libclang` shared library at {} ({}) is older than the minimum \
+                         version required ({})",
+                        path.display(),
+                        version.map_or("older than 3.5.x".into(), |v| v.to_string()),
+                        minimum,
+                    ),
+                    LoadError::VerificationFailed { path, message } => write!(
+                        f,
+                        "the `libclang` shared library at {} doesn't work: {}",
+                        path.display(),
+                        message,
+                    ),
+                    LoadError::AllFailed { failures } => write!(
+                        f,
+                        "none of the `libclang` shared libraries found could be opened: [{}]",
+                        failures.iter().map(|e| format!("({})", e)).collect::<Vec<_>>().join(", "),
+                    ),
+                    #[cfg(feature = "in-memory")]
+                    LoadError::WriteFailed { path, source } => write!(
+                        f,
+                        "the contents of a `libclang` shared library could not be written to {}: {}",
+                        path.display(),
+                        source,
+                    ),
+                    LoadError::IntegrityMismatch { path, digest: Some(digest) } => write!(
+                        f,
+                        "the SHA-256 digest of the `libclang` shared library at {} ({}) is not in \
+                        the allowlist (see the `CLANG_SYS_LIBCLANG_SHA256` environment variable)",
+                        path.display(),
+                        digest,
+                    ),
+                    LoadError::IntegrityMismatch { path, digest: None } => write!(
+                        f,
+                        "the SHA-256 digest of the `libclang` shared library at {} could not be \
+                        computed because it could not be read",
+                        path.display(),
+                    ),
+                }
             }
+        }
 
-            $(#[doc=$doc] #[cfg($cfg)])*
-            pub mod $name {
-                pub fn is_loaded() -> bool {
-                    super::with_library(|l| l.functions.$name.is_some()).unwrap_or(false)
+        /// An error returned when calling a function which is not available (e.g., with
+        /// `clang_createIndex::call`).
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        #[non_exhaustive]
+        pub enum MissingFunction {
+            /// No `libclang` shared library is loaded on this thread or process-wide.
+            NotLoaded {
+                /// The name of the function.
+                name: &'static str,
+            },
+            /// The function is not supported by the loaded `libclang` shared library.
+            NotSupported {
+                /// The name of the function.
+                name: &'static str,
+                /// The version of the loaded `libclang` shared library (if it could be
+                /// determined).
+                version: Option<Version>,
+            },
+        }
+
+        impl fmt::Display for MissingFunction {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self {
+                    MissingFunction::NotLoaded { name } => write!(
+                        f,
+                        "`{}` was called but a `libclang` shared library is not loaded on this \
+                         thread or process-wide",
+                        name,
+                    ),
+                    MissingFunction::NotSupported { name, version } => write!(
+                        f,
+                        "`{}` is not supported by the loaded `libclang` shared library ({})",
+                        name,
+                        version.map_or("unsupported version".into(), |v| v.to_string()),
+                    ),
                 }
             }
-        )+
+        }
 
-        mod load {
-            $(link!(@LOAD: $(#[cfg($cfg)])* fn $name($($pname: $pty), *) $(-> $ret)*);)+
+        impl std::error::Error for MissingFunction {}
+
+        impl std::error::Error for LoadError {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                match self {
+                    LoadError::OpenFailed { source, .. }
+                    | LoadError::MissingDependency { source, .. }
+                    | LoadError::RuntimeTooOld { source, .. }
+                    | LoadError::WrongArchitecture { source, .. }
+                    | LoadError::UndefinedSymbol { source, .. } => Some(source),
+                    LoadError::AllFailed { failures } => failures.first().map(|e| e as _),
+                    #[cfg(feature = "in-memory")]
+                    LoadError::WriteFailed { source, .. } => Some(source),
+                    _ => None,
+                }
+            }
         }
 
-        /// Loads a `libclang` shared library and returns the library instance.
-        ///
-        /// This function does not attempt to load any functions from the shared library. The caller
-        /// is responsible for loading the functions they require.
-        ///
-        /// # Failures
-        ///
-        /// * a `libclang` shared library could not be found
-        /// * the `libclang` shared library could not be opened
-        pub fn load_manually() -> Result<SharedLibrary, String> {
-            #[allow(dead_code)]
-            mod build {
-                include!(concat!(env!("OUT_DIR"), "/macros.rs"));
-                pub mod common { include!(concat!(env!("OUT_DIR"), "/common.rs")); }
-                pub mod dynamic { include!(concat!(env!("OUT_DIR"), "/dynamic.rs")); }
+        /// Returns the message of an error encountered while opening a shared library including
+        /// the messages of its sources (e.g., the message returned by `dlerror`).
+        fn error_message(error: &libloading::Error) -> String {
+            let mut message = error.to_string();
+            let mut source = std::error::Error::source(error);
+            while let Some(error) = source {
+                message.push_str(": ");
+                message.push_str(&error.to_string());
+                source = error.source();
             }
 
-            let (directory, filename) = build::dynamic::find(true)?;
-            let path = directory.join(filename);
+            message
+        }
 
-            unsafe {
-                let library = libloading::Library::new(&path).map_err(|e| {
-                    format!(
-                        "the `libclang` shared library at {} could not be opened: {}",
-                        path.display(),
-                        e,
-                    )
-                });
+        impl LoadError {
+            /// Constructs an error for a shared library which could not be opened, which describes
+            /// the cause if it is a common cause (e.g., `LoadError::MissingDependency`).
+            fn open_failed(path: PathBuf, source: libloading::Error) -> Self {
+                use discovery::dynamic::OpenFailure;
 
-                let mut library = SharedLibrary::new(library?, path);
-                $(load::$name(&mut library);)+
-                Ok(library)
+                let filename = path.to_string_lossy();
+                match discovery::dynamic::parse_open_error(&filename, &error_message(&source)) {
+                    Some(OpenFailure::MissingDependency(dependency)) => {
+                        LoadError::MissingDependency { path, dependency, source }
+                    }
+                    Some(OpenFailure::RuntimeTooOld(requirement)) => {
+                        LoadError::RuntimeTooOld { path, requirement, source }
+                    }
+                    Some(OpenFailure::WrongArchitecture) => {
+                        LoadError::WrongArchitecture { path, source }
+                    }
+                    Some(OpenFailure::UndefinedSymbol(symbol)) => {
+                        LoadError::UndefinedSymbol { path, symbol, source }
+                    }
+                    None => LoadError::OpenFailed { path, source },
+                }
             }
         }
 
-        /// Loads a `libclang` shared library for use in the current thread.
-        ///
-        /// This functions attempts to load all the functions in the shared library. Whether a
-        /// function has been loaded can be tested by calling the `is_loaded` function on the
-        /// module with the same name as the function (e.g., `clang_createIndex::is_loaded()` for
-        /// the `clang_createIndex` function).
-        ///
-        /// # Failures
-        ///
-        /// * a `libclang` shared library could not be found
-        /// * the `libclang` shared library could not be opened
+        /// Allows functions which return `Result<_, String>` to keep using `?` with the functions
+        /// which load `libclang` shared libraries (which returned `String` errors previously).
+        impl From<LoadError> for String {
+            fn from(error: LoadError) -> String {
+                error.to_string()
+            }
+        }
+
+        use crate::runtime::discovery;
+        use crate::runtime::sha256;
+
+        pub use crate::runtime::discovery::common::{
+            set_discovery_strategies, DiscoverySource, DiscoveryStrategy,
+        };
+        pub use crate::runtime::discovery::dynamic::{Candidate, SelectionPolicy};
+
+        thread_local!(static LIBRARY: RefCell<Option<Arc<SharedLibrary>>> = RefCell::new(None));
+
+        thread_local!(
+            /// The number of `load` calls in this thread which haven't been matched by `unload`
+            /// calls yet (see `load`).
+            static LOADS: Cell<usize> = const { Cell::new(0) }
+        );
+
+        thread_local!(
+            /// The library instances in use in this thread (and the number of `load` calls which
+            /// haven't been matched by `unload` calls yet for each) which were replaced by another
+            /// library instance loaded by a nested `load_*` call (see `store`).
+            static SHADOWED: RefCell<Vec<(Arc<SharedLibrary>, usize)>> = const { RefCell::new(vec![]) }
+        );
+
+        /// The library instance used by threads which don't have a library instance stored in TLS.
+        static GLOBAL_LIBRARY: RwLock<Option<Arc<SharedLibrary>>> = RwLock::new(None);
+
+        /// The `libclang` shared libraries which are in use in any thread by their canonical paths
+        /// (see `open_shared`).
+        static REGISTRY: std::sync::Mutex<Vec<(PathBuf, std::sync::Weak<SharedLibrary>)>> =
+            std::sync::Mutex::new(vec![]);
+
+        /// The environment consulted by the last search for a `libclang` shared library (see
+        /// `rediscover`).
+        static SNAPSHOT: std::sync::Mutex<Option<Snapshot>> = std::sync::Mutex::new(None);
+
+        /// The function which provides the path to a `libclang` shared library before one is
+        /// searched for (see `set_discovery_hook`).
+        static DISCOVERY_HOOK: RwLock<Option<fn() -> Option<PathBuf>>> = RwLock::new(None);
+
+        /// The flags `libclang` shared libraries are loaded with (see `set_dll_search_flags`).
+        #[cfg(windows)]
+        static DLL_SEARCH_FLAGS: RwLock<Option<u32>> = RwLock::new(None);
+
+        /// Returns whether a `libclang` shared library is loaded on this thread (either in TLS or
+        /// process-wide).
+        pub fn is_loaded() -> bool {
+            LIBRARY.with(|l| l.borrow().is_some()) || get_global_library().is_some()
+        }
+
+        fn with_library<T, F>(f: F) -> Option<T> where F: FnOnce(&SharedLibrary) -> T {
+            LIBRARY.with(|l| {
+                match l.borrow().as_ref() {
+                    Some(library) => Some(f(&library)),
+                    _ => get_global_library().map(|l| f(&l)),
+                }
+            })
+        }
+
+        /// The names of the functions which have been called and the `cfg` attributes which gate
+        /// them in the order they were first called (see `used_functions`).
+        #[cfg(feature = "usage-audit")]
+        static USED_FUNCTIONS: std::sync::Mutex<Vec<(&'static str, Option<&'static str>)>> =
+            std::sync::Mutex::new(vec![]);
+
+        /// Records that the function with the supplied name has been called.
+        #[cfg(feature = "usage-audit")]
+        fn record_usage(name: &'static str, cfg: Option<&'static str>) {
+            let mut used = USED_FUNCTIONS.lock().unwrap_or_else(|e| e.into_inner());
+            if !used.iter().any(|(n, _)| *n == name) {
+                used.push((name, cfg));
+            }
+        }
+
+        /// Returns the names of the functions which have been called in this process (in the
+        /// order they were first called).
+        ///
+        /// Calls made through the functions of a specific shared library (see
+        /// `SharedLibrary::call`) and through the `call` functions in the modules with the same
+        /// names as the functions are included.
+        #[cfg(feature = "usage-audit")]
+        pub fn used_functions() -> Vec<&'static str> {
+            let used = USED_FUNCTIONS.lock().unwrap_or_else(|e| e.into_inner());
+            used.iter().map(|(n, _)| *n).collect()
+        }
+
+        /// Returns the names of the functions which have been called in this process grouped by
+        /// the `cfg` attribute which gates them (e.g., `feature = "clang_16_0"`), or `None` for
+        /// the functions which aren't gated.
+        ///
+        /// Groups are in the order the first of their functions was called.
+        #[cfg(feature = "usage-audit")]
+        pub fn used_functions_by_cfg() -> Vec<(Option<&'static str>, Vec<&'static str>)> {
+            let mut groups: Vec<(Option<&'static str>, Vec<&'static str>)> = vec![];
+            let used = USED_FUNCTIONS.lock().unwrap_or_else(|e| e.into_inner());
+            for (name, cfg) in used.iter() {
+                match groups.iter_mut().find(|(c, _)| c == cfg) {
+                    Some((_, names)) => names.push(name),
+                    None => groups.push((*cfg, vec![name])),
+                }
+            }
+
+            groups
+        }
+
+        /// Returns the `clang_*` Cargo feature for the oldest version of `libclang` which
+        /// provides all of the functions which have been called in this process (e.g.,
+        /// `clang_16_0`), or `None` if none of these functions are gated by such a feature.
+        ///
+        /// This is the minimum version of `libclang` actually required by the functions used
+        /// (e.g., during a test suite) rather than the version implied by the enabled Cargo
+        /// features.
+        #[cfg(feature = "usage-audit")]
+        pub fn required_feature() -> Option<&'static str> {
+            let version = |f: &str| -> Vec<u32> {
+                f.split('_').skip(1).map(|c| c.parse().unwrap_or(0)).collect()
+            };
+
+            let used = USED_FUNCTIONS.lock().unwrap_or_else(|e| e.into_inner());
+            used.iter()
+                .filter_map(|(_, c)| c.and_then(|c| c.split('"').nth(1)))
+                .filter(|f| f.starts_with("clang_"))
+                .max_by_key(|f| version(f))
+        }
+
+        $(
+            #[cfg_attr(clippy, allow(clippy::missing_safety_doc))]
+            #[cfg_attr(clippy, allow(clippy::too_many_arguments))]
+            $(#[doc=$doc])* $(#[cfg($cfg)])*
+            $(#[cfg_attr($acfg, $attr)])* $(#[deprecated = $deprecated])?
+            pub unsafe fn $name($($pname: $pty), *) $(-> $ret)* {
+                link!(@USED: $name $(#[cfg($cfg)])*);
+                let f = with_library(|library| {
+                    lazy::$name(library).unwrap_or_else(|| unsupported(stringify!($name), library))
+                });
+                #[cfg(feature = "hybrid")]
+                let f = f.or(Some(linked::$name as _));
+                let f = f.expect("a `libclang` shared library is not loaded on this thread or process-wide");
+                unsafe { f($($pname), *) }
+            }
+
+            $(#[doc=$doc])* $(#[cfg($cfg)])*
+            pub mod $name {
+                use super::*;
+
+                pub fn is_loaded() -> bool {
+                    super::with_library(|l| super::lazy::$name(l).is_some())
+                        .unwrap_or(cfg!(feature = "hybrid"))
+                }
+
+                /// Calls this function and returns the result or returns an error (instead of
+                /// panicking) if this function is not available.
+                ///
+                /// # Safety
+                ///
+                /// See the function of the same name.
+                #[cfg_attr(clippy, allow(clippy::too_many_arguments))]
+                pub unsafe fn call(
+                    $($pname: $pty), *
+                ) -> Result<link!(@RET: $($ret)*), MissingFunction> {
+                    link!(@USED: $name $(#[cfg($cfg)])*);
+                    let name = stringify!($name);
+                    let f = super::with_library(|l| {
+                        super::lazy::$name(l)
+                            .ok_or_else(|| MissingFunction::NotSupported { name, version: l.version() })
+                    });
+                    #[cfg(feature = "hybrid")]
+                    let f = f.or(Some(Ok(super::linked::$name as _)));
+                    let f = f.unwrap_or(Err(MissingFunction::NotLoaded { name }))?;
+                    Ok(unsafe { f($($pname), *) })
+                }
+            }
+        )+
+
+        export!($($(#[cfg($cfg)])* pub fn $name($($pname: $pty), *) $(-> $ret)*;)+);
+
+        /// With the `hybrid` Cargo feature, the functions of the `libclang` library linked to at
+        /// compile time which are called when no `libclang` shared library is loaded.
+        #[cfg(feature = "hybrid")]
+        mod linked {
+            use super::*;
+
+            unsafe extern "C" {
+                $(
+                    $(#[cfg($cfg)])*
+                    pub fn $name($($pname: $pty), *) $(-> $ret)*;
+                )+
+            }
+        }
+
+        /// Panics because the function with the supplied name is not supported by the supplied
+        /// `libclang` shared library.
+        fn unsupported(name: &str, library: &SharedLibrary) -> ! {
+            panic!(
+                r#"
+A `libclang` function was called that is not supported by the loaded `libclang` instance.
+
+    called function = `{0}`
+    required `libclang` version = {1}
+    loaded `libclang` instance = {2}
+
+The minimum `libclang` requirement for this particular function can be found here:
+https://docs.rs/clang-sys/latest/clang_sys/{0}/index.html
+
+Instructions for installing `libclang` can be found here:
+https://rust-lang.github.io/rust-bindgen/requirements.html
+"#,
+                name,
+                FUNCTION_VERSIONS
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| format!("{}", v))
+                    .unwrap_or_else(|| "unknown".into()),
+                library
+                    .version()
+                    .map(|v| format!("{}", v))
+                    .unwrap_or_else(|| "unsupported version".into()),
+            );
+        }
+
+        /// The functions in a specific `libclang` shared library (see `SharedLibrary::call`).
+        #[derive(Copy, Clone, Debug)]
+        pub struct Calls<'a>(&'a SharedLibrary);
+
+        impl Calls<'_> {
+            $(
+                #[cfg_attr(clippy, allow(clippy::missing_safety_doc))]
+                #[cfg_attr(clippy, allow(clippy::too_many_arguments))]
+                $(#[doc=$doc])* $(#[cfg($cfg)])*
+                $(#[cfg_attr($acfg, $attr)])* $(#[deprecated = $deprecated])?
+                pub unsafe fn $name(self, $($pname: $pty), *) $(-> $ret)* {
+                    link!(@USED: $name $(#[cfg($cfg)])*);
+                    let f = lazy::$name(self.0)
+                        .unwrap_or_else(|| unsupported(stringify!($name), self.0));
+                    unsafe { f($($pname), *) }
+                }
+            )+
+        }
+
+        impl SharedLibrary {
+            /// Returns the functions in this `libclang` shared library which can be called
+            /// without making it the shared library used by the current thread.
+            ///
+            /// This allows using multiple `libclang` shared libraries (e.g., of different
+            /// versions) in the same thread, for example:
+            ///
+            /// ```no_run
+            /// # use clang_sys::*;
+            /// let old = SharedLibrary::open("/usr/lib/llvm-16/lib/libclang.so").unwrap();
+            /// let new = SharedLibrary::open("/usr/lib/llvm-19/lib/libclang.so").unwrap();
+            /// unsafe {
+            ///     let index = new.call().clang_createIndex(0, 0);
+            ///     new.call().clang_disposeIndex(index);
+            /// }
+            /// ```
+            ///
+            /// The values returned by the functions in a shared library (e.g., a `CXIndex`) must
+            /// only be used with the functions in the same shared library.
+            pub fn call(&self) -> Calls<'_> {
+                Calls(self)
+            }
+        }
+
+        mod load {
+            $(link!(@LOAD: $(#[cfg($cfg)])* fn $name($($pname: $pty), *) $(-> $ret)*);)+
+        }
+
+        mod lazy {
+            use super::*;
+
+            $(
+                /// Returns the function loaded from the supplied shared library, resolving it
+                /// first if the shared library was opened lazily.
+                $(#[cfg($cfg)])*
+                pub fn $name(
+                    library: &SharedLibrary,
+                ) -> Option<unsafe extern "C" fn($($pname: $pty), *) $(-> $ret)*> {
+                    let lazy = match &library.lazy {
+                        Some(lazy) => lazy,
+                        None => return library.functions.$name,
+                    };
+
+                    *lazy.$name.get_or_init(|| unsafe { library.get_symbol(stringify!($name)) })
+                }
+            )+
+        }
+
+        impl SharedLibrary {
+            /// Opens the `libclang` shared library at the supplied path and returns the library
+            /// instance.
+            ///
+            /// Unlike `load_manually`, this function doesn't search for a `libclang` shared library
+            /// so it can be used to open a specific shared library (e.g., one bundled with an
+            /// application).
+            ///
+            /// # Failures
+            ///
+            /// * the `libclang` shared library could not be opened (`LoadError::OpenFailed` or, for
+            ///   common causes, `LoadError::MissingDependency`, `LoadError::RuntimeTooOld`,
+            ///   `LoadError::WrongArchitecture`, or `LoadError::UndefinedSymbol`)
+            /// * the `libclang` shared library is too old (`LoadError::VersionTooOld`)
+            /// * the `libclang` shared library is a stub or doesn't work and the `verify-on-load`
+            ///   Cargo feature is enabled (`LoadError::VerificationFailed`)
+            /// * the SHA-256 digest of the `libclang` shared library isn't in the allowlist
+            ///   provided by the `CLANG_SYS_LIBCLANG_SHA256` environment variable
+            ///   (`LoadError::IntegrityMismatch`)
+            pub fn open(path: impl AsRef<Path>) -> Result<SharedLibrary, LoadError> {
+                Self::open_with(path.as_ref(), false, false)
+            }
+
+            /// Opens a `libclang` shared library from its contents (e.g., embedded in an
+            /// executable with `include_bytes!`) and returns the library instance.
+            ///
+            /// On Linux, the contents are written to an anonymous in-memory file (created with
+            /// `memfd_create`) which is opened without touching the filesystem. On other platforms
+            /// (or if `memfd_create` isn't supported), the contents are written to a temporary file
+            /// which is removed after it is opened (except on Windows, where a shared library can't
+            /// be removed while it is loaded so the temporary file is left in the temporary
+            /// directory). The path of the library instance (see `path`) is the path to the
+            /// in-memory file (e.g., `/proc/self/fd/3`, which is never closed) or to the removed
+            /// temporary file.
+            ///
+            /// The dependencies of the shared library (e.g., an LLVM shared library) are still
+            /// loaded from the filesystem.
+            ///
+            /// # Failures
+            ///
+            /// * the contents could not be written to a file (`LoadError::WriteFailed`)
+            /// * see `open`
+            #[cfg(feature = "in-memory")]
+            pub fn from_bytes(bytes: &[u8]) -> Result<SharedLibrary, LoadError> {
+                #[cfg(target_os = "linux")]
+                if let Some(result) = write_in_memory(bytes) {
+                    return Self::open_with(&result?, false, false);
+                }
+
+                let path = write_temporary(bytes)?;
+                let result = Self::open_with(&path, false, false);
+                if !cfg!(windows) || result.is_err() {
+                    let _ = std::fs::remove_file(&path);
+                    let _ = path.parent().map(std::fs::remove_dir);
+                }
+                result
+            }
+
+            /// Opens the `libclang` shared library at the supplied path like `open` but doesn't
+            /// load any functions until they are first called (or checked with `is_loaded`).
+            ///
+            /// Loading all of the functions in a shared library takes a measurable amount of time
+            /// which is wasted by applications which only call a few of them. Each function in a
+            /// shared library opened lazily is instead resolved the first time it is used and
+            /// cached for subsequent uses. The `functions` of such a shared library are never
+            /// populated.
+            ///
+            /// # Failures
+            ///
+            /// See `open`.
+            pub fn open_lazy(path: impl AsRef<Path>) -> Result<SharedLibrary, LoadError> {
+                Self::open_with(path.as_ref(), true, false)
+            }
+
+            /// Opens the `libclang` shared library at the supplied path like `open` but isolates
+            /// its symbols from the symbols of the shared libraries already loaded in the current
+            /// process.
+            ///
+            /// Processes which already contain a different version of LLVM (e.g., one linked into
+            /// the executable or loaded by another plugin) can crash when `libclang` (or the LLVM
+            /// shared library it depends on) binds to the symbols of that version instead of its
+            /// own. The shared library is instead loaded into a new link-map namespace with
+            /// `dlmopen(LM_ID_NEWLM, ...)` or, if that fails (e.g., because `glibc` has run out of
+            /// namespaces), with `RTLD_DEEPBIND` so that it prefers its own symbols.
+            ///
+            /// The shared libraries listed in the `LIBCLANG_PRELOAD` environment variable are
+            /// loaded into the default namespace so the functions which can't be found in
+            /// `libclang` are still loaded from them, but `libclang` itself can't bind to them.
+            ///
+            /// # Failures
+            ///
+            /// See `open`.
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            pub fn open_isolated(path: impl AsRef<Path>) -> Result<SharedLibrary, LoadError> {
+                Self::open_with(path.as_ref(), false, true)
+            }
+
+            fn open_with(path: &Path, lazy: bool, isolated: bool) -> Result<SharedLibrary, LoadError> {
+                let path = path.to_path_buf();
+
+                // Shared libraries inside app bundles or toolchains are often found through
+                // symlinks and depend on other shared libraries in the bundle through `@rpath`
+                // install names which can't be resolved without the bundle's executable.
+                #[cfg(target_os = "macos")]
+                let path = path.canonicalize().unwrap_or(path);
+
+                if let Ok(list) = discovery::common::env::var("CLANG_SYS_LIBCLANG_SHA256") {
+                    check_integrity(&path, &sha256::split_digests(&list))?;
+                }
+
+                // Some distributions split the `libclang` API across multiple shared libraries
+                // which must be loaded first (see `load_preloaded_dependencies`).
+                #[allow(unused_mut)]
+                let mut dependencies = load_preloaded_dependencies(&path)?;
+                #[cfg(all(unix, not(target_os = "macos")))]
+                if !isolated {
+                    dependencies.extend(load_llvm_dependencies(&path));
+                }
+                #[cfg(target_os = "macos")]
+                dependencies.extend(load_rpath_dependencies(&path));
+
+                // A corrupted or mismatched shared library can crash or hang the process when it
+                // is opened or its version is determined (see `probe_timeout`).
+                if let Some(timeout) = probe_timeout()
+                    && let Some(message) = probe(&path, timeout)
+                {
+                    return Err(LoadError::VerificationFailed { path, message });
+                }
+
+                // `LoadLibraryExW` doesn't support paths longer than `MAX_PATH` without an
+                // extended-length prefix (e.g., in the deeply nested directories of CI agents).
+                #[cfg(windows)]
+                let path = match path.to_str() {
+                    Some(string) => PathBuf::from(discovery::common::normalize_windows_path(string)),
+                    None => path,
+                };
+
+                unsafe {
+                    #[cfg(feature = "log")]
+                    log::debug!(target: "clang_sys", "opening {} (isolated: {})", path.display(), isolated);
+                    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+                    let library = if isolated {
+                        open_isolated(&path)
+                    } else {
+                        libloading::Library::new(&path)
+                    };
+                    #[cfg(windows)]
+                    let library = {
+                        let _ = isolated;
+                        open_windows(&path)
+                    };
+                    #[cfg(not(any(all(target_os = "linux", target_env = "gnu"), windows)))]
+                    let library = {
+                        let _ = isolated;
+                        libloading::Library::new(&path)
+                    };
+
+                    let library = match library {
+                        Ok(library) => library,
+                        Err(source) => return Err(LoadError::open_failed(path, source)),
+                    };
+
+                    let mut library = SharedLibrary::new(library, path);
+                    library.dependencies = dependencies;
+                    library.check_loaded(lazy)
+                }
+            }
+
+            /// Checks a `libclang` shared library which has just been opened and loads its
+            /// functions (unless they are loaded lazily).
+            fn check_loaded(mut self, lazy: bool) -> Result<SharedLibrary, LoadError> {
+                // Determining the version of a stub would call one of its functions.
+                if self.is_stub() {
+                    let (path, message) = (self.path, STUB_MESSAGE.into());
+                    return Err(LoadError::VerificationFailed { path, message });
+                }
+
+                if self.version().is_none() {
+                    let (path, minimum) = (self.path, Version::V3_5);
+                    return Err(LoadError::VersionTooOld { path, version: None, minimum });
+                }
+
+                if lazy {
+                    self.lazy = Some(Box::default());
+                } else {
+                    $(load::$name(&mut self);)+
+                }
+
+                if cfg!(feature = "verify-on-load") {
+                    self.verify()?;
+                }
+
+                #[cfg(feature = "log")]
+                log::debug!(
+                    target: "clang_sys",
+                    "loaded {} (version: {:?}, lazy: {})",
+                    self.path.display(),
+                    self.version(),
+                    lazy,
+                );
+                Ok(self)
+            }
+        }
+
+        /// Returns the maximum time a `libclang` shared library may take to be opened and have its
+        /// version determined in a child process before it is opened in the current process, if
+        /// shared libraries are probed in a child process at all.
+        ///
+        /// This is provided by the `CLANG_SYS_PROBE_TIMEOUT` environment variable (in seconds).
+        /// Shared libraries aren't probed if it isn't set or is set to `0`.
+        fn probe_timeout() -> Option<std::time::Duration> {
+            let timeout = discovery::common::env::var("CLANG_SYS_PROBE_TIMEOUT").ok()?;
+            match timeout.trim().parse::<f64>() {
+                Ok(timeout) if timeout > 0.0 => std::time::Duration::try_from_secs_f64(timeout).ok(),
+                _ => None,
+            }
+        }
+
+        /// Opens a `libclang` shared library and determines its version in a child process and
+        /// returns why the shared library doesn't work if the child process crashes or doesn't
+        /// finish within the supplied timeout.
+        ///
+        /// The child process executes the build script of this crate which was recorded when this
+        /// crate was built (see `build.rs`) with the arguments it accepts for probing `libclang`
+        /// shared libraries (see `build/common.rs`). Other failures (e.g., the shared library could
+        /// not be opened or the build script no longer exists) are left to be reported when the
+        /// shared library is opened in the current process.
+        fn probe(path: &Path, timeout: std::time::Duration) -> Option<String> {
+            use std::process::{Command, Stdio};
+            use std::time::{Duration, Instant};
+
+            let Some(helper) = option_env!("CLANG_SYS_PROBE_HELPER").map(Path::new) else {
+                #[cfg(feature = "log")]
+                log::warn!(target: "clang_sys", "not probing {} (the build script can't be executed on this platform)", path.display());
+                return None;
+            };
+
+            let mut child = match Command::new(helper)
+                .arg(discovery::common::PROBE_ARGUMENT)
+                .arg(path)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+            {
+                Ok(child) => child,
+                Err(_error) => {
+                    #[cfg(feature = "log")]
+                    log::warn!(target: "clang_sys", "not probing {} ({}: {})", path.display(), helper.display(), _error);
+                    return None;
+                }
+            };
+
+            #[cfg(feature = "log")]
+            log::debug!(target: "clang_sys", "probing {} (pid: {})", path.display(), child.id());
+
+            let start = Instant::now();
+            let status = loop {
+                match child.try_wait() {
+                    Ok(Some(status)) => break status,
+                    Ok(None) if start.elapsed() < timeout => std::thread::sleep(Duration::from_millis(5)),
+                    Ok(None) => {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Some(format!(
+                            "determining its version didn't finish within {:?} (see the \
+                            `CLANG_SYS_PROBE_TIMEOUT` environment variable)",
+                            timeout,
+                        ));
+                    }
+                    Err(_) => return None,
+                }
+            };
+
+            (!status.success()).then(|| {
+                format!("opening it or determining its version crashed a child process ({})", status)
+            })
+        }
+
+        /// Writes the contents of a shared library to an anonymous in-memory file and returns the
+        /// path to the file, or returns `None` if `memfd_create` isn't supported.
+        ///
+        /// The file is never closed. The dynamic linker reuses an already loaded shared library
+        /// with the same path (and `libclang` shared libraries usually can't be unloaded), so the
+        /// file descriptor (and thus the path) must never be reused for another file.
+        #[cfg(all(feature = "in-memory", target_os = "linux"))]
+        fn write_in_memory(bytes: &[u8]) -> Option<Result<PathBuf, LoadError>> {
+            use std::io::Write;
+            use std::os::fd::FromRawFd;
+
+            let fd = unsafe { libc::memfd_create(c"libclang".as_ptr(), libc::MFD_CLOEXEC) };
+            if fd < 0 {
+                return None;
+            }
+
+            // SAFETY: The file descriptor was just created and isn't owned by anything else.
+            let mut file = unsafe { std::fs::File::from_raw_fd(fd) };
+            let path = PathBuf::from(format!("/proc/self/fd/{}", fd));
+            Some(match file.write_all(bytes) {
+                Ok(()) => {
+                    std::mem::forget(file);
+                    Ok(path)
+                }
+                Err(source) => Err(LoadError::WriteFailed { path, source }),
+            })
+        }
+
+        /// Writes the contents of a shared library to a new file in a new directory in the
+        /// temporary directory and returns the path to the file.
+        ///
+        /// The directory is only accessible by the current user (on Unix systems) and neither it
+        /// nor the file may already exist, so another user can't replace the shared library
+        /// before it is opened (e.g., in a shared `/tmp`).
+        #[cfg(feature = "in-memory")]
+        fn write_temporary(bytes: &[u8]) -> Result<PathBuf, LoadError> {
+            use std::io::Write;
+            use std::sync::atomic::{AtomicUsize, Ordering};
+
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+            let filename = if cfg!(windows) {
+                "libclang.dll"
+            } else if cfg!(target_os = "macos") {
+                "libclang.dylib"
+            } else {
+                "libclang.so"
+            };
+
+            #[cfg_attr(not(unix), allow(unused_mut))]
+            let mut builder = std::fs::DirBuilder::new();
+            #[cfg(unix)]
+            std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+            let directory = loop {
+                let count = COUNT.fetch_add(1, Ordering::Relaxed);
+                let directory = format!("clang-sys-{}-{}", std::process::id(), count);
+                let directory = std::env::temp_dir().join(directory);
+                match builder.create(&directory) {
+                    Ok(()) => break directory,
+                    // Another process (or user) created a directory with the same name.
+                    Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                    Err(source) => return Err(LoadError::WriteFailed { path: directory, source }),
+                }
+            };
+
+            let path = directory.join(filename);
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(bytes))
+                .map_err(|source| LoadError::WriteFailed { path: path.clone(), source })?;
+            Ok(path)
+        }
+
+        /// Opens the shared library at the supplied path in a new link-map namespace or, if that
+        /// fails, with `RTLD_DEEPBIND` (see `SharedLibrary::open_isolated`).
+        #[cfg(all(target_os = "linux", target_env = "gnu"))]
+        unsafe fn open_isolated(path: &Path) -> Result<libloading::Library, libloading::Error> {
+            use std::os::unix::ffi::OsStrExt;
+            use libloading::os::unix::{Library, RTLD_LOCAL, RTLD_NOW};
+
+            if let Ok(filename) = std::ffi::CString::new(path.as_os_str().as_bytes()) {
+                // The binding is only relaxed if requested and a symbol isn't defined (see
+                // `open_relaxed`).
+                let bindings = if relaxed_binding() {
+                    &[libc::RTLD_NOW, libc::RTLD_LAZY][..]
+                } else {
+                    &[libc::RTLD_NOW][..]
+                };
+                for &binding in bindings {
+                    let flags = binding | libc::RTLD_LOCAL;
+                    let handle = unsafe { libc::dlmopen(libc::LM_ID_NEWLM, filename.as_ptr(), flags) };
+                    if !handle.is_null() {
+                        return Ok(unsafe { Library::from_raw(handle) }.into());
+                    }
+
+                    let error = unsafe { libc::dlerror() };
+                    if error.is_null() || !is_undefined_symbol(path, &unsafe { std::ffi::CStr::from_ptr(error) }.to_string_lossy()) {
+                        break;
+                    }
+                }
+            }
+
+            unsafe { open_relaxed(path, RTLD_NOW | RTLD_LOCAL | libc::RTLD_DEEPBIND) }
+        }
+
+        /// Returns whether the supplied error message for the shared library at the supplied path
+        /// reports that a symbol isn't defined.
+        #[cfg(unix)]
+        fn is_undefined_symbol(path: &Path, message: &str) -> bool {
+            let failure = discovery::dynamic::parse_open_error(&path.to_string_lossy(), message);
+            matches!(failure, Some(discovery::dynamic::OpenFailure::UndefinedSymbol(_)))
+        }
+
+        /// Returns whether shared libraries which can't be opened with `RTLD_NOW` because a symbol
+        /// isn't defined are opened again with `RTLD_LAZY` (see `LoadOptions::relaxed`).
+        #[cfg(unix)]
+        fn relaxed_binding() -> bool {
+            discovery::common::env::var_os("CLANG_SYS_RELAXED_BINDING").is_some()
+        }
+
+        /// Opens the shared library at the supplied path with the supplied flags (which include
+        /// `RTLD_NOW`) or, if that fails because a symbol isn't defined and relaxed binding was
+        /// requested (see `relaxed_binding`), with `RTLD_LAZY` instead.
+        ///
+        /// Shared libraries can reference symbols which aren't defined by their dependencies but
+        /// which are only used by functions which are never called (e.g., functions of `libclang`
+        /// which require a newer version of LLVM), so they may still work with lazy binding. The
+        /// missing symbols are only reported when such a function is called (which terminates the
+        /// process), so this isn't done by default.
+        #[cfg(unix)]
+        unsafe fn open_relaxed(
+            path: &Path,
+            flags: std::os::raw::c_int,
+        ) -> Result<libloading::Library, libloading::Error> {
+            use libloading::os::unix::{Library, RTLD_LAZY, RTLD_NOW};
+
+            let result = match unsafe { Library::open(Some(path), flags) } {
+                Err(error) if relaxed_binding() && is_undefined_symbol(path, &error_message(&error)) => {
+                    #[cfg(feature = "log")]
+                    log::debug!(target: "clang_sys", "retrying {} with lazy binding ({})", path.display(), error);
+                    unsafe { Library::open(Some(path), (flags & !RTLD_NOW) | RTLD_LAZY) }
+                }
+                result => result,
+            };
+
+            result.map(libloading::Library::from)
+        }
+
+        /// Opens the shared library at the supplied path with the flags set with
+        /// `set_dll_search_flags` (if any).
+        #[cfg(windows)]
+        unsafe fn open_windows(path: &Path) -> Result<libloading::Library, libloading::Error> {
+            use libloading::os::windows::Library;
+
+            let flags = *DLL_SEARCH_FLAGS.read().unwrap_or_else(|e| e.into_inner());
+            match flags {
+                Some(flags) => unsafe { Library::load_with_flags(path, flags) }.map(Into::into),
+                None => unsafe { libloading::Library::new(path) },
+            }
+        }
+
+        /// Sets the flags `libclang` shared libraries (and the shared libraries listed in the
+        /// `LIBCLANG_PRELOAD` environment variable) are loaded with by `LoadLibraryExW` and
+        /// returns the previous flags.
+        ///
+        /// By default (or if `None` is supplied), shared libraries are loaded with the standard
+        /// search order which doesn't include the directory containing the shared library being
+        /// loaded, so its dependencies (e.g., the LLVM shared library) often can't be found
+        /// unless they are in a directory in the `PATH` environment variable. Loading shared
+        /// libraries with `LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR | LOAD_LIBRARY_SEARCH_DEFAULT_DIRS`
+        /// (see `libloading::os::windows`) instead searches the directory containing the shared
+        /// library being loaded, the application directory, `System32`, and the directories
+        /// added with `add_dll_directory` (but not the directories in the `PATH` environment
+        /// variable).
+        #[cfg(windows)]
+        pub fn set_dll_search_flags(flags: Option<u32>) -> Option<u32> {
+            let mut global = DLL_SEARCH_FLAGS.write().unwrap_or_else(|e| e.into_inner());
+            mem::replace(&mut *global, flags)
+        }
+
+        /// Adds a directory to the directories searched for the dependencies of `libclang`
+        /// shared libraries (with `AddDllDirectory`).
+        ///
+        /// The directories added with this function are only searched when loading shared
+        /// libraries with `LOAD_LIBRARY_SEARCH_USER_DIRS` or `LOAD_LIBRARY_SEARCH_DEFAULT_DIRS`
+        /// so, if no flags have been set with `set_dll_search_flags`, this also sets them to
+        /// `LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR | LOAD_LIBRARY_SEARCH_DEFAULT_DIRS`. The directory
+        /// is added for the entire process and must be an absolute path.
+        ///
+        /// # Failures
+        ///
+        /// * the directory could not be added (e.g., because it isn't an absolute path)
+        #[cfg(windows)]
+        pub fn add_dll_directory(directory: impl AsRef<Path>) -> std::io::Result<()> {
+            use std::os::windows::ffi::OsStrExt;
+            use libloading::os::windows::{
+                LOAD_LIBRARY_SEARCH_DEFAULT_DIRS, LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR,
+            };
+
+            #[link(name = "kernel32")]
+            unsafe extern "system" {
+                fn AddDllDirectory(directory: *const u16) -> *mut std::ffi::c_void;
+            }
+
+            let directory = directory
+                .as_ref()
+                .as_os_str()
+                .encode_wide()
+                .chain(Some(0))
+                .collect::<Vec<_>>();
+            if unsafe { AddDllDirectory(directory.as_ptr()) }.is_null() {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            let mut global = DLL_SEARCH_FLAGS.write().unwrap_or_else(|e| e.into_inner());
+            global.get_or_insert(LOAD_LIBRARY_SEARCH_DLL_LOAD_DIR | LOAD_LIBRARY_SEARCH_DEFAULT_DIRS);
+            Ok(())
+        }
+
+        /// Loads a `libclang` shared library and returns the library instance.
+        ///
+        /// This function does not attempt to load any functions from the shared library. The caller
+        /// is responsible for loading the functions they require.
+        ///
+        /// # Failures
+        ///
+        /// * a `libclang` shared library could not be found (`LoadError::NotFound`)
+        /// * the `libclang` shared library could not be opened (`LoadError::OpenFailed` or, for
+        ///   common causes, `LoadError::MissingDependency`, `LoadError::RuntimeTooOld`,
+        ///   `LoadError::WrongArchitecture`, or `LoadError::UndefinedSymbol`)
+        /// * the `libclang` shared library is too old (`LoadError::VersionTooOld`)
+        /// * the `libclang` shared library is a stub or doesn't work and the `verify-on-load`
+        ///   Cargo feature is enabled (`LoadError::VerificationFailed`)
+        /// * the SHA-256 digest of the `libclang` shared library isn't in the allowlist
+        ///   provided by the `CLANG_SYS_LIBCLANG_SHA256` environment variable
+        ///   (`LoadError::IntegrityMismatch`)
+        /// * none of the `libclang` shared libraries found could be opened
+        ///   (`LoadError::AllFailed`)
+        ///
+        /// If the most preferred `libclang` shared library can't be opened, the other `libclang`
+        /// shared libraries found are tried in order of preference (unless the shared library was
+        /// provided by a discovery hook). The error for the most preferred shared library is
+        /// returned if no other shared libraries were found.
+        pub fn load_manually() -> Result<SharedLibrary, LoadError> {
+            open_first(find_all()?, |p| SharedLibrary::open(p))
+        }
+
+        /// Finds a `libclang` shared library and returns the path to it.
+        fn find() -> Result<PathBuf, LoadError> {
+            Ok(find_all()?.next().expect("a path"))
+        }
+
+        /// Finds the `libclang` shared libraries to try to open in order of preference (see
+        /// `open_first`) and returns the paths to them.
+        ///
+        /// The first path is the path to the shared library provided by the discovery hook or
+        /// found by the search. The other shared libraries found by the search are only collected
+        /// (by searching again) if the first shared library can't be opened.
+        fn find_all() -> Result<Box<dyn Iterator<Item = PathBuf>>, LoadError> {
+            let hook = *DISCOVERY_HOOK.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(path) = hook.and_then(|h| h()) {
+                #[cfg(feature = "log")]
+                log::debug!(target: "clang_sys", "using {} provided by the discovery hook", path.display());
+                return Ok(Box::new(std::iter::once(path)));
+            }
+
+            let result = discovery::dynamic::find(true);
+            let searched = discovery::common::searched_directories();
+            Snapshot::record(searched.clone());
+            let (directory, filename) = result.map_err(|message| LoadError::NotFound { searched, message })?;
+
+            let path = directory.join(filename);
+            let first = path.clone();
+            let others = std::iter::once_with(move || {
+                let candidates = discovery::dynamic::find_candidates(&[], &[], None).unwrap_or_default();
+                candidates.into_iter().map(|c| c.path()).filter(|p| *p != path).collect::<Vec<_>>()
+            });
+            Ok(Box::new(std::iter::once(first).chain(others.flatten())))
+        }
+
+        /// Opens the first of the supplied `libclang` shared libraries which can be opened with
+        /// the supplied function and returns the result.
+        ///
+        /// Shared libraries which the supplied function rejects for any reason (including
+        /// `LoadError::VersionTooOld`) are skipped in favor of the next shared library.
+        ///
+        /// If none of the shared libraries can be opened, the error for the only shared library
+        /// or the errors for all of the shared libraries (`LoadError::AllFailed`) are returned.
+        fn open_first<T>(
+            paths: impl IntoIterator<Item = PathBuf>,
+            open: impl Fn(&Path) -> Result<T, LoadError>,
+        ) -> Result<T, LoadError> {
+            let mut failures = vec![];
+            for path in paths {
+                match open(&path) {
+                    Ok(library) => return Ok(library),
+                    Err(error) => {
+                        #[cfg(feature = "log")]
+                        log::warn!(target: "clang_sys", "couldn't open {} ({})", path.display(), error);
+                        failures.push(error);
+                    }
+                }
+            }
+
+            if failures.len() == 1 {
+                Err(failures.remove(0))
+            } else {
+                Err(LoadError::AllFailed { failures })
+            }
+        }
+
+        /// The environment consulted by a search for a `libclang` shared library (see
+        /// `rediscover`).
+        #[derive(Debug, PartialEq, Eq)]
+        struct Snapshot {
+            variables: Vec<Option<std::ffi::OsString>>,
+            directories: Vec<(PathBuf, Option<std::time::SystemTime>)>,
+        }
+
+        impl Snapshot {
+            /// Takes a snapshot of the environment variables which affect the search (including
+            /// the overrides in effect, see `LoadOptions::env_override`) and of the modification
+            /// times of the supplied searched directories.
+            fn take(directories: Vec<PathBuf>) -> Self {
+                let variables = discovery::common::RECORDED_VARIABLES
+                    .iter()
+                    .map(discovery::common::env::var_os)
+                    .collect();
+                let directories = directories.into_iter().map(|d| {
+                    let modified = std::fs::metadata(&d).and_then(|m| m.modified()).ok();
+                    (d, modified)
+                });
+                Snapshot { variables, directories: directories.collect() }
+            }
+
+            /// Takes a snapshot of the environment consulted by a search which has just been
+            /// performed and which searched the supplied directories (see `rediscover`).
+            fn record(directories: Vec<PathBuf>) {
+                *SNAPSHOT.lock().unwrap_or_else(|e| e.into_inner()) = Some(Snapshot::take(directories));
+            }
+
+            /// Returns whether the environment has changed since this snapshot was taken.
+            fn is_stale(&self) -> bool {
+                let directories = self.directories.iter().map(|(d, _)| d.clone()).collect();
+                *self != Snapshot::take(directories)
+            }
+        }
+
+        /// Searches for a `libclang` shared library again if the environment consulted by the
+        /// last search has changed and returns the path to the shared library found if it isn't
+        /// the one in use on this thread (see `get_library`).
+        ///
+        /// The environment consulted by a search consists of the environment variables which
+        /// affect the search (e.g., `LIBCLANG_PATH` and `LD_LIBRARY_PATH`) and the modification
+        /// times of the directories searched (which change when shared libraries are installed or
+        /// removed, e.g., when a toolchain is upgraded). This allows a long-running process (e.g.,
+        /// a language server) to cheaply check whether a better `libclang` shared library has
+        /// become available and load it (e.g., with `load_from_path`) when it is next idle.
+        ///
+        /// Returns `None` without searching if the environment hasn't changed since the last
+        /// search performed by this function, `load`, `LoadOptions::load`, or any of the other
+        /// functions which search for a shared library to load (taking the environment variable
+        /// overrides of `LoadOptions` into account, see `LoadOptions::env_override`). Otherwise, the path returned is the path to the shared library `load`
+        /// would now load, which is always returned if no shared library is in use on this
+        /// thread.
+        ///
+        /// # Failures
+        ///
+        /// * no `libclang` shared libraries could be found (`LoadError::NotFound`)
+        pub fn rediscover() -> Result<Option<PathBuf>, LoadError> {
+            let snapshot = SNAPSHOT.lock().unwrap_or_else(|e| e.into_inner());
+            if snapshot.as_ref().is_some_and(|s| !s.is_stale()) {
+                return Ok(None);
+            }
+
+            drop(snapshot);
+            let path = find()?;
+            let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.into());
+            match get_library() {
+                Some(library) if canonical(library.path()) == canonical(&path) => Ok(None),
+                _ => Ok(Some(path)),
+            }
+        }
+
+        /// Checks that the SHA-256 digest of the supplied shared library is in the supplied
+        /// allowlist of digests (as lowercase hexadecimal digits), if the allowlist isn't empty.
+        fn check_integrity(path: &Path, allowlist: &[String]) -> Result<(), LoadError> {
+            if allowlist.is_empty() {
+                return Ok(());
+            }
+
+            let digest = sha256::digest_file(path).ok();
+            if digest.as_ref().is_some_and(|d| allowlist.contains(d)) {
+                return Ok(());
+            }
+
+            #[cfg(feature = "log")]
+            log::warn!(target: "clang_sys", "refusing to open {} (SHA-256: {:?})", path.display(), digest);
+            Err(LoadError::IntegrityMismatch { path: path.into(), digest })
+        }
+
+        /// Opens the `libclang` shared library at the supplied path (see `SharedLibrary::open`)
+        /// or, if it is already in use in any thread, returns the library instance in use.
+        ///
+        /// Opening the same shared library more than once wastes memory and breaks shared
+        /// libraries which register global state (e.g., LLVM command line options), so the
+        /// library instances are shared by their canonical paths. A library instance is only kept
+        /// while it is in use so a shared library is opened again once it has been unloaded.
+        fn open_shared(path: &Path) -> Result<Arc<SharedLibrary>, LoadError> {
+            let key = path.canonicalize().unwrap_or_else(|_| path.into());
+
+            // The registry is locked while opening the shared library so that threads loading
+            // the same shared library concurrently don't open it more than once.
+            let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+            registry.retain(|(_, l)| l.strong_count() != 0);
+            if let Some(library) = registry.iter().find(|(p, _)| *p == key).and_then(|(_, l)| l.upgrade()) {
+                return Ok(library);
+            }
+
+            let library = Arc::new(SharedLibrary::open(path)?);
+            registry.push((key, Arc::downgrade(&library)));
+            Ok(library)
+        }
+
+        /// Loads the shared libraries listed in the `LIBCLANG_PRELOAD` environment variable which
+        /// must be loaded before the supplied `libclang` shared library.
+        ///
+        /// Some distributions split the `libclang` API across multiple shared libraries (e.g.,
+        /// `libclang-cpp`) which `libclang` doesn't depend on itself. These shared libraries are
+        /// loaded (with `RTLD_GLOBAL` where supported) before `libclang` and functions which
+        /// can't be found in `libclang` are loaded from them instead. Shared libraries listed
+        /// with just a file name are loaded from the directory containing `libclang` if they
+        /// exist there (and are otherwise searched for by the dynamic loader).
+        fn load_preloaded_dependencies(path: &Path) -> Result<Vec<libloading::Library>, LoadError> {
+            let Some(preload) = discovery::common::env::var_os("LIBCLANG_PRELOAD") else {
+                return Ok(vec![]);
+            };
+
+            let directory = path.parent().unwrap_or_else(|| Path::new(""));
+            let mut libraries = vec![];
+            for dependency in std::env::split_paths(&preload) {
+                if dependency.as_os_str().is_empty() {
+                    continue;
+                }
+
+                let dependency = match directory.join(&dependency) {
+                    sibling if dependency.components().count() == 1 && sibling.is_file() => sibling,
+                    _ => dependency,
+                };
+
+                #[cfg(unix)]
+                let library = {
+                    use libloading::os::unix::{RTLD_GLOBAL, RTLD_NOW};
+                    unsafe { open_relaxed(&dependency, RTLD_NOW | RTLD_GLOBAL) }
+                };
+                #[cfg(windows)]
+                let library = unsafe { open_windows(&dependency) };
+                #[cfg(not(any(unix, windows)))]
+                let library = unsafe { libloading::Library::new(&dependency) };
+
+                #[cfg(feature = "log")]
+                log::debug!(target: "clang_sys", "preloading {}", dependency.display());
+                match library {
+                    Ok(library) => libraries.push(library),
+                    Err(source) => return Err(LoadError::open_failed(dependency, source)),
+                }
+            }
+
+            Ok(libraries)
+        }
+
+        /// Loads the LLVM shared library a `libclang` shared library depends on (e.g.,
+        /// `libLLVM-18.so.1`) from the directory containing `libclang` or the directory provided
+        /// by `llvm-config --libdir`.
+        ///
+        /// Some distributions install the LLVM shared library in a directory which isn't searched
+        /// by the dynamic loader, so opening `libclang` would fail with an error about the LLVM
+        /// shared library not being found. It is loaded (with `RTLD_GLOBAL`) before `libclang`
+        /// so that the dynamic loader uses the already loaded shared library instead. Dependencies
+        /// which can't be found or loaded are left for the dynamic loader to resolve (or fail to
+        /// resolve).
+        #[cfg(all(unix, not(target_os = "macos")))]
+        fn load_llvm_dependencies(path: &Path) -> Vec<libloading::Library> {
+            use libloading::os::unix::{RTLD_GLOBAL, RTLD_NOW};
+
+            let libdir = || discovery::common::run_llvm_config(&["--libdir"]).map(|d| PathBuf::from(d.trim()));
+
+            let mut libraries = vec![];
+            for dependency in discovery::dynamic::find_llvm_dependencies(path, libdir) {
+                #[cfg(feature = "log")]
+                log::debug!(target: "clang_sys", "preloading {}", dependency.display());
+                if let Ok(library) = unsafe { open_relaxed(&dependency, RTLD_NOW | RTLD_GLOBAL) } {
+                    libraries.push(library);
+                }
+            }
+
+            libraries
+        }
+
+        /// Loads the dependencies of a `libclang` shared library which are referenced with `@rpath`
+        /// install names and can be found in the same bundle or toolchain as the shared library.
+        ///
+        /// These dependencies are loaded before the shared library so that `dyld` can resolve the
+        /// `@rpath` install names to the already loaded dependencies. Dependencies which can't be
+        /// found or loaded are left for `dyld` to resolve (or fail to resolve).
+        #[cfg(target_os = "macos")]
+        fn load_rpath_dependencies(path: &Path) -> Vec<libloading::Library> {
+            let Some(directory) = path.parent() else {
+                return vec![];
+            };
+
+            let Ok(dylibs) = discovery::dynamic::parse_mach_o_dylibs(path) else {
+                return vec![];
+            };
+
+            let mut libraries = vec![];
+            for dependency in dylibs.dependencies {
+                let Some(name) = dependency.strip_prefix("@rpath/") else {
+                    continue;
+                };
+
+                let candidates = [
+                    directory.join(name),
+                    directory.join("../Frameworks").join(name),
+                    directory.join("../lib").join(name),
+                ];
+
+                if let Some(candidate) = candidates.iter().find(|c| c.is_file())
+                    && let Ok(library) = unsafe { libloading::Library::new(candidate) }
+                {
+                    libraries.push(library);
+                }
+            }
+
+            libraries
+        }
+
+        /// Loads a `libclang` shared library packaged with the current application and returns
+        /// the library instance.
+        ///
+        /// Processes running in an AppContainer (e.g., UWP or MSIX-packaged applications) are not
+        /// allowed to load shared libraries from arbitrary paths, so the search performed by
+        /// `load_manually` is not usable in these processes. Instead, this function only searches
+        /// the application directory (i.e., the directory containing the executable of the current
+        /// process) for `libclang.dll` or `clang.dll`, so one of these must be included in the
+        /// package alongside the executable.
+        ///
+        /// This function does not attempt to load any functions from the shared library. The caller
+        /// is responsible for loading the functions they require.
+        ///
+        /// # Failures
+        ///
+        /// * a `libclang` shared library could not be opened from the application directory
+        ///   (`LoadError::NotFound`)
+        /// * the SHA-256 digest of the `libclang` shared library isn't in the allowlist
+        ///   provided by the `CLANG_SYS_LIBCLANG_SHA256` environment variable
+        ///   (`LoadError::IntegrityMismatch`)
+        /// * the `libclang` shared library is a stub or doesn't work and the `verify-on-load`
+        ///   Cargo feature is enabled (`LoadError::VerificationFailed`)
+        /// * the version of the `libclang` shared library can't be determined
+        ///   (`LoadError::VersionTooOld`)
+        #[cfg(windows)]
+        pub fn load_packaged_manually() -> Result<SharedLibrary, LoadError> {
+            use libloading::os::windows;
+
+            let directory = std::env::current_exe()
+                .ok()
+                .and_then(|e| e.parent().map(Path::to_path_buf))
+                .unwrap_or_default();
+
+            let mut errors = vec![];
+            for filename in ["libclang.dll", "clang.dll"] {
+                let path = directory.join(filename);
+                if path.is_file()
+                    && let Ok(list) = discovery::common::env::var("CLANG_SYS_LIBCLANG_SHA256")
+                {
+                    check_integrity(&path, &sha256::split_digests(&list))?;
+                }
+
+                let flags = windows::LOAD_LIBRARY_SEARCH_APPLICATION_DIR;
+                match unsafe { windows::Library::load_with_flags(filename, flags) } {
+                    Ok(library) => return SharedLibrary::new(library.into(), path).check_loaded(false),
+                    Err(error) => errors.push(format!("({}: {})", filename, error)),
+                }
+            }
+
+            let message = format!(
+                "a `libclang` shared library could not be opened from the application directory \
+                 (errors: [{}])",
+                errors.join(", "),
+            );
+
+            Err(LoadError::NotFound { searched: vec![directory], message })
+        }
+
+        /// Loads a `libclang` shared library for use in the current thread.
+        ///
+        /// This functions attempts to load all the functions in the shared library. Whether a
+        /// function has been loaded can be tested by calling the `is_loaded` function on the
+        /// module with the same name as the function (e.g., `clang_createIndex::is_loaded()` for
+        /// the `clang_createIndex` function).
+        ///
+        /// Calls to this function are reference counted so that libraries which each call `load`
+        /// and `unload` can be nested: if a `libclang` shared library loaded by this function (or
+        /// another `load_*` function) is already in use in the current thread, it is used again
+        /// instead of loading another one and it is only unloaded once `unload` has been called
+        /// as many times as it has been loaded. If a nested call to another `load_*` function
+        /// loads a different library instance, that library instance is used until it is
+        /// unloaded and the library instance it replaced is then in use again.
+        ///
+        /// If the `libclang` shared library is already in use in another thread (i.e., it was
+        /// loaded by this function, `load_from_path`, `load_with_version`, `load_global`, or
+        /// `LoadOptions::load`), the library instance in use in that thread is used instead of
+        /// opening the shared library again.
+        ///
+        /// # Failures
+        ///
+        /// See `load_manually`.
         #[allow(dead_code)]
-        pub fn load() -> Result<(), String> {
-            let library = Arc::new(load_manually()?);
+        pub fn load() -> Result<(), LoadError> {
+            let loads = LOADS.with(Cell::get);
+            if loads != 0 && get_library().is_some() {
+                LOADS.with(|l| l.set(loads + 1));
+                return Ok(());
+            }
+
+            store(open_first(find_all()?, open_shared)?);
+            Ok(())
+        }
+
+        /// Stores the supplied library instance in TLS as a loaded library.
+        ///
+        /// If the same library instance is already in use in the current thread, the number of
+        /// outstanding `load` calls is incremented instead. If another library instance loaded by
+        /// a `load_*` function is in use, it is replaced until the supplied library instance is
+        /// unloaded (see `unload`).
+        fn store(library: Arc<SharedLibrary>) {
+            let loads = LOADS.with(Cell::get);
+            match get_library() {
+                Some(current) if loads != 0 && Arc::ptr_eq(&current, &library) => {
+                    LOADS.with(|l| l.set(loads + 1));
+                    return;
+                }
+                Some(current) if loads != 0 => SHADOWED.with(|s| s.borrow_mut().push((current, loads))),
+                _ => {}
+            }
+
             LIBRARY.with(|l| *l.borrow_mut() = Some(library));
+            LOADS.with(|l| l.set(1));
+        }
+
+        /// Loads a `libclang` shared library for use in the current thread like `load` but doesn't
+        /// load any functions until they are first called (see `SharedLibrary::open_lazy`).
+        ///
+        /// # Failures
+        ///
+        /// See `load_manually`.
+        pub fn load_lazy() -> Result<(), LoadError> {
+            store(Arc::new(open_first(find_all()?, |p| SharedLibrary::open_lazy(p))?));
+            Ok(())
+        }
+
+        /// Loads the `libclang` shared library at the supplied path for use in the current thread.
+        ///
+        /// See `SharedLibrary::open` for how the shared library is opened and `load` for how the
+        /// functions in the shared library are loaded.
+        ///
+        /// # Failures
+        ///
+        /// See `SharedLibrary::open`.
+        pub fn load_from_path(path: impl AsRef<Path>) -> Result<(), LoadError> {
+            store(open_shared(path.as_ref())?);
+            Ok(())
+        }
+
+        /// Loads a `libclang` shared library which is at least the supplied version for use in
+        /// the current thread.
+        ///
+        /// This function searches for and loads a `libclang` shared library like `load` but
+        /// doesn't make the shared library available for use if it is older than the supplied
+        /// version (which would otherwise only be discovered when calling a function which is
+        /// not available in the shared library). Shared libraries older than the supplied version
+        /// are skipped in favor of the other `libclang` shared libraries found (see
+        /// `load_manually`).
+        ///
+        /// # Failures
+        ///
+        /// * see `load_manually`
+        /// * the `libclang` shared library is older than the supplied version
+        ///   (`LoadError::VersionTooOld`)
+        pub fn load_with_version(minimum: Version) -> Result<(), LoadError> {
+            let library = open_first(find_all()?, |path| {
+                let library = open_shared(path)?;
+                match library.version() {
+                    Some(version) if version >= minimum => Ok(library),
+                    version => Err(LoadError::VersionTooOld {
+                        path: library.path.clone(),
+                        version,
+                        minimum,
+                    }),
+                }
+            })?;
+
+            store(library);
+            Ok(())
+        }
+
+        /// Options which constrain the search for a `libclang` shared library performed when
+        /// loading one.
+        ///
+        /// By default, the search is the same as the search performed by `load`. Unlike `load`,
+        /// the discovery hook (see `set_discovery_hook`) is not used.
+        ///
+        /// # Examples
+        ///
+        /// ```no_run
+        /// # #[cfg(feature = "runtime")]
+        /// # fn example() {
+        /// # use clang_sys::{LoadOptions, Version};
+        /// LoadOptions::new()
+        ///     .search_dir("/usr/lib/llvm-18/lib")
+        ///     .filename_pattern("libclang.so.*")
+        ///     .min_version(Version::V16_0)
+        ///     .load()
+        ///     .expect("Failed to load libclang");
+        /// # }
+        /// ```
+        #[derive(Clone, Debug, Default)]
+        pub struct LoadOptions {
+            directories: Vec<PathBuf>,
+            patterns: Vec<String>,
+            minimum: Option<Version>,
+            policy: Option<SelectionPolicy>,
+            overrides: Vec<(String, String)>,
+            allowlist: Vec<String>,
+            isolated: bool,
+            verify: bool,
+        }
+
+        impl LoadOptions {
+            /// Constructs a new set of options which perform the same search as `load`.
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            /// Adds a directory (or a path to a shared library) to search.
+            ///
+            /// If any directories are added, only those directories are searched (in the order
+            /// they were added) instead of the locations searched by `load`.
+            pub fn search_dir(mut self, directory: impl Into<PathBuf>) -> Self {
+                self.directories.push(directory.into());
+                self
+            }
+
+            /// Adds a file name glob pattern (e.g., `libclang.so.*`) for `libclang` shared
+            /// libraries.
+            ///
+            /// If any patterns are added, only shared libraries with file names matching those
+            /// patterns are used instead of the file names used by `load`.
+            pub fn filename_pattern(mut self, pattern: impl Into<String>) -> Self {
+                self.patterns.push(pattern.into());
+                self
+            }
+
+            /// Sets the minimum version of the `libclang` shared library.
+            ///
+            /// Shared libraries which are known to be older than this version before loading them
+            /// (e.g., from their file names) are skipped and the shared library which is loaded is
+            /// checked against this version (like `load_with_version`).
+            pub fn min_version(mut self, minimum: Version) -> Self {
+                self.minimum = Some(minimum);
+                self
+            }
+
+            /// Sets how the `libclang` shared library is selected when several are found.
+            ///
+            /// By default, the selection policy provided by the `LIBCLANG_SELECTION_POLICY`
+            /// environment variable is used (see `SelectionPolicy::from_env`), which prefers the
+            /// shared library with the highest version if it is not set.
+            pub fn selection_policy(mut self, policy: SelectionPolicy) -> Self {
+                self.policy = Some(policy);
+                self
+            }
+
+            /// Sets the value of an environment variable used by the search (e.g., `LIBCLANG_PATH`
+            /// or `LIBCLANG_PRELOAD`) instead of the value in the environment of the process.
+            ///
+            /// Unlike setting the environment variable with `std::env::set_var` (which isn't
+            /// thread-safe), this only affects the searches performed with these options.
+            pub fn env_override(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+                self.overrides.push((name.into(), value.into()));
+                self
+            }
+
+            /// Adds a SHA-256 digest (as hexadecimal digits) to the allowlist of `libclang` shared
+            /// libraries which may be loaded.
+            ///
+            /// If any digests are added, shared libraries with other digests are refused
+            /// (`LoadError::IntegrityMismatch`) before they are opened. This is in addition to the
+            /// allowlist provided by the `CLANG_SYS_LIBCLANG_SHA256` environment variable. The
+            /// shared libraries loaded before `libclang` (e.g., the shared libraries listed in the
+            /// `LIBCLANG_PRELOAD` environment variable) aren't checked.
+            pub fn sha256(mut self, digest: impl AsRef<str>) -> Self {
+                self.allowlist.push(digest.as_ref().trim().to_ascii_lowercase());
+                self
+            }
+
+            /// Sets the maximum time a `libclang` shared library may take to be opened and have its
+            /// version determined in a child process before it is opened in the current process
+            /// (like the `CLANG_SYS_PROBE_TIMEOUT` environment variable).
+            ///
+            /// Shared libraries are only opened in a child process first if a timeout is set.
+            /// Shared libraries which crash or hang the child process are then skipped
+            /// (`LoadError::VerificationFailed`) instead of taking down the current process. A
+            /// timeout of zero disables the child process.
+            pub fn probe_timeout(self, timeout: std::time::Duration) -> Self {
+                let timeout = timeout.as_secs_f64().to_string();
+                self.env_override("CLANG_SYS_PROBE_TIMEOUT", timeout)
+            }
+
+            /// Sets whether the `libclang` shared library is verified to work after it has been
+            /// loaded (see `SharedLibrary::verify`).
+            ///
+            /// Shared libraries are always verified if the `verify-on-load` Cargo feature is
+            /// enabled.
+            pub fn verify(mut self, verify: bool) -> Self {
+                self.verify = verify;
+                self
+            }
+
+            /// Sets whether the shared libraries opened with `RTLD_NOW` (e.g., those listed in the
+            /// `LIBCLANG_PRELOAD` environment variable) are opened again with `RTLD_LAZY` if a
+            /// symbol isn't defined (like the `CLANG_SYS_RELAXED_BINDING` environment variable).
+            ///
+            /// Such shared libraries may still work if the functions which use the missing symbols
+            /// are never called, but calling one of them terminates the process. This has no effect
+            /// on Windows.
+            pub fn relaxed(mut self, relaxed: bool) -> Self {
+                self.overrides.retain(|(name, _)| name != "CLANG_SYS_RELAXED_BINDING");
+                if relaxed {
+                    self.overrides.push(("CLANG_SYS_RELAXED_BINDING".into(), "1".into()));
+                }
+                self
+            }
+
+            /// Sets whether the symbols of the `libclang` shared library are isolated from the
+            /// symbols of the shared libraries already loaded in the current process (see
+            /// `SharedLibrary::open_isolated`).
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            pub fn isolated(mut self, isolated: bool) -> Self {
+                self.isolated = isolated;
+                self
+            }
+
+            /// Loads a `libclang` shared library with these options and returns the library
+            /// instance.
+            ///
+            /// Like the `load_manually` function, this does not attempt to load any functions from
+            /// the shared library.
+            ///
+            /// # Failures
+            ///
+            /// * see `load_manually`
+            /// * the `libclang` shared library is older than the minimum version
+            ///   (`LoadError::VersionTooOld`)
+            /// * the `libclang` shared library doesn't work (`LoadError::VerificationFailed`)
+            /// * the SHA-256 digest of the `libclang` shared library isn't in the allowlist
+            ///   (`LoadError::IntegrityMismatch`)
+            pub fn load_manually(&self) -> Result<SharedLibrary, LoadError> {
+                discovery::common::with_env_overrides(&self.overrides, || {
+                    open_first(self.find()?, |path| {
+                        check_integrity(path, &self.allowlist)?;
+                        let library = SharedLibrary::open_with(path, false, self.isolated)?;
+                        self.check(&library)?;
+                        Ok(library)
+                    })
+                })
+            }
+
+            /// Loads a `libclang` shared library with these options for use in the current thread.
+            ///
+            /// See `load_manually` for how the shared library is found and `load` for how the
+            /// functions in the shared library are loaded. Unless the symbols of the shared
+            /// library are isolated, the library instance in use in another thread is used if
+            /// the shared library is already in use (see `load`).
+            ///
+            /// # Failures
+            ///
+            /// See `LoadOptions::load_manually`.
+            pub fn load(&self) -> Result<(), LoadError> {
+                let library = discovery::common::with_env_overrides(&self.overrides, || {
+                    open_first(self.find()?, |path| {
+                        check_integrity(path, &self.allowlist)?;
+                        let library = if self.isolated {
+                            Arc::new(SharedLibrary::open_with(path, false, true)?)
+                        } else {
+                            open_shared(path)?
+                        };
+
+                        self.check(&library)?;
+                        Ok(library)
+                    })
+                })?;
+
+                store(library);
+                Ok(())
+            }
+
+            /// Finds the `libclang` shared libraries with these options (ignoring the minimum
+            /// version) and returns them in order of preference (see `discover`).
+            pub fn discover(&self) -> Vec<Candidate> {
+                discovery::common::with_env_overrides(&self.overrides, || {
+                    let policy = self.policy.unwrap_or_else(|| {
+                        SelectionPolicy::from_env().unwrap_or_default()
+                    });
+                    discovery::dynamic::find_candidates(&self.directories, &self.patterns, Some(policy))
+                        .unwrap_or_default()
+                })
+            }
+
+            /// Finds the `libclang` shared libraries with these options and returns the paths to
+            /// them in order of preference.
+            fn find(&self) -> Result<Vec<PathBuf>, LoadError> {
+                let not_found = |message| {
+                    let searched = discovery::common::searched_directories();
+                    LoadError::NotFound { searched, message }
+                };
+
+                let candidates = discovery::dynamic::find_candidates(
+                    &self.directories,
+                    &self.patterns,
+                    self.policy,
+                );
+                Snapshot::record(discovery::common::searched_directories());
+                let candidates = candidates.map_err(not_found)?;
+
+                let minimum = self.minimum.map(|m| vec![m as u32 / 10, m as u32 % 10]);
+                let paths = candidates
+                    .iter()
+                    .filter(|c| c.version.is_empty() || minimum.as_ref().is_none_or(|m| &c.version >= m))
+                    .map(|c| c.path())
+                    .collect::<Vec<_>>();
+                if paths.is_empty() {
+                    return Err(not_found(format!(
+                        "couldn't find any `libclang` shared libraries which are at least version \
+                         {} (found: [{}])",
+                        self.minimum.unwrap(),
+                        candidates
+                            .iter()
+                            .map(|c| c.directory.join(&c.filename).display().to_string())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    )));
+                }
+
+                Ok(paths)
+            }
+
+            /// Checks that a loaded `libclang` shared library satisfies these options.
+            fn check(&self, library: &SharedLibrary) -> Result<(), LoadError> {
+                if self.verify && !cfg!(feature = "verify-on-load") {
+                    library.verify()?;
+                }
+
+                match (self.minimum, library.version()) {
+                    (Some(minimum), version) if version.is_none_or(|v| v < minimum) => {
+                        Err(LoadError::VersionTooOld {
+                            path: library.path.clone(),
+                            version,
+                            minimum,
+                        })
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+
+        /// How a `libclang` shared library links to LLVM.
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum Linkage {
+            /// LLVM is linked into the shared library (e.g., the official LLVM releases).
+            Static,
+            /// The shared library depends on an LLVM shared library (e.g., `libLLVM-18.so` in
+            /// most Linux distributions).
+            Shared,
+        }
+
+        /// A `libclang` shared library found by `enumerate_libraries`.
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct LibraryInfo {
+            /// The path to this shared library.
+            pub path: PathBuf,
+            /// The version components of this shared library known before loading it (e.g., from
+            /// its filename), empty if unknown.
+            pub version: Vec<u32>,
+            /// Whether the C and C++ standard library requirements of this shared library are
+            /// known to be satisfied (only checked on Linux).
+            pub compatible: bool,
+            /// The architectures this shared library contains code for, named like the
+            /// `target_arch` values (e.g., `x86_64`), empty if unknown.
+            pub architectures: Vec<String>,
+            /// The minimum operating system version this shared library supports, if specified
+            /// (the minimum macOS version on macOS and the subsystem version on Windows).
+            pub min_os_version: Option<Vec<u32>>,
+            /// How this shared library links to LLVM, if known.
+            pub linkage: Option<Linkage>,
+            /// The names of the shared libraries this shared library depends on.
+            pub dependencies: Vec<String>,
+            /// The kind of location this shared library was found in.
+            pub source: DiscoverySource,
+        }
+
+        impl LibraryInfo {
+            /// Reads the properties of a `libclang` shared library found by a search.
+            fn read(candidate: discovery::dynamic::Candidate) -> Self {
+                let path = candidate.directory.join(&candidate.filename);
+                let binary = discovery::dynamic::parse_binary_info(&path).ok();
+                let linkage = binary.as_ref().map(|b| {
+                    if b.dependencies.iter().any(|d| d.contains("LLVM")) {
+                        Linkage::Shared
+                    } else {
+                        Linkage::Static
+                    }
+                });
+                let binary = binary.unwrap_or_default();
+
+                LibraryInfo {
+                    path,
+                    version: candidate.version,
+                    compatible: candidate.compatible,
+                    architectures: binary.architectures,
+                    min_os_version: binary.min_os_version,
+                    linkage,
+                    dependencies: binary.dependencies,
+                    source: candidate.source,
+                }
+            }
+        }
+
+        /// Finds the `libclang` shared libraries which `load` searches for and returns them in
+        /// order of preference (i.e., the first shared library is the one `load` loads unless a
+        /// discovery hook is set).
+        ///
+        /// See `Candidates` for only finding the shared libraries which can be loaded into the
+        /// current process.
+        ///
+        /// # Failures
+        ///
+        /// * no `libclang` shared libraries could be found (`LoadError::NotFound`)
+        pub fn enumerate_libraries() -> Result<Vec<LibraryInfo>, LoadError> {
+            Candidates::new().enumerate()
+        }
+
+        /// Finds every `libclang` shared library which `load` searches for and returns them in
+        /// order of preference along with where each one was found (e.g., `LIBCLANG_PATH` or the
+        /// installation returned by `llvm-config`).
+        ///
+        /// Unlike `enumerate_libraries`, this function doesn't read the shared libraries beyond
+        /// what the search itself does (e.g., to determine their versions) and returns an empty
+        /// vector if no shared libraries could be found. This is intended for tools which let
+        /// users select one of the installations of `libclang` (which can then be loaded with
+        /// `load_manually`).
+        ///
+        /// # Examples
+        ///
+        /// ```no_run
+        /// # #[cfg(feature = "runtime")]
+        /// # fn example() {
+        /// for candidate in clang_sys::discover() {
+        ///     println!("{} ({:?}, {:?})", candidate.path().display(), candidate.version, candidate.source);
+        /// }
+        /// # }
+        /// ```
+        pub fn discover() -> Vec<Candidate> {
+            LoadOptions::new().discover()
+        }
+
+        /// Options for the search for `libclang` shared libraries which can be shared between
+        /// `LoadOptions` and the build scripts of other packages (which can use this crate as a
+        /// build dependency with the `runtime` Cargo feature enabled).
+        ///
+        /// # Examples
+        ///
+        /// ```no_run
+        /// # #[cfg(feature = "runtime")]
+        /// # fn example() {
+        /// use clang_sys::{DiscoveryOptions, LoadOptions};
+        ///
+        /// let mut options = DiscoveryOptions::default();
+        /// options.env_overrides.insert("LIBCLANG_PATH".into(), "/opt/llvm/lib".into());
+        /// let candidates = options.discover();
+        /// let library = LoadOptions::from(options).load_manually();
+        /// # }
+        /// ```
+        #[derive(Clone, Debug, Default, PartialEq, Eq)]
+        pub struct DiscoveryOptions {
+            /// The values of the environment variables used by the search (e.g.,
+            /// `LIBCLANG_PATH`) which are used instead of the values in the environment of the
+            /// process (see `LoadOptions::env_override`).
+            pub env_overrides: std::collections::HashMap<String, String>,
+        }
+
+        impl DiscoveryOptions {
+            /// Finds the `libclang` shared libraries with these options and returns them in order
+            /// of preference (see `discover`).
+            pub fn discover(&self) -> Vec<Candidate> {
+                LoadOptions::from(self.clone()).discover()
+            }
+        }
+
+        impl From<DiscoveryOptions> for LoadOptions {
+            fn from(options: DiscoveryOptions) -> Self {
+                let mut overrides = options.env_overrides.into_iter().collect::<Vec<_>>();
+                overrides.sort();
+                overrides.into_iter().fold(LoadOptions::new(), |o, (n, v)| o.env_override(n, v))
+            }
+        }
+
+        /// Returns the version of the running operating system, if known (only on macOS).
+        fn current_os_version() -> Option<Vec<u32>> {
+            if !cfg!(target_os = "macos") {
+                return None;
+            }
+
+            let path = "/System/Library/CoreServices/SystemVersion.plist";
+            let plist = std::fs::read_to_string(path).ok()?;
+            let value = plist.split("<key>ProductVersion</key>").nth(1)?;
+            let version = value.split("<string>").nth(1)?.split("</string>").next()?;
+            Some(crate::version::parse_components(version))
+        }
+
+        /// Filters for the `libclang` shared libraries found by `enumerate_libraries`.
+        ///
+        /// By default, every shared library is included.
+        ///
+        /// # Examples
+        ///
+        /// ```no_run
+        /// # #[cfg(feature = "runtime")]
+        /// # fn example() {
+        /// # use clang_sys::Candidates;
+        /// for library in Candidates::for_current_process().enumerate().unwrap() {
+        ///     println!("{} ({:?})", library.path.display(), library.version);
+        /// }
+        /// # }
+        /// ```
+        #[derive(Clone, Debug, Default)]
+        pub struct Candidates {
+            architecture: Option<String>,
+            os_version: Option<Vec<u32>>,
+            linkage: Option<Linkage>,
+            compatible: bool,
+        }
+
+        impl Candidates {
+            /// Constructs a new set of filters which include every shared library.
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            /// Constructs a new set of filters which only include the shared libraries which can
+            /// be loaded into the current process.
+            ///
+            /// These are the shared libraries for the architecture of the current process, which
+            /// support the running version of the operating system (only checked on macOS), and
+            /// whose C and C++ standard library requirements are satisfied.
+            pub fn for_current_process() -> Self {
+                let filters = Self::new().architecture(std::env::consts::ARCH).compatible(true);
+                match current_os_version() {
+                    Some(version) => filters.os_version(version),
+                    None => filters,
+                }
+            }
+
+            /// Only includes the shared libraries which contain code for the supplied
+            /// architecture (named like the `target_arch` values, e.g., `aarch64`).
+            ///
+            /// Shared libraries whose architectures couldn't be determined are included.
+            pub fn architecture(mut self, architecture: impl Into<String>) -> Self {
+                self.architecture = Some(architecture.into());
+                self
+            }
+
+            /// Only includes the shared libraries which support the supplied operating system
+            /// version (e.g., `[14, 4]` for macOS 14.4).
+            ///
+            /// Shared libraries which don't specify a minimum operating system version are
+            /// included.
+            pub fn os_version(mut self, version: impl Into<Vec<u32>>) -> Self {
+                self.os_version = Some(version.into());
+                self
+            }
+
+            /// Only includes the shared libraries which link to LLVM in the supplied way.
+            ///
+            /// Shared libraries whose linkage couldn't be determined are excluded.
+            pub fn linkage(mut self, linkage: Linkage) -> Self {
+                self.linkage = Some(linkage);
+                self
+            }
+
+            /// Sets whether only the shared libraries whose C and C++ standard library
+            /// requirements are known to be satisfied are included.
+            pub fn compatible(mut self, compatible: bool) -> Self {
+                self.compatible = compatible;
+                self
+            }
+
+            /// Returns whether the supplied shared library passes these filters.
+            pub fn matches(&self, library: &LibraryInfo) -> bool {
+                // Ignore trailing zero components (e.g., `14.4.0` is `14.4`).
+                fn trim(version: &[u32]) -> &[u32] {
+                    let length = version.iter().rposition(|c| *c != 0).map_or(0, |i| i + 1);
+                    &version[..length]
+                }
+
+                let architecture = self.architecture.as_ref().is_none_or(|a| {
+                    library.architectures.is_empty() || library.architectures.contains(a)
+                });
+                let os_version = match (&self.os_version, &library.min_os_version) {
+                    (Some(version), Some(minimum)) => trim(minimum) <= trim(version),
+                    _ => true,
+                };
+                let linkage = self.linkage.is_none_or(|l| library.linkage == Some(l));
+                architecture && os_version && linkage && (library.compatible || !self.compatible)
+            }
+
+            /// Finds the `libclang` shared libraries which pass these filters and returns them in
+            /// order of preference (see `enumerate_libraries`).
+            ///
+            /// # Failures
+            ///
+            /// * no `libclang` shared libraries could be found (`LoadError::NotFound`)
+            pub fn enumerate(&self) -> Result<Vec<LibraryInfo>, LoadError> {
+                let candidates = discovery::dynamic::find_candidates(&[], &[], None).map_err(|message| {
+                    let searched = discovery::common::searched_directories();
+                    LoadError::NotFound { searched, message }
+                })?;
+
+                let libraries = candidates.into_iter().map(LibraryInfo::read);
+                Ok(libraries.filter(|l| self.matches(l)).collect())
+            }
+        }
+
+        /// Loads a `libclang` shared library for use in all threads.
+        ///
+        /// Unlike `load`, which stores the library instance in TLS, this function stores the
+        /// library instance process-wide so that it is used by any thread which doesn't have its
+        /// own library instance stored in TLS (e.g., the worker threads of a thread pool) without
+        /// having to call `set_library` on each thread. See `load` for how the functions in the
+        /// shared library are loaded.
+        ///
+        /// # Failures
+        ///
+        /// See `load_manually`.
+        pub fn load_global() -> Result<(), LoadError> {
+            let library = open_first(find_all()?, open_shared)?;
+            set_global_library(Some(library));
+            Ok(())
+        }
+
+        /// Loads a `libclang` shared library packaged with the current application for use in the
+        /// current thread.
+        ///
+        /// See `load_packaged_manually` for how the shared library is found and `load` for how the
+        /// functions in the shared library are loaded.
+        ///
+        /// # Failures
+        ///
+        /// See `load_packaged_manually`.
+        #[cfg(windows)]
+        pub fn load_packaged() -> Result<(), LoadError> {
+            store(Arc::new(load_packaged_manually()?));
             Ok(())
         }
 
         /// Unloads the `libclang` shared library in use in the current thread.
         ///
+        /// If the shared library has been loaded more than once (see `load`), this only
+        /// decrements the number of outstanding `load` calls and the shared library remains in
+        /// use until the last of them is matched by a call to this function.
+        ///
         /// # Failures
         ///
         /// * a `libclang` shared library is not in use in the current thread
         pub fn unload() -> Result<(), String> {
+            let loads = LOADS.with(|l| l.replace(0));
+            if loads > 1 && get_library().is_some() {
+                LOADS.with(|l| l.set(loads - 1));
+                return Ok(());
+            }
+
             let library = set_library(None);
+
+            // Restore the library instance replaced by a nested `load_*` call (see `store`).
+            if library.is_some()
+                && let Some((outer, loads)) = SHADOWED.with(|s| s.borrow_mut().pop())
+            {
+                set_library(Some(outer));
+                LOADS.with(|l| l.set(loads));
+            }
+
             if library.is_some() {
                 Ok(())
             } else {
@@ -504,6 +3081,33 @@ https://rust-lang.github.io/rust-bindgen/requirements.html
             }
         }
 
+        /// Keeps a `libclang` shared library loaded by `load_guarded` in use in the current thread
+        /// until it is dropped.
+        ///
+        /// Dropping this guard (including while unwinding from a panic) calls `unload`. This
+        /// guard can't be sent to other threads since the shared library is only in use in the
+        /// thread which loaded it.
+        #[must_use = "the `libclang` shared library is unloaded when the guard is dropped"]
+        #[derive(Debug)]
+        pub struct LoadGuard(std::marker::PhantomData<*const ()>);
+
+        impl Drop for LoadGuard {
+            fn drop(&mut self) {
+                let _ = unload();
+            }
+        }
+
+        /// Loads a `libclang` shared library for use in the current thread like `load` and returns
+        /// a guard which unloads it when dropped (see `LoadGuard`).
+        ///
+        /// # Failures
+        ///
+        /// See `load_manually`.
+        pub fn load_guarded() -> Result<LoadGuard, LoadError> {
+            load()?;
+            Ok(LoadGuard(std::marker::PhantomData))
+        }
+
         /// Returns the library instance stored in TLS.
         ///
         /// This functions allows for sharing library instances between threads.
@@ -517,6 +3121,59 @@ https://rust-lang.github.io/rust-bindgen/requirements.html
         pub fn set_library(library: Option<Arc<SharedLibrary>>) -> Option<Arc<SharedLibrary>> {
             LIBRARY.with(|l| mem::replace(&mut *l.borrow_mut(), library))
         }
+
+        /// Calls the supplied function with the supplied library instance stored in TLS and
+        /// returns the result.
+        ///
+        /// The library instance previously stored in TLS (if any) is restored afterwards (even if
+        /// the supplied function panics). This allows using the free functions in this crate with
+        /// multiple `libclang` shared libraries (e.g., of different versions) in the same thread
+        /// (see also `SharedLibrary::call`).
+        pub fn with_library_instance<T>(library: Arc<SharedLibrary>, f: impl FnOnce() -> T) -> T {
+            struct Restore(Option<Arc<SharedLibrary>>);
+
+            impl Drop for Restore {
+                fn drop(&mut self) {
+                    set_library(self.0.take());
+                }
+            }
+
+            let _restore = Restore(set_library(Some(library)));
+            f()
+        }
+
+        /// Returns the library instance stored process-wide.
+        pub fn get_global_library() -> Option<Arc<SharedLibrary>> {
+            GLOBAL_LIBRARY.read().unwrap_or_else(|e| e.into_inner()).clone()
+        }
+
+        /// Sets the function which provides the path to a `libclang` shared library before one is
+        /// searched for and returns the previous function (if any).
+        ///
+        /// The function is called whenever a `libclang` shared library is searched for at runtime
+        /// (e.g., by `load` or `load_manually`) before anything else (including the
+        /// `LIBCLANG_PATH` environment variable) is consulted. If it returns a path, the shared
+        /// library at that path is loaded without searching for one. Otherwise, a shared library
+        /// is searched for as usual. This allows an application (e.g., an IDE plugin or a test
+        /// harness) to provide a `libclang` shared library bundled with it without setting
+        /// environment variables.
+        pub fn set_discovery_hook(
+            hook: Option<fn() -> Option<PathBuf>>,
+        ) -> Option<fn() -> Option<PathBuf>> {
+            let mut global = DISCOVERY_HOOK.write().unwrap_or_else(|e| e.into_inner());
+            mem::replace(&mut *global, hook)
+        }
+
+        /// Sets the library instance stored process-wide and returns the previous library.
+        ///
+        /// The library instance stored process-wide is used by any thread which doesn't have its
+        /// own library instance stored in TLS (see `set_library`).
+        pub fn set_global_library(
+            library: Option<Arc<SharedLibrary>>,
+        ) -> Option<Arc<SharedLibrary>> {
+            let mut global = GLOBAL_LIBRARY.write().unwrap_or_else(|e| e.into_inner());
+            mem::replace(&mut *global, library)
+        }
     )
 }
 
@@ -524,22 +3181,29 @@ https://rust-lang.github.io/rust-bindgen/requirements.html
 macro_rules! link {
     (
         $(
-            $(#[doc=$doc:expr] #[cfg($cfg:meta)])*
+            $(#[doc=$doc:expr])* $(#[cfg($cfg:meta)])*
+            $(#[cfg_attr($acfg:meta, $attr:meta)])* $(#[deprecated = $deprecated:literal])?
             pub fn $name:ident($($pname:ident: $pty:ty), *) $(-> $ret:ty)*;
         )+
     ) => (
+        // With the `stub` Cargo feature, the functions are imported from `libclang.dll` without an
+        // import library on Windows (see `build/dynamic.rs`).
+        #[cfg_attr(all(windows, feature = "stub"), link(name = "libclang", kind = "raw-dylib"))]
         unsafe extern "C" {
             $(
-                $(#[doc=$doc] #[cfg($cfg)])*
+                $(#[doc=$doc])* $(#[cfg($cfg)])*
+                $(#[cfg_attr($acfg, $attr)])* $(#[deprecated = $deprecated])?
                 pub fn $name($($pname: $pty), *) $(-> $ret)*;
             )+
         }
 
         $(
-            $(#[doc=$doc] #[cfg($cfg)])*
+            $(#[doc=$doc])* $(#[cfg($cfg)])*
             pub mod $name {
                 pub fn is_loaded() -> bool { true }
             }
         )+
+
+        export!($($(#[cfg($cfg)])* pub fn $name($($pname: $pty), *) $(-> $ret)*;)+);
     )
 }
@@ -0,0 +1,6 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Support for loading `libclang` shared libraries at runtime.
+
+pub mod discovery;
+pub mod sha256;
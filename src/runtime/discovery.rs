@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Finds `libclang` shared libraries at runtime.
+//!
+//! The search performed at runtime is the same search the build script
+//! performs when linking to `libclang` dynamically, so the modules below are
+//! compiled from the same sources as the build script (`build/common.rs` and
+//! `build/dynamic.rs`). They are compiled for the target like the rest of this
+//! crate (e.g., `cfg!(target_os = "macos")` describes the platform the search
+//! is performed on) rather than included from copies made by the build script.
+
+#![allow(dead_code)]
+
+use crate::version;
+
+#[macro_use]
+#[path = "../../build/macros.rs"]
+mod macros;
+
+#[path = "../../build/common.rs"]
+pub mod common;
+#[path = "../../build/dynamic.rs"]
+pub mod dynamic;
+
+/// Whether the modules above are compiled into the build script (see
+/// `build.rs`).
+const BUILD_SCRIPT: bool = false;
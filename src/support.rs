@@ -2,24 +2,143 @@
 
 //! Provides helper functionality.
 
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::{env, io};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::{env, fs, io, mem, ops, ptr, slice};
 
 use glob::{self, Pattern};
 
-use libc::c_int;
+use libc::{c_char, c_int, c_longlong, c_uint};
 
-use super::CXVersion;
+use super::{
+    CXCursor, CXError_InvalidArguments, CXError_Success, CXErrorCode, CXIndex, CXSourceLocation,
+    CXSourceRange, CXToken, CXTranslationUnit, CXTranslationUnit_Flags, CXTypeLayoutError,
+    CXTypeLayoutError_Invalid, CXUnsavedFile, CXVersion, clang_VirtualFileOverlay_addFileMapping,
+    clang_VirtualFileOverlay_create, clang_VirtualFileOverlay_dispose,
+    clang_VirtualFileOverlay_setCaseSensitivity, clang_VirtualFileOverlay_writeToBuffer,
+    clang_annotateTokens, clang_disposeTokens, clang_getToken, clang_parseTranslationUnit2,
+    clang_tokenize,
+};
 
 //================================================
 // Structs
 //================================================
 
+/// A set of command-line arguments (e.g., for `clang_parseTranslationUnit`).
+///
+/// Functions which accept command-line arguments expect an array of pointers
+/// to C strings which must stay alive until the function returns. This type
+/// keeps the C strings together with the array so they can't be freed early.
+#[derive(Debug)]
+pub struct Arguments {
+    pointers: Vec<*const c_char>,
+    _arguments: Vec<CString>,
+}
+
+impl Arguments {
+    /// Returns a set of command-line arguments with the supplied values.
+    ///
+    /// An error is returned if an argument can't be converted into a C string
+    /// (e.g., because it contains a null byte).
+    pub fn new<I, S>(arguments: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let arguments = arguments
+            .into_iter()
+            .map(|a| {
+                let a = a.as_ref();
+                CString::new(a).map_err(|_| format!("invalid argument: {:?}", a))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            pointers: arguments.iter().map(|a| a.as_ptr()).collect(),
+            _arguments: arguments,
+        })
+    }
+
+    /// Returns a pointer to the arguments (as expected by functions such as
+    /// `clang_parseTranslationUnit`).
+    pub fn as_ptr(&self) -> *const *const c_char {
+        self.pointers.as_ptr()
+    }
+}
+
+impl ops::Deref for Arguments {
+    type Target = [*const c_char];
+
+    fn deref(&self) -> &[*const c_char] {
+        &self.pointers
+    }
+}
+
+/// The kind of `#include` directives a directory is searched for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IncludeKind {
+    /// The directory is only searched for `#include "..."` directives (e.g., a
+    /// directory added with `-iquote`).
+    Quote,
+    /// The directory is searched for both `#include "..."` and `#include <...>`
+    /// directives (e.g., a directory added with `-I` or `-isystem`).
+    Angle,
+}
+
+/// A directory searched for headers.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IncludeDirectory {
+    /// The path to this directory.
+    pub path: PathBuf,
+    /// The kind of `#include` directives this directory is searched for.
+    pub kind: IncludeKind,
+    /// Whether this directory is searched for framework headers (e.g.,
+    /// `#include <Foo/Foo.h>` for `Foo.framework/Headers/Foo.h`) instead of
+    /// headers.
+    pub framework: bool,
+}
+
+/// The header search configuration of a compiler for a language.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct IncludeSearch {
+    /// The directories searched for headers in the order they are searched
+    /// (the directories only searched for `#include "..."` directives are
+    /// searched first).
+    pub directories: Vec<IncludeDirectory>,
+    /// Whether the standard system directories (including the directory
+    /// containing the compiler's builtin headers) are not searched (i.e.,
+    /// `-nostdinc` or `--no-standard-includes` was provided).
+    pub nostdinc: bool,
+    /// Whether the standard system directories (but not the directory
+    /// containing the compiler's builtin headers) are not searched (i.e.,
+    /// `-nostdlibinc` was provided).
+    pub nostdlibinc: bool,
+    /// Whether the standard C++ library directories are not searched (i.e.,
+    /// `-nostdinc++` was provided or implied by `-nostdinc` or `-nostdlibinc`).
+    pub nostdincxx: bool,
+}
+
+impl IncludeSearch {
+    /// Returns the paths to the directories searched for `#include <...>`
+    /// directives in the order they are searched.
+    pub fn angle_paths(&self) -> Vec<PathBuf> {
+        self.directories
+            .iter()
+            .filter(|d| d.kind == IncludeKind::Angle)
+            .map(|d| d.path.clone())
+            .collect()
+    }
+}
+
 /// A `clang` executable.
 #[derive(Clone, Debug)]
 pub struct Clang {
-    /// The path to this `clang` executable.
+    /// The path to this `clang` executable (or to the wrapper script it was
+    /// found as, see [`Clang::wrapper`]).
     pub path: PathBuf,
     /// The version of this `clang` executable if it could be parsed.
     pub version: Option<CXVersion>,
@@ -33,17 +152,140 @@ pub struct Clang {
 
 impl Clang {
     fn new(path: impl AsRef<Path>, args: &[String]) -> Self {
+        let search_paths =
+            |language| parse_include_search(path.as_ref(), language, args).map(|s| s.angle_paths());
         Self {
             path: path.as_ref().into(),
             version: parse_version(path.as_ref()),
-            c_search_paths: parse_search_paths(path.as_ref(), "c", args),
-            cpp_search_paths: parse_search_paths(path.as_ref(), "c++", args),
+            c_search_paths: search_paths("c"),
+            cpp_search_paths: search_paths("c++"),
+        }
+    }
+
+    /// Returns the arguments a `clang` executable found by [`Clang::find`]
+    /// with the supplied arguments is queried with (the supplied arguments
+    /// followed by any arguments provided by a Yocto SDK environment such as
+    /// `--sysroot`) which should also be used with `libclang`.
+    pub fn args(args: &[String]) -> Vec<String> {
+        let mut args = args.to_vec();
+        if let Some((_, extra)) = Clang::find_yocto() {
+            args.extend(extra);
         }
+        args
+    }
+
+    /// Returns the arguments a `clang` executable found by
+    /// [`Clang::find_for_bindgen`] with the supplied arguments is queried with
+    /// (see [`Clang::args`]).
+    pub fn args_for_bindgen(args: &[String]) -> Vec<String> {
+        let mut args = args.to_vec();
+        args.extend(bindgen_extra_clang_args());
+        Clang::args(&args)
+    }
+
+    /// Returns whether the path to this `clang` executable is a wrapper script
+    /// (e.g., a `clang.cmd` batch script or a shell script in a hermetic
+    /// toolchain) which runs the actual `clang` executable.
+    ///
+    /// The version and search paths of this `clang` executable are queried
+    /// through the wrapper script (with the interpreter for the wrapper script)
+    /// since it may add arguments of its own (e.g., `--sysroot`).
+    pub fn wrapper(&self) -> bool {
+        interpreter(&self.path).is_some()
+    }
+
+    /// Returns the path to the `clang` executable run by the wrapper script if
+    /// the path to this `clang` executable is a wrapper script (see
+    /// [`Clang::wrapper`]) and the path could be determined.
+    ///
+    /// The supplied arguments should be the arguments this `clang` executable
+    /// was queried with (see [`Clang::args`]).
+    pub fn resolved_path(&self, args: &[String]) -> Option<PathBuf> {
+        self.wrapper()
+            .then(|| parse_executable(&self.path, args))
+            .flatten()
+    }
+
+    /// Returns the header search configuration of this `clang` executable for
+    /// C if it could be parsed ([`Clang::c_search_paths`] only contains the
+    /// directories searched for `#include <...>` directives).
+    ///
+    /// The supplied arguments should be the arguments this `clang` executable
+    /// was queried with (see [`Clang::args`]).
+    pub fn c_include_search(&self, args: &[String]) -> Option<IncludeSearch> {
+        parse_include_search(&self.path, "c", args)
+    }
+
+    /// Returns the header search configuration of this `clang` executable for
+    /// C++ if it could be parsed ([`Clang::cpp_search_paths`] only contains
+    /// the directories searched for `#include <...>` directives).
+    ///
+    /// The supplied arguments should be the arguments this `clang` executable
+    /// was queried with (see [`Clang::args`]).
+    pub fn cpp_include_search(&self, args: &[String]) -> Option<IncludeSearch> {
+        parse_include_search(&self.path, "c++", args)
+    }
+
+    /// Returns the `clang` executable (if any) and the arguments provided by a
+    /// Yocto SDK environment (as set up by an `environment-setup-*` script).
+    ///
+    /// These environments are recognized by the `OECORE_TARGET_SYSROOT`
+    /// environment variable which provides the target sysroot. The `clang`
+    /// executable and its arguments are provided by the `CLANGCC` environment
+    /// variable or by the `CC` environment variable if it names a `clang`
+    /// executable (e.g., `x86_64-poky-linux-clang -m64 --sysroot=...`).
+    fn find_yocto() -> Option<(Option<PathBuf>, Vec<String>)> {
+        let sysroot = env::var("OECORE_TARGET_SYSROOT").ok()?;
+
+        let command = env::var("CLANGCC")
+            .ok()
+            .or_else(|| env::var("CC").ok().filter(|c| c.contains("clang")))
+            .unwrap_or_default();
+
+        let mut words = command.split_whitespace();
+        let path = words.next().and_then(|name| {
+            let p = Path::new(name);
+            if p.is_absolute() && p.is_file() && is_executable(p).unwrap_or(false) {
+                return Some(p.into());
+            }
+
+            let pattern = Pattern::escape(name);
+            let paths = env::var("PATH").unwrap_or_default();
+            env::split_paths(&paths).find_map(|p| find(&p, &[&pattern]))
+        });
+
+        let mut args = words.map(|w| w.to_string()).collect::<Vec<_>>();
+        if !args.iter().any(|a| a.starts_with("--sysroot")) {
+            args.push(format!("--sysroot={}", sysroot));
+        }
+
+        Some((path, args))
+    }
+
+    /// Returns a `clang` executable if one can be found, taking into account
+    /// the extra arguments `bindgen` will pass to `libclang`.
+    ///
+    /// This is the same as [`Clang::find`] except that the `--target`,
+    /// `--sysroot`, and `-isysroot` arguments (and their values) in the
+    /// `BINDGEN_EXTRA_CLANG_ARGS_<target>` or `BINDGEN_EXTRA_CLANG_ARGS`
+    /// environment variable are appended to the supplied arguments so that the
+    /// `clang` executable and search paths found agree with what `bindgen`
+    /// will actually use. As with `bindgen`, the target is provided by the
+    /// `TARGET` environment variable (which Cargo sets for build scripts) and
+    /// both the target itself and the target with dashes replaced by
+    /// underscores are recognized (e.g., `BINDGEN_EXTRA_CLANG_ARGS_x86_64-pc-windows-msvc`
+    /// and `BINDGEN_EXTRA_CLANG_ARGS_x86_64_pc_windows_msvc`).
+    pub fn find_for_bindgen(path: Option<&Path>, args: &[String]) -> Option<Clang> {
+        let mut args = args.to_vec();
+        args.extend(bindgen_extra_clang_args());
+        Clang::find(path, &args)
     }
 
     /// Returns a `clang` executable if one can be found.
     ///
     /// If the `CLANG_PATH` environment variable is set, that is the instance of
+    /// `clang` used. Otherwise, if running in a Yocto SDK environment which
+    /// provides a `clang` executable (see below), that is the instance of
     /// `clang` used. Otherwise, these directories are searched in order:
     ///
     ///   1. The supplied path (if provided)
@@ -58,7 +300,26 @@ impl Clang {
     /// like `x86_64-unknown-linux-gnu`) then this method will prefer a
     /// target-prefixed instance of `clang` (e.g.,
     /// `x86_64-unknown-linux-gnu-clang` for the above example).
+    ///
+    /// ## Yocto SDKs
+    ///
+    /// If the `OECORE_TARGET_SYSROOT` environment variable is set (as it is by
+    /// the `environment-setup-*` scripts of Yocto SDKs), the target `clang`
+    /// executable and its arguments are taken from the `CLANGCC` environment
+    /// variable (or the `CC` environment variable if it names a `clang`
+    /// executable). These arguments are appended to the supplied arguments
+    /// along with a `--sysroot` argument for the target sysroot (if not already
+    /// present) so that the search paths are those of the target. The resulting
+    /// arguments are returned by [`Clang::args`].
     pub fn find(path: Option<&Path>, args: &[String]) -> Option<Clang> {
+        let mut args = args.to_vec();
+        let mut yocto = None;
+        if let Some((path, extra)) = Clang::find_yocto() {
+            args.extend(extra);
+            yocto = path;
+        }
+        let args = &args[..];
+
         if let Ok(path) = env::var("CLANG_PATH") {
             let p = Path::new(&path);
             if p.is_file() && is_executable(p).unwrap_or(false) {
@@ -68,12 +329,18 @@ impl Clang {
             }
         }
 
+        if let Some(path) = yocto {
+            return Some(Clang::new(path, args));
+        }
+
         // Determine the cross-compilation target, if any.
 
         let mut target = None;
         for i in 0..args.len() {
             if (args[i] == "-target" || args[i] == "--target") && i + 1 < args.len() {
-                target = Some(&args[i + 1]);
+                target = Some(&args[i + 1][..]);
+            } else if let Some(value) = args[i].strip_prefix("--target=") {
+                target = Some(value);
             }
         }
 
@@ -129,7 +396,11 @@ impl Clang {
 
         let default = format!("clang{}", env::consts::EXE_SUFFIX);
         let versioned = format!("clang-[0-9]*{}", env::consts::EXE_SUFFIX);
-        let patterns = &[&default[..], &versioned[..]];
+        let mut patterns = vec![&default[..], &versioned[..]];
+        if cfg!(windows) {
+            patterns.extend(["clang.cmd", "clang.bat"]);
+        }
+        let patterns = &patterns[..];
         for path in paths {
             if let Some(path) = find(&path, patterns) {
                 return Some(Clang::new(path, args));
@@ -140,10 +411,568 @@ impl Clang {
     }
 }
 
+/// A GCC-compatible C compiler (e.g., `gcc` or `cc`) used as a fallback source
+/// of system header search paths.
+///
+/// This is intended for systems where a `libclang` shared library is available
+/// but a `clang` executable is not (e.g., minimal distributions which only
+/// package `libclang`). The search paths reported by such a compiler are not
+/// guaranteed to match those `clang` would use (e.g., GCC reports its own
+/// builtin header directory), so [`Clang`] should be preferred whenever one can
+/// be found.
+#[derive(Clone, Debug)]
+pub struct GccFallback {
+    /// The path to this C compiler executable.
+    pub path: PathBuf,
+    /// The directories searched by this C compiler for C headers if they could
+    /// be parsed.
+    pub c_search_paths: Option<Vec<PathBuf>>,
+    /// The directories searched by this C compiler for C++ headers if they
+    /// could be parsed.
+    pub cpp_search_paths: Option<Vec<PathBuf>>,
+}
+
+impl GccFallback {
+    fn new(path: impl AsRef<Path>) -> Self {
+        let c_include_search = parse_include_search(path.as_ref(), "c", &[]);
+        let cpp_include_search = parse_include_search(path.as_ref(), "c++", &[]);
+        Self {
+            path: path.as_ref().into(),
+            c_search_paths: c_include_search.map(|s| s.angle_paths()),
+            cpp_search_paths: cpp_include_search.map(|s| s.angle_paths()),
+        }
+    }
+
+    /// Returns a GCC-compatible C compiler if one can be found.
+    ///
+    /// This is never used by [`Clang::find`] and must be explicitly requested.
+    /// These executables are tried in order:
+    ///
+    ///   1. The executable in the `CC` environment variable (if set)
+    ///   2. `gcc`
+    ///   3. `cc`
+    ///
+    /// Each executable may be a full path or a name to search for in the
+    /// directories in the system's `PATH` environment variable.
+    pub fn find() -> Option<GccFallback> {
+        let mut names = vec![];
+        if let Ok(cc) = env::var("CC")
+            && !cc.is_empty()
+        {
+            names.push(cc);
+        }
+        names.push(format!("gcc{}", env::consts::EXE_SUFFIX));
+        names.push(format!("cc{}", env::consts::EXE_SUFFIX));
+
+        let paths = env::var("PATH")
+            .map(|p| env::split_paths(&p).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        for name in &names {
+            let p = Path::new(name);
+            if p.is_absolute() && p.is_file() && is_executable(p).unwrap_or(false) {
+                return Some(GccFallback::new(p));
+            }
+
+            let pattern = Pattern::escape(name);
+            for path in &paths {
+                if let Some(path) = find(path, &[&pattern]) {
+                    return Some(GccFallback::new(path));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// A buffer of tokens owned by this crate which disposes of the tokens when
+/// dropped.
+///
+/// Tokens returned by `clang_tokenize` (or `clang_getToken`) must be disposed of
+/// with `clang_disposeTokens` using the same translation unit that produced
+/// them and before that translation unit is disposed of. This type keeps the
+/// translation unit together with the tokens so that the tokens are always
+/// disposed of correctly and borrows the handle to the translation unit for
+/// the lifetime `'tu` so that the tokens can't outlive the variable holding
+/// that handle.
+///
+/// Note that `CXTranslationUnit` handles are plain pointers, so this type can't
+/// prevent the translation unit from being disposed of early with an explicit
+/// call to `clang_disposeTranslationUnit` (which is why the constructors are
+/// `unsafe`).
+#[derive(Debug)]
+pub struct Tokens<'tu> {
+    tu: CXTranslationUnit,
+    tokens: *mut CXToken,
+    len: c_uint,
+    _marker: PhantomData<&'tu CXTranslationUnit>,
+}
+
+impl<'tu> Tokens<'tu> {
+    /// Tokenizes the supplied source range in the supplied translation unit.
+    ///
+    /// # Safety
+    ///
+    /// `tu` must be a valid translation unit which is not disposed of until
+    /// after the returned buffer is dropped.
+    pub unsafe fn tokenize(tu: &'tu CXTranslationUnit, range: CXSourceRange) -> Self {
+        let mut tokens = ptr::null_mut();
+        let mut len = 0;
+        unsafe { clang_tokenize(*tu, range, &mut tokens, &mut len) };
+        Self {
+            tu: *tu,
+            tokens,
+            len,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the single token which starts at the supplied source location in
+    /// the supplied translation unit if there is one (`libclang` 7.0 and later).
+    ///
+    /// # Safety
+    ///
+    /// `tu` must be a valid translation unit which is not disposed of until
+    /// after the returned buffer is dropped.
+    pub unsafe fn get(tu: &'tu CXTranslationUnit, location: CXSourceLocation) -> Option<Self> {
+        let token = unsafe { clang_getToken(*tu, location) };
+        (!token.is_null()).then_some(Self {
+            tu: *tu,
+            tokens: token,
+            len: 1,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the translation unit that produced these tokens.
+    pub fn translation_unit(&self) -> CXTranslationUnit {
+        self.tu
+    }
+
+    /// Returns the cursors (as determined by `clang_annotateTokens`) for these
+    /// tokens.
+    pub fn annotate(&self) -> Vec<CXCursor> {
+        let mut cursors = vec![CXCursor::default(); self.len as usize];
+        if !self.tokens.is_null() {
+            unsafe { clang_annotateTokens(self.tu, self.tokens, self.len, cursors.as_mut_ptr()) };
+        }
+        cursors
+    }
+}
+
+impl ops::Deref for Tokens<'_> {
+    type Target = [CXToken];
+
+    fn deref(&self) -> &[CXToken] {
+        if self.tokens.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.tokens, self.len as usize) }
+        }
+    }
+}
+
+impl Drop for Tokens<'_> {
+    fn drop(&mut self) {
+        if !self.tokens.is_null() {
+            unsafe { clang_disposeTokens(self.tu, self.tokens, self.len) };
+        }
+    }
+}
+
+/// A set of in-memory files to be passed to `libclang` as unsaved files which
+/// owns the buffers referenced by the `CXUnsavedFile`s.
+///
+/// `CXUnsavedFile`s only contain pointers to the filenames and contents of the
+/// files, so those buffers must outlive any use of the `CXUnsavedFile`s by
+/// `libclang` (including reparsing the translation unit). This type keeps the
+/// buffers together with the `CXUnsavedFile`s so they can't be freed early.
+#[derive(Debug)]
+pub struct UnsavedFiles {
+    files: Vec<CXUnsavedFile>,
+    _buffers: Vec<(CString, Vec<u8>)>,
+}
+
+impl UnsavedFiles {
+    /// Returns a set of in-memory files with the supplied paths and contents.
+    ///
+    /// The files are ordered by path. An error is returned if a path can't be
+    /// converted into a C string (e.g., because it contains a null byte).
+    pub fn new(files: HashMap<PathBuf, Vec<u8>>) -> Result<Self, String> {
+        let mut files = files.into_iter().collect::<Vec<_>>();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let buffers = files
+            .into_iter()
+            .map(|(path, contents)| {
+                let filename = path
+                    .to_str()
+                    .and_then(|p| CString::new(p).ok())
+                    .ok_or_else(|| format!("invalid unsaved file path: {}", path.display()))?;
+                Ok((filename, contents))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let files = buffers
+            .iter()
+            .map(|(filename, contents)| CXUnsavedFile {
+                Filename: filename.as_ptr(),
+                Contents: contents.as_ptr() as *const _,
+                Length: contents.len() as _,
+            })
+            .collect();
+
+        Ok(Self {
+            files,
+            _buffers: buffers,
+        })
+    }
+
+    /// Returns a pointer to the `CXUnsavedFile`s (as expected by functions
+    /// such as `clang_parseTranslationUnit`).
+    pub fn as_mut_ptr(&mut self) -> *mut CXUnsavedFile {
+        self.files.as_mut_ptr()
+    }
+}
+
+impl ops::Deref for UnsavedFiles {
+    type Target = [CXUnsavedFile];
+
+    fn deref(&self) -> &[CXUnsavedFile] {
+        &self.files
+    }
+}
+
+/// Runs the executables queried by this module (e.g., `clang --version`).
+///
+/// The default implementation ([`CommandRunner`]) runs the executables as
+/// child processes. Another implementation can be installed with
+/// [`set_process_runner`] (e.g., to simulate the output of compilers which
+/// aren't installed in tests). Closures with the same signature as
+/// [`ProcessRunner::run`] implement this trait.
+pub trait ProcessRunner: Send + Sync {
+    /// Runs the supplied executable with the supplied arguments and returns
+    /// the `stdout` and `stderr` output or an error if it could not be run.
+    fn run(&self, executable: &str, arguments: &[&str]) -> Result<(String, String), String>;
+}
+
+impl<F> ProcessRunner for F
+where
+    F: Fn(&str, &[&str]) -> Result<(String, String), String> + Send + Sync,
+{
+    fn run(&self, executable: &str, arguments: &[&str]) -> Result<(String, String), String> {
+        self(executable, arguments)
+    }
+}
+
+/// Runs executables as child processes.
+///
+/// The executables are run with the `C` locale so that their output (e.g., the
+/// header search path listing printed by `clang -v`) is not localized.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CommandRunner;
+
+impl ProcessRunner for CommandRunner {
+    fn run(&self, executable: &str, arguments: &[&str]) -> Result<(String, String), String> {
+        Command::new(executable)
+            .args(arguments)
+            .env("LC_ALL", "C")
+            .env("LANG", "C")
+            .env("LANGUAGE", "C")
+            .output()
+            .map(|o| {
+                let stdout = String::from_utf8_lossy(&o.stdout).into_owned();
+                let stderr = String::from_utf8_lossy(&o.stderr).into_owned();
+                (stdout, stderr)
+            })
+            .map_err(|e| format!("could not run executable `{}`: {}", executable, e))
+    }
+}
+
+/// The process runner installed with `set_process_runner` (if any).
+static PROCESS_RUNNER: RwLock<Option<Arc<dyn ProcessRunner>>> = RwLock::new(None);
+
 //================================================
 // Functions
 //================================================
 
+/// Sets the process runner used to run the executables queried by this module
+/// and returns the previous process runner.
+///
+/// If `None` is supplied, executables are run as child processes (see
+/// [`CommandRunner`]).
+pub fn set_process_runner(
+    runner: Option<Arc<dyn ProcessRunner>>,
+) -> Option<Arc<dyn ProcessRunner>> {
+    let mut global = PROCESS_RUNNER
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    mem::replace(&mut *global, runner)
+}
+
+/// Parses the source file at the supplied path with the supplied arguments
+/// and in-memory files using `clang_parseTranslationUnit2` and returns the
+/// translation unit.
+///
+/// This takes care of passing the pointers to and the lengths of the arguments
+/// and in-memory files (which must outlive the translation unit when
+/// reparsing it, see [`UnsavedFiles`]). An error is returned if the path can't
+/// be converted into a C string or if the source file can't be parsed.
+///
+/// # Safety
+///
+/// `index` must be a valid index.
+pub unsafe fn parse_translation_unit(
+    index: CXIndex,
+    path: &Path,
+    arguments: &Arguments,
+    unsaved: Option<&mut UnsavedFiles>,
+    flags: CXTranslationUnit_Flags,
+) -> Result<CXTranslationUnit, CXErrorCode> {
+    let path = path.to_str().and_then(|p| CString::new(p).ok());
+    let path = path.ok_or(CXError_InvalidArguments)?;
+
+    let (unsaved, n_unsaved) = match unsaved {
+        Some(unsaved) => (unsaved.as_mut_ptr(), unsaved.len()),
+        None => (ptr::null_mut(), 0),
+    };
+
+    let mut tu = ptr::null_mut();
+    let code = unsafe {
+        clang_parseTranslationUnit2(
+            index,
+            path.as_ptr(),
+            arguments.as_ptr(),
+            arguments.len() as c_int,
+            unsaved,
+            n_unsaved as c_uint,
+            flags,
+            &mut tu,
+        )
+    };
+
+    if code == CXError_Success {
+        Ok(tu)
+    } else {
+        Err(code)
+    }
+}
+
+/// Returns the serialized virtual file system overlay (as expected by the
+/// `-ivfsoverlay` argument) which maps the supplied virtual paths to the
+/// supplied real paths.
+///
+/// This uses `clang_VirtualFileOverlay_writeToBuffer` and takes care of freeing
+/// the buffer it returns. Both the virtual paths and the real paths must be
+/// absolute. If `case_sensitive` is provided, the case-sensitivity of the
+/// overlay is set accordingly.
+///
+/// Unlike [`UnsavedFiles`], an overlay can only redirect paths to other files
+/// on disk, so it is useful for presenting generated files (e.g., in a
+/// temporary directory) at the paths expected by the parsed code.
+///
+/// With the `runtime` Cargo feature, `CXError_Failure` is returned if the
+/// functions this uses aren't loaded (e.g., if no `libclang` shared library is
+/// loaded).
+pub fn virtual_file_overlay(
+    mappings: &HashMap<PathBuf, PathBuf>,
+    case_sensitive: Option<bool>,
+) -> Result<Vec<u8>, CXErrorCode> {
+    #[cfg(feature = "runtime")]
+    {
+        let loaded = [
+            crate::clang_VirtualFileOverlay_create::is_loaded(),
+            crate::clang_VirtualFileOverlay_addFileMapping::is_loaded(),
+            crate::clang_VirtualFileOverlay_setCaseSensitivity::is_loaded(),
+            crate::clang_VirtualFileOverlay_writeToBuffer::is_loaded(),
+            crate::clang_VirtualFileOverlay_dispose::is_loaded(),
+        ];
+        if loaded.contains(&false) {
+            return Err(crate::CXError_Failure);
+        }
+    }
+
+    let cstring = |path: &Path| path.to_str().and_then(|p| CString::new(p).ok());
+
+    let mut mappings = mappings
+        .iter()
+        .map(|(v, r)| Some((cstring(v)?, cstring(r)?)))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(CXError_InvalidArguments)?;
+    mappings.sort();
+
+    let check = |code| {
+        if code == CXError_Success {
+            Ok(())
+        } else {
+            Err(code)
+        }
+    };
+
+    unsafe {
+        let overlay = clang_VirtualFileOverlay_create(0);
+
+        let result = (|| {
+            for (virtual_path, real_path) in &mappings {
+                check(clang_VirtualFileOverlay_addFileMapping(
+                    overlay,
+                    virtual_path.as_ptr(),
+                    real_path.as_ptr(),
+                ))?;
+            }
+
+            if let Some(case_sensitive) = case_sensitive {
+                let case_sensitive = c_int::from(case_sensitive);
+                check(clang_VirtualFileOverlay_setCaseSensitivity(
+                    overlay,
+                    case_sensitive,
+                ))?;
+            }
+
+            let mut buffer = ptr::null_mut();
+            let mut size = 0;
+            check(clang_VirtualFileOverlay_writeToBuffer(
+                overlay,
+                0,
+                &mut buffer,
+                &mut size,
+            ))?;
+
+            if buffer.is_null() {
+                return Ok(vec![]);
+            }
+
+            let bytes = slice::from_raw_parts(buffer as *const u8, size as usize).to_vec();
+            free(buffer as *mut _);
+            Ok(bytes)
+        })();
+
+        clang_VirtualFileOverlay_dispose(overlay);
+        result
+    }
+}
+
+/// Frees a buffer allocated by `libclang`.
+///
+/// `libclang` may not use the same heap as this process (e.g., if it uses
+/// another C runtime on Windows), so `clang_free` is used if the loaded
+/// `libclang` shared library provides it regardless of the enabled `clang_*`
+/// Cargo features.
+unsafe fn free(buffer: *mut libc::c_void) {
+    #[cfg(feature = "runtime")]
+    if let Some(library) = crate::get_library().or_else(crate::get_global_library) {
+        type Free = unsafe extern "C" fn(*mut libc::c_void);
+        // SAFETY: `clang_free` has this signature.
+        match unsafe { library.get_function::<Free>("clang_free") } {
+            Some(clang_free) => unsafe { clang_free(buffer) },
+            None => unsafe { libc::free(buffer) },
+        }
+        return;
+    }
+
+    #[cfg(feature = "clang_3_7")]
+    unsafe {
+        super::clang_free(buffer)
+    };
+    // `clang_free` is not available before `libclang` 3.7 and these buffers
+    // were allocated with `malloc` instead.
+    #[cfg(not(feature = "clang_3_7"))]
+    unsafe {
+        libc::free(buffer)
+    };
+}
+
+/// Converts the value returned by a `libclang` type layout query (e.g.,
+/// `clang_Type_getSizeOf`) into a `Result`.
+///
+/// These functions return negative `CXTypeLayoutError` values on failure, so
+/// a negative value is returned as an error and any other value is returned as
+/// the size, alignment, or offset.
+///
+/// # Example
+///
+/// ```
+/// # use clang_sys::*;
+/// assert_eq!(support::type_layout(32), Ok(32));
+/// assert_eq!(support::type_layout(-2), Err(CXTypeLayoutError_Incomplete));
+/// ```
+pub fn type_layout(value: c_longlong) -> Result<u64, CXTypeLayoutError> {
+    if value >= 0 {
+        Ok(value as u64)
+    } else {
+        Err(CXTypeLayoutError::try_from(value).unwrap_or(CXTypeLayoutError_Invalid))
+    }
+}
+
+/// Splits the supplied string into arguments as a POSIX shell would (without
+/// expanding anything), which is how `bindgen` splits the value of the
+/// `BINDGEN_EXTRA_CLANG_ARGS` environment variable.
+fn split_args(string: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let arg = arg.get_or_insert_with(String::new);
+                arg.extend(chars.by_ref().take_while(|c| *c != '\''));
+            }
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => arg.push(c),
+                            Some(c) => arg.extend(['\\', c]),
+                            None => arg.push('\\'),
+                        },
+                        c => arg.push(c),
+                    }
+                }
+            }
+            '\\' => arg.get_or_insert_with(String::new).extend(chars.next()),
+            c if c.is_whitespace() => args.extend(arg.take()),
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    args
+}
+
+/// Returns the `--target`, `--sysroot`, and `-isysroot` arguments (and their
+/// values) in the extra `clang` arguments `bindgen` reads from the environment.
+fn bindgen_extra_clang_args() -> Vec<String> {
+    let mut names = vec![];
+    if let Ok(target) = env::var("TARGET") {
+        names.push(format!("BINDGEN_EXTRA_CLANG_ARGS_{}", target));
+        names.push(format!(
+            "BINDGEN_EXTRA_CLANG_ARGS_{}",
+            target.replace('-', "_")
+        ));
+    }
+    names.push("BINDGEN_EXTRA_CLANG_ARGS".into());
+
+    let Some(value) = names.iter().find_map(|n| env::var(n).ok()) else {
+        return vec![];
+    };
+
+    let mut args = vec![];
+    let mut split = split_args(&value).into_iter();
+    while let Some(arg) = split.next() {
+        if matches!(&arg[..], "-target" | "--target" | "--sysroot" | "-isysroot") {
+            args.extend(split.next().map(|value| [arg, value]).into_iter().flatten());
+        } else if ["--target=", "--sysroot=", "-isysroot"]
+            .iter()
+            .any(|p| arg.starts_with(p))
+        {
+            args.push(arg);
+        }
+    }
+    args
+}
+
 /// Returns the first match to the supplied glob patterns in the supplied
 /// directory if there are any matches.
 fn find(directory: &Path, patterns: &[&str]) -> Option<PathBuf> {
@@ -182,23 +1011,72 @@ fn is_executable(_: &Path) -> io::Result<bool> {
     Ok(true)
 }
 
-/// Attempts to run an executable, returning the `stdout` and `stderr` output if
+/// Attempts to run an executable with the installed process runner (see
+/// `set_process_runner`), returning the `stdout` and `stderr` output if
 /// successful.
 fn run(executable: &str, arguments: &[&str]) -> Result<(String, String), String> {
-    Command::new(executable)
-        .args(arguments)
-        .output()
-        .map(|o| {
-            let stdout = String::from_utf8_lossy(&o.stdout).into_owned();
-            let stderr = String::from_utf8_lossy(&o.stderr).into_owned();
-            (stdout, stderr)
-        })
-        .map_err(|e| format!("could not run executable `{}`: {}", executable, e))
+    // The lock isn't held while running so the runner can replace itself.
+    let runner = PROCESS_RUNNER.read().map(|r| r.clone());
+    match runner.unwrap_or_else(|e| e.into_inner().clone()) {
+        Some(runner) => runner.run(executable, arguments),
+        None => CommandRunner.run(executable, arguments),
+    }
+}
+
+/// Returns the interpreter (and its arguments) which runs the supplied
+/// executable if it is a wrapper script rather than a binary executable.
+///
+/// Batch scripts (i.e., `.cmd` and `.bat` files) are run by `cmd /C` and
+/// scripts which start with an interpreter directive (e.g., `#!/bin/sh`) are
+/// run by that interpreter.
+fn interpreter(path: &Path) -> Option<Vec<String>> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    if extension.eq_ignore_ascii_case("cmd") || extension.eq_ignore_ascii_case("bat") {
+        return Some(vec!["cmd".into(), "/C".into()]);
+    }
+
+    let mut header = [0; 256];
+    let length = io::Read::read(&mut fs::File::open(path).ok()?, &mut header).ok()?;
+    let directive = header[..length].strip_prefix(b"#!")?;
+    let directive = String::from_utf8_lossy(directive);
+    let words = directive.lines().next()?.split_whitespace();
+    let words = words.map(String::from).collect::<Vec<_>>();
+    (!words.is_empty()).then_some(words)
 }
 
-/// Runs `clang`, returning the `stdout` and `stderr` output.
+/// Runs `clang` (with its interpreter if it is a wrapper script), returning the
+/// `stdout` and `stderr` output.
 fn run_clang(path: &Path, arguments: &[&str]) -> (String, String) {
-    run(&path.to_string_lossy(), arguments).unwrap()
+    let path = path.to_string_lossy();
+    match interpreter(Path::new(&*path)) {
+        Some(mut interpreter) => {
+            let executable = interpreter.remove(0);
+            let mut words = interpreter.iter().map(|w| &**w).collect::<Vec<_>>();
+            words.push(&path);
+            words.extend(arguments);
+            run(&executable, &words).unwrap()
+        }
+        None => run(&path, arguments).unwrap(),
+    }
+}
+
+/// Parses the path to the `clang` executable run by a wrapper script from the
+/// commands it prints with `-###` if possible.
+fn parse_executable(path: &Path, args: &[String]) -> Option<PathBuf> {
+    let mut clang_args = vec!["-###", "-E", "-x", "c", "-"];
+    clang_args.extend(args.iter().map(|s| &**s));
+    let output = run_clang(path, &clang_args).1;
+
+    // The frontend is invoked by the driver with its own path (e.g.,
+    // `"/usr/lib/llvm-18/bin/clang" "-cc1" ...`).
+    output.lines().find_map(|l| {
+        let mut words = l.trim().split("\" \"");
+        let executable = words.next()?.strip_prefix('"')?;
+        (words.next()? == "-cc1").then(|| executable.into())
+    })
 }
 
 /// Runs `llvm-config`, returning the `stdout` output if successful.
@@ -207,44 +1085,86 @@ fn run_llvm_config(arguments: &[&str]) -> Result<String, String> {
     run(&config, arguments).map(|(o, _)| o)
 }
 
-/// Parses a version number if possible, ignoring trailing non-digit characters.
-fn parse_version_number(number: &str) -> Option<c_int> {
-    number
-        .chars()
-        .take_while(|c| c.is_ascii_digit())
-        .collect::<String>()
-        .parse()
-        .ok()
-}
-
 /// Parses the version from the output of a `clang` executable if possible.
 fn parse_version(path: &Path) -> Option<CXVersion> {
     let output = run_clang(path, &["--version"]).0;
-    let start = output.find("version ")? + 8;
-    let mut numbers = output[start..].split_whitespace().next()?.split('.');
-    let major = numbers.next().and_then(parse_version_number)?;
-    let minor = numbers.next().and_then(parse_version_number)?;
-    let subminor = numbers.next().and_then(parse_version_number).unwrap_or(0);
+    let version = crate::version::parse_clang_version(&output)?;
+    let component = |index: usize| c_int::try_from(*version.get(index)?).ok();
     Some(CXVersion {
-        Major: major,
-        Minor: minor,
-        Subminor: subminor,
+        Major: component(0)?,
+        Minor: component(1)?,
+        Subminor: component(2).unwrap_or(0),
     })
 }
 
-/// Parses the search paths from the output of a `clang` executable if possible.
-fn parse_search_paths(path: &Path, language: &str, args: &[String]) -> Option<Vec<PathBuf>> {
+/// Parses the header search configuration from the output of a `clang` (or
+/// GCC-compatible) executable run with the supplied arguments if possible.
+fn parse_include_search(path: &Path, language: &str, args: &[String]) -> Option<IncludeSearch> {
     let mut clang_args = vec!["-E", "-x", language, "-", "-v"];
     clang_args.extend(args.iter().map(|s| &**s));
     let output = run_clang(path, &clang_args).1;
-    let start = output.find("#include <...> search starts here:")? + 34;
-    let end = output.find("End of search list.")?;
-    let paths = output[start..end].replace("(framework directory)", "");
-    Some(
-        paths
-            .lines()
-            .filter(|l| !l.is_empty())
-            .map(|l| Path::new(l.trim()).into())
-            .collect(),
-    )
+
+    let has = |names: &[&str]| args.iter().any(|a| names.contains(&a.as_str()));
+    let nostdinc = has(&["-nostdinc", "--no-standard-includes"]);
+    let nostdlibinc = has(&["-nostdlibinc"]);
+    Some(IncludeSearch {
+        directories: parse_include_search_output(&output)?,
+        nostdinc,
+        nostdlibinc,
+        nostdincxx: nostdinc || nostdlibinc || has(&["-nostdinc++"]),
+    })
+}
+
+/// Parses the `#include "..."` and `#include <...>` search paths listed in the
+/// verbose output of a `clang` (or GCC-compatible) executable if possible.
+///
+/// Although the executable is run with the `C` locale, this only relies on the
+/// untranslated `#include "..."` and `#include <...>` prefixes of the lines
+/// which start the listings and on the listed paths being indented (rather
+/// than on the English text of the surrounding lines) since some toolchains
+/// ignore the locale overrides. Both LF and CRLF line endings are accepted.
+fn parse_include_search_output(output: &str) -> Option<Vec<IncludeDirectory>> {
+    let mut directories = vec![];
+    let mut kind = None;
+    for line in output.lines() {
+        if line.starts_with("#include \"...\"") {
+            kind = Some(IncludeKind::Quote);
+            continue;
+        } else if line.starts_with("#include <...>") {
+            kind = Some(IncludeKind::Angle);
+            continue;
+        }
+
+        let Some(current) = kind else {
+            continue;
+        };
+
+        if !line.starts_with([' ', '\t']) {
+            if current == IncludeKind::Angle {
+                return Some(directories);
+            }
+
+            kind = None;
+            continue;
+        }
+
+        let line = line.trim();
+        let (line, framework) = match line.strip_suffix("(framework directory)") {
+            Some(line) => (line.trim_end(), true),
+            None => (line, false),
+        };
+
+        if !line.is_empty() {
+            let path = Path::new(line).into();
+            directories.push(IncludeDirectory {
+                path,
+                kind: current,
+                framework,
+            });
+        }
+    }
+
+    // The listing was not found or was not terminated (e.g., the output was
+    // truncated).
+    None
 }
@@ -0,0 +1,167 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Provides types and functions for using `libclang` from multiple threads.
+//!
+//! The handles returned by `libclang` are raw pointers (or structs containing
+//! raw pointers) so Rust considers them neither `Send` nor `Sync`. The rules
+//! `libclang` actually imposes on these handles are:
+//!
+//! | Handle | Moved between threads | Used by multiple threads at once |
+//! |--------|-----------------------|----------------------------------|
+//! | `CXIndex` | yes | yes (see [`SharedIndex`]) |
+//! | `CXTranslationUnit` | yes | no (see [`SendTranslationUnit`]) |
+//! | `CXCursor`, `CXType`, `CXToken`, `CXSourceLocation`, `CXSourceRange`, `CXFile`, `CXDiagnostic`, etc. | only with their translation unit | only with their translation unit |
+//! | `CXString` | yes | no |
+//!
+//! In other words, each translation unit (along with everything obtained from
+//! it) must only be used by one thread at a time, but different translation
+//! units (even ones created with the same index) may be parsed and used by
+//! different threads at the same time. The wrappers in this module have the
+//! same representation as the handles they wrap, so they can be used to move
+//! handles between threads without any overhead.
+//!
+//! A few functions change global state and must not be called while other
+//! threads are using `libclang` (e.g., `clang_toggleCrashRecovery` and
+//! `clang_enableStackTraces`), these calls can be made with [`serialize`].
+//!
+//! When the `runtime` Cargo feature is enabled, the loaded `libclang` shared
+//! library is stored in thread-local storage so it must also be made available
+//! to any other threads that use it (e.g., with `threading::spawn`) unless it
+//! was loaded for all threads (e.g., with `load_global`).
+
+use std::sync::{Mutex, PoisonError};
+
+use super::*;
+
+//================================================
+// Structs
+//================================================
+
+/// A `CXIndex` which may be moved to and shared between threads.
+///
+/// Translation units created with this index may be parsed by different
+/// threads at the same time.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct SharedIndex(CXIndex);
+
+// SAFETY: `libclang` allows using an index from multiple threads (see
+// `SharedIndex::new` for the requirements of the caller).
+unsafe impl Send for SharedIndex {}
+unsafe impl Sync for SharedIndex {}
+
+impl SharedIndex {
+    /// Wraps the supplied index so it may be moved to and shared between
+    /// threads.
+    ///
+    /// # Safety
+    ///
+    /// The supplied index must be valid and must not be disposed of while any
+    /// thread is still using it.
+    pub unsafe fn new(index: CXIndex) -> Self {
+        Self(index)
+    }
+
+    /// Returns the wrapped index.
+    pub fn get(self) -> CXIndex {
+        self.0
+    }
+}
+
+/// A `CXTranslationUnit` which may be moved to another thread.
+///
+/// A translation unit must only be used by one thread at a time, so this type
+/// is not `Sync`. Wrap it in [`Serialized`] to share it between threads.
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct SendTranslationUnit(CXTranslationUnit);
+
+// SAFETY: `libclang` allows using a translation unit from any thread as long as
+// only one thread uses it at a time (which `&mut` access ensures).
+unsafe impl Send for SendTranslationUnit {}
+
+impl SendTranslationUnit {
+    /// Wraps the supplied translation unit so it may be moved to another
+    /// thread.
+    ///
+    /// # Safety
+    ///
+    /// The supplied translation unit must be valid and nothing obtained from it
+    /// (e.g., cursors or tokens) may be used by the current thread after this
+    /// wrapper is moved to another thread.
+    pub unsafe fn new(tu: CXTranslationUnit) -> Self {
+        Self(tu)
+    }
+
+    /// Returns the wrapped translation unit.
+    pub fn get(&mut self) -> CXTranslationUnit {
+        self.0
+    }
+
+    /// Returns the wrapped translation unit, consuming this wrapper.
+    pub fn into_inner(self) -> CXTranslationUnit {
+        self.0
+    }
+}
+
+/// A value (e.g., a [`SendTranslationUnit`]) which may be shared between
+/// threads by serializing access to it.
+#[derive(Debug, Default)]
+pub struct Serialized<T>(Mutex<T>);
+
+impl<T> Serialized<T> {
+    /// Wraps the supplied value so access to it is serialized.
+    pub fn new(value: T) -> Self {
+        Self(Mutex::new(value))
+    }
+
+    /// Calls the supplied function with exclusive access to the wrapped value
+    /// and returns the result.
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.0.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Returns the wrapped value, consuming this wrapper.
+    pub fn into_inner(self) -> T {
+        self.0.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+//================================================
+// Functions
+//================================================
+
+/// The lock held while calling functions with [`serialize`].
+static GLOBAL: Mutex<()> = Mutex::new(());
+
+/// Calls the supplied function while no other function supplied to this
+/// function is being called by another thread and returns the result.
+///
+/// This should be used for calls which change the global state of `libclang`
+/// (e.g., `clang_toggleCrashRecovery` or `clang_enableStackTraces`) and
+/// for calls which are not thread-safe for other reasons (e.g., calls that
+/// use a translation unit which is not wrapped in [`Serialized`]). Note that
+/// this only serializes calls made with this function.
+pub fn serialize<R>(f: impl FnOnce() -> R) -> R {
+    let _guard = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+    f()
+}
+
+/// Spawns a new thread which uses the same `libclang` shared library as the
+/// current thread (if any).
+///
+/// This is a replacement for `std::thread::spawn` for threads that call
+/// `libclang` functions since the `libclang` shared library loaded with the
+/// `runtime` Cargo feature is stored in thread-local storage.
+#[cfg(feature = "runtime")]
+pub fn spawn<F, T>(f: F) -> std::thread::JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let library = get_library();
+    std::thread::spawn(move || {
+        set_library(library);
+        f()
+    })
+}
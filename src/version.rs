@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Parses the version numbers of Clang and LLVM.
+//!
+//! These are the parsers used by the build script of this crate to select
+//! `llvm-config` executables and `libclang` shared libraries and by
+//! `clang_sys::load` to detect the version of a `libclang` shared library
+//! (the same source is compiled into the build script). They are provided so
+//! that the build scripts of packages which depend on this crate (e.g., to parse
+//! `DEP_CLANG_VERSION` or the output of `llvm-config --version`) parse versions
+//! in exactly the same way.
+//!
+//! Versions are returned as their numeric components (e.g., `[17, 0, 6]`).
+//! Suffixes such as those of development builds (`17.0.6git`), release
+//! candidates (`18.1.0-rc1` or `18.1.0rc1`), and distribution packages
+//! (`14.0.0-1ubuntu1.1`) are ignored.
+
+use std::path::Path;
+
+/// Parses the leading version components in a string (e.g., `[17, 0, 6]` for
+/// `17.0.6git` or `17.0.6-1ubuntu1`).
+///
+/// Components are separated by `.` and parsing stops after the first component
+/// which contains anything other than digits (only the leading digits of that
+/// component are used) or at the first component which does not start with a
+/// digit or which doesn't fit in a `u32`. The returned version is therefore
+/// empty if the string doesn't start with a version.
+pub fn parse_components(version: &str) -> Vec<u32> {
+    let mut components = vec![];
+    for component in version.split('.') {
+        let end = component
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(component.len());
+        match component[..end].parse() {
+            Ok(number) => components.push(number),
+            Err(_) => break,
+        }
+        if end != component.len() {
+            break;
+        }
+    }
+    components
+}
+
+/// Parses the version in a `clang` version string (e.g., `[17, 0, 6]` for the
+/// string returned by `clang_getClangVersion` or printed by `clang --version`).
+///
+/// Vendor prefixes and suffixes are ignored, so all of the following are
+/// parsed as expected:
+///
+/// - `clang version 17.0.6`
+/// - `Ubuntu clang version 14.0.0-1ubuntu1.1`
+/// - `Apple clang version 15.0.0 (clang-1500.3.9.4)`
+/// - `Android (11349228, +pgo, +bolt, +lto, -mlgo, based on r487747e) clang version 17.0.2`
+pub fn parse_clang_version(version: &str) -> Option<Vec<u32>> {
+    let mut words = version.split_whitespace();
+    words.find(|w| *w == "version")?;
+    let version = parse_components(words.next()?);
+    (!version.is_empty()).then_some(version)
+}
+
+/// Parses the version printed by `llvm-config --version` (e.g., `[18, 1, 8]`
+/// for `18.1.8`, `18.1.8git`, or `18.1.8-rc1`).
+///
+/// Only the first non-empty line is used. If it doesn't start with a version,
+/// it is parsed like a `clang` version string instead (see
+/// `parse_clang_version`) since some wrappers print `LLVM version 18.1.8`.
+pub fn parse_llvm_config_version(output: &str) -> Option<Vec<u32>> {
+    let line = output.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let version = parse_components(line);
+    if version.is_empty() {
+        parse_clang_version(line)
+    } else {
+        Some(version)
+    }
+}
+
+/// Parses the version in the path to an `llvm-config` executable (e.g., `[17]`
+/// for `/usr/lib/llvm-17/bin/llvm-config`).
+///
+/// The version is taken from the filename of a versioned executable (e.g.,
+/// `llvm-config-17`) or otherwise from the first directory with a versioned
+/// name (e.g., `llvm-17` or Homebrew's `llvm@17`). Names which merely start
+/// with `llvm-config-`, `llvm-`, or `llvm@` but are not followed by a version
+/// (e.g., `llvm-project-build`) are ignored, but the directories of an
+/// executable named like `llvm-config-garbage` aren't considered.
+pub fn parse_llvm_config_path(path: &Path) -> Option<Vec<u32>> {
+    if let Some(name) = path.file_name().and_then(|n| n.to_str())
+        && let Some(rest) = name.strip_prefix("llvm-config-")
+    {
+        let version = parse_components(rest);
+        return (!version.is_empty()).then_some(version);
+    }
+
+    path.components().find_map(|c| {
+        let s = c.as_os_str().to_string_lossy();
+        let rest = s
+            .strip_prefix("llvm@")
+            .or_else(|| s.strip_prefix("llvm-"))?;
+        let version = parse_components(rest);
+        (!version.is_empty()).then_some(version)
+    })
+}
+
+/// Formats version components as a version string (e.g., `18.1.8` for
+/// `[18, 1, 8]`).
+pub fn format(version: &[u32]) -> String {
+    let components = version.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+    components.join(".")
+}
@@ -0,0 +1,132 @@
+#![cfg(feature = "audit")]
+
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use clang_sys::support::Clang;
+use clang_sys::*;
+
+/// Returns the directory containing the `clang-c` headers if they are
+/// available locally.
+fn find_include_directory() -> Option<PathBuf> {
+    let llvm_config = std::env::var("LLVM_CONFIG_PATH").unwrap_or_else(|_| "llvm-config".into());
+    let output = Command::new(llvm_config).arg("--includedir").output();
+    let output = output.ok().filter(|o| o.status.success());
+    let from_llvm_config = output.map(|o| String::from_utf8_lossy(&o.stdout).trim().into());
+
+    build_info::INCLUDEDIR
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(from_llvm_config)
+        .find(|d: &PathBuf| d.join("clang-c/Index.h").is_file())
+}
+
+extern "C" fn visit(cursor: CXCursor, _: CXCursor, data: CXClientData) -> CXChildVisitResult {
+    unsafe {
+        if clang_getCursorKind(cursor) == CXCursor_EnumConstantDecl {
+            let spelling = clang_getCursorSpelling(cursor);
+            let name = CStr::from_ptr(clang_getCString(spelling))
+                .to_string_lossy()
+                .into_owned();
+            clang_disposeString(spelling);
+
+            let constants = &mut *(data as *mut HashMap<String, i64>);
+            constants.insert(name, clang_getEnumConstantDeclValue(cursor));
+        }
+    }
+
+    CXChildVisit_Recurse
+}
+
+/// Returns the names and values of the enum constants in the `clang-c`
+/// headers in the supplied directory.
+fn parse_headers(directory: &Path) -> HashMap<String, i64> {
+    let mut headers = glob::glob(directory.join("clang-c/*.h").to_str().unwrap())
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect::<Vec<_>>();
+    headers.sort();
+
+    let contents = headers
+        .iter()
+        .map(|h| format!("#include \"{}\"\n", h.display()))
+        .collect::<String>();
+    let contents = CString::new(contents).unwrap();
+
+    let mut arguments = vec![format!("-I{}", directory.display())];
+    if let Some(paths) = Clang::find(None, &[]).and_then(|c| c.c_search_paths) {
+        arguments.extend(paths.iter().map(|p| format!("-isystem{}", p.display())));
+    }
+    let arguments = arguments
+        .into_iter()
+        .map(|a| CString::new(a).unwrap())
+        .collect::<Vec<_>>();
+    let arguments = arguments.iter().map(|a| a.as_ptr()).collect::<Vec<_>>();
+
+    let mut constants = HashMap::new();
+    unsafe {
+        let index = clang_createIndex(0, 0);
+        let mut unsaved = CXUnsavedFile {
+            Filename: c"audit.c".as_ptr(),
+            Contents: contents.as_ptr(),
+            Length: contents.as_bytes().len() as _,
+        };
+        let tu = clang_parseTranslationUnit(
+            index,
+            c"audit.c".as_ptr(),
+            arguments.as_ptr(),
+            arguments.len() as _,
+            &mut unsaved,
+            1,
+            0,
+        );
+        assert!(!tu.is_null());
+
+        let cursor = clang_getTranslationUnitCursor(tu);
+        let data = &mut constants as *mut HashMap<String, i64> as CXClientData;
+        clang_visitChildren(cursor, visit, data);
+
+        clang_disposeTranslationUnit(tu);
+        clang_disposeIndex(index);
+    }
+
+    constants
+}
+
+#[test]
+fn test_constants() {
+    let Some(directory) = find_include_directory() else {
+        eprintln!("skipping audit, the `clang-c` headers could not be found");
+        return;
+    };
+
+    #[cfg(feature = "runtime")]
+    load().unwrap();
+
+    let headers = parse_headers(&directory);
+    assert!(
+        !headers.is_empty(),
+        "no enum constants found in {}",
+        directory.display()
+    );
+
+    // Constants which aren't in the headers (e.g., constants added in a later
+    // release of `libclang`) can't be audited.
+    let mismatches = audit::constants()
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let expected = *headers.get(name)?;
+            (value != expected).then(|| format!("{} = {} (expected {})", name, value, expected))
+        })
+        .collect::<Vec<_>>();
+
+    assert!(
+        mismatches.is_empty(),
+        "constants differ from the `clang-c` headers in {} (enable the `clang_*` Cargo feature \
+         for the version of these headers):\n  {}",
+        directory.display(),
+        mismatches.join("\n  "),
+    );
+}
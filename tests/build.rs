@@ -14,12 +14,20 @@ use tempfile::TempDir;
 #[path = "../build/macros.rs"]
 mod macros;
 
+#[path = "../build/audit.rs"]
+mod audit;
 #[path = "../build/common.rs"]
 mod common;
 #[path = "../build/dynamic.rs"]
 mod dynamic;
 #[path = "../build/static.rs"]
 mod r#static;
+#[path = "../src/version.rs"]
+mod version;
+
+/// The modules above are tested as they are compiled into the build script
+/// (see `build.rs`).
+const BUILD_SCRIPT: bool = true;
 
 #[derive(Debug, Default)]
 struct RunCommandMock {
@@ -83,12 +91,49 @@ impl Env {
             files: vec![],
             commands: Default::default(),
         }
+        .var("ANDROID_HOME", None)
+        .var("ANDROID_NDK", None)
+        .var("ANDROID_NDK_HOME", None)
+        .var("ANDROID_NDK_ROOT", None)
+        .var("ANDROID_SDK_ROOT", None)
+        .var("ASDF_DATA_DIR", None)
         .var("CLANG_PATH", None)
+        .var("CLANG_SYS_LIBCLANG_PATH", None)
+        .var("CLANG_SYS_NO_XCODE", None)
+        .var("CLANG_SYS_RECORD", None)
+        .var("CLANG_SYS_SELECTION", None)
+        .var("CLANG_SYS_VERSION_POLICY", None)
+        .var("CLANG_SYS_WARNINGS", None)
+        .var("HOME", None)
+        .var("HOST", None)
         .var("LD_LIBRARY_PATH", None)
+        .var("LIBCLANG_GLIBCXX_BASELINE", None)
+        .var("LIBCLANG_GLIBC_BASELINE", None)
         .var("LIBCLANG_PATH", None)
+        .var("LIBCLANG_PATH_DEBUG", None)
+        .var("LIBCLANG_PATH_RELEASE", None)
+        .var("LIBCLANG_PROBE", None)
         .var("LIBCLANG_STATIC_PATH", None)
+        .var("LIBCLANG_STATIC_PATTERN", None)
+        .var("LIBCLANG_STATIC_VERIFY", None)
+        .var("LIBCLANG_STUB_SONAME", None)
+        .var("LLVM_CONFIG_OUTPUT", None)
         .var("LLVM_CONFIG_PATH", None)
+        .var("LLVM_PATH", None)
+        .var("MISE_DATA_DIR", None)
         .var("PATH", None)
+        .var("PKG_CONFIG", None)
+        .var("PKG_CONFIG_ALLOW_CROSS", None)
+        .var("PKG_CONFIG_SYSROOT_DIR", None)
+        .var("PROFILE", None)
+        .var("PROTO_HOME", None)
+        .var("RUNNER_TOOL_CACHE", None)
+        .var("TARGET", None)
+        .var("VCPKGRS_TRIPLET", None)
+        .var("VCPKG_INSTALLATION_ROOT", None)
+        .var("VCPKG_INSTALLED_DIR", None)
+        .var("VCPKG_ROOT", None)
+        .var("XDG_DATA_HOME", None)
     }
 
     fn env(mut self, env: &str) -> Self {
@@ -138,6 +183,91 @@ impl Env {
         self.file(path, &contents)
     }
 
+    fn dylib(self, path: &str, cpu_type: u32, id: &str, dependencies: &[&str]) -> Self {
+        // Mach-O load command for a shared library.
+        fn command(kind: u32, name: &str) -> Vec<u8> {
+            let size = (24 + name.len() + 1).next_multiple_of(8);
+            let mut command = vec![0; size];
+            command[0..4].copy_from_slice(&kind.to_le_bytes());
+            command[4..8].copy_from_slice(&(size as u32).to_le_bytes());
+            command[8..12].copy_from_slice(&24u32.to_le_bytes());
+            command[24..24 + name.len()].copy_from_slice(name.as_bytes());
+            command
+        }
+
+        let mut commands = vec![command(0xD, id)];
+        commands.extend(dependencies.iter().map(|d| command(0xC, d)));
+
+        // Mach-O header.
+        let mut contents = vec![0; 32];
+        contents[0..4].copy_from_slice(&0xFEEDFACFu32.to_le_bytes());
+        contents[4..8].copy_from_slice(&cpu_type.to_le_bytes());
+        contents[16..20].copy_from_slice(&(commands.len() as u32).to_le_bytes());
+        contents.extend(commands.concat());
+
+        self.file(path, &contents)
+    }
+
+    fn so_with_version_needs(self, path: &str, needs: &[&str]) -> Self {
+        // String table.
+        let mut strings = b"\0libc.so.6\0".to_vec();
+        let mut names = vec![];
+        for need in needs {
+            names.push(strings.len() as u32);
+            strings.extend(need.as_bytes());
+            strings.push(0);
+        }
+        strings.resize(strings.len().next_multiple_of(8), 0);
+
+        // Version requirements (a single `Elf64_Verneed` with `Elf64_Vernaux`s).
+        let mut verneed = vec![];
+        verneed.extend(1u16.to_le_bytes());
+        verneed.extend((needs.len() as u16).to_le_bytes());
+        verneed.extend(1u32.to_le_bytes());
+        verneed.extend(16u32.to_le_bytes());
+        verneed.extend(0u32.to_le_bytes());
+        for (index, name) in names.iter().enumerate() {
+            let next = if index + 1 < names.len() { 16u32 } else { 0 };
+            verneed.extend([0; 8]);
+            verneed.extend(name.to_le_bytes());
+            verneed.extend(next.to_le_bytes());
+        }
+
+        // Section headers (null, `.dynstr`, and `.gnu.version_r`).
+        let section = |kind: u32, offset: usize, size: usize, link: u32, info: u32| {
+            let mut header = vec![0; 64];
+            header[4..8].copy_from_slice(&kind.to_le_bytes());
+            header[24..32].copy_from_slice(&(offset as u64).to_le_bytes());
+            header[32..40].copy_from_slice(&(size as u64).to_le_bytes());
+            header[40..44].copy_from_slice(&link.to_le_bytes());
+            header[44..48].copy_from_slice(&info.to_le_bytes());
+            header
+        };
+
+        let shoff = 64 + strings.len() + verneed.len();
+        let sections = [
+            section(0, 0, 0, 0, 0),
+            section(3, 64, strings.len(), 0, 0),
+            section(0x6FFFFFFE, 64 + strings.len(), verneed.len(), 1, 1),
+        ];
+
+        // ELF header.
+        let mut contents = vec![0; 64];
+        contents[0..6].copy_from_slice(&[127, 69, 76, 70, 2, 1]);
+        contents[0x28..0x30].copy_from_slice(&(shoff as u64).to_le_bytes());
+        contents[0x3A..0x3C].copy_from_slice(&64u16.to_le_bytes());
+        contents[0x3C..0x3E].copy_from_slice(&3u16.to_le_bytes());
+        contents.extend(strings);
+        contents.extend(verneed);
+        contents.extend(sections.concat());
+
+        self.file(path, &contents)
+    }
+
+    fn so_with_sections(self, path: &str, sections: &[(u32, u64, &[u8], u32)]) -> Self {
+        self.file(path, &elf(sections))
+    }
+
     fn command(self, command: &str, args: &[&str], response: &str) -> Self {
         let command = command.to_string();
         let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
@@ -249,13 +379,122 @@ impl Drop for Env {
     }
 }
 
+/// Returns the contents of a 64-bit ELF file with the supplied sections (the
+/// type, flags, contents, and link of each section) following a null section.
+fn elf(sections: &[(u32, u64, &[u8], u32)]) -> Vec<u8> {
+    let mut contents = vec![0; 64];
+    let mut headers = vec![0; 64];
+    for (kind, flags, data, link) in sections {
+        let mut header = vec![0; 64];
+        header[4..8].copy_from_slice(&kind.to_le_bytes());
+        header[8..16].copy_from_slice(&flags.to_le_bytes());
+        header[24..32].copy_from_slice(&(contents.len() as u64).to_le_bytes());
+        header[32..40].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        header[40..44].copy_from_slice(&link.to_le_bytes());
+        headers.extend(header);
+        contents.extend(*data);
+    }
+
+    // ELF header.
+    contents[0..6].copy_from_slice(&[127, 69, 76, 70, 2, 1]);
+    let shoff = contents.len() as u64;
+    contents[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+    contents[0x3A..0x3C].copy_from_slice(&64u16.to_le_bytes());
+    contents[0x3C..0x3E].copy_from_slice(&(sections.len() as u16 + 1).to_le_bytes());
+    contents.extend(headers);
+    contents
+}
+
+/// Returns the `.dynstr` and `.dynamic` sections of a 64-bit ELF shared
+/// library with the supplied `DT_SONAME`.
+fn elf_soname(soname: &str) -> [Vec<u8>; 2] {
+    let strings = [b"\0", soname.as_bytes(), b"\0"].concat();
+    let dynamic = [14u64, 1, 0, 0].map(u64::to_le_bytes).concat();
+    [strings, dynamic]
+}
+
 #[test]
 fn test_all() {
     // Run tests serially since they alter the environment.
 
     test_linux_directory_preference();
     test_linux_version_preference();
+    test_linux_versioned_filenames();
+    test_freebsd_versioned_filenames();
+    test_macos_versioned_filenames();
     test_linux_directory_and_version_preference();
+    test_linux_path_tiebreak();
+    test_linux_probe();
+    test_linux_probe_failure();
+    test_linux_probe_cross_compiling();
+    test_linux_runtime_requirements();
+    test_linux_soname_version();
+    test_linux_embedded_version();
+    #[cfg(target_os = "linux")]
+    test_build_stub();
+    test_candidate_ordering();
+    test_linux_llvm_config_output();
+    test_linux_runtime_path();
+    test_linux_profile_path();
+    test_linux_selection();
+    test_linux_selection_precedence();
+    test_linux_selection_policy();
+    test_version_policy();
+    test_selection_problems();
+    test_linux_android_ndk();
+    test_linux_android_ndk_revision_preference();
+    test_linux_long_path();
+    test_linux_user_directories();
+    test_linux_user_directories_overrides();
+    test_linux_github_actions();
+    test_linux_discovery_strategies();
+    test_linux_discovery_sources();
+    test_linux_pkg_config();
+    test_linux_pkg_config_cross();
+    test_windows_vcpkg();
+    test_windows_vcpkg_triplet();
+    test_linux_env_overrides();
+    test_linux_record_and_replay();
+    test_directory_patterns();
+    test_linux_searched_directories();
+    test_macos_install_names();
+    test_macos_universal_binary();
+    test_macos_no_xcode();
+    test_macos_embedded_version();
+    test_macos_offline();
+    test_linux_optional_system_libraries();
+    test_linux_optional_system_libraries_missing();
+    test_linux_clang_libraries();
+    test_linux_clang_libraries_thin_archive();
+    test_linux_clang_libraries_invalid();
+    test_linux_clang_libraries_pattern();
+    test_linux_clang_shim();
+    test_linux_clang_shim_llvm_config();
+    test_verify_static_libraries();
+    test_wasm_target();
+    test_wasm_libraries();
+    test_warnings();
+
+    #[cfg(feature = "log")]
+    {
+        test_linux_log();
+    }
+
+    #[cfg(unix)]
+    {
+        test_linux_unreadable_directory();
+    }
+
+    // Replay a recording made with `CLANG_SYS_RECORD` (e.g., one attached to a
+    // bug report) if one is provided.
+    if let Some(path) = env::var_os("CLANG_SYS_REPLAY") {
+        test_replay_file(path.into());
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        test_macos_invalid_install_name();
+    }
 
     #[cfg(target_os = "windows")]
     {
@@ -264,10 +503,11 @@ fn test_all() {
         test_windows_mingw_msvc();
         test_windows_arm64_on_x86_64();
         test_windows_x86_64_on_arm64();
+        test_windows_extended_length_path();
+        test_windows_github_actions();
     }
 }
 
-#[cfg(target_os = "windows")]
 macro_rules! assert_error {
     ($result:expr, $contents:expr $(,)?) => {
         if let Err(error) = $result {
@@ -314,6 +554,95 @@ fn test_linux_version_preference() {
     );
 }
 
+fn test_linux_versioned_filenames() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .so("usr/lib/libclang.so.13", "64")
+        .so("usr/lib/libclang-15.so.1", "64")
+        .so("usr/lib/libclang.so.15.0.7", "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(true),
+        Ok(("usr/lib".into(), "libclang.so.15.0.7".into())),
+    );
+
+    // These filenames can't be linked to.
+    assert!(dynamic::find(false).is_err());
+}
+
+fn test_freebsd_versioned_filenames() {
+    let _env = Env::new("freebsd", Arch::X86_64, "64")
+        .so("usr/local/lib/libclang.so.13", "64")
+        .so("usr/local/lib/libclang.so.7.0", "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("usr/local/lib".into(), "libclang.so.13".into())),
+    );
+}
+
+fn test_macos_versioned_filenames() {
+    let _env = Env::new("macos", Arch::X86_64, "64")
+        .var("LIBCLANG_PATH", Some("usr/local/opt/llvm/lib"))
+        .dylib(
+            "usr/local/opt/llvm/lib/libclang.1.dylib",
+            0x01000007,
+            "@rpath/libclang.1.dylib",
+            &[],
+        )
+        .enable();
+
+    assert_eq!(
+        dynamic::find(true),
+        Ok(("usr/local/opt/llvm/lib".into(), "libclang.1.dylib".into())),
+    );
+}
+
+#[cfg(feature = "log")]
+fn test_linux_log() {
+    struct Logger(Mutex<Vec<String>>);
+
+    impl log::Log for Logger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target() == "clang_sys"
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: Logger = Logger(Mutex::new(vec![]));
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("LIBCLANG_PATH", Some("usr/lib"))
+        .so("usr/lib/libclang-3.5.so", "64")
+        .so("usr/lib/libclang-3.9.so", "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(true),
+        Ok(("usr/lib".into(), "libclang-3.9.so".into())),
+    );
+    assert_eq!(
+        *LOGGER.0.lock().unwrap(),
+        vec![
+            "searching directory usr/lib",
+            "found usr/lib/libclang-3.5.so",
+            "found usr/lib/libclang-3.9.so",
+            "found 2 valid candidates",
+            "selected usr/lib/libclang-3.9.so (version: [3, 9], search priority: 0, compatible: true)",
+        ],
+    );
+}
+
 fn test_linux_directory_and_version_preference() {
     let _env = Env::new("linux", Arch::X86_64, "64")
         .so("usr/local/llvm/lib/libclang-3.so", "64")
@@ -327,77 +656,2162 @@ fn test_linux_directory_and_version_preference() {
     );
 }
 
-// Windows ---------------------------------------
+fn test_linux_path_tiebreak() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .so("usr/lib/b/libclang.so.1", "64")
+        .so("usr/lib/a/libclang.so.1", "64")
+        .enable();
 
-#[cfg(target_os = "windows")]
-fn test_windows_bin_sibling() {
-    let _env = Env::new("windows", Arch::X86_64, "64")
-        .dir("Program Files\\LLVM\\lib")
-        .dll("Program Files\\LLVM\\bin\\libclang.dll", Arch::X86_64, "64")
+    assert_eq!(
+        dynamic::find(true),
+        Ok(("usr/lib/a".into(), "libclang.so.1".into())),
+    );
+}
+
+fn test_linux_probe() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("LIBCLANG_PROBE", Some("1"))
+        .so("usr/lib/libclang-16.so", "64")
+        .so("usr/lib/libclang-15.so", "64")
+        .so("usr/lib/libclang-14.so", "64")
+        .command(
+            "libclang-probe",
+            &["--clang-sys-probe", "usr/lib/libclang-16.so"],
+            "libtinfo.so.5: cannot open shared object file\n",
+        )
+        .command(
+            "libclang-probe",
+            &["--clang-sys-probe", "usr/lib/libclang-15.so"],
+            "ok\n",
+        )
         .enable();
 
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("usr/lib".into(), "libclang-15.so".into())),
+    );
+
+    // Probing is only performed when linking at compiletime.
     assert_eq!(
         dynamic::find(true),
-        Ok(("Program Files\\LLVM\\bin".into(), "libclang.dll".into())),
+        Ok(("usr/lib".into(), "libclang-16.so".into())),
     );
 }
 
-#[cfg(target_os = "windows")]
-fn test_windows_mingw_gnu() {
-    let _env = Env::new("windows", Arch::X86_64, "64")
-        .env("gnu")
-        .dir("MSYS\\MinGW\\lib")
-        .dll("MSYS\\MinGW\\bin\\clang.dll", Arch::X86_64, "64")
-        .dir("Program Files\\LLVM\\lib")
-        .dll("Program Files\\LLVM\\bin\\libclang.dll", Arch::X86_64, "64")
+fn test_linux_probe_failure() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("LIBCLANG_PROBE", Some("1"))
+        .so("usr/lib/libclang-16.so", "64")
+        .command(
+            "libclang-probe",
+            &["--clang-sys-probe", "usr/lib/libclang-16.so"],
+            "libtinfo.so.5: cannot open shared object file\n",
+        )
+        .enable();
+
+    let error = dynamic::find(false).unwrap_err();
+    assert!(error.contains("libtinfo.so.5"), "{}", error);
+}
+
+fn test_linux_probe_cross_compiling() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("TARGET", Some("aarch64-unknown-linux-gnu"))
+        .var("HOST", Some("x86_64-unknown-linux-gnu"))
+        .var("LIBCLANG_PROBE", Some("1"))
+        .so("usr/lib/libclang-16.so", "64")
+        .command(
+            "libclang-probe",
+            &["--clang-sys-probe", "usr/lib/libclang-16.so"],
+            "libtinfo.so.5: cannot open shared object file\n",
+        )
+        .enable();
+
+    // Target shared libraries can't be loaded on the host.
+    let count = common::warnings().len();
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("usr/lib".into(), "libclang-16.so".into())),
+    );
+    let warnings = &common::warnings()[count..];
+    assert!(
+        warnings.iter().any(|(_, w)| w.contains("cross-compiling")),
+        "{:?}",
+        warnings,
+    );
+}
+
+fn test_linux_runtime_requirements() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("LIBCLANG_GLIBC_BASELINE", Some("2.17"))
+        .var("LIBCLANG_GLIBCXX_BASELINE", Some("3.4.19"))
+        .so_with_version_needs("usr/lib/libclang-18.so", &["GLIBC_2.2.5", "GLIBC_2.34"])
+        .so_with_version_needs("usr/lib/libclang-17.so", &["GLIBC_2.17", "GLIBCXX_3.4.29"])
+        .so_with_version_needs("usr/lib/libclang-16.so", &["GLIBC_2.14", "GLIBCXX_3.4.19"])
         .enable();
 
+    let needs = dynamic::parse_elf_version_needs("usr/lib/libclang-17.so".as_ref()).unwrap();
+    assert_eq!(needs, &["GLIBC_2.17", "GLIBCXX_3.4.29"]);
+    assert_eq!(
+        dynamic::max_symbol_version(&needs, "GLIBC_"),
+        Some(vec![2, 17])
+    );
+    assert_eq!(
+        dynamic::max_symbol_version(&needs, "GLIBCXX_"),
+        Some(vec![3, 4, 29])
+    );
+
     assert_eq!(
         dynamic::find(true),
-        Ok(("MSYS\\MinGW\\bin".into(), "clang.dll".into())),
+        Ok(("usr/lib".into(), "libclang-16.so".into())),
     );
 }
 
-#[cfg(target_os = "windows")]
-fn test_windows_mingw_msvc() {
-    let _env = Env::new("windows", Arch::X86_64, "64")
-        .env("msvc")
-        .dir("MSYS\\MinGW\\lib")
-        .dll("MSYS\\MinGW\\bin\\clang.dll", Arch::X86_64, "64")
-        .dir("Program Files\\LLVM\\lib")
-        .dll("Program Files\\LLVM\\bin\\libclang.dll", Arch::X86_64, "64")
+fn test_linux_soname_version() {
+    let [strings, dynamic] = elf_soname("libclang.so.13");
+    let sections: &[(u32, u64, &[u8], u32)] = &[(3, 2, &strings, 0), (6, 3, &dynamic, 1)];
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .so_with_sections("usr/lib/libclang.so", sections)
+        .so("usr/lib/libclang-12.so", "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::parse_elf_soname("usr/lib/libclang.so".as_ref()).unwrap(),
+        Some("libclang.so.13".into()),
+    );
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("usr/lib".into(), "libclang.so".into())),
+    );
+}
+
+fn test_linux_embedded_version() {
+    // The `.comment` section (which isn't loaded) contains the version of the
+    // compiler used to build the shared library.
+    let sections: &[(u32, u64, &[u8], u32)] = &[
+        (
+            1,
+            2,
+            b"\0%s clang version \0Ubuntu clang version 16.0.6-1\0",
+            0,
+        ),
+        (1, 0x30, b"clang version 19.1.0\0", 0),
+        // Executable sections aren't scanned either.
+        (1, 6, b"clang version 20.1.0\0", 0),
+    ];
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .so_with_sections("usr/lib/libclang.so", sections)
+        .so_with_sections("usr/lib/libclang-15.so", &[])
+        .enable();
+
+    assert_eq!(
+        dynamic::scan_clang_version("usr/lib/libclang.so".as_ref()).unwrap(),
+        Some(vec![16, 0, 6]),
+    );
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("usr/lib".into(), "libclang.so".into())),
+    );
+}
+
+#[cfg(target_os = "linux")]
+fn test_build_stub() {
+    let tmp = tempfile::tempdir().unwrap();
+    let functions = ["clang_createIndex".to_string()];
+    let filename = dynamic::build_stub(tmp.path(), &functions, "libclang.so.18").unwrap();
+    assert_eq!(filename, "libclang.so");
+
+    let path = tmp.path().join(filename);
+    assert_eq!(
+        dynamic::parse_elf_soname(&path).unwrap(),
+        Some("libclang.so.18".into())
+    );
+}
+
+fn test_candidate_ordering() {
+    let candidate = |directory: &str, version: &[u32], priority, compatible| dynamic::Candidate {
+        directory: directory.into(),
+        filename: "libclang.so".into(),
+        version: version.into(),
+        priority,
+        compatible,
+        source: common::DiscoverySource::KnownDirectory,
+    };
+
+    let mut candidates = [
+        candidate("c", &[14], 1, true),
+        candidate("b", &[14], 1, true),
+        candidate("a", &[14], 2, true),
+        candidate("d", &[], 0, true),
+        candidate("e", &[15], 3, true),
+        candidate("f", &[16], 0, false),
+    ];
+    candidates.sort_by(dynamic::compare_candidates);
+
+    let directories = candidates
+        .iter()
+        .map(|c| c.directory.to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(directories, &["e", "b", "c", "a", "d", "f"]);
+}
+
+fn test_linux_llvm_config_output() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("LLVM_CONFIG_OUTPUT", Some("llvm-config.txt"))
+        .file(
+            "llvm-config.txt",
+            b"# Recorded output.\n--prefix=opt/llvm\n--libdir=opt/llvm/lib\n",
+        )
+        .so("usr/lib/libclang.so.1", "64")
+        .so("opt/llvm/lib/libclang.so.1", "64")
         .enable();
 
     assert_eq!(
         dynamic::find(true),
-        Ok(("Program Files\\LLVM\\bin".into(), "libclang.dll".into())),
+        Ok(("opt/llvm/lib".into(), "libclang.so.1".into())),
+    );
+    assert_eq!(
+        common::run_llvm_config(&["--libdir"]),
+        Some("opt/llvm/lib\n".into()),
     );
+    assert_eq!(common::run_llvm_config(&["--libs", "--link-static"]), None);
+    assert_eq!(common::run_llvm_config(&[]), None);
 }
 
-#[cfg(target_os = "windows")]
-fn test_windows_arm64_on_x86_64() {
-    let _env = Env::new("windows", Arch::X86_64, "64")
-        .env("msvc")
-        .dir("Program Files\\LLVM\\lib")
-        .dll("Program Files\\LLVM\\bin\\libclang.dll", Arch::ARM64, "64")
+fn test_linux_runtime_path() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("LIBCLANG_PATH", Some("usr/lib"))
+        .var(
+            "CLANG_SYS_LIBCLANG_PATH",
+            Some("opt/llvm/lib/libclang-14.so"),
+        )
+        .so("usr/lib/libclang-16.so", "64")
+        .so("opt/llvm/lib/libclang-14.so", "64")
         .enable();
 
-    assert_error!(
+    // `CLANG_SYS_LIBCLANG_PATH` is only used at runtime.
+    assert_eq!(
         dynamic::find(true),
-        "invalid: [(Program Files\\LLVM\\bin\\libclang.dll: invalid DLL (ARM64)",
+        Ok(("opt/llvm/lib".into(), "libclang-14.so".into())),
+    );
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("usr/lib".into(), "libclang-16.so".into())),
     );
 }
 
-#[cfg(target_os = "windows")]
-fn test_windows_x86_64_on_arm64() {
-    let _env = Env::new("windows", Arch::ARM64, "64")
-        .env("msvc")
-        .dir("Program Files\\LLVM\\lib")
-        .dll("Program Files\\LLVM\\bin\\libclang.dll", Arch::X86_64, "64")
+fn test_linux_profile_path() {
+    let env = Env::new("linux", Arch::X86_64, "64")
+        .var("PROFILE", Some("debug"))
+        .var("LIBCLANG_PATH", Some("usr/lib"))
+        .var("LIBCLANG_PATH_DEBUG", Some("opt/llvm-assertions/lib"))
+        .so("usr/lib/libclang-16.so", "64")
+        .so("opt/llvm-assertions/lib/libclang-14.so", "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("opt/llvm-assertions/lib".into(), "libclang-14.so".into())),
+    );
+    drop(env);
+
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("PROFILE", Some("release"))
+        .var("LIBCLANG_PATH", Some("usr/lib"))
+        .var("LIBCLANG_PATH_DEBUG", Some("opt/llvm-assertions/lib"))
+        .so("usr/lib/libclang-16.so", "64")
+        .so("opt/llvm-assertions/lib/libclang-14.so", "64")
         .enable();
 
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("usr/lib".into(), "libclang-16.so".into())),
+    );
+}
+
+fn test_linux_selection() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("CLANG_SYS_SELECTION", Some("toolchain/selection.txt"))
+        .so("usr/lib/libclang-16.so", "64")
+        .so("toolchain/llvm/lib/libclang-14.so", "64")
+        .file(
+            "toolchain/selection.txt",
+            b"# Selected by the toolchain manager.\nLIBCLANG_PATH=llvm/lib/libclang-14.so\n",
+        )
+        .enable();
+
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("toolchain/llvm/lib".into(), "libclang-14.so".into())),
+    );
+}
+
+fn test_linux_selection_precedence() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("CLANG_SYS_SELECTION", Some("selection.txt"))
+        .var("LIBCLANG_PATH", Some("usr/lib/libclang-16.so"))
+        .so("usr/lib/libclang-16.so", "64")
+        .so("llvm/lib/libclang-14.so", "64")
+        .file("selection.txt", b"LIBCLANG_PATH=llvm/lib/libclang-14.so\n")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("usr/lib".into(), "libclang-16.so".into())),
+    );
+    assert_eq!(
+        common::check_selection(),
+        vec![
+            "LIBCLANG_PATH is set so the value provided for it by the CLANG_SYS_SELECTION \
+             file (selection.txt) is ignored"
+        ],
+    );
+}
+
+fn test_linux_selection_policy() {
+    let env = || {
+        Env::new("linux", Arch::X86_64, "64")
+            .so("usr/lib/libclang-3.5.so", "64")
+            .so("usr/local/lib/libclang-2.so", "64")
+            .so("usr/local/lib/libclang-3.so", "64")
+    };
+
+    let selected = |policy| {
+        let _env = env().var("LIBCLANG_SELECTION_POLICY", policy).enable();
+        dynamic::find(true)
+    };
+
+    let newest = Ok(("usr/lib".into(), "libclang-3.5.so".into()));
+    assert_eq!(selected(None), newest);
+    assert_eq!(selected(Some("newest")), newest);
+    assert_eq!(
+        selected(Some("env-path")),
+        Ok(("usr/local/lib".into(), "libclang-3.so".into())),
+    );
+    assert_eq!(
+        selected(Some("first-match")),
+        Ok(("usr/local/lib".into(), "libclang-2.so".into())),
+    );
     assert_error!(
-        dynamic::find(true),
-        "invalid: [(Program Files\\LLVM\\bin\\libclang.dll: invalid DLL (x86-64)",
+        selected(Some("oldest")),
+        "invalid `LIBCLANG_SELECTION_POLICY`"
+    );
+
+    // The selection policy provided to `find_candidates` takes precedence.
+    let _env = env()
+        .var("LIBCLANG_SELECTION_POLICY", Some("newest"))
+        .enable();
+    let oldest = |a: &dynamic::Candidate, b: &dynamic::Candidate| a.version.cmp(&b.version);
+    let policy = dynamic::SelectionPolicy::Custom(oldest);
+    let candidates = dynamic::find_candidates(&[], &[], Some(policy)).unwrap();
+    let filenames = candidates
+        .iter()
+        .map(|c| c.filename.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        filenames,
+        ["libclang-2.so", "libclang-3.so", "libclang-3.5.so"],
+    );
+}
+
+fn test_version_policy() {
+    use common::VersionPolicy;
+
+    let candidates: [(PathBuf, Vec<u32>); 4] = [
+        ("usr/bin/llvm-config-20".into(), vec![20]),
+        ("usr/bin/llvm-config-16".into(), vec![16]),
+        ("usr/bin/llvm-config-18".into(), vec![18]),
+        ("usr/lib/llvm-18/bin/llvm-config".into(), vec![18]),
+    ];
+
+    let select = |policy: VersionPolicy, target| {
+        let index = policy.select(&candidates, target)?;
+        Some(candidates[index].0.to_str().unwrap())
+    };
+
+    let exact = VersionPolicy::Exact;
+    assert_eq!(select(exact, 18), Some("usr/bin/llvm-config-18"));
+    assert_eq!(select(exact, 17), None);
+
+    let at_least = VersionPolicy::AtLeast;
+    assert_eq!(select(at_least, 18), Some("usr/bin/llvm-config-18"));
+    assert_eq!(select(at_least, 17), Some("usr/bin/llvm-config-18"));
+    assert_eq!(select(at_least, 21), None);
+
+    let newest = VersionPolicy::Newest;
+    assert_eq!(select(newest, 18), Some("usr/bin/llvm-config-20"));
+    assert_eq!(select(newest, 16), Some("usr/bin/llvm-config-20"));
+    assert_eq!(select(newest, 21), None);
+
+    let policy = |value| {
+        let _env = Env::new("linux", Arch::X86_64, "64")
+            .var("CLANG_SYS_VERSION_POLICY", value)
+            .enable();
+        VersionPolicy::from_env()
+    };
+
+    assert_eq!(policy(None), Ok(VersionPolicy::Exact));
+    assert_eq!(policy(Some("at-least")), Ok(VersionPolicy::AtLeast));
+    assert_eq!(policy(Some("newest")), Ok(VersionPolicy::Newest));
+    assert_error!(policy(Some("oldest")), "invalid `CLANG_SYS_VERSION_POLICY`");
+}
+
+fn test_selection_problems() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("CLANG_SYS_SELECTION", Some("selection.txt"))
+        .file(
+            "selection.txt",
+            b"LLVM_CONFIG_PATH=llvm-config-3\nversion=3.5.0\nversion=latest\nLIBCLANG=lib\n",
+        )
+        .enable();
+
+    assert_eq!(
+        common::var("LLVM_CONFIG_PATH"),
+        Some("llvm-config-3".into())
+    );
+
+    let mut expected = vec![
+        "invalid version in CLANG_SYS_SELECTION file (selection.txt): latest".to_string(),
+        "unrecognized key in CLANG_SYS_SELECTION file (selection.txt): LIBCLANG".to_string(),
+    ];
+    if cfg!(feature = "clang_4_0") {
+        let problem = "the `libclang` version selected by the CLANG_SYS_SELECTION file (3.5.0) \
+                       is older than the version required by the enabled Cargo features";
+        expected.insert(0, problem.into());
+    }
+    let mut problems = common::check_selection();
+    if cfg!(feature = "clang_4_0") {
+        problems[0].truncate(expected[0].len());
+    }
+    assert_eq!(problems, expected);
+}
+
+fn test_linux_android_ndk() {
+    let ndk = "Android/Sdk/ndk/26.1.10909125";
+    let toolchain = format!("{ndk}/toolchains/llvm/prebuilt/linux-x86_64");
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("ANDROID_HOME", Some("Android/Sdk"))
+        .so("usr/lib/libclang-14.so", "64")
+        .file(
+            &format!("{ndk}/source.properties"),
+            b"Pkg.Revision = 26.1.10909125\n",
+        )
+        .file(
+            &format!("{toolchain}/AndroidVersion.txt"),
+            b"17.0.2\nbased on r487747e\n",
+        )
+        .so(&format!("{toolchain}/lib/libclang.so"), "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(false),
+        Ok((format!("{toolchain}/lib").into(), "libclang.so".into())),
     );
 }
+
+fn test_linux_android_ndk_revision_preference() {
+    let old = "Android/Sdk/ndk/25.2.9519653/toolchains/llvm/prebuilt/linux-x86_64";
+    let new = "Android/Sdk/ndk/26.1.10909125/toolchains/llvm/prebuilt/linux-x86_64";
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("ANDROID_SDK_ROOT", Some("Android/Sdk"))
+        .file(
+            "Android/Sdk/ndk/25.2.9519653/source.properties",
+            b"Pkg.Revision = 25.2.9519653\n",
+        )
+        .file(
+            "Android/Sdk/ndk/26.1.10909125/source.properties",
+            b"Pkg.Revision = 26.1.10909125\n",
+        )
+        .so(&format!("{old}/lib64/libclang.so"), "64")
+        .so(&format!("{new}/lib/libclang.so"), "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(false),
+        Ok((format!("{new}/lib").into(), "libclang.so".into())),
+    );
+}
+
+/// Returns a relative path which is longer than `MAX_PATH` on Windows.
+fn long_path(separator: &str) -> String {
+    ["ci-agent-work-directory"; 12].join(separator)
+}
+
+fn test_linux_long_path() {
+    let directory = long_path("/");
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("LIBCLANG_PATH", Some(directory.as_str()))
+        .so(&format!("{directory}/libclang.so.1"), "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(true),
+        Ok((directory.into(), "libclang.so.1".into())),
+    );
+}
+
+fn test_linux_user_directories() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("HOME", Some("home"))
+        .so("home/.asdf/installs/llvm/16.0.6/lib/libclang-16.so", "64")
+        .so(
+            "home/.local/share/mise/installs/llvm/17.0.6/lib/libclang-17.so",
+            "64",
+        )
+        .enable();
+
+    assert_eq!(
+        dynamic::find(false),
+        Ok((
+            "home/.local/share/mise/installs/llvm/17.0.6/lib".into(),
+            "libclang-17.so".into()
+        )),
+    );
+
+    // Installations for the current user are less preferred than system-wide
+    // installations of the same version.
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("HOME", Some("home"))
+        .so("home/.local/lib/libclang-17.so", "64")
+        .so("usr/lib/libclang-17.so", "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("usr/lib".into(), "libclang-17.so".into())),
+    );
+}
+
+fn test_linux_user_directories_overrides() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("HOME", Some("home"))
+        .var("XDG_DATA_HOME", Some("data"))
+        .var("PROTO_HOME", Some("proto"))
+        .so("home/.local/share/llvm17/lib/libclang-17.so", "64")
+        .so("data/llvm16/lib/libclang-16.so", "64")
+        .so("proto/tools/llvm/15.0.7/lib/libclang-15.so", "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("data/llvm16/lib".into(), "libclang-16.so".into())),
+    );
+}
+
+fn test_linux_github_actions() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("RUNNER_TOOL_CACHE", Some("cache"))
+        .so(
+            "opt/hostedtoolcache/LLVM/16.0.6/x64/lib/libclang-16.so",
+            "64",
+        )
+        .so("cache/LLVM/17.0.6/x64/lib/libclang-17.so", "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("cache/LLVM/17.0.6/x64/lib".into(), "libclang-17.so".into())),
+    );
+
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("LLVM_PATH", Some("llvm"))
+        .so(
+            "opt/hostedtoolcache/LLVM/17.0.6/x64/lib/libclang-17.so",
+            "64",
+        )
+        .so("llvm/lib/libclang-18.so", "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(false),
+        Ok(("llvm/lib".into(), "libclang-18.so".into())),
+    );
+}
+
+fn test_linux_discovery_strategies() {
+    struct Custom;
+
+    impl common::DiscoveryStrategy for Custom {
+        fn locations(&self) -> Vec<Vec<PathBuf>> {
+            vec![vec!["custom".into()], vec!["other/libclang-17.so".into()]]
+        }
+    }
+
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .so("usr/lib/libclang-17.so", "64")
+        .so("custom/libclang-16.so", "64")
+        .so("other/libclang-17.so", "64")
+        .so("other/libclang-18.so", "64")
+        .enable();
+
+    // Custom strategies are used before the built-in strategies (but the
+    // version of a shared library is still the most important criterion).
+    let previous = common::set_discovery_strategies(vec![Arc::new(Custom)]);
+    let result = dynamic::find(false);
+    common::set_discovery_strategies(previous);
+
+    assert_eq!(result, Ok(("other".into(), "libclang-17.so".into())));
+}
+
+fn test_linux_env_overrides() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("LIBCLANG_PATH", Some("a"))
+        .so("a/libclang.so", "64")
+        .so("b/libclang.so", "64")
+        .enable();
+
+    let overrides = [("LIBCLANG_PATH".to_string(), "b".to_string())];
+    let result = common::with_env_overrides(&overrides, || dynamic::find(true));
+    assert_eq!(result, Ok(("b".into(), "libclang.so".into())));
+
+    // The overrides only apply to the supplied function.
+    assert_eq!(dynamic::find(true), Ok(("a".into(), "libclang.so".into())));
+}
+
+fn test_linux_discovery_sources() {
+    use common::DiscoverySource::*;
+
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .command("llvm-config", &["--prefix"], "opt/llvm\n")
+        .var("LD_LIBRARY_PATH", Some("ld/lib"))
+        .so("opt/llvm/lib/libclang-16.so", "64")
+        .so("ld/lib/libclang-17.so", "64")
+        .so("usr/lib/libclang-18.so", "64")
+        .enable();
+
+    let candidates = dynamic::find_candidates(&[], &[], None).unwrap();
+    let sources = candidates
+        .iter()
+        .map(|c| (c.path(), c.source))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sources,
+        [
+            ("usr/lib/libclang-18.so".into(), KnownDirectory),
+            ("ld/lib/libclang-17.so".into(), Environment),
+            ("opt/llvm/lib/libclang-16.so".into(), LlvmConfig),
+        ],
+    );
+
+    let candidates = dynamic::find_candidates(&["usr/lib".into()], &[], None).unwrap();
+    assert_eq!(candidates[0].source, Custom);
+}
+
+fn test_linux_pkg_config() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .command("pkg-config", &["--variable=libdir", "libclang"], "\n")
+        .command(
+            "pkg-config",
+            &["--variable=libdir", "clang"],
+            "opt/clang/lib\n",
+        )
+        .command(
+            "pkg-config",
+            &["--variable=includedir", "clang"],
+            "opt/clang/include\n",
+        )
+        .so("opt/clang/lib/libclang.so", "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(true),
+        Ok(("opt/clang/lib".into(), "libclang.so".into())),
+    );
+
+    let candidates = dynamic::find_candidates(&[], &[], None).unwrap();
+    assert_eq!(candidates[0].source, common::DiscoverySource::PkgConfig);
+    assert_eq!(
+        common::find_include_directory(),
+        Some("opt/clang/include".into()),
+    );
+}
+
+fn test_linux_pkg_config_cross() {
+    let cross = |allow: Option<&str>| {
+        Env::new("linux", Arch::ARM64, "64")
+            .var("TARGET", Some("aarch64-unknown-linux-gnu"))
+            .var("HOST", Some("x86_64-unknown-linux-gnu"))
+            .var("PKG_CONFIG_ALLOW_CROSS", allow)
+            .var(
+                "PKG_CONFIG_aarch64_unknown_linux_gnu",
+                Some("aarch64-pkg-config"),
+            )
+            .command(
+                "pkg-config",
+                &["--variable=libdir", "libclang"],
+                "opt/clang/lib\n",
+            )
+            .so("opt/clang/lib/libclang.so", "64")
+            .enable()
+    };
+
+    // The packages found by `pkg-config` are usually built for the host.
+    let env = cross(None);
+    assert!(dynamic::find(true).is_err());
+    let invocations = env.commands.lock().unwrap().invocations.clone();
+    assert!(!invocations.iter().any(|(c, _, _)| c == "pkg-config"));
+    drop(env);
+
+    let env = cross(Some("1"));
+    assert_eq!(
+        dynamic::find(true),
+        Ok(("opt/clang/lib".into(), "libclang.so".into())),
+    );
+    let invocations = env.commands.lock().unwrap().invocations.clone();
+    let path = invocations
+        .iter()
+        .find(|(c, _, _)| c == "pkg-config")
+        .map(|(_, p, _)| p);
+    assert_eq!(path.map(String::as_str), Some("aarch64-pkg-config"));
+}
+
+fn test_windows_vcpkg() {
+    let _env = Env::new("windows", Arch::X86_64, "64")
+        .var("VCPKG_ROOT", Some("vcpkg"))
+        .dir("vcpkg/installed/x64-windows/lib")
+        .dll(
+            "vcpkg/installed/x64-windows/bin/libclang.dll",
+            Arch::X86_64,
+            "64",
+        )
+        .enable();
+
+    assert_eq!(
+        dynamic::find(true),
+        Ok((
+            "vcpkg/installed/x64-windows/bin".into(),
+            "libclang.dll".into()
+        )),
+    );
+
+    let candidates = dynamic::find_candidates(&[], &[], None).unwrap();
+    assert_eq!(candidates[0].source, common::DiscoverySource::Vcpkg);
+}
+
+fn test_windows_vcpkg_triplet() {
+    let _env = Env::new("windows", Arch::ARM64, "64")
+        .var("VCPKG_INSTALLED_DIR", Some("vcpkg_installed"))
+        .var("VCPKGRS_TRIPLET", Some("arm64-windows-release"))
+        .dll(
+            "vcpkg_installed/arm64-windows/bin/libclang.dll",
+            Arch::ARM64,
+            "64",
+        )
+        .dll(
+            "vcpkg_installed/arm64-windows-release/bin/libclang.dll",
+            Arch::ARM64,
+            "64",
+        )
+        .enable();
+
+    assert_eq!(
+        dynamic::find(true),
+        Ok((
+            "vcpkg_installed/arm64-windows-release/bin".into(),
+            "libclang.dll".into()
+        )),
+    );
+}
+
+/// Finds `libclang` again by replaying a recording made with `CLANG_SYS_RECORD`
+/// and returns the result.
+fn replay(recording: &common::Recording) -> Result<(PathBuf, String), String> {
+    let target = |index: usize| recording.target.get(index).map_or("", String::as_str);
+    let arch = match target(1) {
+        "aarch64" => Arch::ARM64,
+        "x86" => Arch::X86,
+        _ => Arch::X86_64,
+    };
+
+    let mut env = Env::new(target(0), arch, target(2));
+    if !target(3).is_empty() {
+        env = env.env(target(3));
+    }
+    for (name, value) in &recording.variables {
+        env = env.var(name, Some(value));
+    }
+    let _env = env.enable();
+
+    common::set_replay(Some(recording.clone()));
+    let result = dynamic::find(recording.runtime);
+    common::set_replay(None);
+    result
+}
+
+fn test_linux_record_and_replay() {
+    let [strings, dynamic] = elf_soname("libclang.so.13");
+    let sections: &[(u32, u64, &[u8], u32)] = &[(3, 2, &strings, 0), (6, 3, &dynamic, 1)];
+    let env = Env::new("linux", Arch::X86_64, "64")
+        .var("CLANG_SYS_RECORD", Some("recording.txt"))
+        .var("LD_LIBRARY_PATH", Some("ld/lib"))
+        .so_with_sections("usr/lib/libclang.so", sections)
+        .so("usr/local/lib/libclang-12.so", "64")
+        .so("ld/lib/libclang-14.so", "32")
+        .enable();
+
+    let result = dynamic::find(false);
+    assert_eq!(result, Ok(("usr/lib".into(), "libclang.so".into())));
+    let recording = fs::read_to_string("recording.txt").unwrap();
+    drop(env);
+
+    let recording = common::Recording::parse(&recording).unwrap();
+    assert_eq!(recording.result, Some(Ok("usr/lib/libclang.so".into())));
+    assert!(
+        recording
+            .libraries
+            .iter()
+            .any(|(p, l)| { p.ends_with("libclang-14.so") && l.valid.is_err() })
+    );
+
+    // The files no longer exist but the recording still finds the same one.
+    assert_eq!(replay(&recording), result);
+}
+
+fn test_replay_file(path: PathBuf) {
+    let recording = fs::read_to_string(&path).unwrap();
+    let recording = common::Recording::parse(&recording).unwrap();
+    let result = replay(&recording);
+    println!("Recorded: {:?}", recording.result);
+    println!("Replayed: {:?}", result);
+    let result = result.map(|(d, f)| d.join(f));
+    assert_eq!(recording.result, Some(result));
+}
+
+fn test_directory_patterns() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .dir("usr/lib/llvm-14/lib")
+        .dir("usr/lib/llvm-15/lib")
+        .dir("usr/lib/x86_64-linux-gnu")
+        .dir("usr/lib64")
+        .so("usr/lib/libclang-15.so", "64")
+        .dir("opt/a/b/lib")
+        .dir("opt/lib")
+        .enable();
+
+    let patterns = [
+        "usr/lib*/*",
+        "usr/lib*",
+        "usr/lib/llvm-*/lib",
+        "opt/**/lib",
+        "missing/*",
+    ];
+
+    let patterns = patterns.map(String::from);
+    let directories = common::find_directories(&patterns, glob::MatchOptions::new());
+    let directories = directories
+        .iter()
+        .map(|d| d.iter().map(|d| d.to_str().unwrap()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        directories,
+        [
+            vec![
+                "usr/lib/llvm-14",
+                "usr/lib/llvm-15",
+                "usr/lib/x86_64-linux-gnu"
+            ],
+            vec!["usr/lib", "usr/lib64"],
+            vec!["usr/lib/llvm-14/lib", "usr/lib/llvm-15/lib"],
+            vec!["opt/a/b/lib", "opt/lib"],
+            vec![],
+        ],
+    );
+}
+
+fn test_linux_searched_directories() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .dir("usr/local/llvm14/lib")
+        .enable();
+
+    assert!(dynamic::find(true).is_err());
+    let directories = common::searched_directories();
+    assert!(directories.contains(&PathBuf::from("usr/local/llvm14/lib")));
+    assert!(!directories.contains(&PathBuf::from("usr/local/llvm15/lib")));
+}
+
+#[cfg(unix)]
+fn test_linux_unreadable_directory() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .dir("usr/local/llvm14/lib")
+        .enable();
+
+    let set_mode = |mode| {
+        let permissions = fs::Permissions::from_mode(mode);
+        fs::set_permissions("usr/local/llvm14/lib", permissions).unwrap();
+    };
+
+    set_mode(0o000);
+    let readable = fs::read_dir("usr/local/llvm14/lib").is_ok();
+    let result = dynamic::find(false);
+    let errors = common::search_errors();
+    set_mode(0o755);
+
+    // Permissions are not enforced for privileged users (e.g., `root`).
+    if readable {
+        return;
+    }
+
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with("(usr/local/llvm14/lib: "));
+    assert_error!(result, "(unreadable: [(usr/local/llvm14/lib: ");
+}
+
+// Windows ---------------------------------------
+
+#[cfg(target_os = "windows")]
+fn test_windows_bin_sibling() {
+    let _env = Env::new("windows", Arch::X86_64, "64")
+        .dir("Program Files\\LLVM\\lib")
+        .dll("Program Files\\LLVM\\bin\\libclang.dll", Arch::X86_64, "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(true),
+        Ok(("Program Files\\LLVM\\bin".into(), "libclang.dll".into())),
+    );
+}
+
+#[cfg(target_os = "windows")]
+fn test_windows_mingw_gnu() {
+    let _env = Env::new("windows", Arch::X86_64, "64")
+        .env("gnu")
+        .dir("MSYS\\MinGW\\lib")
+        .dll("MSYS\\MinGW\\bin\\clang.dll", Arch::X86_64, "64")
+        .dir("Program Files\\LLVM\\lib")
+        .dll("Program Files\\LLVM\\bin\\libclang.dll", Arch::X86_64, "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(true),
+        Ok(("MSYS\\MinGW\\bin".into(), "clang.dll".into())),
+    );
+}
+
+#[cfg(target_os = "windows")]
+fn test_windows_mingw_msvc() {
+    let _env = Env::new("windows", Arch::X86_64, "64")
+        .env("msvc")
+        .dir("MSYS\\MinGW\\lib")
+        .dll("MSYS\\MinGW\\bin\\clang.dll", Arch::X86_64, "64")
+        .dir("Program Files\\LLVM\\lib")
+        .dll("Program Files\\LLVM\\bin\\libclang.dll", Arch::X86_64, "64")
+        .enable();
+
+    assert_eq!(
+        dynamic::find(true),
+        Ok(("Program Files\\LLVM\\bin".into(), "libclang.dll".into())),
+    );
+}
+
+#[cfg(target_os = "windows")]
+fn test_windows_arm64_on_x86_64() {
+    let _env = Env::new("windows", Arch::X86_64, "64")
+        .env("msvc")
+        .dir("Program Files\\LLVM\\lib")
+        .dll("Program Files\\LLVM\\bin\\libclang.dll", Arch::ARM64, "64")
+        .enable();
+
+    assert_error!(
+        dynamic::find(true),
+        "invalid: [(Program Files\\LLVM\\bin\\libclang.dll: invalid DLL (ARM64)",
+    );
+}
+
+#[cfg(target_os = "windows")]
+fn test_windows_x86_64_on_arm64() {
+    let _env = Env::new("windows", Arch::ARM64, "64")
+        .env("msvc")
+        .dir("Program Files\\LLVM\\lib")
+        .dll("Program Files\\LLVM\\bin\\libclang.dll", Arch::X86_64, "64")
+        .enable();
+
+    assert_error!(
+        dynamic::find(true),
+        "invalid: [(Program Files\\LLVM\\bin\\libclang.dll: invalid DLL (x86-64)",
+    );
+}
+
+#[cfg(target_os = "windows")]
+fn test_windows_github_actions() {
+    let _env = Env::new("windows", Arch::X86_64, "64")
+        .dir("hostedtoolcache\\windows\\LLVM\\18.1.8\\x64\\lib")
+        .dll(
+            "hostedtoolcache\\windows\\LLVM\\18.1.8\\x64\\bin\\libclang.dll",
+            Arch::X86_64,
+            "64",
+        )
+        .enable();
+
+    assert_eq!(
+        dynamic::find(true),
+        Ok((
+            "hostedtoolcache\\windows\\LLVM\\18.1.8\\x64\\bin".into(),
+            "libclang.dll".into()
+        )),
+    );
+}
+
+#[cfg(target_os = "windows")]
+fn test_windows_extended_length_path() {
+    let directory = long_path("\\");
+    let env = Env::new("windows", Arch::X86_64, "64");
+    let absolute = env.tmp.path().join(&directory);
+    let verbatim = format!("\\\\?\\{}", absolute.display());
+    let _env = env
+        .var("LIBCLANG_PATH", Some(verbatim.as_str()))
+        .dll(&format!("{directory}\\libclang.dll"), Arch::X86_64, "64")
+        .enable();
+
+    assert_eq!(dynamic::find(true), Ok((absolute, "libclang.dll".into())));
+}
+
+// macOS -----------------------------------------
+
+fn test_macos_install_names() {
+    let _env = Env::new("macos", Arch::X86_64, "64")
+        .dylib(
+            "Xcode.app/Contents/Frameworks/libclang.dylib",
+            0x01000007,
+            "@rpath/libclang.dylib",
+            &["@rpath/libLLVM.dylib", "/usr/lib/libSystem.B.dylib"],
+        )
+        .enable();
+
+    let dylibs =
+        dynamic::parse_mach_o_dylibs("Xcode.app/Contents/Frameworks/libclang.dylib".as_ref());
+    assert_eq!(
+        dylibs.unwrap(),
+        dynamic::MachODylibs {
+            id: Some("@rpath/libclang.dylib".into()),
+            dependencies: vec![
+                "@rpath/libLLVM.dylib".into(),
+                "/usr/lib/libSystem.B.dylib".into(),
+            ],
+        },
+    );
+
+    // An `LC_ID_DYLIB` command larger than the file.
+    let mut contents = vec![0; 32 + 24];
+    contents[0..4].copy_from_slice(&0xFEEDFACFu32.to_le_bytes());
+    contents[4..8].copy_from_slice(&0x01000007u32.to_le_bytes());
+    contents[16..20].copy_from_slice(&1u32.to_le_bytes());
+    contents[32..36].copy_from_slice(&0xDu32.to_le_bytes());
+    contents[36..40].copy_from_slice(&0xFFFFFFF0u32.to_le_bytes());
+    contents[40..44].copy_from_slice(&24u32.to_le_bytes());
+
+    let _env = _env.file("usr/lib/libclang.dylib", &contents).enable();
+
+    let dylibs = dynamic::parse_mach_o_dylibs("usr/lib/libclang.dylib".as_ref());
+    assert_eq!(dylibs.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}
+
+fn test_macos_embedded_version() {
+    // Mach-O header and an `LC_SEGMENT_64` command for the `__TEXT` segment
+    // with a `__cstring` section which contains the second string.
+    let strings = b"clang version 20.1.0\0Apple clang version 17.0.0\0";
+    let mut contents = vec![0; 32 + 72 + 80];
+    contents[0..4].copy_from_slice(&0xFEEDFACFu32.to_le_bytes());
+    contents[4..8].copy_from_slice(&0x01000007u32.to_le_bytes());
+    contents[16..20].copy_from_slice(&1u32.to_le_bytes());
+    contents[32..36].copy_from_slice(&0x19u32.to_le_bytes());
+    contents[36..40].copy_from_slice(&(72u32 + 80).to_le_bytes());
+    contents[40..46].copy_from_slice(b"__TEXT");
+    contents[96..100].copy_from_slice(&1u32.to_le_bytes());
+    contents[104..113].copy_from_slice(b"__cstring");
+    contents[120..126].copy_from_slice(b"__TEXT");
+    contents[144..152].copy_from_slice(&28u64.to_le_bytes());
+    contents[152..156].copy_from_slice(&(32u32 + 72 + 80 + 21).to_le_bytes());
+    contents.extend(strings);
+
+    let _env = Env::new("macos", Arch::X86_64, "64")
+        .file("usr/lib/libclang.dylib", &contents)
+        .enable();
+
+    assert_eq!(
+        dynamic::scan_clang_version("usr/lib/libclang.dylib".as_ref()).unwrap(),
+        Some(vec![17, 0, 0]),
+    );
+}
+
+fn test_macos_offline() {
+    let developer = "/Applications/Xcode.app/Contents/Developer\n";
+
+    // `xcode-select` doesn't access the network, so it is still executed in
+    // offline builds and builds without a `HOME` directory.
+    let env = Env::new("macos", Arch::ARM64, "64")
+        .var("CARGO_NET_OFFLINE", Some("true"))
+        .command("xcode-select", &["--print-path"], developer)
+        .enable();
+    assert_eq!(
+        common::run_xcode_select(&["--print-path"]),
+        Some(developer.into())
+    );
+    assert_eq!(env.commands.lock().unwrap().invocations.len(), 1);
+}
+
+fn test_macos_no_xcode() {
+    let developer = "/Applications/Xcode.app/Contents/Developer\n";
+
+    let env = Env::new("macos", Arch::ARM64, "64")
+        .command("xcode-select", &["--print-path"], developer)
+        .enable();
+    assert_eq!(
+        common::run_xcode_select(&["--print-path"]),
+        Some(developer.into())
+    );
+    drop(env);
+
+    let env = Env::new("macos", Arch::ARM64, "64")
+        .var("CLANG_SYS_NO_XCODE", Some("1"))
+        .command("xcode-select", &["--print-path"], developer)
+        .enable();
+    assert_eq!(common::run_xcode_select(&["--print-path"]), None);
+    drop(env);
+
+    // The selection file can also disable `xcode-select`.
+    let _env = Env::new("macos", Arch::ARM64, "64")
+        .var("CLANG_SYS_SELECTION", Some("selection.txt"))
+        .file("selection.txt", b"CLANG_SYS_NO_XCODE=1\n")
+        .command("xcode-select", &["--print-path"], developer)
+        .enable();
+    assert_eq!(common::run_xcode_select(&["--print-path"]), None);
+    assert!(common::check_selection().is_empty());
+}
+
+#[cfg(target_os = "macos")]
+fn test_macos_invalid_install_name() {
+    let _env = Env::new("macos", Arch::X86_64, "64")
+        .var("LIBCLANG_PATH", Some("Xcode.app/Contents/Frameworks"))
+        .dylib(
+            "Xcode.app/Contents/Frameworks/libclang.dylib",
+            0x01000007,
+            "@rpath/libfoo.dylib",
+            &[],
+        )
+        .dylib(
+            "usr/local/opt/llvm/lib/libclang.dylib",
+            0x01000007,
+            "/usr/local/opt/llvm/lib/libclang.dylib",
+            &[],
+        )
+        .enable();
+
+    assert_eq!(
+        dynamic::find(true),
+        Ok(("usr/local/opt/llvm/lib".into(), "libclang.dylib".into())),
+    );
+}
+
+fn test_macos_universal_binary() {
+    let _env = Env::new("macos", Arch::ARM64, "64")
+        .dylib("arm64.dylib", 0x0100000C, "@rpath/libclang.dylib", &[])
+        .dylib("x86_64.dylib", 0x01000007, "@rpath/libclang.dylib", &[])
+        .enable();
+
+    // Build a universal binary containing the x86-64 and ARM64 slices.
+    let x86_64 = fs::read("x86_64.dylib").unwrap();
+    let arm64 = fs::read("arm64.dylib").unwrap();
+    let mut contents = vec![0; 4096];
+    contents[0..4].copy_from_slice(&0xCAFEBABEu32.to_be_bytes());
+    contents[4..8].copy_from_slice(&2u32.to_be_bytes());
+    for (index, (cpu_type, slice)) in [(0x01000007u32, &x86_64), (0x0100000C, &arm64)]
+        .into_iter()
+        .enumerate()
+    {
+        let start = 8 + index * 20;
+        let offset = contents.len() as u32;
+        contents[start..start + 4].copy_from_slice(&cpu_type.to_be_bytes());
+        contents[start + 8..start + 12].copy_from_slice(&offset.to_be_bytes());
+        contents[start + 12..start + 16].copy_from_slice(&(slice.len() as u32).to_be_bytes());
+        contents.extend(slice.iter());
+    }
+    fs::write("libclang.dylib", contents).unwrap();
+
+    let dylibs = dynamic::parse_mach_o_dylibs("libclang.dylib".as_ref()).unwrap();
+    assert_eq!(dylibs.id, Some("@rpath/libclang.dylib".into()));
+    assert!(dynamic::parse_mach_o_dylibs("arm64.dylib".as_ref()).is_ok());
+    assert!(dynamic::parse_mach_o_dylibs("x86_64.dylib".as_ref()).is_err());
+}
+
+//================================================
+// Static
+//================================================
+
+fn test_linux_optional_system_libraries() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .command(
+            "llvm-config",
+            &["--system-libs", "--link-static"],
+            "-lrt -lm -Lopt/zstd/lib -lzstd opt/z3/lib/libz3.so -lxml2 -lz",
+        )
+        .command("llvm-config", &["--libdir"], "opt/llvm/lib\n")
+        .command(
+            "pkg-config",
+            &["--libs-only-L", "libxml-2.0"],
+            "-Lopt/xml2/lib\n",
+        )
+        .file("opt/zstd/lib/libzstd.a", b"")
+        .file("opt/llvm/lib/libxml2.so", b"")
+        .file("opt/xml2/lib/libxml2.a", b"")
+        .enable();
+
+    assert_eq!(
+        r#static::find_optional_system_libraries(),
+        vec![
+            ("zstd".into(), Some("opt/zstd/lib".into())),
+            ("xml2".into(), Some("opt/llvm/lib".into())),
+        ],
+    );
+}
+
+fn test_linux_optional_system_libraries_missing() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .command(
+            "llvm-config",
+            &["--system-libs", "--link-static"],
+            "-lrt -lm -lz -lzstd",
+        )
+        .enable();
+
+    assert_eq!(
+        r#static::find_optional_system_libraries(),
+        vec![("zstd".into(), None)],
+    );
+}
+
+fn test_linux_clang_libraries() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .file("lib/libclangAST.a", b"!<arch>\n")
+        .file(
+            "lib/libclangBasic.a",
+            b"/* GNU ld script */\nGROUP ( libclangBasic_real.a AS_NEEDED ( -lclangLex ) )\n",
+        )
+        .file("lib/libclangBasic_real.a", b"!<arch>\n")
+        .file("lib/libclangLex.a", b"!<arch>\n")
+        .file(
+            "lib/libclangSema.a",
+            b"CREATE libclangSema.a\nADDLIB ../vendor/libclangSema_static.a\nSAVE\nEND\n",
+        )
+        .file("vendor/libclangSema_static.a", b"!<arch>\n")
+        .file(
+            "lib/libclangParse.a",
+            b"CREATE libclangParse.a\nADDLIB parse/libclangParse.a (Parser.o, ParseDecl.o)\nSAVE\nEND\n",
+        )
+        .file("lib/parse/libclangParse.a", b"!<arch>\n")
+        .enable();
+
+    let mut libraries = r#static::get_clang_libraries("lib", "libclang*.a").unwrap();
+    libraries.sort();
+    assert_eq!(
+        libraries,
+        vec![
+            ("lib".into(), "clangAST".into()),
+            ("lib".into(), "clangBasic_real".into()),
+            ("lib".into(), "clangLex".into()),
+            ("lib/../vendor".into(), "clangSema_static".into()),
+            ("lib/parse".into(), "clangParse".into()),
+        ],
+    );
+}
+
+fn test_linux_clang_libraries_thin_archive() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .file("lib/libclangAST.a", b"!<arch>\n")
+        .file("lib/libclangBasic.a", b"!<thin>\n")
+        .enable();
+
+    assert_error!(
+        r#static::get_clang_libraries("lib", "libclang*.a"),
+        "lib/libclangBasic.a is a thin archive",
+    );
+}
+
+fn test_linux_clang_libraries_invalid() {
+    let env = Env::new("linux", Arch::X86_64, "64")
+        .file("lib/libclangBasic.a", b"INPUT(libclangMissing.a)")
+        .enable();
+
+    assert_error!(
+        r#static::get_clang_libraries("lib", "libclang*.a"),
+        "lib/libclangBasic.a lists lib/libclangMissing.a which could not be found",
+    );
+
+    drop(env);
+
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .file("lib/libclangBasic.a", b"\x7fELF")
+        .enable();
+
+    assert_error!(
+        r#static::get_clang_libraries("lib", "libclang*.a"),
+        "lib/libclangBasic.a is neither an archive nor a linker or MRI script",
+    );
+}
+
+fn test_linux_clang_libraries_pattern() {
+    let env = Env::new("linux", Arch::X86_64, "64")
+        .var("LIBCLANG_STATIC_PATTERN", Some("libclang*_static.a"))
+        .file("lib/libclangAST_static.a", b"!<arch>\n")
+        .file("lib/libclangBasic_static.a", b"!<arch>\n")
+        .file("lib/libclangBasic.so", b"")
+        .enable();
+
+    let pattern = r#static::get_pattern().unwrap();
+    assert_eq!(pattern, "libclang*_static.a");
+
+    let mut libraries = r#static::get_clang_libraries("lib", &pattern).unwrap();
+    libraries.sort();
+    assert_eq!(
+        libraries,
+        vec![
+            ("lib".into(), "clangAST_static".into()),
+            ("lib".into(), "clangBasic_static".into()),
+        ],
+    );
+
+    drop(env);
+
+    for pattern in ["libclang.a", "libclang**.a", "lib/libclang*.a"] {
+        let _env = Env::new("linux", Arch::X86_64, "64")
+            .var("LIBCLANG_STATIC_PATTERN", Some(pattern))
+            .enable();
+        assert_error!(
+            r#static::get_pattern(),
+            "must be a file name containing exactly one `*`"
+        );
+    }
+}
+
+/// Constructs an archive with a symbol table (in the GNU or BSD format) which
+/// lists the supplied symbols.
+fn archive(symbols: &[&str], bsd: bool) -> Vec<u8> {
+    let header = |name: &str, size: usize| {
+        format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            name, 0, 0, 0, 644, size
+        )
+    };
+
+    let mut strings = vec![];
+    let mut offsets = vec![];
+    for symbol in symbols {
+        offsets.push(strings.len() as u32);
+        strings.extend(symbol.as_bytes());
+        strings.push(0);
+    }
+
+    let mut contents = b"!<arch>\n".to_vec();
+    if bsd {
+        let mut data = b"__.SYMDEF\0\0\0".to_vec();
+        data.extend((offsets.len() as u32 * 8).to_le_bytes());
+        for offset in offsets {
+            data.extend(offset.to_le_bytes());
+            data.extend(0u32.to_le_bytes());
+        }
+        data.extend((strings.len() as u32).to_le_bytes());
+        data.extend(strings);
+        contents.extend(header("#1/12", data.len()).as_bytes());
+        contents.extend(data);
+    } else {
+        let mut data = (symbols.len() as u32).to_be_bytes().to_vec();
+        data.extend(symbols.iter().flat_map(|_| 0u32.to_be_bytes()));
+        data.extend(strings);
+        contents.extend(header("/", data.len()).as_bytes());
+        contents.extend(data);
+    }
+
+    contents
+}
+
+fn test_linux_clang_shim() {
+    use r#static::StaticContents::*;
+
+    let api = "clang_createIndex";
+    let clang = "_ZN5clang4Sema11ActOnTypeofEv";
+    let llvm = "_ZNK4llvm5Twine3strEv";
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .file("complete/libclang.a", &archive(&[api, clang, llvm], false))
+        .file("clang/libclang.a", &archive(&[api, clang], false))
+        .file("shim/libclang.a", &archive(&[api], true))
+        .file("shim/libLLVM-18.so", b"")
+        .file("shim/libLLVM.so", b"")
+        .file("shim/libclang-cpp.so", b"")
+        .enable();
+
+    let contents = |path: &str| {
+        let symbols = r#static::parse_archive_symbols(path.as_ref()).unwrap();
+        r#static::get_static_contents(&symbols)
+    };
+
+    assert_eq!(
+        r#static::parse_archive_symbols("clang/libclang.a".as_ref()).unwrap(),
+        [api, clang],
+    );
+    assert_eq!(
+        r#static::parse_archive_symbols("shim/libclang.a".as_ref()).unwrap(),
+        [api],
+    );
+    assert_eq!(contents("complete/libclang.a"), Complete);
+    assert_eq!(contents("clang/libclang.a"), WithoutLlvm);
+    assert_eq!(contents("shim/libclang.a"), CApiOnly);
+
+    // Files which aren't archives are rejected.
+    assert!(r#static::parse_archive_symbols("shim/libLLVM.so".as_ref()).is_err());
+
+    assert_eq!(
+        r#static::get_shared_llvm_libraries("shim".as_ref()),
+        ["LLVM"],
+    );
+}
+
+fn test_linux_clang_shim_llvm_config() {
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .command("llvm-config", &["--libs", "--link-shared"], "-lLLVM-18\n")
+        .enable();
+
+    assert_eq!(
+        r#static::get_shared_llvm_libraries("lib".as_ref()),
+        ["LLVM-18"],
+    );
+}
+
+fn test_verify_static_libraries() {
+    let tmp = tempfile::tempdir().unwrap();
+    let empty = tmp.path().join("lib");
+    fs::create_dir(&empty).unwrap();
+
+    // A program which calls `clang_createIndex` can't be linked without the
+    // Clang static libraries.
+    let arguments = ["-L".into(), format!("native={}", empty.display())];
+    assert_error!(
+        r#static::verify(tmp.path(), &arguments),
+        "clang_createIndex"
+    );
+    assert_error!(
+        r#static::verify(tmp.path(), &arguments),
+        "LIBCLANG_STATIC_VERIFY is set"
+    );
+}
+
+//================================================
+// WebAssembly
+//================================================
+
+fn test_wasm_target() {
+    let env = Env::new("linux", Arch::X86_64, "64")
+        .var("TARGET", Some("wasm32-wasip1"))
+        .enable();
+
+    assert_eq!(common::wasm_target(), Some("wasm32-wasip1".into()));
+
+    drop(env);
+
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .var("TARGET", Some("x86_64-unknown-linux-gnu"))
+        .enable();
+
+    assert_eq!(common::wasm_target(), None);
+}
+
+fn test_wasm_libraries() {
+    let env = Env::new("linux", Arch::X86_64, "64")
+        .file("wasm/libclangAST.a", b"!<arch>\n")
+        .file("wasm/libclangBasic.a", b"!<arch>\n")
+        .file("wasm/libLLVMSupport.a", b"!<arch>\n")
+        .enable();
+
+    let mut libraries = r#static::get_wasm_libraries("wasm".as_ref(), "libclang*.a").unwrap();
+    libraries[..2].sort();
+    assert_eq!(
+        libraries,
+        vec![
+            ("wasm".into(), "clangAST".into()),
+            ("wasm".into(), "clangBasic".into()),
+            ("wasm".into(), "LLVMSupport".into()),
+        ],
+    );
+
+    drop(env);
+
+    let _env = Env::new("linux", Arch::X86_64, "64")
+        .file("wasm/libLLVMSupport.a", b"!<arch>\n")
+        .enable();
+
+    assert_error!(
+        r#static::get_wasm_libraries("wasm".as_ref(), "libclang*.a"),
+        "could not find any Clang static libraries matching libclang*.a in wasm",
+    );
+}
+
+//================================================
+// Warnings
+//================================================
+
+fn test_warnings() {
+    use common::Severity;
+
+    let printed = |value: Option<&str>| {
+        let _env = Env::new("linux", Arch::X86_64, "64")
+            .var("CLANG_SYS_WARNINGS", value)
+            .enable();
+        [Severity::Debug, Severity::Info, Severity::Warning].map(common::is_printed)
+    };
+
+    assert_eq!(printed(None), [false, true, true]);
+    assert_eq!(printed(Some("normal")), [false, true, true]);
+    assert_eq!(printed(Some("quiet")), [false, false, true]);
+    assert_eq!(printed(Some("verbose")), [true, true, true]);
+
+    let env = Env::new("linux", Arch::X86_64, "64")
+        .var("CLANG_SYS_WARNINGS", Some("quiet"))
+        .enable();
+
+    // Warnings are reported regardless of whether they are printed.
+    let env = env.var("CLANG_SYS_WARNINGS", Some("quiet")).enable();
+    let count = common::warnings().len();
+    common::warn(Severity::Info, "found \"llvm-config\"");
+    common::warn(Severity::Warning, "skipped C:\\libclang.dll\n  (error)");
+    assert_eq!(
+        common::warnings()[count..],
+        [
+            (Severity::Info, "found \"llvm-config\"".into()),
+            (
+                Severity::Warning,
+                "skipped C:\\libclang.dll\n  (error)".into()
+            ),
+        ],
+    );
+
+    let path = env.tmp.path().join("warnings.json");
+    drop(common::WarningReport::new(&path));
+    let json = fs::read_to_string(&path).unwrap();
+    assert!(json.starts_with("[\n"));
+    assert!(json.ends_with(concat!(
+        "  {\"severity\": \"info\", \"message\": \"found \\\"llvm-config\\\"\"},\n",
+        "  {\"severity\": \"warning\", \"message\": \"skipped C:\\\\libclang.dll\\n  (error)\"}\n",
+        "]\n",
+    )));
+}
+
+//================================================
+// Paths
+//================================================
+
+#[test]
+fn test_parse_functions() {
+    let source = "link! {\n    pub fn clang_createIndex(exclude: c_int) -> CXIndex;\n    \
+                  /// Only available on `libclang` 16.0 and later.\n    \
+                  #[cfg(feature = \"clang_16_0\")]\n    \
+                  pub fn clang_disposeIndex(index: CXIndex);\n}\n";
+    let functions = dynamic::parse_functions(source);
+    assert_eq!(functions, vec!["clang_createIndex", "clang_disposeIndex"]);
+    assert!(dynamic::generate_stub(&functions).contains("pub static clang_sys_stub: u8 = 0;"));
+    assert!(dynamic::generate_stub(&functions).contains(
+        "#[no_mangle]\npub extern \"C\" fn clang_disposeIndex() {\n    std::process::abort()\n}"
+    ));
+}
+
+#[test]
+fn test_parse_constants() {
+    let source = r#"
+cenum! {
+    enum CXA {
+        const CXA_First = 0,
+        /// Only available on `libclang` 15.0 and later.
+        #[cfg(feature = "clang_15_0")]
+        const CXA_Second = 1,
+    }
+}
+
+cenum! {
+    /// Only available on `libclang` 17.0 and later.
+    #[cfg(feature = "clang_17_0")]
+    #[repr(c_uchar)]
+    enum CXB {
+        const CXB_First = 0;
+    }
+}
+
+/// Only available on `libclang` 17.0 and later.
+#[cfg(feature = "clang_17_0")]
+pub const CXC_First: CXC = 1;
+
+pub const CXC_Second: CXC = 2;
+"#;
+
+    let cfg = |v: &str| format!("#[cfg(feature = \"clang_{}\")]", v);
+    let constants = audit::parse_constants(source);
+    assert_eq!(
+        constants,
+        vec![
+            ("CXA_First".into(), vec![]),
+            ("CXA_Second".into(), vec![cfg("15_0")]),
+            ("CXB_First".into(), vec![cfg("17_0")]),
+            ("CXC_First".into(), vec![cfg("17_0")]),
+            ("CXC_Second".into(), vec![]),
+        ],
+    );
+
+    let code = audit::generate(&constants[1..2]);
+    assert!(code.contains(
+        "    #[cfg(feature = \"clang_15_0\")]\n    constants.push((\"CXA_Second\", crate::CXA_Second as i64));"
+    ));
+
+    let header = r#"
+/* The kind of an enum (not `enum CXA { ... }`). */
+enum CXA {
+  CXA_First, // = 7
+  CXA_Second = 0x4,
+  CXA_Third,
+  CXA_Fourth = (1 << 3) | CXA_Second,
+  CXA_Fifth = sizeof(int),
+  CXA_Sixth
+};
+
+CINDEX_LINKAGE void clang_a(enum CXA a);
+
+typedef enum {
+#if defined(B)
+  CXB_First = -1
+#endif
+} CXB;
+"#;
+
+    let mut headers = HashMap::new();
+    audit::parse_header_constants(header, &mut headers);
+    let mut headers = headers.into_iter().collect::<Vec<_>>();
+    headers.sort();
+    assert_eq!(
+        headers,
+        vec![
+            ("CXA_First".into(), 0),
+            ("CXA_Fourth".into(), 12),
+            ("CXA_Second".into(), 4),
+            ("CXA_Third".into(), 5),
+            ("CXB_First".into(), -1),
+        ],
+    );
+
+    let headers = headers.into_iter().collect();
+    let code = audit::generate_checks(&constants, &headers);
+    assert!(code.contains(
+        "#[cfg(feature = \"clang_15_0\")]\nconst _: () = assert!(\n    crate::CXA_Second as i64 == 4,"
+    ));
+    assert!(!code.contains("CXC_"));
+}
+
+#[test]
+fn test_recording() {
+    let recording = common::Recording {
+        target: vec!["linux".into(), "x86_64".into(), "64".into(), "gnu".into()],
+        runtime: true,
+        variables: vec![("LIBCLANG_PATH".into(), "C:\\LLVM\\lib".into())],
+        strategies: vec![
+            (true, common::DiscoverySource::Environment, vec![]),
+            (
+                false,
+                common::DiscoverySource::KnownDirectory,
+                vec![vec!["a b".into()], vec!["c".into(), "d\te".into()]],
+            ),
+        ],
+        commands: vec![
+            (
+                "llvm-config".into(),
+                vec!["--prefix".into()],
+                Some("/usr\n".into()),
+            ),
+            ("xcode-select".into(), vec![], None),
+        ],
+        directories: vec![
+            ("a b".into(), Some(vec![].into())),
+            ("c".into(), None),
+            (
+                "d\te".into(),
+                Some(vec![("lib".into(), true), ("libclang.so".into(), false)].into()),
+            ),
+        ],
+        libraries: vec![
+            (
+                "d\te/libclang.so".into(),
+                common::LibraryProperties {
+                    valid: Ok(()),
+                    version: vec![17, 0, 6],
+                    compatible: Err("requires GLIBC_2.38".into()),
+                },
+            ),
+            (
+                "d\te/libclang-16.so".into(),
+                common::LibraryProperties {
+                    valid: Err("invalid ELF header".into()),
+                    version: vec![],
+                    compatible: Ok(()),
+                },
+            ),
+        ],
+        result: Some(Err("couldn't find\nany".into())),
+    };
+
+    let text = recording.serialize();
+    assert_eq!(common::Recording::parse(&text), Ok(recording));
+    assert!(common::Recording::parse("unknown\t1").is_err());
+}
+
+#[test]
+fn test_extended_length_prefixes() {
+    let long = format!(r"C:\{}\libclang.dll", long_path("\\"));
+
+    assert_eq!(
+        common::strip_extended_length_prefix(r"\\?\C:\LLVM\bin"),
+        r"C:\LLVM\bin",
+    );
+    assert_eq!(
+        common::strip_extended_length_prefix(r"\\?\UNC\server\share\LLVM"),
+        r"\\server\share\LLVM",
+    );
+    assert_eq!(
+        common::strip_extended_length_prefix(r"\\?\Volume{1}\LLVM"),
+        r"\\?\Volume{1}\LLVM",
+    );
+    assert_eq!(common::strip_extended_length_prefix("/usr/lib"), "/usr/lib");
+
+    assert_eq!(
+        common::add_extended_length_prefix(r"C:\LLVM\bin\libclang.dll"),
+        r"C:\LLVM\bin\libclang.dll",
+    );
+    assert_eq!(
+        common::add_extended_length_prefix(&long),
+        format!(r"\\?\{long}"),
+    );
+    assert_eq!(
+        common::add_extended_length_prefix(&long.replace('\\', "/")),
+        format!(r"\\?\{long}"),
+    );
+    assert_eq!(
+        common::add_extended_length_prefix(&format!(r"\\server\share\{long}")),
+        format!(r"\\?\UNC\server\share\{long}"),
+    );
+    assert_eq!(common::add_extended_length_prefix(&long[3..]), &long[3..],);
+
+    assert_eq!(
+        common::normalize_windows_path(r"\\?\C:\LLVM\bin\libclang.dll"),
+        r"C:\LLVM\bin\libclang.dll",
+    );
+    assert_eq!(
+        common::normalize_windows_path(&format!(r"\\?\{long}")),
+        format!(r"\\?\{long}"),
+    );
+}
+
+//================================================
+// Versions
+//================================================
+
+#[test]
+fn test_parse_pe_file_version() {
+    fn write(contents: &mut [u8], offset: usize, bytes: &[u8]) {
+        contents[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    let mut contents = vec![0; 0x300];
+
+    // PE header with a single `.rsrc` section.
+    write(&mut contents, 0x3C, &0x40u32.to_le_bytes());
+    write(&mut contents, 0x40, b"PE\0\0");
+    write(&mut contents, 0x44, &0x8664u16.to_le_bytes());
+    write(&mut contents, 0x46, &1u16.to_le_bytes());
+    write(&mut contents, 0x54, &240u16.to_le_bytes());
+    write(&mut contents, 0x58, &523u16.to_le_bytes());
+    write(&mut contents, 0x58 + 112 + 16, &0x1000u32.to_le_bytes());
+    write(&mut contents, 0x58 + 112 + 20, &0x100u32.to_le_bytes());
+    write(&mut contents, 0x148, b".rsrc\0\0\0");
+    write(&mut contents, 0x148 + 12, &0x1000u32.to_le_bytes());
+    write(&mut contents, 0x148 + 16, &0x100u32.to_le_bytes());
+    write(&mut contents, 0x148 + 20, &0x200u32.to_le_bytes());
+
+    // `VS_FIXEDFILEINFO` for version 18.1.8.
+    write(&mut contents, 0x220, &0xFEEF04BDu32.to_le_bytes());
+    write(&mut contents, 0x228, &((18u32 << 16) | 1).to_le_bytes());
+    write(&mut contents, 0x22C, &(8u32 << 16).to_le_bytes());
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("libclang.dll");
+    fs::write(&path, &contents).unwrap();
+    assert_eq!(
+        dynamic::parse_pe_file_version(&path).unwrap(),
+        Some(vec![18, 1, 8]),
+    );
+
+    // Shared libraries without a version resource.
+    write(&mut contents, 0x220, &[0; 4]);
+    fs::write(&path, &contents).unwrap();
+    assert_eq!(dynamic::parse_pe_file_version(&path).unwrap(), None);
+}
+
+#[test]
+fn test_parse_binary_info() {
+    fn write(contents: &mut [u8], offset: usize, bytes: &[u8]) {
+        contents[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+
+    // ELF shared library for x86-64 which depends on `libLLVM`.
+    let strings = b"\0libLLVM.so.18.1\0libc.so.6\0";
+    let dynamic = [1u64, 1, 1, 17, 0, 0].map(u64::to_le_bytes).concat();
+    let mut contents = elf(&[(3, 2, strings, 0), (6, 3, &dynamic, 1)]);
+    write(&mut contents, 18, &62u16.to_le_bytes());
+    let path = tmp.path().join("libclang.so");
+    fs::write(&path, &contents).unwrap();
+    assert_eq!(
+        dynamic::parse_binary_info(&path).unwrap(),
+        dynamic::BinaryInfo {
+            architectures: vec!["x86_64".into()],
+            min_os_version: None,
+            dependencies: vec!["libLLVM.so.18.1".into(), "libc.so.6".into()],
+        },
+    );
+
+    // PE shared library for ARM64 with a single `.idata` section.
+    let mut contents = vec![0; 0x300];
+    write(&mut contents, 0, b"MZ");
+    write(&mut contents, 0x3C, &0x40u32.to_le_bytes());
+    write(&mut contents, 0x40, b"PE\0\0");
+    write(&mut contents, 0x44, &0xAA64u16.to_le_bytes());
+    write(&mut contents, 0x46, &1u16.to_le_bytes());
+    write(&mut contents, 0x54, &240u16.to_le_bytes());
+    write(&mut contents, 0x58, &523u16.to_le_bytes());
+    write(&mut contents, 0x58 + 48, &6u16.to_le_bytes());
+    write(&mut contents, 0x58 + 50, &2u16.to_le_bytes());
+    write(&mut contents, 0x58 + 112 + 8, &0x1000u32.to_le_bytes());
+    write(&mut contents, 0x58 + 112 + 12, &40u32.to_le_bytes());
+    write(&mut contents, 0x148, b".idata\0\0");
+    write(&mut contents, 0x148 + 12, &0x1000u32.to_le_bytes());
+    write(&mut contents, 0x148 + 16, &0x100u32.to_le_bytes());
+    write(&mut contents, 0x148 + 20, &0x200u32.to_le_bytes());
+    write(&mut contents, 0x200 + 12, &0x1080u32.to_le_bytes());
+    write(&mut contents, 0x280, b"KERNEL32.dll\0");
+    let path = tmp.path().join("libclang.dll");
+    fs::write(&path, &contents).unwrap();
+    assert_eq!(
+        dynamic::parse_binary_info(&path).unwrap(),
+        dynamic::BinaryInfo {
+            architectures: vec!["aarch64".into()],
+            min_os_version: Some(vec![6, 2]),
+            dependencies: vec!["KERNEL32.dll".into()],
+        },
+    );
+
+    // Mach-O shared library for ARM64 which requires macOS 14.4.
+    let mut contents = vec![0; 32 + 24];
+    write(&mut contents, 0, &0xFEEDFACFu32.to_le_bytes());
+    write(&mut contents, 4, &0x0100000Cu32.to_le_bytes());
+    write(&mut contents, 16, &1u32.to_le_bytes());
+    write(&mut contents, 32, &0x32u32.to_le_bytes());
+    write(&mut contents, 36, &24u32.to_le_bytes());
+    write(&mut contents, 40, &1u32.to_le_bytes());
+    write(&mut contents, 44, &0x000E0400u32.to_le_bytes());
+    let path = tmp.path().join("libclang.dylib");
+    fs::write(&path, &contents).unwrap();
+    assert_eq!(
+        dynamic::parse_binary_info(&path).unwrap(),
+        dynamic::BinaryInfo {
+            architectures: vec!["aarch64".into()],
+            min_os_version: Some(vec![14, 4, 0]),
+            dependencies: vec![],
+        },
+    );
+}
+
+#[test]
+fn test_parse_open_error() {
+    use dynamic::OpenFailure::*;
+
+    let cases = [
+        (
+            "libtinfo.so.5: cannot open shared object file: No such file or directory",
+            Some(MissingDependency("libtinfo.so.5".into())),
+        ),
+        (
+            "/usr/lib/libclang.so: cannot open shared object file: No such file or directory",
+            None,
+        ),
+        (
+            "dlopen(/opt/llvm/lib/libclang.dylib, 0x0005): Library not loaded: \
+             @rpath/libLLVM.dylib\n  Referenced from: /opt/llvm/lib/libclang.dylib",
+            Some(MissingDependency("@rpath/libLLVM.dylib".into())),
+        ),
+        (
+            "/lib/x86_64-linux-gnu/libc.so.6: version `GLIBC_2.34' not found (required by \
+             /usr/lib/libclang.so)",
+            Some(RuntimeTooOld("GLIBC_2.34".into())),
+        ),
+        (
+            "/usr/lib/libclang.so: wrong ELF class: ELFCLASS32",
+            Some(WrongArchitecture),
+        ),
+        (
+            "%1 is not a valid Win32 application. (os error 193)",
+            Some(WrongArchitecture),
+        ),
+        (
+            "/usr/lib/libclang.so: undefined symbol: _ZN4llvm3foo, version LLVM_18",
+            Some(UndefinedSymbol("_ZN4llvm3foo".into())),
+        ),
+        ("/usr/lib/libclang.so: file too short", None),
+    ];
+
+    for (message, expected) in cases {
+        let failure = dynamic::parse_open_error("/usr/lib/libclang.so", message);
+        assert_eq!(failure, expected, "{}", message);
+    }
+}
+
+#[test]
+fn test_find_llvm_dependencies() {
+    let tmp = tempfile::tempdir().unwrap();
+    let libdir = tmp.path().join("llvm/lib");
+    let directory = tmp.path().join("lib");
+    fs::create_dir_all(&libdir).unwrap();
+    fs::create_dir_all(&directory).unwrap();
+
+    let strings = b"\0libLLVM-18.so.1\0libc.so.6\0";
+    let dynamic = [1u64, 1, 1, 17, 0, 0].map(u64::to_le_bytes).concat();
+    let path = directory.join("libclang.so");
+    fs::write(&path, elf(&[(3, 2, strings, 0), (6, 3, &dynamic, 1)])).unwrap();
+
+    // Dependencies which can't be found are omitted.
+    let dependencies = dynamic::find_llvm_dependencies(&path, || Some(libdir.clone()));
+    assert!(dependencies.is_empty());
+
+    // Dependencies are found in the supplied directory.
+    fs::write(libdir.join("libLLVM-18.so.1"), b"").unwrap();
+    assert_eq!(
+        dynamic::find_llvm_dependencies(&path, || Some(libdir.clone())),
+        vec![libdir.join("libLLVM-18.so.1")],
+    );
+
+    // Dependencies in the same directory are preferred (without determining
+    // the supplied directory).
+    fs::write(directory.join("libLLVM-18.so.1"), b"").unwrap();
+    assert_eq!(
+        dynamic::find_llvm_dependencies(&path, || unreachable!()),
+        vec![directory.join("libLLVM-18.so.1")],
+    );
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+#[test]
+fn test_parse_elf_version_needs() {
+    // The test executable itself requires some version of `glibc`.
+    let needs = dynamic::parse_elf_version_needs(&env::current_exe().unwrap()).unwrap();
+    assert!(
+        dynamic::max_symbol_version(&needs, "GLIBC_").is_some(),
+        "{:?}",
+        needs
+    );
+}
+
+/// A deterministic pseudo-random number generator (xorshift) used to generate
+/// inputs for the property tests below.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn string(&mut self, alphabet: &[&str], length: u64) -> String {
+        let length = self.below(length);
+        (0..length)
+            .map(|_| alphabet[self.below(alphabet.len() as u64) as usize])
+            .collect()
+    }
+}
+
+#[test]
+fn test_parse_clang_version() {
+    let cases: &[(&str, Option<&[u32]>)] = &[
+        ("clang version 17.0.6", Some(&[17, 0, 6])),
+        (
+            "clang version 17.0.6 (https://github.com/llvm/llvm-project 6009708b)",
+            Some(&[17, 0, 6]),
+        ),
+        ("Ubuntu clang version 14.0.0-1ubuntu1.1", Some(&[14, 0, 0])),
+        (
+            "Apple clang version 15.0.0 (clang-1500.3.9.4)",
+            Some(&[15, 0, 0]),
+        ),
+        (
+            "Android (11349228, based on r487747e) clang version 17.0.2",
+            Some(&[17, 0, 2]),
+        ),
+        ("clang version 18.1.8git", Some(&[18, 1, 8])),
+        ("clang version 19", Some(&[19])),
+        ("clang version 3.10", Some(&[3, 10])),
+        ("clang version .5", None),
+        ("clang version", None),
+        ("clang version x.y.z", None),
+        ("clang version 99999999999.0", None),
+        ("", None),
+    ];
+
+    for (string, expected) in cases {
+        let expected = expected.map(|v| v.to_vec());
+        assert_eq!(version::parse_clang_version(string), expected, "{string:?}");
+    }
+}
+
+#[test]
+fn test_extract_version_from_llvm_path() {
+    let cases: &[(&str, &[u32])] = &[
+        ("/usr/lib/llvm-17/bin/llvm-config", &[17]),
+        ("/usr/lib/llvm-17.0.6/bin/llvm-config", &[17, 0, 6]),
+        ("/opt/homebrew/opt/llvm@18/bin/llvm-config", &[18]),
+        ("/opt/homebrew/opt/llvm/bin/llvm-config", &[999]),
+        ("/usr/bin/llvm-config-16", &[16]),
+        ("/usr/bin/llvm-config-16.exe", &[16]),
+        ("/usr/bin/llvm-config-garbage", &[0]),
+        ("/opt/llvm@garbage/bin/llvm-config", &[999]),
+        ("/opt/llvm@garbage.17/bin/llvm-config", &[999]),
+        ("/home/me/llvm-project-build/bin/llvm-config", &[999]),
+        ("/home/me/llvm-project-build/llvm-15/bin/llvm-config", &[15]),
+        ("/usr/lib/llvm-99999999999/bin/llvm-config", &[999]),
+    ];
+
+    for (path, expected) in cases {
+        let version = common::extract_version_from_llvm_path(std::path::Path::new(path));
+        assert_eq!(&version, expected, "{path:?}");
+    }
+}
+
+#[test]
+fn test_parse_llvm_config_version() {
+    let cases: &[(&str, Option<&[u32]>)] = &[
+        ("18.1.8\n", Some(&[18, 1, 8])),
+        ("18.1.8git\n", Some(&[18, 1, 8])),
+        ("18.1.0-rc1\n", Some(&[18, 1, 0])),
+        ("18.1.0rc1\n", Some(&[18, 1, 0])),
+        ("14.0.0-1ubuntu1.1\n", Some(&[14, 0, 0])),
+        ("\n  17.0.6 \n19.1.0\n", Some(&[17, 0, 6])),
+        ("LLVM version 16.0.6\n", Some(&[16, 0, 6])),
+        ("llvm-config: command not found\n", None),
+        ("", None),
+    ];
+
+    for (output, expected) in cases {
+        let expected = expected.map(|v| v.to_vec());
+        let version = version::parse_llvm_config_version(output);
+        assert_eq!(version, expected, "{output:?}");
+    }
+
+    assert_eq!(version::format(&[18, 1, 8]), "18.1.8");
+    assert_eq!(version::format(&[19]), "19");
+    assert_eq!(version::format(&[]), "");
+}
+
+#[test]
+fn test_version_parsers_arbitrary_input() {
+    let alphabet = &[
+        "0",
+        "1",
+        "9",
+        "42",
+        ".",
+        "..",
+        "-",
+        "@",
+        " ",
+        "\t",
+        "/",
+        "llvm",
+        "llvm-",
+        "llvm@",
+        "config",
+        "clang",
+        "version",
+        "git",
+        "rc1",
+        "é",
+        "4294967296",
+    ];
+
+    let mut rng = Rng(0x2545F4914F6CDD1D);
+    for _ in 0..20_000 {
+        let string = rng.string(alphabet, 16);
+
+        let components = version::parse_components(&string);
+        assert!(
+            components.len() <= string.matches('.').count() + 1,
+            "{string:?}"
+        );
+
+        if let Some(version) = version::parse_clang_version(&string) {
+            assert!(!version.is_empty(), "{string:?}");
+            assert!(string.contains("version"), "{string:?}");
+        }
+
+        let version = common::extract_version_from_llvm_path(std::path::Path::new(&string));
+        assert!(!version.is_empty(), "{string:?}");
+    }
+}
+
+#[test]
+fn test_version_parsers_roundtrip() {
+    let mut rng = Rng(0x9E3779B97F4A7C15);
+    for _ in 0..5_000 {
+        let length = 1 + rng.below(4) as usize;
+        let version = (0..length)
+            .map(|_| rng.below(u32::MAX as u64 + 1) as u32)
+            .collect::<Vec<_>>();
+        let string = version
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(".");
+
+        let suffix = ["", "git", "-rc1", "-1ubuntu1", " (trunk)"][rng.below(5) as usize];
+        let clang = format!("vendor clang version {string}{suffix}");
+        assert_eq!(version::parse_clang_version(&clang), Some(version.clone()));
+
+        let output = format!("{string}{suffix}\n");
+        let parsed = version::parse_llvm_config_version(&output);
+        assert_eq!(parsed, Some(version.clone()));
+        assert_eq!(
+            version::parse_components(&version::format(&version)),
+            version
+        );
+
+        let path = format!("/usr/lib/llvm-{string}/bin/llvm-config");
+        let path = std::path::Path::new(&path);
+        assert_eq!(common::extract_version_from_llvm_path(path), version);
+    }
+}
+
+#[test]
+fn test_parse_filename_version() {
+    let cases: &[(&str, &[u32])] = &[
+        ("libclang.so", &[]),
+        ("libclang.so.13", &[13]),
+        ("libclang.so.15.0.7", &[15, 0, 7]),
+        ("libclang-15.so", &[15]),
+        ("libclang-3.9.so", &[3, 9]),
+        ("libclang-15.so.1", &[15]),
+        ("libclang-cpp.so.15", &[]),
+        ("libclang.1.dylib", &[]),
+        ("libclang.dylib", &[]),
+        ("libclang.dll", &[]),
+    ];
+
+    for (filename, version) in cases {
+        assert_eq!(dynamic::parse_version(filename), *version, "{filename}");
+    }
+}
@@ -0,0 +1,161 @@
+use std::env;
+use std::fs;
+
+use clang_sys::env::*;
+
+/// Sets an environment variable.
+fn set(name: &str, value: &str) {
+    unsafe { env::set_var(name, value) };
+}
+
+/// Unsets an environment variable.
+fn remove(name: &str) {
+    unsafe { env::remove_var(name) };
+}
+
+#[test]
+fn test_markdown() {
+    let readme = fs::read_to_string("README.md").unwrap();
+    assert!(readme.contains(&markdown()));
+}
+
+#[test]
+fn test_validate() {
+    for variable in VARIABLES {
+        remove(variable.name);
+    }
+    remove("LIB_CLANG_PATH");
+    remove("LIBCLANG_PAHT");
+
+    let directory = tempfile::tempdir().unwrap();
+    let file = directory.path().join("output.txt");
+    fs::write(&file, "").unwrap();
+    let directory = directory.path().to_str().unwrap();
+    let file = file.to_str().unwrap();
+
+    // Nothing set.
+    let report = validate();
+    assert!(report.set.is_empty());
+    assert!(report.is_ok(), "{}", report);
+
+    // Valid paths.
+    set("LIBCLANG_PATH", directory);
+    set("LLVM_CONFIG_OUTPUT", file);
+    set("LLVM_CONFIG_PATH", "llvm-config");
+    let report = validate();
+    assert_eq!(report.set.len(), 3);
+    assert_eq!(
+        report.issues,
+        vec![Issue::Conflict {
+            names: vec!["LLVM_CONFIG_PATH", "LLVM_CONFIG_OUTPUT"],
+            message: "the build script uses the recorded `llvm-config` output instead of \
+                      executing the `llvm-config` executable",
+        }],
+    );
+    remove("LLVM_CONFIG_PATH");
+
+    // Invalid paths.
+    set("LLVM_CONFIG_OUTPUT", directory);
+    set("CLANG_PATH", &format!("{}/clang", directory));
+    let report = validate();
+    assert_eq!(report.issues.len(), 2, "{}", report);
+    assert!(matches!(
+        report.issues[0],
+        Issue::InvalidPath {
+            name: "LLVM_CONFIG_OUTPUT",
+            kind: Kind::File,
+            ..
+        },
+    ));
+    assert!(matches!(
+        report.issues[1],
+        Issue::InvalidPath {
+            name: "CLANG_PATH",
+            kind: Kind::File,
+            ..
+        },
+    ));
+    remove("LLVM_CONFIG_OUTPUT");
+    remove("CLANG_PATH");
+
+    // Output paths.
+    set("CLANG_SYS_RECORD", &format!("{}/recording.txt", directory));
+    assert!(validate().is_ok());
+    set(
+        "CLANG_SYS_RECORD",
+        &format!("{}/missing/recording.txt", directory),
+    );
+    let report = validate();
+    assert!(matches!(
+        report.issues[..],
+        [Issue::InvalidPath {
+            name: "CLANG_SYS_RECORD",
+            kind: Kind::Output,
+            ..
+        }],
+    ));
+    remove("CLANG_SYS_RECORD");
+
+    // Invalid choices.
+    set("CLANG_SYS_WARNINGS", "loud");
+    let report = validate();
+    assert_eq!(report.issues.len(), 1, "{}", report);
+    assert_eq!(
+        report.issues[0].to_string(),
+        "`CLANG_SYS_WARNINGS` is set to loud which is not one of `quiet`, `normal`, `verbose`",
+    );
+    set("CLANG_SYS_WARNINGS", "quiet");
+    assert!(validate().is_ok());
+    remove("CLANG_SYS_WARNINGS");
+
+    // Invalid lists of files.
+    let separator = if cfg!(windows) { ";" } else { ":" };
+    set(
+        "LIBCLANG_PRELOAD",
+        &format!("libclang-cpp.so.18{}{}/missing.so", separator, directory),
+    );
+    let report = validate();
+    assert_eq!(report.issues.len(), 1, "{}", report);
+    assert!(
+        report.issues[0]
+            .to_string()
+            .contains("which is not a list of existing files")
+    );
+    set("LIBCLANG_PRELOAD", "libclang-cpp.so.18");
+    assert!(validate().is_ok());
+    remove("LIBCLANG_PRELOAD");
+
+    // Invalid patterns.
+    set("LIBCLANG_STATIC_PATTERN", "lib/libclang*.a");
+    let report = validate();
+    assert_eq!(report.issues.len(), 1, "{}", report);
+    assert_eq!(
+        report.issues[0].to_string(),
+        "`LIBCLANG_STATIC_PATTERN` is set to lib/libclang*.a which is not a file name pattern containing exactly one `*`",
+    );
+    set("LIBCLANG_STATIC_PATTERN", "libclang*_static.a");
+    assert!(validate().is_ok());
+    remove("LIBCLANG_STATIC_PATTERN");
+
+    // Typos.
+    set("LIB_CLANG_PATH", directory);
+    set("LIBCLANG_PAHT", directory);
+    let mut report = validate();
+    report.issues.sort_by_key(|i| i.to_string());
+    assert_eq!(
+        report.issues,
+        vec![
+            Issue::Typo {
+                name: "LIBCLANG_PAHT".into(),
+                suggestion: "LIBCLANG_PATH"
+            },
+            Issue::Typo {
+                name: "LIB_CLANG_PATH".into(),
+                suggestion: "LIBCLANG_PATH"
+            },
+        ],
+    );
+    remove("LIB_CLANG_PATH");
+    remove("LIBCLANG_PAHT");
+    remove("LIBCLANG_PATH");
+}
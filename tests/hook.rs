@@ -0,0 +1,29 @@
+#![cfg(feature = "runtime")]
+
+// The discovery hook is process-wide and affects every search for a `libclang`
+// shared library, so it is tested in a test binary of its own.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use clang_sys::*;
+
+#[test]
+fn test_discovery_hook() {
+    static PATH: OnceLock<PathBuf> = OnceLock::new();
+    PATH.get_or_init(|| load_manually().unwrap().path().to_path_buf());
+
+    // The path provided by the hook is loaded without searching for a shared library.
+    set_discovery_hook(Some(|| PATH.get().cloned()));
+    assert_eq!(load_manually().unwrap().path(), PATH.get().unwrap());
+
+    // A shared library is searched for if the hook doesn't provide a path.
+    set_discovery_hook(Some(|| None));
+    assert_eq!(load_manually().unwrap().path(), PATH.get().unwrap());
+
+    // The shared library provided by the hook is used even if it can't be opened.
+    set_discovery_hook(Some(|| Some("missing/libclang.so".into())));
+    assert!(matches!(load_manually(), Err(LoadError::OpenFailed { .. })));
+
+    set_discovery_hook(None);
+}
@@ -1,7 +1,15 @@
+use std::ffi::CStr;
 use std::ptr;
 
 use clang_sys::*;
 
+/// Serializes the tests which set the library instance stored process-wide.
+#[cfg(feature = "runtime")]
+fn lock_global_library() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
 fn parse() {
     unsafe {
         let index = clang_createIndex(0, 0);
@@ -20,12 +28,403 @@ fn parse() {
     }
 }
 
+fn tokenize() {
+    unsafe {
+        let index = clang_createIndex(0, 0);
+        let tu = clang_parseTranslationUnit(
+            index,
+            c"tests/header.h".as_ptr(),
+            ptr::null_mut(),
+            0,
+            ptr::null_mut(),
+            0,
+            0,
+        );
+        assert!(!tu.is_null());
+
+        let range = clang_getCursorExtent(clang_getTranslationUnitCursor(tu));
+        let tokens = support::Tokens::tokenize(&tu, range);
+        assert!(!tokens.is_empty());
+        assert_eq!(tokens.annotate().len(), tokens.len());
+        drop(tokens);
+
+        clang_disposeTranslationUnit(tu);
+        clang_disposeIndex(index);
+    }
+}
+
+fn diagnostics() {
+    unsafe {
+        let index = clang_createIndex(0, 0);
+
+        let contents = c"void f(int);\nvoid f(long);\nint x = f();\n";
+        let mut unsaved = CXUnsavedFile {
+            Filename: c"diagnostics.c".as_ptr(),
+            Contents: contents.as_ptr(),
+            Length: contents.to_bytes().len() as _,
+        };
+        let tu = clang_parseTranslationUnit(
+            index,
+            c"diagnostics.c".as_ptr(),
+            [c"-xc++".as_ptr()].as_ptr(),
+            1,
+            &mut unsaved,
+            1,
+            0,
+        );
+        assert!(!tu.is_null());
+
+        // Errors about ambiguous overloads have child notes for the candidates.
+        let set = clang_getDiagnosticSetFromTU(tu);
+        assert_ne!(clang_getNumDiagnosticsInSet(set), 0);
+        let diagnostic = clang_getDiagnosticInSet(set, 0);
+        let children = clang_getChildDiagnostics(diagnostic);
+        assert_ne!(clang_getNumDiagnosticsInSet(children), 0);
+        let child = clang_getDiagnosticInSet(children, 0);
+        assert_eq!(clang_getDiagnosticSeverity(child), CXDiagnostic_Note);
+        clang_disposeDiagnostic(diagnostic);
+        clang_disposeDiagnosticSet(set);
+
+        clang_disposeTranslationUnit(tu);
+        clang_disposeIndex(index);
+
+        // Serialized diagnostics which can't be loaded are reported as such.
+        let mut error = CXLoadDiag_None;
+        let mut message = CXString::default();
+        let set = clang_loadDiagnostics(c"missing.dia".as_ptr(), &mut error, &mut message);
+        assert!(set.is_null());
+        assert_eq!(error, CXLoadDiag_CannotLoad);
+        clang_disposeString(message);
+    }
+}
+
+fn usrs() {
+    unsafe fn string(string: CXString) -> String {
+        unsafe {
+            let result = CStr::from_ptr(clang_getCString(string))
+                .to_str()
+                .unwrap()
+                .into();
+            clang_disposeString(string);
+            result
+        }
+    }
+
+    unsafe {
+        let class = clang_constructUSR_ObjCClass(c"Foo".as_ptr());
+        let category = clang_constructUSR_ObjCCategory(c"Foo".as_ptr(), c"Bar".as_ptr());
+        let protocol = clang_constructUSR_ObjCProtocol(c"Baz".as_ptr());
+        let ivar = clang_constructUSR_ObjCIvar(c"_x".as_ptr(), class);
+        let method = clang_constructUSR_ObjCMethod(c"x".as_ptr(), 1, class);
+        let property = clang_constructUSR_ObjCProperty(c"x".as_ptr(), class);
+
+        assert_eq!(string(ivar), "c:objc(cs)Foo@_x");
+        assert_eq!(string(method), "c:objc(cs)Foo(im)x");
+        assert_eq!(string(property), "c:objc(cs)Foo(py)x");
+        assert_eq!(string(class), "c:objc(cs)Foo");
+        assert_eq!(string(category), "c:objc(cy)Foo@Bar");
+        assert_eq!(string(protocol), "c:objc(pl)Baz");
+    }
+}
+
+fn default_options() {
+    unsafe {
+        let options = clang_defaultEditingTranslationUnitOptions();
+        assert_ne!(options & CXTranslationUnit_PrecompiledPreamble, 0);
+
+        let index = clang_createIndex(0, 0);
+        let tu = clang_parseTranslationUnit(
+            index,
+            c"tests/header.h".as_ptr(),
+            ptr::null_mut(),
+            0,
+            ptr::null_mut(),
+            0,
+            options,
+        );
+        assert!(!tu.is_null());
+
+        let reparse = clang_defaultReparseOptions(tu);
+        let error = clang_reparseTranslationUnit(tu, 0, ptr::null_mut(), reparse);
+        assert_eq!(error, CXError_Success);
+        assert_eq!(clang_defaultSaveOptions(tu), CXSaveTranslationUnit_None);
+
+        #[cfg(feature = "flags")]
+        {
+            use clang_sys::flags::*;
+
+            let flags = TranslationUnitFlags::default_editing();
+            assert_eq!(flags.bits(), options);
+            assert!(flags.contains(TranslationUnitFlags::PRECOMPILED_PREAMBLE));
+            assert_eq!(ReparseFlags::default_for(tu).bits(), reparse);
+            assert!(SaveTranslationUnitFlags::default_for(tu).is_empty());
+        }
+
+        clang_disposeTranslationUnit(tu);
+        clang_disposeIndex(index);
+    }
+}
+
+fn layout() {
+    unsafe {
+        let index = clang_createIndex(0, 0);
+
+        let contents = c"struct S { int a; int b : 3; };\nstruct T;\n";
+        let mut unsaved = CXUnsavedFile {
+            Filename: c"layout.c".as_ptr(),
+            Contents: contents.as_ptr(),
+            Length: contents.to_bytes().len() as _,
+        };
+        let tu = clang_parseTranslationUnit(
+            index,
+            c"layout.c".as_ptr(),
+            ptr::null_mut(),
+            0,
+            &mut unsaved,
+            1,
+            0,
+        );
+        assert!(!tu.is_null());
+
+        extern "C" fn visit(
+            cursor: CXCursor,
+            _: CXCursor,
+            data: CXClientData,
+        ) -> CXChildVisitResult {
+            unsafe {
+                let cursors = &mut *(data as *mut Vec<CXCursor>);
+                let kind = clang_getCursorKind(cursor);
+                if (kind == CXCursor_StructDecl || kind == CXCursor_FieldDecl)
+                    && clang_Location_isFromMainFile(clang_getCursorLocation(cursor)) != 0
+                {
+                    cursors.push(cursor);
+                }
+                CXChildVisit_Recurse
+            }
+        }
+
+        let mut cursors: Vec<CXCursor> = vec![];
+        let root = clang_getTranslationUnitCursor(tu);
+        clang_visitChildren(root, visit, &mut cursors as *mut _ as CXClientData);
+        let [s, a, b, t] = cursors[..] else {
+            panic!("unexpected cursors");
+        };
+
+        // Complete record types and their fields have layouts.
+        let type_ = clang_getCursorType(s);
+        assert_eq!(clang_Type_getSizeOf(type_), 8);
+        assert_eq!(clang_Type_getAlignOf(type_), 4);
+        assert_eq!(clang_Type_getOffsetOf(type_, c"b".as_ptr()), 32);
+        #[cfg(feature = "clang_3_7")]
+        assert_eq!(clang_Cursor_getOffsetOfField(b), 32);
+        assert_eq!(clang_Cursor_isBitField(a), 0);
+        assert_eq!(clang_getFieldDeclBitWidth(a), -1);
+        assert_ne!(clang_Cursor_isBitField(b), 0);
+        assert_eq!(clang_getFieldDeclBitWidth(b), 3);
+
+        // Layout errors are reported as negative values.
+        let invalid = clang_Type_getOffsetOf(type_, c"c".as_ptr());
+        assert_eq!(invalid, i64::from(CXTypeLayoutError_InvalidFieldName));
+        let incomplete = clang_Type_getSizeOf(clang_getCursorType(t));
+        assert_eq!(incomplete, i64::from(CXTypeLayoutError_Incomplete));
+        let result = support::type_layout(incomplete);
+        assert_eq!(result, Err(CXTypeLayoutError_Incomplete));
+        assert_eq!(support::type_layout(clang_Type_getSizeOf(type_)), Ok(8));
+
+        clang_disposeTranslationUnit(tu);
+        clang_disposeIndex(index);
+    }
+}
+
+fn linkage() {
+    unsafe {
+        let index = clang_createIndex(0, 0);
+
+        let contents = c"static _Thread_local int a;\n\
+            __attribute__((visibility(\"hidden\"))) int b;\n\
+            __attribute__((deprecated)) void c(void);\n";
+        let mut unsaved = CXUnsavedFile {
+            Filename: c"linkage.c".as_ptr(),
+            Contents: contents.as_ptr(),
+            Length: contents.to_bytes().len() as _,
+        };
+        let tu = clang_parseTranslationUnit(
+            index,
+            c"linkage.c".as_ptr(),
+            ptr::null_mut(),
+            0,
+            &mut unsaved,
+            1,
+            0,
+        );
+        assert!(!tu.is_null());
+
+        extern "C" fn visit(
+            cursor: CXCursor,
+            _: CXCursor,
+            data: CXClientData,
+        ) -> CXChildVisitResult {
+            unsafe {
+                let cursors = &mut *(data as *mut Vec<CXCursor>);
+                if clang_Location_isFromMainFile(clang_getCursorLocation(cursor)) != 0 {
+                    cursors.push(cursor);
+                }
+                CXChildVisit_Continue
+            }
+        }
+
+        let mut cursors: Vec<CXCursor> = vec![];
+        let root = clang_getTranslationUnitCursor(tu);
+        clang_visitChildren(root, visit, &mut cursors as *mut _ as CXClientData);
+        let [a, b, c] = cursors[..] else {
+            panic!("unexpected cursors");
+        };
+
+        assert_eq!(clang_getCursorLanguage(a), CXLanguage_C);
+        assert_eq!(clang_getCursorLinkage(a), CXLinkage_Internal);
+        assert_eq!(clang_getCursorLinkage(b), CXLinkage_External);
+        assert_eq!(clang_getCursorAvailability(a), CXAvailability_Available);
+        assert_eq!(clang_getCursorAvailability(c), CXAvailability_Deprecated);
+        #[cfg(feature = "clang_3_8")]
+        assert_eq!(clang_getCursorVisibility(b), CXVisibility_Hidden);
+        #[cfg(feature = "clang_6_0")]
+        {
+            assert_eq!(clang_getCursorTLSKind(a), CXTLS_Static);
+            assert_eq!(clang_getCursorTLSKind(b), CXTLS_None);
+        }
+
+        clang_disposeTranslationUnit(tu);
+        clang_disposeIndex(index);
+    }
+}
+
+fn in_memory() {
+    use std::collections::HashMap;
+
+    unsafe {
+        let index = clang_createIndex(0, 0);
+
+        let files = HashMap::from([
+            (
+                "main.c".into(),
+                b"#include \"header.h\"\nint x = Y;\n".to_vec(),
+            ),
+            ("header.h".into(), b"#define Y 42\n".to_vec()),
+        ]);
+        let mut unsaved = support::UnsavedFiles::new(files).unwrap();
+        assert_eq!(unsaved.len(), 2);
+
+        let arguments = support::Arguments::new(["-DZ=1", "-std=c11"]).unwrap();
+        assert_eq!(arguments.len(), 2);
+        assert!(support::Arguments::new(["-D\0"]).is_err());
+
+        let path = std::path::Path::new("main.c");
+        let tu = support::parse_translation_unit(index, path, &arguments, Some(&mut unsaved), 0)
+            .unwrap();
+        assert_eq!(clang_getNumDiagnostics(tu), 0);
+        clang_disposeTranslationUnit(tu);
+
+        let tu = clang_parseTranslationUnit(
+            index,
+            c"main.c".as_ptr(),
+            ptr::null_mut(),
+            0,
+            unsaved.as_mut_ptr(),
+            unsaved.len() as _,
+            0,
+        );
+        assert!(!tu.is_null());
+        assert_eq!(clang_getNumDiagnostics(tu), 0);
+
+        // The buffers must outlive the translation unit when reparsing.
+        let result = clang_reparseTranslationUnit(tu, unsaved.len() as _, unsaved.as_mut_ptr(), 0);
+        assert_eq!(result, 0);
+
+        clang_disposeTranslationUnit(tu);
+        clang_disposeIndex(index);
+
+        let root = std::env::current_dir().unwrap();
+        let mappings = HashMap::from([(root.join("virtual.h"), root.join("tests/header.h"))]);
+        let overlay = support::virtual_file_overlay(&mappings, Some(true)).unwrap();
+        let overlay = String::from_utf8(overlay).unwrap();
+        assert!(overlay.contains("'case-sensitive': 'true'"), "{}", overlay);
+        assert!(overlay.contains("virtual.h"), "{}", overlay);
+
+        let mappings = HashMap::from([("virtual.h".into(), "tests/header.h".into())]);
+        let result = support::virtual_file_overlay(&mappings, None);
+        assert_eq!(result, Err(CXError_InvalidArguments));
+    }
+}
+
+fn threading() {
+    use std::sync::Arc;
+
+    use clang_sys::threading::*;
+
+    #[cfg(feature = "runtime")]
+    use clang_sys::threading::spawn;
+    #[cfg(not(feature = "runtime"))]
+    use std::thread::spawn;
+
+    unsafe {
+        let index = SharedIndex::new(clang_createIndex(0, 0));
+        serialize(|| clang_toggleCrashRecovery(1));
+
+        // Translation units may be parsed by different threads at the same time.
+        let threads = (0..2)
+            .map(|_| {
+                spawn(move || {
+                    let tu = clang_parseTranslationUnit(
+                        index.get(),
+                        c"tests/header.h".as_ptr(),
+                        ptr::null_mut(),
+                        0,
+                        ptr::null_mut(),
+                        0,
+                        0,
+                    );
+                    assert!(!tu.is_null());
+                    SendTranslationUnit::new(tu)
+                })
+            })
+            .collect::<Vec<_>>();
+        let tus = threads
+            .into_iter()
+            .map(|t| Arc::new(Serialized::new(t.join().unwrap())))
+            .collect::<Vec<_>>();
+
+        // A translation unit may be shared between threads if access to it is
+        // serialized.
+        let tu = tus[0].clone();
+        let cursors = spawn(move || {
+            tu.with(|tu| {
+                let cursor = clang_getTranslationUnitCursor(tu.get());
+                clang_getCursorKind(cursor)
+            })
+        });
+        assert_eq!(cursors.join().unwrap(), CXCursor_TranslationUnit);
+
+        for tu in tus {
+            let tu = Arc::into_inner(tu).unwrap().into_inner();
+            clang_disposeTranslationUnit(tu.into_inner());
+        }
+        clang_disposeIndex(index.get());
+    }
+}
+
 #[cfg(feature = "runtime")]
 #[test]
 fn test() {
     load().unwrap();
     let library = get_library().unwrap();
     println!("{:?} ({:?})", library.version(), library.path());
+    println!(
+        "{:?} ({:?})",
+        library.version_full(),
+        library.clang_version()
+    );
+    println!("{:?} ({:?})", library.vendor(), library.default_target());
+    println!("Assertions: {}", library.has_assertions());
     parse();
     unload().unwrap();
 }
@@ -36,6 +435,13 @@ fn test() {
     parse();
 }
 
+#[cfg(feature = "hybrid")]
+#[test]
+fn test_hybrid() {
+    assert!(clang_createIndex::is_loaded());
+    parse();
+}
+
 #[test]
 fn test_support() {
     let clang = support::Clang::find(None, &[]).unwrap();
@@ -49,6 +455,489 @@ fn test_support_target() {
     println!("{:?}", clang);
 }
 
+#[cfg(feature = "runtime")]
+#[test]
+fn test_functions() {
+    load().unwrap();
+    let functions = get_library().unwrap().functions;
+    assert!(functions.clang_createIndex.is_some());
+    unsafe {
+        let index = functions.clang_createIndex.unwrap()(0, 0);
+        assert!(!index.is_null());
+        functions.clang_disposeIndex.unwrap()(index);
+    }
+    unload().unwrap();
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_load_from_path() {
+    let path = load_manually().unwrap().path().to_path_buf();
+    load_from_path(&path).unwrap();
+    assert_eq!(get_library().unwrap().path(), path);
+    parse();
+    unload().unwrap();
+
+    let result = load_from_path("missing/libclang.so");
+    assert!(matches!(result, Err(LoadError::OpenFailed { .. })));
+}
+
+#[cfg(all(feature = "runtime", target_os = "linux"))]
+#[test]
+fn test_load_wrong_architecture() {
+    // An ELF header for the other ELF class.
+    let class = if cfg!(target_pointer_width = "64") {
+        1
+    } else {
+        2
+    };
+    let mut contents = vec![0; 64];
+    contents[..7].copy_from_slice(&[127, 69, 76, 70, class, 1, 1]);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("libclang.so");
+    std::fs::write(&path, contents).unwrap();
+    let result = SharedLibrary::open(&path);
+    assert!(matches!(result, Err(LoadError::WrongArchitecture { .. })));
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_load_with_version() {
+    load_with_version(Version::V3_5).unwrap();
+    let library = get_library().unwrap();
+    let version = library.version().unwrap();
+    let (major, _, _) = library.version_full().unwrap();
+    assert!(
+        library
+            .clang_version()
+            .unwrap()
+            .contains(&major.to_string())
+    );
+    if let Some(vendor) = library.vendor() {
+        assert!(library.clang_version().unwrap().starts_with(&vendor));
+    }
+    if version >= Version::V5_0 {
+        assert!(library.default_target().unwrap().contains('-'));
+    }
+    drop(library);
+    unload().unwrap();
+
+    if version < Version::V23_0 {
+        // Every `libclang` shared library found is tried before giving up.
+        match load_with_version(Version::V23_0) {
+            Err(LoadError::VersionTooOld { .. }) => {}
+            Err(LoadError::AllFailed { failures }) => assert!(
+                failures
+                    .iter()
+                    .any(|f| matches!(f, LoadError::VersionTooOld { .. }))
+            ),
+            result => panic!("expected an error, received: {:?}", result),
+        }
+        assert!(get_library().is_none());
+    }
+}
+
+#[cfg(feature = "usage-audit")]
+#[test]
+fn test_usage_audit() {
+    load().unwrap();
+    parse();
+    unload().unwrap();
+
+    let used = used_functions();
+    assert!(used.contains(&"clang_createIndex"));
+    assert!(used.contains(&"clang_parseTranslationUnit"));
+
+    let groups = used_functions_by_cfg();
+    let ungated = groups.iter().find(|(c, _)| c.is_none()).unwrap();
+    assert!(ungated.1.contains(&"clang_createIndex"));
+    assert!(required_feature().is_none_or(|f| f.starts_with("clang_")));
+}
+
+#[cfg(all(feature = "runtime", target_os = "linux", target_env = "gnu"))]
+#[test]
+fn test_open_isolated() {
+    let path = load_manually().unwrap().path().to_path_buf();
+    let library = SharedLibrary::open_isolated(&path).unwrap();
+    assert_eq!(library.path(), path);
+    unsafe {
+        let index = library.call().clang_createIndex(0, 0);
+        assert!(!index.is_null());
+        library.call().clang_disposeIndex(index);
+    }
+
+    let result = SharedLibrary::open_isolated("missing/libclang.so");
+    assert!(matches!(result, Err(LoadError::OpenFailed { .. })));
+}
+
+#[cfg(all(feature = "runtime", windows))]
+#[test]
+fn test_dll_search_flags() {
+    assert!(add_dll_directory("relative").is_err());
+
+    let path = load_manually().unwrap().path().to_path_buf();
+    add_dll_directory(path.parent().unwrap()).unwrap();
+    load_from_path(&path).unwrap();
+    parse();
+    unload().unwrap();
+
+    let flags = set_dll_search_flags(None);
+    assert_eq!(flags, Some(0x100 | 0x1000));
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_verify() {
+    load_manually().unwrap().verify().unwrap();
+
+    let library = LoadOptions::new().verify(true).load_manually().unwrap();
+    assert!(library.functions.clang_createIndex.is_some());
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_load_options() {
+    let path = load_manually().unwrap().path().to_path_buf();
+    let directory = path.parent().unwrap();
+    let filename = path.file_name().unwrap().to_str().unwrap();
+
+    LoadOptions::new()
+        .search_dir(directory)
+        .filename_pattern(filename)
+        .min_version(Version::V3_5)
+        .load()
+        .unwrap();
+    assert_eq!(get_library().unwrap().path(), path);
+    parse();
+    unload().unwrap();
+
+    let result = LoadOptions::new().search_dir("missing").load();
+    assert!(matches!(result, Err(LoadError::NotFound { .. })));
+
+    let result = LoadOptions::new()
+        .filename_pattern("libclang-missing*")
+        .load();
+    assert!(matches!(result, Err(LoadError::NotFound { .. })));
+    assert!(get_library().is_none());
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_enumerate_libraries() {
+    let path = load_manually().unwrap().path().to_path_buf();
+    let libraries = enumerate_libraries().unwrap();
+    assert_eq!(libraries[0].path, path);
+
+    let filters = Candidates::for_current_process();
+    let loadable = filters.enumerate().unwrap();
+    assert!(loadable.iter().all(|l| filters.matches(l)));
+    assert!(loadable.iter().all(|l| libraries.contains(l)));
+    for library in &loadable {
+        let architectures = &library.architectures;
+        assert!(
+            architectures.is_empty() || architectures.iter().any(|a| a == std::env::consts::ARCH)
+        );
+    }
+
+    let foreign = Candidates::new().architecture("foreign").os_version([0]);
+    assert!(
+        foreign
+            .enumerate()
+            .unwrap()
+            .iter()
+            .all(|l| l.architectures.is_empty())
+    );
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_discover() {
+    let candidates = discover();
+    let libraries = enumerate_libraries().unwrap();
+    assert_eq!(candidates.len(), libraries.len());
+    for (candidate, library) in candidates.iter().zip(&libraries) {
+        assert_eq!(candidate.path(), library.path);
+        assert_eq!(candidate.source, library.source);
+    }
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_preload() {
+    // Loading `libclang` itself before loading `libclang` is harmless.
+    let path = load_manually().unwrap().path().to_path_buf();
+    let library = LoadOptions::new()
+        .search_dir(&path)
+        .env_override("LIBCLANG_PRELOAD", path.to_str().unwrap())
+        .load_manually();
+    assert!(library.unwrap().functions.clang_createIndex.is_some());
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_call() {
+    let library = load_manually().unwrap();
+    assert!(get_library().is_none());
+
+    unsafe {
+        let index = library.call().clang_createIndex(0, 0);
+        assert!(!index.is_null());
+        library.call().clang_disposeIndex(index);
+    }
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_call_or_error() {
+    load().unwrap();
+
+    unsafe {
+        let index = clang_createIndex::call(0, 0).unwrap();
+        assert!(!index.is_null());
+        clang_disposeIndex::call(index).unwrap();
+    }
+
+    // Functions which are not supported by the loaded library return an error.
+    let library = get_library().unwrap();
+    if let Some(name) = library.missing_functions().first() {
+        let error = MissingFunction::NotSupported {
+            name,
+            version: library.version(),
+        };
+        assert!(error.to_string().contains("is not supported by the loaded"));
+    }
+
+    unload().unwrap();
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_get_function() {
+    let library = load_manually().unwrap();
+
+    unsafe {
+        type F = unsafe extern "C" fn(std::ffi::c_int, std::ffi::c_int) -> CXIndex;
+        let create = library.get_function::<F>("clang_createIndex").unwrap();
+        let index = create(0, 0);
+        assert!(!index.is_null());
+        library.call().clang_disposeIndex(index);
+
+        assert!(library.get_function::<F>("clang_doesNotExist").is_none());
+        assert!(library.get_function::<u8>("clang_createIndex").is_none());
+    }
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_with_library_instance() {
+    let first = std::sync::Arc::new(load_manually().unwrap());
+    let second = std::sync::Arc::new(load_manually().unwrap());
+
+    set_library(Some(first.clone()));
+    with_library_instance(second.clone(), || {
+        assert!(std::sync::Arc::ptr_eq(&get_library().unwrap(), &second));
+        parse();
+    });
+    assert!(std::sync::Arc::ptr_eq(&get_library().unwrap(), &first));
+    set_library(None);
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_loaded_functions() {
+    let library = load_manually().unwrap();
+    let all = Functions::all();
+    assert!(all.contains(&"clang_createIndex"));
+
+    let loaded = library.functions.loaded().collect::<Vec<_>>();
+    assert!(loaded.contains(&"clang_createIndex"));
+    assert!(loaded.iter().all(|f| all.contains(f)));
+    assert_eq!(all.len() - loaded.len(), library.missing_functions().len());
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_function_versions() {
+    let version = |name| {
+        FUNCTION_VERSIONS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| *v)
+    };
+    assert_eq!(version("clang_createIndex"), Some(Version::V3_5));
+    assert_eq!(version("clang_Cursor_getStorageClass"), Some(Version::V3_6));
+    assert_eq!(
+        version("clang_CXXMethod_isCopyAssignmentOperator"),
+        Some(Version::V16_0)
+    );
+    assert_eq!(version("clang_getCursorUSR2"), None);
+    assert!(Functions::all().iter().all(|f| version(f).is_some()));
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_rediscover() {
+    let library = std::sync::Arc::new(load_manually().unwrap());
+    set_library(Some(library.clone()));
+    assert_eq!(rediscover().unwrap(), None);
+    set_library(None);
+
+    // Other tests may search concurrently, so the environment may not have
+    // changed since the last search.
+    let path = rediscover().unwrap();
+    assert!(path.is_none_or(|p| p == library.path()));
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_supports() {
+    let library = load_manually().unwrap();
+    assert!(library.supports(Capability::Pch));
+    for name in Capability::Pch.functions() {
+        assert!(!library.missing_functions().contains(name));
+    }
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_load_guarded() {
+    let guard = load_guarded().unwrap();
+    parse();
+    drop(guard);
+    assert!(get_library().is_none());
+
+    // The shared library is unloaded even if the thread panics.
+    let result = std::panic::catch_unwind(|| {
+        let _guard = load_guarded().unwrap();
+        panic!("unwinding");
+    });
+    assert!(result.is_err());
+    assert!(get_library().is_none());
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_load_nested() {
+    load().unwrap();
+    let library = get_library().unwrap();
+
+    // Nested calls use the library which is already in use.
+    load().unwrap();
+    assert!(std::sync::Arc::ptr_eq(&get_library().unwrap(), &library));
+    unload().unwrap();
+    assert!(std::sync::Arc::ptr_eq(&get_library().unwrap(), &library));
+    parse();
+
+    // Nested calls which load the same library instance are counted like `load`.
+    load_from_path(library.path()).unwrap();
+    assert!(std::sync::Arc::ptr_eq(&get_library().unwrap(), &library));
+    unload().unwrap();
+    assert!(std::sync::Arc::ptr_eq(&get_library().unwrap(), &library));
+
+    // Nested calls which load another library instance replace it until they are unloaded.
+    load_lazy().unwrap();
+    assert!(!std::sync::Arc::ptr_eq(&get_library().unwrap(), &library));
+    unload().unwrap();
+    assert!(std::sync::Arc::ptr_eq(&get_library().unwrap(), &library));
+    parse();
+
+    unload().unwrap();
+    assert!(get_library().is_none());
+    assert!(unload().is_err());
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_load_lazy() {
+    load_lazy().unwrap();
+    let library = get_library().unwrap();
+    assert!(library.is_lazy());
+    assert!(library.functions.clang_createIndex.is_none());
+
+    parse();
+    assert!(clang_createIndex::is_loaded());
+    assert!(
+        library
+            .missing_functions()
+            .iter()
+            .all(|f| *f != "clang_createIndex")
+    );
+    unload().unwrap();
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_missing_functions() {
+    let library = load_manually().unwrap();
+    let missing = library.missing_functions();
+    let groups = library.missing_functions_by_cfg();
+
+    // Functions available in every supported version of `libclang` are never missing.
+    assert!(!missing.contains(&"clang_createIndex"));
+    assert!(groups.iter().all(|(cfg, _)| cfg.is_some()));
+    assert_eq!(
+        groups.into_iter().flat_map(|(_, f)| f).collect::<Vec<_>>(),
+        missing
+    );
+
+    for name in missing {
+        assert!(!name.is_empty());
+    }
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_load_global() {
+    let _lock = lock_global_library();
+    load_global().unwrap();
+    assert!(get_global_library().is_some());
+
+    // Threads without a library instance in TLS use the global library instance.
+    std::thread::spawn(|| {
+        assert!(is_loaded());
+        parse();
+    })
+    .join()
+    .unwrap();
+
+    assert!(set_global_library(None).is_some());
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_load_shared() {
+    use std::sync::Arc;
+
+    load().unwrap();
+    let library = get_library().unwrap();
+
+    // Threads loading the same shared library use the same library instance.
+    let other = std::thread::spawn(|| {
+        load().unwrap();
+        let library = get_library().unwrap();
+        unload().unwrap();
+        library
+    })
+    .join()
+    .unwrap();
+    assert!(Arc::ptr_eq(&library, &other));
+
+    let path = library.path().to_path_buf();
+    let other = std::thread::spawn(move || {
+        load_from_path(path).unwrap();
+        let library = get_library().unwrap();
+        unload().unwrap();
+        library
+    })
+    .join()
+    .unwrap();
+    assert!(Arc::ptr_eq(&library, &other));
+
+    drop((library, other));
+    unload().unwrap();
+}
+
 #[cfg(feature = "runtime")]
 #[test]
 fn test_support_runtime() {
@@ -59,3 +948,415 @@ fn test_support_runtime() {
     println!("Clang path:   {}", clang.path.display());
     unload().unwrap();
 }
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_diagnostics() {
+    load().unwrap();
+    diagnostics();
+    unload().unwrap();
+}
+
+#[cfg(not(feature = "runtime"))]
+#[test]
+fn test_diagnostics() {
+    diagnostics();
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_usrs() {
+    load().unwrap();
+    usrs();
+    unload().unwrap();
+}
+
+#[cfg(not(feature = "runtime"))]
+#[test]
+fn test_usrs() {
+    usrs();
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_support_tokens() {
+    load().unwrap();
+    tokenize();
+    unload().unwrap();
+}
+
+#[cfg(not(feature = "runtime"))]
+#[test]
+fn test_support_tokens() {
+    tokenize();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_support_gcc_fallback() {
+    let gcc = support::GccFallback::find().unwrap();
+    println!("{:?}", gcc);
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_default_options() {
+    load().unwrap();
+    default_options();
+    unload().unwrap();
+}
+
+#[cfg(not(feature = "runtime"))]
+#[test]
+fn test_default_options() {
+    default_options();
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_layout() {
+    load().unwrap();
+    layout();
+    unload().unwrap();
+}
+
+#[cfg(not(feature = "runtime"))]
+#[test]
+fn test_layout() {
+    layout();
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_linkage() {
+    load().unwrap();
+    assert!(clang_getCursorLanguage::is_loaded());
+    assert!(clang_getCursorLinkage::is_loaded());
+    assert!(clang_getCursorAvailability::is_loaded());
+    #[cfg(feature = "clang_3_8")]
+    assert!(clang_getCursorVisibility::is_loaded());
+    #[cfg(feature = "clang_6_0")]
+    assert!(clang_getCursorTLSKind::is_loaded());
+    linkage();
+    unload().unwrap();
+}
+
+#[cfg(not(feature = "runtime"))]
+#[test]
+fn test_linkage() {
+    linkage();
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_threading() {
+    load().unwrap();
+    threading();
+    unload().unwrap();
+}
+
+#[cfg(not(feature = "runtime"))]
+#[test]
+fn test_threading() {
+    threading();
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_in_memory() {
+    load().unwrap();
+    in_memory();
+    unload().unwrap();
+}
+
+#[cfg(not(feature = "runtime"))]
+#[test]
+fn test_in_memory() {
+    in_memory();
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_build_info() {
+    assert_eq!(build_info::LIBDIR, None);
+    assert_eq!(build_info::LIBRARY, None);
+    assert_eq!(build_info::VERSION, None);
+    assert_eq!(build_info::ENV_FILE, None);
+}
+
+#[cfg(not(feature = "runtime"))]
+#[test]
+fn test_build_info() {
+    let libdir = build_info::LIBDIR.unwrap();
+    assert!(std::path::Path::new(libdir).is_dir());
+    let env = std::fs::read_to_string(build_info::ENV_FILE.unwrap()).unwrap();
+    assert!(env.contains(&format!("CLANG_SYS_LIBDIR={}\n", libdir)));
+    if let Some(library) = build_info::LIBRARY {
+        assert!(std::path::Path::new(library).starts_with(libdir));
+        assert!(env.contains(&format!("CLANG_SYS_LIBRARY={}\n", library)));
+    }
+    if let Some(version) = build_info::VERSION {
+        assert!(version.split('.').all(|c| c.parse::<u32>().is_ok()));
+        assert!(env.contains(&format!("CLANG_SYS_VERSION={}\n", version)));
+    }
+}
+
+#[cfg(feature = "c-abi")]
+#[test]
+fn test_c_abi() {
+    use std::ffi::c_int;
+
+    unsafe extern "C" {
+        fn clang_sys_clang_createIndex(excluded: c_int, diagnostics: c_int) -> CXIndex;
+        fn clang_sys_clang_disposeIndex(index: CXIndex);
+    }
+
+    #[cfg(feature = "runtime")]
+    let _lock = lock_global_library();
+
+    assert_eq!(c_abi::clang_sys_load(), 0);
+    assert_eq!(c_abi::clang_sys_is_loaded(), 1);
+
+    unsafe {
+        let index = clang_sys_clang_createIndex(0, 0);
+        assert!(!index.is_null());
+        clang_sys_clang_disposeIndex(index);
+    }
+
+    #[cfg(feature = "runtime")]
+    set_global_library(None);
+}
+
+#[cfg(all(feature = "runtime", unix))]
+#[test]
+fn test_raw_handle() {
+    use std::ffi::{c_char, c_void};
+
+    unsafe extern "C" {
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    }
+
+    let library = load_manually().unwrap();
+    let raw = library.as_raw();
+    assert!(!raw.is_null());
+
+    let symbol = unsafe { dlsym(raw, c"clang_createIndex".as_ptr()) };
+    assert!(!symbol.is_null());
+
+    let library = library.into_library();
+    let library = libloading::os::unix::Library::from(library);
+    assert_eq!(library.into_raw(), raw);
+}
+
+#[cfg(feature = "in-memory")]
+#[test]
+fn test_from_bytes() {
+    let path = load_manually().unwrap().path().to_path_buf();
+    let bytes = std::fs::read(&path).unwrap();
+    let library = SharedLibrary::from_bytes(&bytes).unwrap();
+    let version = SharedLibrary::open(&path).unwrap().version();
+    assert_eq!(library.version(), version);
+
+    assert!(SharedLibrary::from_bytes(b"not a shared library").is_err());
+}
+
+#[cfg(feature = "runtime")]
+#[test]
+fn test_env_overrides() {
+    let options = LoadOptions::new().env_override("LIBCLANG_PATH", "tests/missing");
+    assert!(options.discover().is_empty());
+    assert!(matches!(
+        options.load_manually(),
+        Err(LoadError::NotFound { .. })
+    ));
+
+    let mut options = DiscoveryOptions::default();
+    options
+        .env_overrides
+        .insert("LIBCLANG_PATH".into(), "tests/missing".into());
+    assert!(options.discover().is_empty());
+    assert!(matches!(
+        LoadOptions::from(options).load_manually(),
+        Err(LoadError::NotFound { .. })
+    ));
+}
+
+#[cfg(all(feature = "runtime", target_os = "linux"))]
+#[test]
+fn test_load_fallback() {
+    let path = load_manually().unwrap().path().to_path_buf();
+    let filename = path.file_name().unwrap().to_str().unwrap();
+
+    // A file with a 64-bit ELF header is found but can't be opened.
+    let directory = tempfile::tempdir().unwrap();
+    let mut bytes = b"\x7fELF\x02".to_vec();
+    bytes.resize(64, 0);
+    std::fs::write(directory.path().join(filename), bytes).unwrap();
+
+    let options = LoadOptions::new()
+        .search_dir(directory.path())
+        .filename_pattern(filename)
+        .selection_policy(SelectionPolicy::PreferFirstMatch);
+    let error = options.load_manually().unwrap_err();
+    assert!(matches!(error, LoadError::OpenFailed { .. }));
+
+    let library = options
+        .search_dir(path.parent().unwrap())
+        .load_manually()
+        .unwrap();
+    assert_eq!(library.path(), path);
+}
+
+#[cfg(all(feature = "runtime", target_os = "linux"))]
+#[test]
+fn test_integrity() {
+    const DIGEST: &str = "0e7fe76cef2ede8fddd6235cf97a887acc8a301392e38752dbb9ee0e810c6864";
+
+    // A file with a 64-bit ELF header is found but can't be opened.
+    let directory = tempfile::tempdir().unwrap();
+    let mut bytes = b"\x7fELF\x02".to_vec();
+    bytes.resize(64, 0);
+    std::fs::write(directory.path().join("libclang.so"), bytes).unwrap();
+
+    let options = LoadOptions::new()
+        .search_dir(directory.path())
+        .filename_pattern("libclang.so");
+
+    let error = options
+        .clone()
+        .sha256("0".repeat(64))
+        .load_manually()
+        .unwrap_err();
+    match error {
+        LoadError::IntegrityMismatch { digest, .. } => assert_eq!(digest.as_deref(), Some(DIGEST)),
+        error => panic!("unexpected error: {}", error),
+    }
+
+    let error = options
+        .clone()
+        .env_override("CLANG_SYS_LIBCLANG_SHA256", "0".repeat(64))
+        .load_manually()
+        .unwrap_err();
+    assert!(matches!(error, LoadError::IntegrityMismatch { .. }));
+
+    let error = options
+        .env_override(
+            "CLANG_SYS_LIBCLANG_SHA256",
+            format!("{}, {}", "0".repeat(64), DIGEST.to_uppercase()),
+        )
+        .sha256(DIGEST)
+        .load_manually()
+        .unwrap_err();
+    assert!(matches!(error, LoadError::OpenFailed { .. }));
+}
+
+#[cfg(feature = "flags")]
+#[test]
+fn test_flags() {
+    use clang_sys::flags::*;
+
+    let all = NameRefFlags::all();
+    assert_eq!(all.bits(), 7);
+    assert_eq!(NameRefFlags::from_bits(9), None);
+    assert_eq!(
+        NameRefFlags::from_bits_truncate(9),
+        NameRefFlags::WANT_QUALIFIER
+    );
+    assert_eq!(NameRefFlags::from_bits_retain(9).bits(), 9);
+
+    let mut flags = NameRefFlags::WANT_QUALIFIER | NameRefFlags::WANT_SINGLE_PIECE;
+    assert!(flags.intersects(NameRefFlags::WANT_SINGLE_PIECE | NameRefFlags::WANT_TEMPLATE_ARGS));
+    assert_eq!(!flags, NameRefFlags::WANT_TEMPLATE_ARGS);
+    flags -= NameRefFlags::WANT_QUALIFIER;
+    assert_eq!(
+        flags ^ all,
+        NameRefFlags::WANT_QUALIFIER | NameRefFlags::WANT_TEMPLATE_ARGS
+    );
+
+    let options = DiagnosticDisplayOptions::SOURCE_LOCATION | DiagnosticDisplayOptions::COLUMN;
+    assert_eq!(CXDiagnosticDisplayOptions::from(options), 3);
+    assert_eq!(
+        GlobalOptFlags::all(),
+        GlobalOptFlags::THREAD_BACKGROUND_PRIORITY_FOR_ALL
+    );
+}
+
+#[cfg(all(feature = "runtime", target_os = "linux"))]
+#[test]
+fn test_probe_timeout() {
+    use std::time::Duration;
+
+    let path = discover().into_iter().next().unwrap().path();
+
+    // A shared library with a constructor which aborts crashes the process which opens it.
+    let directory = tempfile::tempdir().unwrap();
+    let source = directory.path().join("crash.c");
+    std::fs::write(
+        &source,
+        "#include <stdlib.h>\n__attribute__((constructor)) static void crash(void) { abort(); }\n",
+    )
+    .unwrap();
+    let status = std::process::Command::new("cc")
+        .args(["-shared", "-fPIC", "-o"])
+        .arg(directory.path().join("libclang.so"))
+        .arg(&source)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let error = LoadOptions::new()
+        .search_dir(directory.path())
+        .filename_pattern("libclang.so")
+        .probe_timeout(Duration::from_secs(30))
+        .load_manually()
+        .unwrap_err();
+    match error {
+        LoadError::VerificationFailed { message, .. } => assert!(message.contains("crashed")),
+        error => panic!("unexpected error: {}", error),
+    }
+
+    // Opening a shared library takes more than a nanosecond.
+    let error = LoadOptions::new()
+        .search_dir(&path)
+        .probe_timeout(Duration::from_nanos(1))
+        .load_manually()
+        .unwrap_err();
+    match error {
+        LoadError::VerificationFailed { message, .. } => assert!(message.contains("finish")),
+        error => panic!("unexpected error: {}", error),
+    }
+}
+
+#[cfg(all(feature = "runtime", target_os = "linux"))]
+#[test]
+fn test_relaxed_binding() {
+    let path = load_manually().unwrap().path().to_path_buf();
+
+    // A shared library which references a symbol no shared library defines.
+    let directory = tempfile::tempdir().unwrap();
+    let source = directory.path().join("undefined.c");
+    std::fs::write(
+        &source,
+        "extern void undefined(void);\nvoid call(void) { undefined(); }\n",
+    )
+    .unwrap();
+    let preload = directory.path().join("libundefined.so");
+    let status = std::process::Command::new("cc")
+        .args(["-shared", "-fPIC", "-o"])
+        .arg(&preload)
+        .arg(&source)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let options = LoadOptions::new()
+        .search_dir(&path)
+        .env_override("LIBCLANG_PRELOAD", preload.to_str().unwrap());
+    match options.clone().load_manually().unwrap_err() {
+        LoadError::UndefinedSymbol { symbol, .. } => assert_eq!(symbol, "undefined"),
+        error => panic!("unexpected error: {}", error),
+    }
+
+    let library = options.relaxed(true).load_manually();
+    assert!(library.unwrap().functions.clang_createIndex.is_some());
+}
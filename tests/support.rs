@@ -0,0 +1,281 @@
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use clang_sys::support::{Clang, IncludeDirectory, IncludeKind, set_process_runner};
+
+/// A fake target `clang` executable which lists `<sysroot>/usr/include` as its
+/// only search path.
+const CLANG: &str = r##"#!/bin/sh
+case "$*" in
+*--version*)
+    echo "clang version 14.0.6"
+    ;;
+*)
+    echo "#include <...> search starts here:" >&2
+    for argument in "$@"; do
+        case "$argument" in
+        --sysroot=*) echo " ${argument#--sysroot=}/usr/include" >&2 ;;
+        esac
+    done
+    echo "End of search list." >&2
+    ;;
+esac
+"##;
+
+#[test]
+fn test_all() {
+    // Run tests serially since they alter the environment.
+
+    test_clang_yocto();
+    test_clang_bindgen();
+    test_clang_include_search();
+    test_clang_wrapper();
+    test_clang_process_runner();
+}
+
+fn test_clang_yocto() {
+    let directory = tempfile::tempdir().unwrap();
+    let clang = directory.path().join("x86_64-poky-linux-clang");
+    fs::write(&clang, CLANG).unwrap();
+    fs::set_permissions(&clang, fs::Permissions::from_mode(0o755)).unwrap();
+    let sysroot = directory.path().join("sysroots/core2-64-poky-linux");
+
+    // SAFETY: The tests in this binary are run serially.
+    unsafe {
+        env::remove_var("CLANG_PATH");
+        env::remove_var("CC");
+        env::set_var("CLANGCC", format!("{} -m64", clang.display()));
+        env::set_var("OECORE_TARGET_SYSROOT", &sysroot);
+    }
+
+    let found = Clang::find(None, &["-DFOO".into()]).unwrap();
+    assert_eq!(found.path, clang);
+    assert_eq!(found.version.map(|v| (v.Major, v.Minor)), Some((14, 0)));
+    assert_eq!(
+        Clang::args(&["-DFOO".into()]),
+        &[
+            "-DFOO".into(),
+            "-m64".into(),
+            format!("--sysroot={}", sysroot.display()),
+        ],
+    );
+    let include = sysroot.join("usr/include");
+    assert_eq!(found.c_search_paths, Some(vec![include.clone()]));
+    assert_eq!(found.cpp_search_paths, Some(vec![include]));
+
+    // Without a `clang` executable, the sysroot is still used.
+    let other = directory.path().join("clang");
+    fs::write(&other, CLANG).unwrap();
+    fs::set_permissions(&other, fs::Permissions::from_mode(0o755)).unwrap();
+    unsafe { env::remove_var("CLANGCC") };
+    let found = Clang::find(Some(directory.path()), &[]).unwrap();
+    assert_eq!(found.path, other);
+    assert_eq!(
+        Clang::args(&[]),
+        &[format!("--sysroot={}", sysroot.display())]
+    );
+
+    unsafe { env::remove_var("OECORE_TARGET_SYSROOT") };
+}
+
+fn test_clang_bindgen() {
+    let directory = tempfile::tempdir().unwrap();
+    let clang = directory.path().join("aarch64-linux-gnu-clang");
+    fs::write(&clang, CLANG).unwrap();
+    fs::set_permissions(&clang, fs::Permissions::from_mode(0o755)).unwrap();
+    let sysroot = directory.path().join("aarch64 sysroot");
+
+    let path = env::var_os("PATH");
+
+    // SAFETY: The tests in this binary are run serially.
+    unsafe {
+        env::remove_var("CLANG_PATH");
+        env::remove_var("BINDGEN_EXTRA_CLANG_ARGS");
+        env::remove_var("LLVM_CONFIG_PATH");
+        env::set_var("PATH", directory.path());
+        env::set_var("TARGET", "aarch64-unknown-linux-gnu");
+        env::set_var(
+            "BINDGEN_EXTRA_CLANG_ARGS_aarch64_unknown_linux_gnu",
+            format!(
+                "-DFOO --target=aarch64-linux-gnu '--sysroot={}' -I/usr/include",
+                sysroot.display(),
+            ),
+        );
+    }
+
+    // The extra arguments are only used if explicitly requested.
+    assert!(Clang::find(None, &[]).is_none());
+
+    let found = Clang::find_for_bindgen(None, &["-DBAR".into()]).unwrap();
+    assert_eq!(found.path, clang);
+    assert_eq!(
+        Clang::args_for_bindgen(&["-DBAR".into()]),
+        &[
+            "-DBAR".into(),
+            "--target=aarch64-linux-gnu".into(),
+            format!("--sysroot={}", sysroot.display()),
+        ],
+    );
+    let include = sysroot.join("usr/include");
+    assert_eq!(found.c_search_paths, Some(vec![include]));
+
+    unsafe {
+        env::remove_var("BINDGEN_EXTRA_CLANG_ARGS_aarch64_unknown_linux_gnu");
+        env::remove_var("TARGET");
+        match path {
+            Some(path) => env::set_var("PATH", path),
+            None => env::remove_var("PATH"),
+        }
+    }
+}
+
+/// A fake `clang` executable which lists a quote search path, an angle search
+/// path, and a framework search path.
+const CLANG_DARWIN: &str = r##"#!/bin/sh
+case "$*" in
+*--version*)
+    echo "Apple clang version 15.0.0 (clang-1500.3.9.4)"
+    ;;
+*)
+    echo "ignoring nonexistent directory \"/missing\"" >&2
+    echo "#include \"...\" search starts here:" >&2
+    echo " /project/quote" >&2
+    echo "#include <...> search starts here:" >&2
+    echo " /usr/local/include" >&2
+    echo " /Library/Frameworks (framework directory)" >&2
+    echo "End of search list." >&2
+    ;;
+esac
+"##;
+
+fn test_clang_include_search() {
+    let directory = tempfile::tempdir().unwrap();
+    let clang = directory.path().join("clang");
+    fs::write(&clang, CLANG_DARWIN).unwrap();
+    fs::set_permissions(&clang, fs::Permissions::from_mode(0o755)).unwrap();
+
+    // SAFETY: The tests in this binary are run serially.
+    unsafe { env::set_var("CLANG_PATH", &clang) };
+
+    let args = Clang::args(&["-iquote/project/quote".into()]);
+    let found = Clang::find(None, &args).unwrap();
+    let search = found.c_include_search(&args).unwrap();
+    assert_eq!(
+        search.directories,
+        &[
+            IncludeDirectory {
+                path: "/project/quote".into(),
+                kind: IncludeKind::Quote,
+                framework: false,
+            },
+            IncludeDirectory {
+                path: "/usr/local/include".into(),
+                kind: IncludeKind::Angle,
+                framework: false,
+            },
+            IncludeDirectory {
+                path: "/Library/Frameworks".into(),
+                kind: IncludeKind::Angle,
+                framework: true,
+            },
+        ],
+    );
+    assert!(!search.nostdinc && !search.nostdlibinc && !search.nostdincxx);
+    assert_eq!(
+        found.c_search_paths,
+        Some(vec![
+            "/usr/local/include".into(),
+            "/Library/Frameworks".into()
+        ]),
+    );
+
+    let found = Clang::find(None, &[]).unwrap();
+    let search = found.cpp_include_search(&["-nostdinc++".into()]).unwrap();
+    assert!(!search.nostdinc && search.nostdincxx);
+
+    let search = found.cpp_include_search(&["-nostdinc".into()]).unwrap();
+    assert!(search.nostdinc && search.nostdincxx);
+
+    unsafe { env::remove_var("CLANG_PATH") };
+}
+
+/// A fake `clang` wrapper script which runs `/opt/llvm/bin/clang`.
+const CLANG_WRAPPER: &str = r##"#!/bin/sh
+case "$*" in
+*-###*)
+    echo "clang version 18.1.8" >&2
+    echo " \"/opt/llvm/bin/clang\" \"-cc1\" \"-triple\" \"x86_64-unknown-linux-gnu\"" >&2
+    ;;
+*--version*)
+    echo "clang version 18.1.8"
+    ;;
+esac
+"##;
+
+fn test_clang_wrapper() {
+    let directory = tempfile::tempdir().unwrap();
+    let clang = directory.path().join("clang");
+    fs::write(&clang, CLANG_WRAPPER).unwrap();
+    fs::set_permissions(&clang, fs::Permissions::from_mode(0o755)).unwrap();
+
+    // SAFETY: The tests in this binary are run serially.
+    unsafe { env::set_var("CLANG_PATH", &clang) };
+
+    let found = Clang::find(None, &[]).unwrap();
+    assert_eq!(found.path, clang);
+    assert!(found.wrapper());
+    assert_eq!(
+        found.resolved_path(&[]),
+        Some(PathBuf::from("/opt/llvm/bin/clang"))
+    );
+    assert_eq!(found.version.map(|v| (v.Major, v.Minor)), Some((18, 1)));
+
+    unsafe { env::remove_var("CLANG_PATH") };
+}
+
+fn test_clang_process_runner() {
+    let directory = tempfile::tempdir().unwrap();
+    let clang = directory.path().join("clang");
+    fs::write(&clang, b"\x7fELF").unwrap();
+    fs::set_permissions(&clang, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let invocations = Arc::new(Mutex::new(vec![]));
+    let recorded = invocations.clone();
+    set_process_runner(Some(Arc::new(
+        move |executable: &str, arguments: &[&str]| {
+            recorded.lock().unwrap().push(executable.to_string());
+            if arguments.contains(&"--version") {
+                let stdout = "Homebrew clang version 19.1.7\nTarget: arm64-apple-darwin24.3.0\n";
+                Ok((stdout.into(), String::new()))
+            } else {
+                let stderr =
+                    "#include <...> search starts here:\r\n /opt/homebrew/include\r\nEnd\r\n";
+                Ok((String::new(), stderr.into()))
+            }
+        },
+    )));
+
+    // SAFETY: The tests in this binary are run serially.
+    unsafe { env::set_var("CLANG_PATH", &clang) };
+
+    let found = Clang::find(None, &[]).unwrap();
+    assert_eq!(found.version.map(|v| (v.Major, v.Minor)), Some((19, 1)));
+    let include = PathBuf::from("/opt/homebrew/include");
+    assert_eq!(found.c_search_paths, Some(vec![include.clone()]));
+    assert_eq!(found.cpp_search_paths, Some(vec![include]));
+    assert!(
+        invocations
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|e| *e == clang.to_string_lossy())
+    );
+
+    assert!(set_process_runner(None).is_some());
+    unsafe { env::remove_var("CLANG_PATH") };
+}